@@ -2,7 +2,10 @@ use std::collections::{BTreeMap, HashMap};
 
 use splay::SplayMap;
 
-use crab_bucket::splay::Splay;
+use crab_bucket::sorted_map::SortedMap;
+use crab_bucket::splay::{IngestSplay, Splay};
+use crab_bucket::versioned_map::VersionedMap;
+use crab_bucket::workload::{Uniform, Zipf};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use rand::prelude::*;
 
@@ -14,14 +17,14 @@ fn criterion_benchmark(c: &mut Criterion) {
     nums2.shuffle(&mut rng);
     nums1.truncate(5000);
     nums2.truncate(100);
-    nums2 = nums2.iter().cycle().take(5000).map(|x| *x).collect();
+    nums2 = nums2.iter().cycle().take(5000).copied().collect();
     c.bench_function("get and set splay", |b| {
         b.iter(|| {
             let mut t: Splay<i32, i32> = Splay::new();
             for &n in nums1.iter() {
                 t.set(n, n);
             }
-            for &n in nums2.iter() {
+            for n in nums2.iter() {
                 black_box(t.get(n));
             }
         })
@@ -76,7 +79,7 @@ fn criterion_benchmark(c: &mut Criterion) {
             for &n in nums1.iter() {
                 t.insert(n, n);
             }
-            let mut t = black_box(t.iter().map(|(k, _)| *k).collect::<Vec<i32>>());
+            let mut t = black_box(t.keys().copied().collect::<Vec<i32>>());
             t.sort();
             black_box(t);
         })
@@ -93,5 +96,74 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, criterion_benchmark);
+/// Inserts `keys`, then looks up `gets` and scans the ranges starting at
+/// each of `scans`, through the `SortedMap` trait.
+fn sorted_map_workload<M: SortedMap<i32, i32> + Default>(
+    keys: &[i32],
+    gets: &[i32],
+    scans: &[i32],
+) {
+    let mut t = M::default();
+    for &n in keys {
+        t.insert(n, n);
+    }
+    for n in gets {
+        black_box(t.get(n));
+    }
+    for &n in scans {
+        black_box(t.range(n..n + 100).count());
+    }
+}
+
+fn sorted_map_benchmark(c: &mut Criterion) {
+    let mut rng = rand::rng();
+    let mut keys: Vec<i32> = (1..10000).collect();
+    keys.shuffle(&mut rng);
+    keys.truncate(5000);
+    // Lookups skewed towards a few keys, and scans starting anywhere.
+    let gets: Vec<i32> = (Zipf::with_seed(keys.len() as u64, 1.0, 0).take(5000))
+        .map(|rank| keys[rank as usize])
+        .collect();
+    let scans: Vec<i32> = (Uniform::with_seed(10000, 0).take(100))
+        .map(|n| n as i32)
+        .collect();
+    c.bench_function("sorted map splay", |b| {
+        b.iter(|| sorted_map_workload::<Splay<_, _>>(&keys, &gets, &scans))
+    });
+    c.bench_function("sorted map treap", |b| {
+        b.iter(|| sorted_map_workload::<VersionedMap<_, _>>(&keys, &gets, &scans))
+    });
+    c.bench_function("sorted map btreemap", |b| {
+        b.iter(|| sorted_map_workload::<BTreeMap<_, _>>(&keys, &gets, &scans))
+    });
+}
+
+fn ingest_benchmark(c: &mut Criterion) {
+    let keys: Vec<i32> = (Uniform::with_seed(1 << 20, 0).take(100_000))
+        .map(|n| n as i32)
+        .collect();
+    c.bench_function("ingest splay set", |b| {
+        b.iter(|| {
+            let mut t = Splay::new();
+            for &n in &keys {
+                t.set(n, n);
+            }
+            black_box(t.len())
+        })
+    });
+    c.bench_function("ingest buffered", |b| {
+        b.iter(|| {
+            let mut t = IngestSplay::new();
+            t.extend(keys.iter().map(|&n| (n, n)));
+            black_box(t.into_tree().len())
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    sorted_map_benchmark,
+    ingest_benchmark
+);
 criterion_main!(benches);
@@ -0,0 +1,123 @@
+#ifndef CRAB_BUCKET_H
+#define CRAB_BUCKET_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Opaque splay map handle.
+ */
+typedef struct CbSplay CbSplay;
+
+/**
+ * Opaque iterator handle.
+ */
+typedef struct CbSplayIter CbSplayIter;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an empty map, to be released with `cb_splay_free`.
+ */
+struct CbSplay *cb_splay_new(void);
+
+/**
+ * # Safety
+ *
+ * `tree` must come from `cb_splay_new` and not have been freed yet, or be
+ * null.
+ */
+void cb_splay_free(struct CbSplay *tree);
+
+/**
+ * # Safety
+ *
+ * `tree` must be a live map.
+ */
+size_t cb_splay_len(const struct CbSplay *tree);
+
+/**
+ * Copies the key and value into the map, replacing any previous value.
+ *
+ * # Safety
+ *
+ * `tree` must be a live map and `key`/`value` must point to `key_len` and
+ * `value_len` readable bytes.
+ */
+void cb_splay_set(struct CbSplay *tree,
+                  const uint8_t *key,
+                  size_t key_len,
+                  const uint8_t *value,
+                  size_t value_len);
+
+/**
+ * Looks up `key`, storing a pointer to the value and its length in
+ * `value`/`value_len` if it's present. Returns whether it was. Doesn't
+ * splay, so it's safe to call while iterating.
+ *
+ * # Safety
+ *
+ * `tree` must be a live map, `key` must point to `key_len` readable bytes
+ * and `value`/`value_len` must be writable.
+ */
+bool cb_splay_get(const struct CbSplay *tree,
+                  const uint8_t *key,
+                  size_t key_len,
+                  const uint8_t **value,
+                  size_t *value_len);
+
+/**
+ * Removes `key` from the map, returning whether it was present.
+ *
+ * # Safety
+ *
+ * `tree` must be a live map and `key` must point to `key_len` readable
+ * bytes.
+ */
+bool cb_splay_remove(struct CbSplay *tree, const uint8_t *key, size_t key_len);
+
+/**
+ * Starts iterating over the map in key order, to be released with
+ * `cb_splay_iter_free`.
+ *
+ * # Safety
+ *
+ * `tree` must be a live map, which must outlive the iterator and stay
+ * unmodified while it's in use.
+ */
+struct CbSplayIter *cb_splay_iter_new(const struct CbSplay *tree);
+
+/**
+ * Advances the iterator, storing the next entry in the out parameters.
+ * Returns false, leaving them untouched, once the map is exhausted.
+ *
+ * # Safety
+ *
+ * `iter` must be a live iterator and all out parameters must be writable.
+ */
+bool cb_splay_iter_next(struct CbSplayIter *iter,
+                        const uint8_t **key,
+                        size_t *key_len,
+                        const uint8_t **value,
+                        size_t *value_len);
+
+/**
+ * # Safety
+ *
+ * `iter` must come from `cb_splay_iter_new` and not have been freed yet, or
+ * be null.
+ */
+void cb_splay_iter_free(struct CbSplayIter *iter);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CRAB_BUCKET_H */
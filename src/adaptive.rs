@@ -0,0 +1,324 @@
+use core::borrow::Borrow;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::slice;
+
+use crate::splay::{Splay, SplayIter};
+
+#[cfg(feature = "std")]
+mod index;
+
+#[cfg(feature = "std")]
+pub use index::{AdaptiveIndex, AdaptiveRange, Backend};
+
+/// Map which keeps up to `N` entries sorted in an inline array and switches
+/// to a splay tree once it grows past that. It goes back to the array once
+/// removals shrink it to `N / 2`, so a map hovering around `N` entries
+/// doesn't switch back and forth.
+#[derive(Clone)]
+pub struct AdaptiveMap<K, V, const N: usize = 16> {
+    repr: Repr<K, V, N>,
+}
+
+#[derive(Clone)]
+enum Repr<K, V, const N: usize> {
+    Small(Inline<(K, V), N>),
+    Large(Splay<K, V>),
+}
+
+/// Up to `N` items in an array, `buf[..len]` initialized.
+struct Inline<T, const N: usize> {
+    buf: [MaybeUninit<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> Inline<T, N> {
+    const fn new() -> Self {
+        Inline {
+            buf: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+        }
+    }
+
+    fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` slots are initialized.
+        unsafe { slice::from_raw_parts(self.buf.as_ptr().cast(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: as for `as_slice`.
+        unsafe { slice::from_raw_parts_mut(self.buf.as_mut_ptr().cast(), self.len) }
+    }
+
+    /// Inserts `item` at `i`, shifting the items after it up.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the array is full or `i > len`.
+    fn insert(&mut self, i: usize, item: T) {
+        assert!(self.len < N && i <= self.len);
+        let ptr = self.buf.as_mut_ptr().cast::<T>();
+        // SAFETY: slots `i..len` are moved up one, which stays below `N`,
+        // then `i` is overwritten without dropping what was moved out.
+        unsafe {
+            ptr::copy(ptr.add(i), ptr.add(i + 1), self.len - i);
+            ptr.add(i).write(item);
+        }
+        self.len += 1;
+    }
+
+    /// Removes the item at `i`, shifting the items after it down.
+    fn remove(&mut self, i: usize) -> T {
+        assert!(i < self.len);
+        let ptr = self.buf.as_mut_ptr().cast::<T>();
+        self.len -= 1;
+        // SAFETY: slot `i` is initialized, and is read out before the
+        // initialized slots after it are moved over it.
+        unsafe {
+            let item = ptr.add(i).read();
+            ptr::copy(ptr.add(i + 1), ptr.add(i), self.len - i);
+            item
+        }
+    }
+}
+
+impl<T, const N: usize> Iterator for Inline<T, N> {
+    type Item = T;
+
+    /// Takes the items out from the front. Only used on the way to a tree,
+    /// so the front being O(len) doesn't matter.
+    fn next(&mut self) -> Option<T> {
+        (self.len > 0).then(|| self.remove(0))
+    }
+}
+
+impl<T, const N: usize> Drop for Inline<T, N> {
+    fn drop(&mut self) {
+        // SAFETY: the slice covers exactly the initialized slots, which
+        // aren't touched again.
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) }
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for Inline<T, N> {
+    fn clone(&self) -> Self {
+        let mut inline = Self::new();
+        for item in self.as_slice() {
+            inline.insert(inline.len, item.clone());
+        }
+        inline
+    }
+}
+
+pub enum AdaptiveIter<'a, K, V> {
+    Small(slice::Iter<'a, (K, V)>),
+    Large(SplayIter<'a, K, V>),
+}
+
+impl<'a, K: Ord, V> Iterator for AdaptiveIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            AdaptiveIter::Small(it) => it.next().map(|(k, v)| (k, v)),
+            AdaptiveIter::Large(it) => it.next(),
+        }
+    }
+}
+
+impl<K: Ord, V, const N: usize> AdaptiveMap<K, V, N> {
+    pub fn new() -> Self {
+        AdaptiveMap {
+            repr: Repr::Small(Inline::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Small(entries) => entries.len,
+            Repr::Large(tree) => tree.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the entries live in the inline array.
+    pub fn is_small(&self) -> bool {
+        matches!(self.repr, Repr::Small(_))
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match &mut self.repr {
+            Repr::Small(entries) => {
+                let entries = entries.as_slice();
+                entries
+                    .binary_search_by(|(k, _)| k.borrow().cmp(key))
+                    .ok()
+                    .map(|i| &entries[i].1)
+            }
+            Repr::Large(tree) => tree.get(key),
+        }
+    }
+
+    pub fn set(&mut self, key: K, value: V) {
+        match &mut self.repr {
+            Repr::Small(entries) => {
+                match entries.as_slice().binary_search_by(|(k, _)| k.cmp(&key)) {
+                    Ok(i) => entries.as_mut_slice()[i].1 = value,
+                    Err(i) if entries.len < N => entries.insert(i, (key, value)),
+                    Err(_) => {
+                        let mut tree: Splay<K, V> = entries.collect();
+                        tree.set(key, value);
+                        self.repr = Repr::Large(tree);
+                    }
+                }
+            }
+            Repr::Large(tree) => tree.set(key, value),
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match &mut self.repr {
+            Repr::Small(entries) => {
+                let i = entries
+                    .as_slice()
+                    .binary_search_by(|(k, _)| k.borrow().cmp(key))
+                    .ok()?;
+                Some(entries.remove(i).1)
+            }
+            Repr::Large(tree) => {
+                let value = tree.remove(key)?;
+                if tree.len() <= N / 2 {
+                    let mut entries = Inline::new();
+                    for entry in core::mem::take(tree) {
+                        entries.insert(entries.len, entry);
+                    }
+                    self.repr = Repr::Small(entries);
+                }
+                Some(value)
+            }
+        }
+    }
+
+    pub fn iter(&self) -> AdaptiveIter<'_, K, V> {
+        match &self.repr {
+            Repr::Small(entries) => AdaptiveIter::Small(entries.as_slice().iter()),
+            Repr::Large(tree) => AdaptiveIter::Large(tree.iter()),
+        }
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug, const N: usize> fmt::Debug for AdaptiveMap<K, V, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V, const N: usize> Default for AdaptiveMap<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn upgrade_test() {
+        let mut map: AdaptiveMap<i32, i32, 4> = AdaptiveMap::new();
+        for i in (0..4).rev() {
+            map.set(i, i);
+        }
+        assert!(map.is_small());
+        map.set(2, 20);
+        assert!(map.is_small());
+        map.set(4, 4);
+        assert!(!map.is_small());
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.get(&2), Some(&20));
+        assert_eq!(map.get(&5), None);
+        assert_eq!(
+            map.iter().map(|(k, _)| *k).collect::<Vec<i32>>(),
+            vec![0, 1, 2, 3, 4]
+        );
+
+        assert_eq!(map.remove(&2), Some(20));
+        assert_eq!(map.remove(&2), None);
+        assert!(!map.is_small());
+        map.remove(&0);
+        map.remove(&4);
+        assert!(map.is_small());
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &1), (&3, &3)]);
+
+        // Entries dropped from the array and on the way to and from the tree
+        // are dropped once.
+        let counted = std::rc::Rc::new(());
+        let mut map: AdaptiveMap<u8, _, 2> = AdaptiveMap::new();
+        for i in 0..4 {
+            map.set(i, counted.clone());
+        }
+        for i in 0..3 {
+            map.remove(&i);
+        }
+        assert!(map.is_small());
+        map.set(2, counted.clone());
+        let copy = map.clone();
+        drop(map);
+        assert_eq!(std::rc::Rc::strong_count(&counted), 3);
+        drop(copy);
+        assert_eq!(std::rc::Rc::strong_count(&counted), 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, i32)>) -> bool {
+        let mut adaptive: AdaptiveMap<u8, i32, 8> = AdaptiveMap::new();
+        let mut map: HashMap<u8, i32> = HashMap::new();
+
+        for &(k, v) in ops.iter() {
+            adaptive.set(k, v);
+            map.insert(k, v);
+            if adaptive.get(&k) != map.get(&k) {
+                return false;
+            }
+        }
+
+        let mut expected: Vec<(u8, i32)> = map.into_iter().collect();
+        expected.sort();
+        adaptive.iter().map(|(k, v)| (*k, *v)).eq(expected)
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_remove(ops: Vec<(bool, u8)>) -> bool {
+        let mut adaptive: AdaptiveMap<u8, u8, 8> = AdaptiveMap::new();
+        let mut model = BTreeMap::new();
+        for (insert, key) in ops {
+            let key = key % 24;
+            let ok = if insert {
+                adaptive.set(key, key);
+                model.insert(key, key);
+                true
+            } else {
+                adaptive.remove(&key) == model.remove(&key)
+            };
+            if !ok || adaptive.len() != model.len() || !(adaptive.is_small() || model.len() > 4) {
+                return false;
+            }
+        }
+        adaptive.iter().eq(model.iter())
+    }
+}
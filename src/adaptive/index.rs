@@ -0,0 +1,315 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::{HashMap, RandomState};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+use std::ops::RangeBounds;
+use std::vec;
+
+use crate::splay::{self, Splay};
+
+// Operations per window, after each of which the backend is reconsidered.
+const WINDOW: u32 = 1024;
+// The share of a window's operations which are ordered queries, and of its
+// lookups which repeat a recent key, for which the splay tree is worth it.
+// The hash map takes over again below half of both.
+const ORDERED: f64 = 1.0 / 16.0;
+const SKEWED: f64 = 1.0 / 2.0;
+// Recent key hashes compared against for skew.
+const RECENT: usize = 64;
+
+/// Which structure an [`AdaptiveIndex`] is using.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Hash,
+    Splay,
+}
+
+enum Repr<K, V, S> {
+    Hash(HashMap<K, V, S>),
+    Splay(Splay<K, V>),
+}
+
+/// A map which moves its entries between a hash map and a splay tree as
+/// the way it's used changes.
+///
+/// It counts, over windows of operations, how many are ordered queries
+/// (`range` and `iter`) and how many lookups hit a key seen among the last
+/// few, a cheap measure of skew. A window with enough of either moves the
+/// entries into a splay tree, which answers ordered queries directly and
+/// keeps hot keys near its root. A window with little of both moves them
+/// back into a hash map, which is faster for scattered point lookups. The
+/// hash map answers ordered queries too, by sorting what they cover. Moving
+/// costs O(n log n) at most once a window.
+pub struct AdaptiveIndex<K, V, S = RandomState> {
+    repr: Repr<K, V, S>,
+    // Hashes the recent keys, and is cloned into each hash map.
+    hasher: S,
+    window: u32,
+    ops: u32,
+    ordered: u32,
+    lookups: u32,
+    repeats: u32,
+    recent: [u64; RECENT],
+    next_recent: usize,
+}
+
+/// The entries of an [`AdaptiveIndex`] within a range, in key order.
+pub enum AdaptiveRange<'a, K, V> {
+    Sorted(vec::IntoIter<(&'a K, &'a V)>),
+    Splay(splay::Range<'a, K, V>),
+}
+
+impl<'a, K: Ord, V> Iterator for AdaptiveRange<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            AdaptiveRange::Sorted(it) => it.next(),
+            AdaptiveRange::Splay(it) => it.next(),
+        }
+    }
+}
+
+impl<K: Hash + Ord, V> AdaptiveIndex<K, V> {
+    /// An empty index, starting out as a hash map.
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K: Hash + Ord, V, S: BuildHasher + Clone> AdaptiveIndex<K, V, S> {
+    /// An empty index hashing keys with `hasher`, in its hash maps and to
+    /// spot repeated lookups.
+    pub fn with_hasher(hasher: S) -> Self {
+        AdaptiveIndex {
+            repr: Repr::Hash(HashMap::with_hasher(hasher.clone())),
+            hasher,
+            window: WINDOW,
+            ops: 0,
+            ordered: 0,
+            lookups: 0,
+            repeats: 0,
+            recent: [0; RECENT],
+            next_recent: 0,
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self.repr {
+            Repr::Hash(_) => Backend::Hash,
+            Repr::Splay(_) => Backend::Splay,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            Repr::Hash(map) => map.len(),
+            Repr::Splay(tree) => tree.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Counts an operation, switching backends at the end of a window.
+    fn count(&mut self) {
+        self.ops += 1;
+        if self.ops < self.window {
+            return;
+        }
+        let ordered = f64::from(self.ordered) / f64::from(self.ops);
+        let skewed = f64::from(self.repeats) / f64::from(self.lookups.max(1));
+        match self.repr {
+            Repr::Hash(ref mut map) if ordered >= ORDERED || skewed >= SKEWED => {
+                let map = mem::replace(map, HashMap::with_hasher(self.hasher.clone()));
+                self.repr = Repr::Splay(map.into_iter().collect());
+            }
+            Repr::Splay(ref mut tree) if ordered < ORDERED / 2.0 && skewed < SKEWED / 2.0 => {
+                let mut map = HashMap::with_capacity_and_hasher(tree.len(), self.hasher.clone());
+                map.extend(mem::take(tree));
+                self.repr = Repr::Hash(map);
+            }
+            _ => {}
+        }
+        (self.ops, self.ordered, self.lookups, self.repeats) = (0, 0, 0, 0);
+    }
+
+    /// Counts a lookup of `key`, and whether it was recently looked up.
+    fn count_lookup<Q: Hash + ?Sized>(&mut self, key: &Q) {
+        let hash = self.hasher.hash_one(key);
+        self.lookups += 1;
+        if self.recent.contains(&hash) {
+            self.repeats += 1;
+        } else {
+            self.recent[self.next_recent] = hash;
+            self.next_recent = (self.next_recent + 1) % RECENT;
+        }
+        self.count();
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.count_lookup(key);
+        match &mut self.repr {
+            Repr::Hash(map) => map.get(key),
+            Repr::Splay(tree) => tree.get(key),
+        }
+    }
+
+    pub fn contains_key<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Inserts or updates an entry, returning the previous value for `key`.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.count();
+        match &mut self.repr {
+            Repr::Hash(map) => map.insert(key, value),
+            Repr::Splay(tree) => tree.insert(key, value),
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.count();
+        match &mut self.repr {
+            Repr::Hash(map) => map.remove(key),
+            Repr::Splay(tree) => tree.remove(key),
+        }
+    }
+
+    /// The entries within `range` in key order, sorted on the spot if the
+    /// entries are in the hash map.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same malformed ranges as `BTreeMap::range`.
+    pub fn range<R: RangeBounds<K>>(&mut self, range: R) -> AdaptiveRange<'_, K, V> {
+        splay::check_range(&range);
+        self.ordered += 1;
+        self.count();
+        match &self.repr {
+            Repr::Hash(map) => {
+                let mut entries: Vec<_> = map.iter().filter(|(k, _)| range.contains(*k)).collect();
+                entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                AdaptiveRange::Sorted(entries.into_iter())
+            }
+            Repr::Splay(tree) => AdaptiveRange::Splay(tree.range(range)),
+        }
+    }
+
+    /// The entries in key order.
+    pub fn iter(&mut self) -> AdaptiveRange<'_, K, V> {
+        self.range(..)
+    }
+
+    pub fn clear(&mut self) {
+        match &mut self.repr {
+            Repr::Hash(map) => map.clear(),
+            Repr::Splay(tree) => tree.clear(),
+        }
+    }
+}
+
+impl<K: Hash + Ord, V, S: BuildHasher + Clone + Default> Default for AdaptiveIndex<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Hash + Ord, V, S: BuildHasher + Clone + Default> FromIterator<(K, V)>
+    for AdaptiveIndex<K, V, S>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut index = Self::default();
+        if let Repr::Hash(map) = &mut index.repr {
+            map.extend(iter);
+        }
+        index
+    }
+}
+
+impl<K: Hash + Ord, V, S: BuildHasher + Clone> fmt::Debug for AdaptiveIndex<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdaptiveIndex")
+            .field("backend", &self.backend())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::BTreeMap;
+    use std::hash::BuildHasherDefault;
+
+    #[test]
+    fn basic_test() {
+        let mut index: AdaptiveIndex<u32, u32> = (0..10_000).map(|k| (k, k)).collect();
+        assert_eq!(index.backend(), Backend::Hash);
+
+        // A phase of scans moves the entries into the tree.
+        for start in 0..WINDOW / 4 {
+            let scan: Vec<_> = index.range(start * 10..start * 10 + 3).collect();
+            assert_eq!(scan.len(), 3);
+            for _ in 0..3 {
+                index.insert(start * 10, start * 10);
+            }
+        }
+        assert_eq!(index.backend(), Backend::Splay);
+        assert_eq!(index.get(&20), Some(&20));
+
+        // Scattered lookups move them back into the hash map.
+        for k in 0..2 * WINDOW {
+            assert_eq!(index.get(&(k * 3 + 1)), Some(&(k * 3 + 1)));
+        }
+        assert_eq!(index.backend(), Backend::Hash);
+
+        // And lookups of a few hot keys into the tree again.
+        for k in 0..WINDOW {
+            index.get(&(k % 8));
+        }
+        assert_eq!(index.backend(), Backend::Splay);
+        assert_eq!(index.len(), 10_000);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8, u8)>) -> bool {
+        // Short windows, so the backend changes often.
+        let mut index = AdaptiveIndex {
+            window: 8,
+            ..AdaptiveIndex::with_hasher(BuildHasherDefault::<DefaultHasher>::default())
+        };
+        let mut model = BTreeMap::new();
+        for (i, (op, a, b)) in ops.into_iter().enumerate() {
+            let ok = match op % 5 {
+                0 | 1 => index.insert(a, i) == model.insert(a, i),
+                2 => index.remove(&a) == model.remove(&a),
+                3 => index.get(&a) == model.get(&a),
+                _ => {
+                    let range = a.min(b)..=a.max(b);
+                    index.range(range.clone()).eq(model.range(range))
+                }
+            };
+            if !ok || index.len() != model.len() {
+                return false;
+            }
+        }
+        index.iter().eq(model.iter())
+    }
+}
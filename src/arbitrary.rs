@@ -0,0 +1,90 @@
+use std::hash::Hash;
+
+use quickcheck::{Arbitrary, Gen};
+
+use crate::adaptive::AdaptiveMap;
+use crate::counter::Counter;
+use crate::ordered_map::OrderedMap;
+use crate::splay::Splay;
+
+/// Inserts in random order and then splays random keys, so the generated
+/// trees come in all kinds of shapes, not just the balanced ones.
+impl<K: Arbitrary + Ord, V: Arbitrary> Arbitrary for Splay<K, V> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let entries: Vec<(K, V)> = Arbitrary::arbitrary(g);
+        let mut tree = Splay::new();
+        for (k, v) in entries.iter().cloned() {
+            tree.set(k, v);
+        }
+        for _ in 0..usize::arbitrary(g) % (entries.len() + 1) {
+            if let Some((k, _)) = g.choose(&entries) {
+                tree.get(k);
+            }
+        }
+        tree
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries: Vec<(K, V)> = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        Box::new(
+            entries
+                .shrink()
+                .map(|entries| entries.into_iter().collect()),
+        )
+    }
+}
+
+impl<K: Arbitrary + Ord, V: Arbitrary, const N: usize> Arbitrary for AdaptiveMap<K, V, N> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let entries: Vec<(K, V)> = Arbitrary::arbitrary(g);
+        let mut map = AdaptiveMap::new();
+        for (k, v) in entries {
+            map.set(k, v);
+        }
+        map
+    }
+}
+
+impl<K: Arbitrary + Hash + Eq, V: Arbitrary> Arbitrary for OrderedMap<K, V> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        let entries: Vec<(K, V)> = Arbitrary::arbitrary(g);
+        let mut map = OrderedMap::new();
+        for (k, v) in entries {
+            map.insert(k, v);
+        }
+        map
+    }
+}
+
+impl<T: Arbitrary + Ord> Arbitrary for Counter<T> {
+    fn arbitrary(g: &mut Gen) -> Self {
+        Vec::<T>::arbitrary(g).into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let items: Vec<T> = self
+            .iter()
+            .flat_map(|(item, count)| std::iter::repeat_n(item.clone(), count))
+            .collect();
+        Box::new(items.shrink().map(|items| items.into_iter().collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[quickcheck]
+    fn test_splay(mut tree: Splay<u8, u8>) -> bool {
+        let keys: Vec<u8> = tree.iter().map(|(k, _)| *k).collect();
+        keys.windows(2).all(|w| w[0] < w[1])
+            && keys.len() == tree.len()
+            && keys.iter().all(|k| tree.get(k).is_some())
+    }
+
+    #[quickcheck]
+    fn test_counter(counter: Counter<u8>) -> bool {
+        counter.iter().map(|(_, count)| count).sum::<usize>() == counter.total()
+    }
+}
@@ -0,0 +1,333 @@
+//! A burst trie, for loading many string keys faster than a balanced tree.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::{fmt, mem};
+
+enum Node<V> {
+    // The rest of each key past this node's depth, unsorted.
+    Bucket(Vec<(Box<[u8]>, V)>),
+    Trie(Box<Trie<V>>),
+}
+
+struct Trie<V> {
+    // The value of the key which ends here.
+    value: Option<V>,
+    // One per next byte.
+    children: Vec<Node<V>>,
+}
+
+/// A map from byte strings, a trie whose leaves are small unsorted buckets.
+///
+/// A bucket takes keys with a plain scan and push, no comparisons beyond
+/// the linear search for a duplicate. Once it holds more than `threshold`
+/// keys it bursts into a trie node with a bucket per next byte, so lookups
+/// stay short while inserts mostly hit a bucket already in cache. Buckets
+/// get sorted only when the map is iterated. Removing keys doesn't undo
+/// bursts.
+pub struct BurstTrie<V> {
+    root: Node<V>,
+    threshold: usize,
+    len: usize,
+}
+
+impl<V> BurstTrie<V> {
+    pub fn new() -> Self {
+        Self::with_threshold(32)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `threshold` is zero.
+    pub fn with_threshold(threshold: usize) -> Self {
+        assert!(threshold > 0, "threshold must be positive");
+        BurstTrie {
+            root: Node::Bucket(Vec::new()),
+            threshold,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert<K: AsRef<[u8]>>(&mut self, key: K, value: V) -> Option<V> {
+        let key = key.as_ref();
+        let (node, depth) = leaf_mut(&mut self.root, key);
+        let bucket = match node {
+            Node::Trie(trie) => {
+                let old = trie.value.replace(value);
+                self.len += old.is_none() as usize;
+                return old;
+            }
+            Node::Bucket(bucket) => bucket,
+        };
+        let suffix = &key[depth..];
+        if let Some((_, old)) = bucket.iter_mut().find(|(k, _)| **k == *suffix) {
+            return Some(mem::replace(old, value));
+        }
+        bucket.push((suffix.into(), value));
+        self.len += 1;
+        if bucket.len() > self.threshold {
+            let entries = mem::take(bucket);
+            *node = burst(entries, self.threshold);
+        }
+        None
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&V> {
+        let key = key.as_ref();
+        let mut node = &self.root;
+        let mut depth = 0;
+        loop {
+            match node {
+                Node::Trie(trie) if depth == key.len() => return trie.value.as_ref(),
+                Node::Trie(trie) => {
+                    node = &trie.children[key[depth] as usize];
+                    depth += 1;
+                }
+                Node::Bucket(bucket) => {
+                    let suffix = &key[depth..];
+                    return bucket.iter().find(|(k, _)| **k == *suffix).map(|(_, v)| v);
+                }
+            }
+        }
+    }
+
+    pub fn get_mut<K: AsRef<[u8]>>(&mut self, key: K) -> Option<&mut V> {
+        let key = key.as_ref();
+        match leaf_mut(&mut self.root, key) {
+            (Node::Trie(trie), _) => trie.value.as_mut(),
+            (Node::Bucket(bucket), depth) => bucket
+                .iter_mut()
+                .find(|(k, _)| **k == key[depth..])
+                .map(|(_, v)| v),
+        }
+    }
+
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<V> {
+        let key = key.as_ref();
+        let removed = match leaf_mut(&mut self.root, key) {
+            (Node::Trie(trie), _) => trie.value.take(),
+            (Node::Bucket(bucket), depth) => {
+                let i = bucket.iter().position(|(k, _)| **k == key[depth..]);
+                i.map(|i| bucket.swap_remove(i).1)
+            }
+        };
+        self.len -= removed.is_some() as usize;
+        removed
+    }
+
+    /// The entries in key order.
+    pub fn iter(&self) -> BurstTrieIter<'_, V> {
+        BurstTrieIter {
+            stack: alloc::vec![Frame::new(&self.root, 0)],
+            prefix: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.root = Node::Bucket(Vec::new());
+        self.len = 0;
+    }
+}
+
+/// The node where the search for `key` ends: the bucket it would be in,
+/// or the trie node it ends at, along with how much of it is used up.
+fn leaf_mut<'a, V>(mut node: &'a mut Node<V>, key: &[u8]) -> (&'a mut Node<V>, usize) {
+    let mut depth = 0;
+    while depth < key.len() && matches!(node, Node::Trie(_)) {
+        let Node::Trie(trie) = node else {
+            unreachable!()
+        };
+        node = &mut trie.children[key[depth] as usize];
+        depth += 1;
+    }
+    (node, depth)
+}
+
+/// Turns a full bucket into a trie node, bursting any child bucket which
+/// ends up full too.
+fn burst<V>(entries: Vec<(Box<[u8]>, V)>, threshold: usize) -> Node<V> {
+    let mut trie = Trie {
+        value: None,
+        children: (0..256).map(|_| Node::Bucket(Vec::new())).collect(),
+    };
+    for (suffix, value) in entries {
+        match suffix.split_first() {
+            None => trie.value = Some(value),
+            Some((&byte, rest)) => {
+                if let Node::Bucket(bucket) = &mut trie.children[byte as usize] {
+                    bucket.push((rest.into(), value));
+                }
+            }
+        }
+    }
+    for child in &mut trie.children {
+        if let Node::Bucket(bucket) = child {
+            if bucket.len() > threshold {
+                *child = burst(mem::take(bucket), threshold);
+            }
+        }
+    }
+    Node::Trie(Box::new(trie))
+}
+
+impl<V> Default for BurstTrie<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: AsRef<[u8]>, V> Extend<(K, V)> for BurstTrie<V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: AsRef<[u8]>, V> FromIterator<(K, V)> for BurstTrie<V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut trie = BurstTrie::new();
+        trie.extend(iter);
+        trie
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for BurstTrie<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+enum Frame<'a, V> {
+    // `next` is 0 for the node's own value, then 1 + each byte.
+    Trie {
+        trie: &'a Trie<V>,
+        next: usize,
+        depth: usize,
+    },
+    // Sorted backwards, to pop from the end.
+    Bucket {
+        entries: Vec<(&'a [u8], &'a V)>,
+        depth: usize,
+    },
+}
+
+impl<'a, V> Frame<'a, V> {
+    fn new(node: &'a Node<V>, depth: usize) -> Self {
+        match node {
+            Node::Trie(trie) => Frame::Trie {
+                trie,
+                next: 0,
+                depth,
+            },
+            Node::Bucket(bucket) => {
+                let mut entries: Vec<_> = bucket.iter().map(|(k, v)| (&**k, v)).collect();
+                entries.sort_unstable_by(|a, b| b.0.cmp(a.0));
+                Frame::Bucket { entries, depth }
+            }
+        }
+    }
+}
+
+/// The entries of a [`BurstTrie`] in key order, from [`BurstTrie::iter`].
+pub struct BurstTrieIter<'a, V> {
+    stack: Vec<Frame<'a, V>>,
+    // The key bytes down to the top frame.
+    prefix: Vec<u8>,
+}
+
+impl<'a, V> Iterator for BurstTrieIter<'a, V> {
+    type Item = (Vec<u8>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.stack.last_mut()? {
+                Frame::Bucket { entries, depth } => match entries.pop() {
+                    Some((suffix, value)) => {
+                        let mut key = self.prefix[..*depth].to_vec();
+                        key.extend_from_slice(suffix);
+                        return Some((key, value));
+                    }
+                    None => {
+                        self.stack.pop();
+                    }
+                },
+                Frame::Trie { trie, next, depth } => {
+                    let (trie, depth, i) = (*trie, *depth, *next);
+                    *next += 1;
+                    if i == 0 {
+                        if let Some(value) = &trie.value {
+                            return Some((self.prefix[..depth].to_vec(), value));
+                        }
+                    } else if i > trie.children.len() {
+                        self.stack.pop();
+                    } else {
+                        self.prefix.truncate(depth);
+                        self.prefix.push((i - 1) as u8);
+                        self.stack
+                            .push(Frame::new(&trie.children[i - 1], depth + 1));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut trie = BurstTrie::with_threshold(2);
+        for (i, word) in ["car", "cart", "cat", "dog", "ca", ""]
+            .into_iter()
+            .enumerate()
+        {
+            assert_eq!(trie.insert(word, i), None);
+        }
+        assert_eq!(trie.insert("cat", 10), Some(2));
+        assert_eq!(trie.get("cat"), Some(&10));
+        assert_eq!(trie.get("c"), None);
+        assert_eq!(trie.remove("car"), Some(0));
+        assert_eq!(trie.remove("car"), None);
+        assert_eq!(trie.len(), 5);
+        let keys: Vec<_> = trie
+            .iter()
+            .map(|(k, _)| String::from_utf8(k).unwrap())
+            .collect();
+        assert_eq!(keys, ["", "ca", "cart", "cat", "dog"]);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, Vec<u8>)>) -> bool {
+        let mut trie = BurstTrie::with_threshold(3);
+        let mut model = BTreeMap::new();
+        for (i, (op, mut key)) in ops.into_iter().enumerate() {
+            // Few distinct bytes, so that keys share prefixes.
+            key.iter_mut().for_each(|b| *b %= 4);
+            let ok = match op % 3 {
+                0 | 1 => trie.insert(&key, i) == model.insert(key, i),
+                _ => trie.remove(&key) == model.remove(&key),
+            };
+            if !ok || trie.len() != model.len() {
+                return false;
+            }
+        }
+        trie.iter().eq(model.iter().map(|(k, v)| (k.clone(), v)))
+    }
+}
@@ -0,0 +1,102 @@
+//! Bounded caches with different eviction policies. Keys only need `Ord`:
+//! entries and recency lists are kept in splay trees, so keys which are hit
+//! often stay cheap to find.
+
+mod arc;
+mod clock;
+#[cfg(feature = "std")]
+mod sharded_lru;
+mod two_q;
+
+pub use arc::ArcCache;
+pub use clock::ClockCache;
+#[cfg(feature = "std")]
+pub use sharded_lru::ShardedLruCache;
+pub use two_q::TwoQCache;
+
+use core::borrow::Borrow;
+
+use crate::splay::Splay;
+
+/// Keys ordered by when they were last pushed, like a linked list with
+/// O(log n) amortized removal from the middle.
+#[derive(Clone)]
+struct Recency<K> {
+    by_stamp: Splay<u64, K>,
+    stamps: Splay<K, u64>,
+    next: u64,
+}
+
+impl<K: Ord + Clone> Recency<K> {
+    fn new() -> Self {
+        Recency {
+            by_stamp: Splay::new(),
+            stamps: Splay::new(),
+            next: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.stamps.len()
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.stamps.contains_key(key)
+    }
+
+    /// Makes `key` the most recent, inserting it if it's new.
+    fn push(&mut self, key: K) {
+        let stamp = self.next;
+        self.next += 1;
+        if let Some(old) = self.stamps.insert(key.clone(), stamp) {
+            self.by_stamp.remove(&old);
+        }
+        self.by_stamp.set(stamp, key);
+    }
+
+    fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.stamps.remove(key) {
+            Some(stamp) => {
+                self.by_stamp.remove(&stamp);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes and returns the least recent key.
+    fn pop_oldest(&mut self) -> Option<K> {
+        let (_, key) = self.by_stamp.pop_first()?;
+        self.stamps.remove(&key);
+        Some(key)
+    }
+
+    fn clear(&mut self) {
+        self.by_stamp.clear();
+        self.stamps.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recency_test() {
+        let mut recency = Recency::new();
+        for key in ["a", "b", "c"] {
+            recency.push(key);
+        }
+        recency.push("a");
+        assert!(recency.remove(&"b"));
+        assert!(!recency.remove(&"b"));
+        assert_eq!(recency.len(), 2);
+        assert_eq!(recency.pop_oldest(), Some("c"));
+        assert_eq!(recency.pop_oldest(), Some("a"));
+        assert_eq!(recency.pop_oldest(), None);
+    }
+}
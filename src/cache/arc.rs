@@ -0,0 +1,282 @@
+use core::borrow::Borrow;
+use core::cmp::{max, min};
+
+use super::Recency;
+use crate::splay::Splay;
+
+/// A cache with the adaptive replacement policy of Megiddo and Modha.
+///
+/// Resident keys are split between `t1`, seen once recently, and `t2`, seen
+/// at least twice. The ghost lists `b1` and `b2` remember keys recently
+/// evicted from each, without their values; a miss on a ghost moves the
+/// target size `p` of `t1` towards the list which would have kept it. A scan
+/// of one-off keys only churns `t1`, so it can't flush the frequent keys.
+#[derive(Clone)]
+pub struct ArcCache<K, V> {
+    capacity: usize,
+    p: usize,
+    t1: Recency<K>,
+    t2: Recency<K>,
+    b1: Recency<K>,
+    b2: Recency<K>,
+    values: Splay<K, V>,
+}
+
+impl<K: Ord + Clone, V> ArcCache<K, V> {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "cache capacity must be positive");
+        ArcCache {
+            capacity,
+            p: 0,
+            t1: Recency::new(),
+            t2: Recency::new(),
+            b1: Recency::new(),
+            b2: Recency::new(),
+            values: Splay::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.values.contains_key(key)
+    }
+
+    /// Looks up `key` without counting it as a hit.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.values.peek(key)
+    }
+
+    /// Looks up `key`, promoting it to the frequently used list.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let key = self.values.get_key_value(key)?.0.clone();
+        self.t1.remove::<K>(&key);
+        self.t2.push(key.clone());
+        self.values.get_mut::<K>(&key)
+    }
+
+    /// Inserts or updates an entry, evicting one if the cache is full.
+    /// Returns the previous value for `key`.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.t1.remove(&key) || self.t2.contains(&key) {
+            self.t2.push(key.clone());
+            return self.values.insert(key, value);
+        }
+
+        if self.b1.contains(&key) {
+            // It would still be resident had `t1` been bigger.
+            let delta = max(self.b2.len() / self.b1.len(), 1);
+            self.p = min(self.p + delta, self.capacity);
+            self.make_room(false);
+            self.b1.remove(&key);
+            self.t2.push(key.clone());
+        } else if self.b2.contains(&key) {
+            let delta = max(self.b1.len() / self.b2.len(), 1);
+            self.p = self.p.saturating_sub(delta);
+            self.make_room(true);
+            self.b2.remove(&key);
+            self.t2.push(key.clone());
+        } else {
+            let l1 = self.t1.len() + self.b1.len();
+            let total = l1 + self.t2.len() + self.b2.len();
+            if l1 == self.capacity {
+                if self.t1.len() < self.capacity {
+                    self.b1.pop_oldest();
+                    self.make_room(false);
+                } else {
+                    // `b1` is empty, so the oldest of `t1` goes for good.
+                    let evicted = self.t1.pop_oldest().unwrap();
+                    self.values.remove(&evicted);
+                }
+            } else if total >= self.capacity {
+                if total == 2 * self.capacity {
+                    self.b2.pop_oldest();
+                }
+                self.make_room(false);
+            }
+            self.t1.push(key.clone());
+        }
+        self.values.insert(key, value)
+    }
+
+    /// Evicts an entry if the resident lists are full, which they may not be
+    /// after a `remove`.
+    fn make_room(&mut self, hit_in_b2: bool) {
+        if self.t1.len() + self.t2.len() >= self.capacity {
+            self.replace(hit_in_b2);
+        }
+    }
+
+    /// Evicts the oldest entry of `t1` or `t2` into its ghost list, picking
+    /// `t1` when it's over its target size.
+    fn replace(&mut self, hit_in_b2: bool) {
+        let t1_len = self.t1.len();
+        let from_t1 = t1_len > 0 && (t1_len > self.p || (hit_in_b2 && t1_len == self.p));
+        let (list, ghosts) = if from_t1 {
+            (&mut self.t1, &mut self.b1)
+        } else {
+            (&mut self.t2, &mut self.b2)
+        };
+        if let Some(key) = list.pop_oldest() {
+            self.values.remove(&key);
+            ghosts.push(key);
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.t1.remove(key) && !self.t2.remove(key) {
+            return None;
+        }
+        self.values.remove(key)
+    }
+
+    /// Drops all entries and the eviction history.
+    pub fn clear(&mut self) {
+        for list in [&mut self.t1, &mut self.t2, &mut self.b1, &mut self.b2] {
+            list.clear();
+        }
+        self.values.clear();
+        self.p = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut cache = ArcCache::new(2);
+        assert_eq!(cache.insert("a", 1), None);
+        assert_eq!(cache.insert("a", 2), Some(1));
+        cache.insert("b", 3);
+        cache.insert("c", 4);
+        assert_eq!(cache.len(), 2);
+        // `a` was used twice, so the one-off `b` went first.
+        assert_eq!(cache.get(&"a"), Some(&2));
+        assert!(!cache.contains_key("b"));
+        assert_eq!(cache.remove(&"c"), Some(4));
+        assert_eq!(cache.peek("a"), Some(&2));
+    }
+
+    #[test]
+    fn remove_test() {
+        let mut cache = ArcCache::new(3);
+        for key in 0..3 {
+            cache.insert(key, key);
+        }
+        cache.get(&0);
+        // Evicts 1 into the ghosts.
+        cache.insert(3, 3);
+        assert_eq!(cache.remove(&3), Some(3));
+        // There's room again, so nothing resident is evicted.
+        cache.insert(4, 4);
+        assert!([0, 2, 4].iter().all(|key| cache.contains_key(key)));
+
+        let mut cache = ArcCache::new(2);
+        cache.insert(String::from("a"), 1);
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.remove("a"), Some(1));
+    }
+
+    #[test]
+    fn scan_test() {
+        let mut cache = ArcCache::new(10);
+        for round in 0..3 {
+            for key in 0..5 {
+                cache.insert(key, round);
+                cache.get(&key);
+            }
+        }
+        for key in 100..1000 {
+            cache.insert(key, 0);
+        }
+        assert!((0..5).all(|key| cache.contains_key(&key)));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>, capacity: u8) -> bool {
+        let capacity = capacity as usize % 8 + 1;
+        let mut cache = ArcCache::new(capacity);
+        let mut last = std::collections::BTreeMap::new();
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 16;
+            match op % 4 {
+                0 | 1 => {
+                    // Nothing resident goes while there's room.
+                    let before = cache.len();
+                    let grows = !cache.contains_key(&key) && before < capacity;
+                    cache.insert(key, i);
+                    last.insert(key, i);
+                    if cache.len() != before + usize::from(grows) {
+                        return false;
+                    }
+                }
+                2 => {
+                    if cache
+                        .remove(&key)
+                        .is_some_and(|value| last.get(&key) != Some(&value))
+                    {
+                        return false;
+                    }
+                }
+                _ => {
+                    if cache
+                        .get(&key)
+                        .is_some_and(|&value| last.get(&key) != Some(&value))
+                    {
+                        return false;
+                    }
+                }
+            }
+            let l1 = cache.t1.len() + cache.b1.len();
+            let total = l1 + cache.t2.len() + cache.b2.len();
+            if cache.len() > capacity
+                || cache.len() != cache.t1.len() + cache.t2.len()
+                || l1 > capacity
+                || total > 2 * capacity
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
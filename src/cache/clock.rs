@@ -0,0 +1,202 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::mem;
+
+use crate::splay::Splay;
+
+struct Slot<K, V> {
+    key: K,
+    value: V,
+    referenced: bool,
+}
+
+/// A cache with the clock (second chance) policy: a hit only sets a bit on
+/// the entry, and eviction sweeps a hand around the slots, clearing bits
+/// until it finds an entry which wasn't used since the last pass.
+///
+/// Hits don't reorder anything, so they're much cheaper than in an LRU at
+/// the price of only approximating it.
+pub struct ClockCache<K, V> {
+    capacity: usize,
+    slots: Vec<Option<Slot<K, V>>>,
+    index: Splay<K, usize>,
+    // Slots emptied by `remove`, filled before anything is evicted.
+    holes: Vec<usize>,
+    hand: usize,
+}
+
+impl<K: Ord + Clone, V> ClockCache<K, V> {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "cache capacity must be positive");
+        ClockCache {
+            capacity,
+            slots: Vec::new(),
+            index: Splay::new(),
+            holes: Vec::new(),
+            hand: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.index.contains_key(key)
+    }
+
+    /// Looks up `key` without counting it as a hit.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let &slot = self.index.peek(key)?;
+        self.slots[slot].as_ref().map(|slot| &slot.value)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let &slot = self.index.get(key)?;
+        let slot = self.slots[slot].as_mut().unwrap();
+        slot.referenced = true;
+        Some(&mut slot.value)
+    }
+
+    /// Inserts or updates an entry, evicting one if the cache is full.
+    /// Returns the previous value for `key`.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(mem::replace(old, value));
+        }
+        let idx = if let Some(idx) = self.holes.pop() {
+            idx
+        } else if self.slots.len() < self.capacity {
+            self.slots.push(None);
+            self.slots.len() - 1
+        } else {
+            self.sweep()
+        };
+        self.slots[idx] = Some(Slot {
+            key: key.clone(),
+            value,
+            referenced: false,
+        });
+        self.index.set(key, idx);
+        None
+    }
+
+    /// Evicts the first entry the hand passes without a reference bit,
+    /// clearing the bits it passes on the way. Every slot is full.
+    fn sweep(&mut self) -> usize {
+        loop {
+            let idx = self.hand;
+            self.hand = (self.hand + 1) % self.slots.len();
+            let slot = self.slots[idx].as_mut().unwrap();
+            if !mem::take(&mut slot.referenced) {
+                self.index.remove(&slot.key);
+                return idx;
+            }
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let idx = self.index.remove(key)?;
+        self.holes.push(idx);
+        self.slots[idx].take().map(|slot| slot.value)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.index.clear();
+        self.holes.clear();
+        self.hand = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut cache = ClockCache::new(3);
+        for key in ["a", "b", "c"] {
+            cache.insert(key, key.len());
+        }
+        cache.get("a");
+        cache.get("c");
+        // The hand skips `a`, clearing its bit, and evicts `b`.
+        cache.insert("d", 1);
+        assert!(!cache.contains_key("b"));
+        // `c` gets its second chance too, and `a` used up its own.
+        cache.insert("e", 1);
+        assert!(cache.contains_key("c") && !cache.contains_key("a"));
+
+        assert_eq!(cache.remove("c"), Some(1));
+        assert_eq!(cache.insert("f", 2), None);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.insert("f", 3), Some(2));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>, capacity: u8) -> bool {
+        let capacity = capacity as usize % 8 + 1;
+        let mut cache = ClockCache::new(capacity);
+        let mut last = std::collections::BTreeMap::new();
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 16;
+            match op % 3 {
+                0 => {
+                    cache.insert(key, i);
+                    last.insert(key, i);
+                }
+                1 => {
+                    if cache.get(&key).is_some_and(|v| last.get(&key) != Some(v)) {
+                        return false;
+                    }
+                }
+                _ => drop(cache.remove(&key)),
+            }
+            let filled = cache.slots.iter().flatten().count();
+            if cache.len() > capacity
+                || filled != cache.len()
+                || filled + cache.holes.len() != cache.slots.len()
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
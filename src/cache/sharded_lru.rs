@@ -0,0 +1,290 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering::Relaxed;
+#[cfg(feature = "async")]
+use std::sync::Arc;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use super::Recency;
+use crate::splay::Splay;
+
+#[cfg(feature = "async")]
+mod load;
+
+/// One shard: a plain LRU.
+struct Shard<K, V> {
+    recency: Recency<K>,
+    values: Splay<K, V>,
+    // The keys being loaded by `get_or_insert_with`.
+    #[cfg(feature = "async")]
+    claims: Splay<K, Arc<load::Claim>>,
+}
+
+impl<K: Ord + Clone, V> Shard<K, V> {
+    fn get(&mut self, key: &K) -> Option<&V> {
+        let value = self.values.get(key)?;
+        self.recency.push(key.clone());
+        Some(value)
+    }
+}
+
+/// An LRU cache many threads can share, split into shards with a lock and
+/// a capacity each.
+///
+/// A key's hash picks its shard, so threads only wait for each other when
+/// they hit the same shard, and a shard evicts its least recently used
+/// entry once it's full, independently of the others: the cache as a whole
+/// is only roughly LRU. With many more keys than shards, they spread about
+/// evenly. Hits and misses are counted with relaxed atomics, outside the
+/// locks. Values are cloned out, since the entry may be evicted as soon as
+/// the lock is released.
+pub struct ShardedLruCache<K, V, S = RandomState> {
+    shards: Box<[Mutex<Shard<K, V>>]>,
+    shard_capacity: usize,
+    hasher: S,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Hash + Ord + Clone, V> ShardedLruCache<K, V> {
+    /// A cache of `shards` shards holding up to `shard_capacity` entries
+    /// each.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either is zero.
+    pub fn new(shards: usize, shard_capacity: usize) -> Self {
+        Self::with_hasher(shards, shard_capacity, RandomState::new())
+    }
+}
+
+impl<K: Hash + Ord + Clone, V, S: BuildHasher> ShardedLruCache<K, V, S> {
+    /// Like [`new`](ShardedLruCache::new), picking shards by `hasher`, so
+    /// which keys share a shard is reproducible given a hasher which
+    /// doesn't change between runs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` or `shard_capacity` is zero.
+    pub fn with_hasher(shards: usize, shard_capacity: usize, hasher: S) -> Self {
+        assert!(shards > 0, "a cache needs a shard");
+        assert!(shard_capacity > 0, "cache capacity must be positive");
+        ShardedLruCache {
+            shards: (0..shards)
+                .map(|_| {
+                    Mutex::new(Shard {
+                        recency: Recency::new(),
+                        values: Splay::new(),
+                        #[cfg(feature = "async")]
+                        claims: Splay::new(),
+                    })
+                })
+                .collect(),
+            shard_capacity,
+            hasher,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn shard(&self, key: &K) -> MutexGuard<'_, Shard<K, V>> {
+        let shard = &self.shards[self.hasher.hash_one(key) as usize % self.shards.len()];
+        shard.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn shards(&self) -> impl Iterator<Item = MutexGuard<'_, Shard<K, V>>> {
+        (self.shards.iter()).map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// The number of entries, summed over the shards one at a time.
+    pub fn len(&self) -> usize {
+        self.shards().map(|shard| shard.values.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards().all(|shard| shard.values.is_empty())
+    }
+
+    /// The number of entries all the shards can hold.
+    pub fn capacity(&self) -> usize {
+        self.shards.len() * self.shard_capacity
+    }
+
+    pub fn shard_capacity(&self) -> usize {
+        self.shard_capacity
+    }
+
+    /// Whether `key` is cached, without counting a hit or a miss.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.shard(key).values.contains_key(key)
+    }
+
+    /// Looks up `key` without counting it as used, or as a hit or miss.
+    pub fn peek(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.shard(key).values.peek(key).cloned()
+    }
+
+    /// Looks up `key`, making it the most recently used.
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        let value = self.shard(key).get(key).cloned();
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Relaxed);
+        value
+    }
+
+    /// Inserts or updates an entry, evicting its shard's least recently
+    /// used one if the shard is full. Returns the previous value for `key`.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let mut shard = self.shard(&key);
+        if !shard.recency.contains(&key) && shard.values.len() == self.shard_capacity {
+            let oldest = shard.recency.pop_oldest().unwrap();
+            shard.values.remove(&oldest);
+        }
+        shard.recency.push(key.clone());
+        shard.values.insert(key, value)
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let mut shard = self.shard(key);
+        shard.recency.remove(key);
+        shard.values.remove(key)
+    }
+
+    /// Drops every entry, one shard at a time. The counts are kept.
+    pub fn clear(&self) {
+        for mut shard in self.shards() {
+            shard.recency.clear();
+            shard.values.clear();
+        }
+    }
+
+    /// The number of `get`s which found their key.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Relaxed)
+    }
+
+    /// The number of `get`s which didn't.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Relaxed)
+    }
+
+    /// Sets the hit and miss counts back to zero.
+    pub fn reset_counts(&self) {
+        self.hits.store(0, Relaxed);
+        self.misses.store(0, Relaxed);
+    }
+}
+
+impl<K, V, S> fmt::Debug for ShardedLruCache<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedLruCache")
+            .field("shards", &self.shards.len())
+            .field("shard_capacity", &self.shard_capacity)
+            .field("hits", &self.hits.load(Relaxed))
+            .field("misses", &self.misses.load(Relaxed))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::VecDeque;
+    use std::hash::BuildHasherDefault;
+    use std::thread;
+
+    #[test]
+    fn basic_test() {
+        let cache = ShardedLruCache::new(1, 3);
+        for key in 0..3 {
+            cache.insert(key, key * 10);
+        }
+        assert_eq!(cache.get(&0), Some(0));
+        // 1 is the least recently used now.
+        assert_eq!(cache.insert(3, 30), None);
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.insert(0, 1), Some(0));
+        assert_eq!(cache.remove(&2), Some(20));
+        assert_eq!((cache.len(), cache.hits(), cache.misses()), (2, 1, 1));
+
+        // A fixed hasher puts the same keys in the same shards every run.
+        let seeded = || {
+            let cache =
+                ShardedLruCache::with_hasher(4, 8, BuildHasherDefault::<DefaultHasher>::default());
+            (0..16).for_each(|key| _ = cache.insert(key, ()));
+            cache
+                .shards()
+                .map(|shard| shard.values.iter().map(|(&k, _)| k).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(seeded(), seeded());
+
+        // Shards evict on their own, so no shard is ever over its capacity.
+        let cache = ShardedLruCache::new(8, 16);
+        thread::scope(|s| {
+            for t in 0..4u32 {
+                let cache = &cache;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        let key = (i * 31 + t) % 300;
+                        if cache.get(&key).is_none() {
+                            cache.insert(key, key);
+                        }
+                    }
+                });
+            }
+        });
+        assert!(cache.shards().all(|shard| shard.values.len() <= 16));
+        assert_eq!(cache.hits() + cache.misses(), 4000);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>, capacity: u8) -> bool {
+        // With one shard, it's an exact LRU.
+        let capacity = capacity as usize % 8 + 1;
+        let cache = ShardedLruCache::new(1, capacity);
+        // Keys and values, least recently used first.
+        let mut model: VecDeque<(u8, usize)> = VecDeque::new();
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 16;
+            let found = model.iter().position(|&(k, _)| k == key);
+            let ok = match op % 3 {
+                0 => {
+                    let old = found.map(|j| model.remove(j).unwrap().1);
+                    if old.is_none() && model.len() == capacity {
+                        model.pop_front();
+                    }
+                    model.push_back((key, i));
+                    cache.insert(key, i) == old
+                }
+                1 => {
+                    let entry = found.map(|j| model.remove(j).unwrap());
+                    model.extend(entry);
+                    cache.get(&key) == entry.map(|(_, v)| v)
+                }
+                _ => cache.remove(&key) == found.map(|j| model.remove(j).unwrap().1),
+            };
+            if !ok || cache.len() != model.len() {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,182 @@
+use std::future::Future;
+use std::hash::{BuildHasher, Hash};
+use std::pin::Pin;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+
+use super::ShardedLruCache;
+
+/// A key being loaded, which callers wanting it too wait on.
+#[derive(Default)]
+pub(super) struct Claim {
+    // Whether the load is over, and who's waiting for it.
+    state: Mutex<(bool, Vec<Waker>)>,
+}
+
+impl Claim {
+    fn finish(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.0 = true;
+        state.1.drain(..).for_each(Waker::wake);
+    }
+}
+
+struct Wait<'a>(&'a Claim);
+
+impl Future for Wait<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.0.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.0 {
+            return Poll::Ready(());
+        }
+        if !state.1.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.1.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Gives up a claim however its load ends, cancelled included.
+struct Release<'a, K: Hash + Ord + Clone, V, S: BuildHasher> {
+    cache: &'a ShardedLruCache<K, V, S>,
+    key: &'a K,
+    claim: Arc<Claim>,
+}
+
+impl<K: Hash + Ord + Clone, V, S: BuildHasher> Drop for Release<'_, K, V, S> {
+    fn drop(&mut self) {
+        let mut shard = self.cache.shard(self.key);
+        if shard
+            .claims
+            .peek(self.key)
+            .is_some_and(|c| Arc::ptr_eq(c, &self.claim))
+        {
+            shard.claims.remove(self.key);
+        }
+        drop(shard);
+        self.claim.finish();
+    }
+}
+
+impl<K: Hash + Ord + Clone, V: Clone, S: BuildHasher> ShardedLruCache<K, V, S> {
+    /// Looks up `key`, or else awaits `load` for its value and caches it.
+    ///
+    /// While it's loading, the key is claimed: other callers wanting it
+    /// await the same load instead of starting their own, and nothing else
+    /// is held, so the rest of the cache is free. If the load is cancelled
+    /// by dropping its future, a waiting caller loads the key with its own
+    /// `load` instead. A call which doesn't find its key counts one miss.
+    pub async fn get_or_insert_with<F, Fut>(&self, key: K, load: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let mut missed = false;
+        loop {
+            let (claim, ours) = {
+                let mut shard = self.shard(&key);
+                if let Some(value) = shard.get(&key) {
+                    let value = value.clone();
+                    drop(shard);
+                    let counter = if missed { &self.misses } else { &self.hits };
+                    counter.fetch_add(1, Relaxed);
+                    return value;
+                }
+                match shard.claims.peek(&key) {
+                    Some(claim) => (claim.clone(), false),
+                    None => {
+                        let claim = Arc::<Claim>::default();
+                        shard.claims.insert(key.clone(), claim.clone());
+                        (claim, true)
+                    }
+                }
+            };
+            missed = true;
+            if !ours {
+                Wait(&claim).await;
+                continue;
+            }
+            let release = Release {
+                cache: self,
+                key: &key,
+                claim,
+            };
+            let value = load().await;
+            self.insert(key.clone(), value.clone());
+            drop(release);
+            self.misses.fetch_add(1, Relaxed);
+            return value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::task::Wake;
+    use std::thread::{self, Thread};
+    use std::time::Duration;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    #[test]
+    fn basic_test() {
+        let cache = ShardedLruCache::new(4, 8);
+        assert_eq!(block_on(cache.get_or_insert_with(1, || async { 10 })), 10);
+        assert_eq!(block_on(cache.get_or_insert_with(1, || async { 20 })), 10);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // Callers wanting a key being loaded wait for that load.
+        let loads = AtomicUsize::new(0);
+        let values: Vec<_> = thread::scope(|s| {
+            let callers: Vec<_> = (0..8)
+                .map(|_| {
+                    s.spawn(|| {
+                        block_on(cache.get_or_insert_with(2, || async {
+                            loads.fetch_add(1, Relaxed);
+                            thread::sleep(Duration::from_millis(50));
+                            7
+                        }))
+                    })
+                })
+                .collect();
+            callers.into_iter().map(|c| c.join().unwrap()).collect()
+        });
+        assert_eq!(values, [7; 8]);
+        assert_eq!(loads.into_inner(), 1);
+
+        // A cancelled load gives up its claim.
+        let mut cancelled = Box::pin(cache.get_or_insert_with(3, std::future::pending));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(cancelled.as_mut().poll(&mut cx).is_pending());
+        drop(cancelled);
+        assert_eq!(block_on(cache.get_or_insert_with(3, || async { 30 })), 30);
+        assert!(cache.shards().all(|shard| shard.claims.is_empty()));
+
+        // The future can go to another thread, as executors need.
+        fn send<T: Send>(_: T) {}
+        send(cache.get_or_insert_with(4, || async { 40 }));
+    }
+}
@@ -0,0 +1,209 @@
+use core::borrow::Borrow;
+
+use super::Recency;
+use crate::splay::Splay;
+
+/// A cache with the full 2Q policy of Johnson and Shasha.
+///
+/// New keys enter `a1_in`, a FIFO which hits don't reorder. Keys falling out
+/// of it are remembered in the ghost list `a1_out`, and only a key missed
+/// again while it's there is admitted to the LRU `am`. One-hit wonders pass
+/// through `a1_in` without ever touching the main queue.
+#[derive(Clone)]
+pub struct TwoQCache<K, V> {
+    capacity: usize,
+    in_len: usize,
+    out_len: usize,
+    a1_in: Recency<K>,
+    a1_out: Recency<K>,
+    am: Recency<K>,
+    values: Splay<K, V>,
+}
+
+impl<K: Ord + Clone, V> TwoQCache<K, V> {
+    /// A cache with the ratios suggested in the paper: a quarter of the
+    /// entries for `a1_in` and ghosts for half as many keys as fit.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_ratios(capacity, 0.25, 0.5)
+    }
+
+    /// `in_ratio` is the share of `capacity` kept for keys seen once, and
+    /// `out_ratio` the number of ghosts relative to `capacity`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero, `in_ratio` isn't within `0.0..=1.0`
+    /// or `out_ratio` is negative.
+    pub fn with_ratios(capacity: usize, in_ratio: f64, out_ratio: f64) -> Self {
+        assert!(capacity > 0, "cache capacity must be positive");
+        assert!(
+            (0.0..=1.0).contains(&in_ratio),
+            "in_ratio must be within 0..=1"
+        );
+        assert!(out_ratio >= 0.0, "out_ratio must not be negative");
+        TwoQCache {
+            capacity,
+            in_len: (capacity as f64 * in_ratio) as usize,
+            out_len: (capacity as f64 * out_ratio) as usize,
+            a1_in: Recency::new(),
+            a1_out: Recency::new(),
+            am: Recency::new(),
+            values: Splay::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.values.contains_key(key)
+    }
+
+    /// Looks up `key` without counting it as a hit.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.values.peek(key)
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Looks up `key`, refreshing it if it's in the main queue. Hits in
+    /// `a1_in` leave it alone, they're likely part of the same burst.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        if self.am.contains(key) {
+            self.am.push(key.clone());
+        } else if !self.a1_in.contains(key) {
+            return None;
+        }
+        self.values.get_mut(key)
+    }
+
+    /// Inserts or updates an entry, evicting one if the cache is full.
+    /// Returns the previous value for `key`.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.am.contains(&key) {
+            self.am.push(key.clone());
+            return self.values.insert(key, value);
+        }
+        if self.a1_in.contains(&key) {
+            return self.values.insert(key, value);
+        }
+        self.reclaim();
+        if self.a1_out.remove(&key) {
+            self.am.push(key.clone());
+        } else {
+            self.a1_in.push(key.clone());
+        }
+        self.values.insert(key, value)
+    }
+
+    /// Makes room for one entry if the cache is full, from `a1_in` while
+    /// it's over its share and from `am` otherwise.
+    fn reclaim(&mut self) {
+        if self.values.len() < self.capacity {
+            return;
+        }
+        if self.a1_in.len() > self.in_len || self.am.len() == 0 {
+            let key = self.a1_in.pop_oldest().unwrap();
+            self.values.remove(&key);
+            self.a1_out.push(key);
+            if self.a1_out.len() > self.out_len {
+                self.a1_out.pop_oldest();
+            }
+        } else {
+            let key = self.am.pop_oldest().unwrap();
+            self.values.remove(&key);
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        if !self.a1_in.remove(key) && !self.am.remove(key) {
+            return None;
+        }
+        self.values.remove(key)
+    }
+
+    /// Drops all entries and the ghost list.
+    pub fn clear(&mut self) {
+        for queue in [&mut self.a1_in, &mut self.a1_out, &mut self.am] {
+            queue.clear();
+        }
+        self.values.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut cache = TwoQCache::with_ratios(4, 0.5, 1.0);
+        for key in 0..4 {
+            cache.insert(key, key);
+        }
+        // 0 falls out to the ghosts and comes back into `am`.
+        cache.insert(4, 4);
+        assert!(!cache.contains_key(&0));
+        assert_eq!(cache.insert(0, 10), None);
+        assert!(cache.am.contains(&0));
+        assert_eq!(cache.get(&0), Some(&10));
+
+        // A scan only churns `a1_in`.
+        for key in 100..200 {
+            cache.insert(key, key);
+        }
+        assert_eq!(cache.peek(&0), Some(&10));
+        assert_eq!(cache.remove(&0), Some(10));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>, capacity: u8, ratio: u8) -> bool {
+        let capacity = capacity as usize % 8 + 1;
+        let in_ratio = (ratio % 5) as f64 / 4.0;
+        let mut cache = TwoQCache::with_ratios(capacity, in_ratio, 0.5);
+        let mut last = std::collections::BTreeMap::new();
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 16;
+            match op % 3 {
+                0 => {
+                    cache.insert(key, i);
+                    last.insert(key, i);
+                }
+                1 => {
+                    if cache.get(&key).is_some_and(|v| last.get(&key) != Some(v)) {
+                        return false;
+                    }
+                }
+                _ => drop(cache.remove(&key)),
+            }
+            if cache.len() > capacity
+                || cache.len() != cache.a1_in.len() + cache.am.len()
+                || cache.a1_out.len() > cache.out_len
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
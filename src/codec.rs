@@ -0,0 +1,207 @@
+use std::io::{self, Read, Write};
+
+/// Binary encoding used by the on-disk formats. Integers are little-endian,
+/// strings and vectors are prefixed with their length as a `u64`.
+pub trait Codec: Sized {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()>;
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self>;
+}
+
+macro_rules! int_codec {
+    ($($t:ty),*) => {
+        $(
+            impl Codec for $t {
+                fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+                    w.write_all(&self.to_le_bytes())
+                }
+
+                fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+                    let mut buf = [0; std::mem::size_of::<$t>()];
+                    r.read_exact(&mut buf)?;
+                    Ok(<$t>::from_le_bytes(buf))
+                }
+            }
+        )*
+    };
+}
+
+int_codec!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Codec for usize {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u64).encode(w)
+    }
+
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        usize::try_from(u64::decode(r)?).map_err(|_| invalid_data("length overflow"))
+    }
+}
+
+impl Codec for bool {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u8).encode(w)
+    }
+
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        match u8::decode(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool")),
+        }
+    }
+}
+
+impl Codec for () {
+    fn encode<W: Write + ?Sized>(&self, _: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn decode<R: Read + ?Sized>(_: &mut R) -> io::Result<Self> {
+        Ok(())
+    }
+}
+
+impl<A: Codec, B: Codec> Codec for (A, B) {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.0.encode(w)?;
+        self.1.encode(w)
+    }
+
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        Ok((A::decode(r)?, B::decode(r)?))
+    }
+}
+
+impl<T: Codec> Codec for Option<T> {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.is_some().encode(w)?;
+        match self {
+            Some(value) => value.encode(w),
+            None => Ok(()),
+        }
+    }
+
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        match bool::decode(r)? {
+            true => Ok(Some(T::decode(r)?)),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<T: Codec> Codec for Vec<T> {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.len().encode(w)?;
+        for item in self.iter() {
+            item.encode(w)?;
+        }
+        Ok(())
+    }
+
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        let len = usize::decode(r)?;
+        // Don't trust the length with an allocation up front.
+        let mut items = Vec::with_capacity(std::cmp::min(len, 1024));
+        for _ in 0..len {
+            items.push(T::decode(r)?);
+        }
+        Ok(items)
+    }
+}
+
+impl Codec for String {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.len().encode(w)?;
+        w.write_all(self.as_bytes())
+    }
+
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        let len = u64::decode(r)?;
+        let mut buf = Vec::new();
+        r.take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        String::from_utf8(buf).map_err(|_| invalid_data("invalid utf-8"))
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a over everything passing through, used as a cheap integrity check.
+pub(crate) struct Checksummed<T> {
+    pub(crate) inner: T,
+    hash: u64,
+}
+
+impl<T> Checksummed<T> {
+    pub(crate) fn new(inner: T) -> Self {
+        Checksummed {
+            inner,
+            hash: FNV_OFFSET,
+        }
+    }
+
+    pub(crate) fn checksum(&self) -> u64 {
+        self.hash
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.hash = (self.hash ^ b as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    fn roundtrip<T: Codec>(value: &T) -> T {
+        let mut buf = Vec::new();
+        value.encode(&mut buf).unwrap();
+        let mut slice = &buf[..];
+        let decoded = T::decode(&mut slice).unwrap();
+        assert!(slice.is_empty());
+        decoded
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(value: Vec<(String, Option<i64>)>) -> bool {
+        roundtrip(&value) == value
+    }
+
+    #[test]
+    fn truncated_test() {
+        let mut buf = Vec::new();
+        "hello".to_string().encode(&mut buf).unwrap();
+        buf.pop();
+        assert!(String::decode(&mut &buf[..]).is_err());
+    }
+}
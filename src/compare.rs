@@ -0,0 +1,331 @@
+//! Ordering keys by something other than their `Ord` impl.
+//!
+//! A [`Splay`](crate::splay::Splay) can hold a [`Compare`] of its own, a
+//! closure or any other comparator, which it uses for every search:
+//!
+//! ```
+//! use crab_bucket::splay::Splay;
+//!
+//! let mut tree = Splay::with_comparator(|a: &&str, b: &&str| {
+//!     a.bytes()
+//!         .map(|b| b.to_ascii_lowercase())
+//!         .cmp(b.bytes().map(|b| b.to_ascii_lowercase()))
+//! });
+//! tree.set("Hello", 1);
+//! assert_eq!(tree.get(&"HELLO"), Some(&1));
+//! ```
+//!
+//! Structures which only take `K: Ord` can have their key wrapped in
+//! [`By`] instead, which orders itself with a comparator type.
+
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+/// A total order on `T`. Closures taking two `&T` are comparators too.
+pub trait Compare<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+impl<T: ?Sized, F: Fn(&T, &T) -> Ordering> Compare<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// Orders by the key's own `Ord` impl.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Natural;
+
+impl<T: Ord + ?Sized> Compare<T> for Natural {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// Reverses another comparator, for maps which iterate from largest to
+/// smallest.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Descending<C = Natural>(pub C);
+
+impl<T: ?Sized, C: Compare<T>> Compare<T> for Descending<C> {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self.0.compare(b, a)
+    }
+}
+
+/// A key sorting in descending order of its `Ord` impl.
+pub type Desc<K> = By<Descending, K>;
+
+/// IEEE 754 `totalOrder` for floats, as in `f64::total_cmp`: negative NaNs
+/// sort before everything, positive NaNs after everything, `-0.0` before
+/// `0.0`, and NaNs with different payloads are different keys.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TotalOrder;
+
+impl Compare<f32> for TotalOrder {
+    fn compare(&self, a: &f32, b: &f32) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+impl Compare<f64> for TotalOrder {
+    fn compare(&self, a: &f64, b: &f64) -> Ordering {
+        a.total_cmp(b)
+    }
+}
+
+/// A float usable as a key, see [`TotalOrder`] for how NaNs and zeros sort.
+pub type FloatKey<F> = By<TotalOrder, F>;
+
+/// A key ordered by `C` instead of its own `Ord` impl. `C` is made with
+/// `Default` for every comparison, so it should be a plain type rather than
+/// a comparator carrying state.
+#[repr(transparent)]
+pub struct By<C, K: ?Sized> {
+    cmp: PhantomData<fn() -> C>,
+    key: K,
+}
+
+impl<C, K> By<C, K> {
+    pub fn new(key: K) -> Self {
+        By {
+            cmp: PhantomData,
+            key,
+        }
+    }
+
+    pub fn into_inner(self) -> K {
+        self.key
+    }
+}
+
+impl<C, K: ?Sized> By<C, K> {
+    /// Views a borrowed key as ordered by `C`, for lookups.
+    pub fn from_ref(key: &K) -> &Self {
+        // SAFETY: `By` is a transparent wrapper around `K`.
+        unsafe { &*(key as *const K as *const Self) }
+    }
+}
+
+impl<C, K: ?Sized> Deref for By<C, K> {
+    type Target = K;
+
+    fn deref(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<C: Compare<K> + Default, K: ?Sized> Ord for By<C, K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        C::default().compare(&self.key, &other.key)
+    }
+}
+
+impl<C: Compare<K> + Default, K: ?Sized> PartialOrd for By<C, K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<C: Compare<K> + Default, K: ?Sized> PartialEq for By<C, K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<C: Compare<K> + Default, K: ?Sized> Eq for By<C, K> {}
+
+impl<C, K: Clone> Clone for By<C, K> {
+    fn clone(&self) -> Self {
+        By::new(self.key.clone())
+    }
+}
+
+impl<C, K: Copy> Copy for By<C, K> {}
+
+impl<C, K: fmt::Debug + ?Sized> fmt::Debug for By<C, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.key.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::Counter;
+    use crate::splay::Splay;
+    use quickcheck_macros::quickcheck;
+    use std::cmp::Reverse;
+    use std::collections::BTreeMap;
+    use std::ops::Bound::{Excluded, Included};
+
+    #[derive(Default)]
+    struct ByLen;
+
+    impl Compare<&str> for ByLen {
+        fn compare(&self, a: &&str, b: &&str) -> Ordering {
+            a.len().cmp(&b.len())
+        }
+    }
+
+    impl Compare<String> for ByLen {
+        fn compare(&self, a: &String, b: &String) -> Ordering {
+            a.len().cmp(&b.len())
+        }
+    }
+
+    #[derive(Debug)]
+    struct Person {
+        name: &'static str,
+        age: u32,
+    }
+
+    #[derive(Default)]
+    struct ByAge;
+
+    impl Compare<Person> for ByAge {
+        fn compare(&self, a: &Person, b: &Person) -> Ordering {
+            a.age.cmp(&b.age)
+        }
+    }
+
+    #[test]
+    fn basic_test() {
+        let mut tree: Splay<By<ByLen, String>, u32> = Splay::new();
+        for word in ["ccc", "a", "bb", "dd"] {
+            tree.set(By::new(word.to_string()), 0);
+        }
+        let keys: Vec<&str> = tree.keys().map(|k| k.as_str()).collect();
+        assert_eq!(keys, ["a", "bb", "ccc"]);
+        assert!(tree.contains_key(By::from_ref(&"xy".to_string())));
+
+        let people = [Person { name: "b", age: 30 }, Person { name: "a", age: 20 }];
+        let tree: Splay<By<ByAge, Person>, ()> =
+            people.into_iter().map(|p| (By::new(p), ())).collect();
+        assert_eq!(tree.first_key_value().unwrap().0.name, "a");
+    }
+
+    #[test]
+    fn float_test() {
+        let keys = [1.5, f64::NAN, -0.0, f64::NEG_INFINITY, 0.0, -f64::NAN, 1.5];
+        let tree: Splay<FloatKey<f64>, usize> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, &k)| (FloatKey::new(k), i))
+            .collect();
+        let sorted: Vec<f64> = tree.keys().map(|k| **k).collect();
+        assert_eq!(sorted.len(), 6);
+        assert!(sorted[0].is_nan() && sorted[0].is_sign_negative());
+        assert_eq!(sorted[1..5], [f64::NEG_INFINITY, -0.0, 0.0, 1.5]);
+        assert!(sorted[2].is_sign_negative() && sorted[3].is_sign_positive());
+        assert!(sorted[5].is_nan());
+        assert_eq!(tree.peek(FloatKey::from_ref(&1.5)), Some(&6));
+        assert_eq!(tree.peek(FloatKey::from_ref(&f64::NAN)), Some(&1));
+    }
+
+    #[test]
+    fn descending_test() {
+        let tree: Splay<Desc<u32>, &str> = [(10, "c"), (30, "a"), (20, "b")]
+            .into_iter()
+            .map(|(score, name)| (Desc::new(score), name))
+            .collect();
+        assert_eq!(tree.values().copied().collect::<Vec<_>>(), ["a", "b", "c"]);
+        let top: Vec<u32> = tree
+            .range(Desc::from_ref(&25)..)
+            .map(|(k, _)| **k)
+            .collect();
+        assert_eq!(top, [20, 10]);
+
+        let floats: Splay<By<Descending<TotalOrder>, f64>, ()> =
+            [0.5, 2.5, 1.5].map(|f| (By::new(f), ())).into();
+        assert_eq!(
+            floats.keys().map(|k| **k).collect::<Vec<_>>(),
+            [2.5, 1.5, 0.5]
+        );
+    }
+
+    #[test]
+    fn counter_test() {
+        let mut counter: Counter<By<ByLen, &str>> = Counter::new();
+        counter.add(By::new("aa"));
+        counter.add(By::new("bb"));
+        counter.add(By::new("c"));
+        assert_eq!(counter.get(By::from_ref(&"zz")), 2);
+        assert_eq!(counter.len(), 2);
+    }
+
+    #[derive(Clone, Copy)]
+    struct CaseInsensitive;
+
+    impl Compare<str> for CaseInsensitive {
+        fn compare(&self, a: &str, b: &str) -> Ordering {
+            let lower = |s: &str| {
+                s.bytes()
+                    .map(|b| b.to_ascii_lowercase())
+                    .collect::<Vec<_>>()
+            };
+            lower(a).cmp(&lower(b))
+        }
+    }
+
+    impl Compare<String> for CaseInsensitive {
+        fn compare(&self, a: &String, b: &String) -> Ordering {
+            self.compare(a.as_str(), b.as_str())
+        }
+    }
+
+    #[test]
+    fn stored_test() {
+        let mut tree = Splay::with_comparator(CaseInsensitive);
+        tree.set("Hello".to_string(), 1);
+        tree.set("world".to_string(), 2);
+        tree.set("HELLO".to_string(), 3);
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.get("hello"), Some(&3));
+        assert_eq!(tree.peek("WORLD"), Some(&2));
+        let keys: Vec<&String> = tree
+            .range::<str, _>((Included("A"), Excluded("x")))
+            .map(|(k, _)| k)
+            .collect();
+        assert_eq!(keys, ["Hello", "world"]);
+        assert_eq!(tree.remove("World"), Some(2));
+        assert!(tree.check_invariants().is_ok());
+
+        // A comparator with state of its own, here a ranking of the keys.
+        let rank = ["low", "mid", "high"];
+        let position = move |k: &&str| rank.iter().position(|r| r == k);
+        let mut tree =
+            Splay::with_comparator(move |a: &&str, b: &&str| position(a).cmp(&position(b)));
+        tree.extend([("high", 3), ("low", 1), ("mid", 2)]);
+        assert_eq!(tree.keys().copied().collect::<Vec<_>>(), rank);
+        assert_eq!(tree.get(&"mid"), Some(&2));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>, a: u8, b: u8) -> bool {
+        let mut tree = Splay::with_comparator(Descending(Natural));
+        let mut model = BTreeMap::new();
+        for (op, key) in ops {
+            let ok = match op % 3 {
+                0 => {
+                    tree.set(key, op);
+                    model.insert(Reverse(key), op);
+                    true
+                }
+                1 => tree.remove(&key) == model.remove(&Reverse(key)),
+                _ => tree.get(&key) == model.get(&Reverse(key)),
+            };
+            if !ok {
+                return false;
+            }
+        }
+        let (hi, lo) = (a.max(b), a.min(b));
+        tree.check_invariants().is_ok()
+            && tree.iter().eq(model.iter().map(|(Reverse(k), v)| (k, v)))
+            && tree.range(hi..=lo).eq(model
+                .range(Reverse(hi)..=Reverse(lo))
+                .map(|(Reverse(k), v)| (k, v)))
+    }
+}
@@ -0,0 +1,35 @@
+//! Concurrent structures for handing work and counts between threads.
+//! The queues and the stack are lock-free and `ShardedCounter` only adds
+//! to atomics, while `SkipMap` locks the nodes a change goes through.
+
+mod deque;
+mod mpmc;
+#[cfg(feature = "std")]
+mod sharded_counter;
+#[cfg(feature = "std")]
+mod skip_map;
+mod stack;
+
+pub use deque::{Stealer, Worker};
+pub use mpmc::MpmcQueue;
+#[cfg(feature = "std")]
+pub use sharded_counter::ShardedCounter;
+#[cfg(feature = "std")]
+pub use skip_map::SkipMap;
+pub use stack::TreiberStack;
+
+use core::ops::Deref;
+
+/// Keeps its contents on a cache line of their own, so that atomics written
+/// by different threads don't slow each other down by sharing one.
+#[derive(Debug, Default)]
+#[repr(align(128))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
@@ -0,0 +1,362 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
+use core::sync::atomic::{fence, AtomicIsize, AtomicPtr};
+
+use super::CachePadded;
+
+const MIN_CAPACITY: usize = 16;
+
+/// A ring of slots, indexed by position modulo its power of two size.
+struct Buffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+impl<T> Buffer<T> {
+    fn alloc(capacity: usize) -> *mut Self {
+        let slots = (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect();
+        Box::into_raw(Box::new(Buffer { slots }))
+    }
+
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, pos: isize) -> *mut MaybeUninit<T> {
+        self.slots[pos as usize & (self.slots.len() - 1)].get()
+    }
+
+    /// # Safety
+    ///
+    /// Nothing may read the slot for `pos` at the same time.
+    unsafe fn write(&self, pos: isize, item: T) {
+        unsafe { (*self.slot(pos)).write(item) };
+    }
+
+    /// Copies out the slot for `pos`, which only holds an item if one was
+    /// written there since.
+    ///
+    /// # Safety
+    ///
+    /// The caller may only treat it as an item when it was written there,
+    /// and it can't be read again.
+    unsafe fn read(&self, pos: isize) -> MaybeUninit<T> {
+        unsafe { ptr::read_volatile(self.slot(pos)) }
+    }
+}
+
+struct Inner<T> {
+    // Thieves take from `top` and the owner pushes and pops at `bottom`.
+    top: CachePadded<AtomicIsize>,
+    bottom: CachePadded<AtomicIsize>,
+    buffer: AtomicPtr<Buffer<T>>,
+    // The buffers grown out of, which a thief may still be reading, kept
+    // until the deque is dropped, at most as much again as the largest.
+    // Only the worker touches this until then.
+    retired: UnsafeCell<Vec<*mut Buffer<T>>>,
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let buffer = *self.buffer.get_mut();
+        let (top, bottom) = (*self.top.0.get_mut(), *self.bottom.0.get_mut());
+        // SAFETY: with the worker and every thief gone, the items between
+        // `top` and `bottom` are the deque's, and so are the buffers.
+        unsafe {
+            for pos in top..bottom {
+                (*(*buffer).slot(pos)).assume_init_drop();
+            }
+            drop(Box::from_raw(buffer));
+            for &old in &*self.retired.get() {
+                drop(Box::from_raw(old));
+            }
+        }
+    }
+}
+
+/// The owner's end of a work-stealing deque (Chase and Lev's), used like a
+/// stack while [`Stealer`]s take the oldest items from the other end.
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// A thief's end of a work-stealing deque, taking from the opposite end
+/// to its [`Worker`]. It can be cloned and shared between threads.
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+// SAFETY: the worker hands items out to other threads, and only it
+// touches `retired` or writes to the buffer.
+unsafe impl<T: Send> Send for Worker<T> {}
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+impl<T> Inner<T> {
+    fn len(&self) -> usize {
+        let top = self.top.load(Acquire);
+        let bottom = self.bottom.load(Acquire);
+        bottom.saturating_sub(top).max(0) as usize
+    }
+}
+
+impl<T> Worker<T> {
+    pub fn new() -> Self {
+        Worker {
+            inner: Arc::new(Inner {
+                top: CachePadded(AtomicIsize::new(0)),
+                bottom: CachePadded(AtomicIsize::new(0)),
+                buffer: AtomicPtr::new(Buffer::alloc(MIN_CAPACITY)),
+                retired: UnsafeCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// A thief's end of this deque.
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// The number of items, which thieves may have lowered by the time it's
+    /// returned.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes `item` on the owner's end.
+    pub fn push(&mut self, item: T) {
+        let inner = &*self.inner;
+        let bottom = inner.bottom.load(Relaxed);
+        let top = inner.top.load(Acquire);
+        let mut buffer = inner.buffer.load(Relaxed);
+        // SAFETY: only the worker replaces the buffer.
+        if bottom - top >= unsafe { (*buffer).capacity() } as isize {
+            buffer = self.grow(top, bottom);
+        }
+        // SAFETY: thieves only read below `bottom`, and the slot for it was
+        // last read for a position the ring has since gone past.
+        unsafe { (*buffer).write(bottom, item) };
+        // Ordering: the fence publishes the item before the new `bottom`,
+        // for thieves which acquire `bottom` in `steal`.
+        fence(Release);
+        inner.bottom.store(bottom + 1, Relaxed);
+    }
+
+    /// Moves the items into a ring twice the size, returning it.
+    fn grow(&self, top: isize, bottom: isize) -> *mut Buffer<T> {
+        let inner = &*self.inner;
+        let old = inner.buffer.load(Relaxed);
+        // SAFETY: the worker owns the buffers, and copying the items leaves
+        // the old ring as it was for thieves still reading it. Each item is
+        // still taken only once, from whichever ring.
+        unsafe {
+            let new = Buffer::alloc(2 * (*old).capacity());
+            for pos in top..bottom {
+                ptr::copy_nonoverlapping((*old).slot(pos), (*new).slot(pos), 1);
+            }
+            inner.buffer.store(new, Release);
+            (*inner.retired.get()).push(old);
+            new
+        }
+    }
+
+    /// Pops the item last pushed, unless thieves took everything.
+    pub fn pop(&mut self) -> Option<T> {
+        let inner = &*self.inner;
+        let bottom = inner.bottom.load(Relaxed) - 1;
+        let buffer = inner.buffer.load(Relaxed);
+        inner.bottom.store(bottom, Relaxed);
+        // Ordering: the fence orders taking the slot by lowering `bottom`
+        // before reading `top`, against the thieves' fence between reading
+        // `top` and `bottom`, so a thief and the owner can't both take the
+        // last item without racing for `top`. Elsewhere the owner's
+        // pushes and pops touch no cache line a thief writes.
+        fence(SeqCst);
+        let top = inner.top.load(Relaxed);
+        if top > bottom {
+            inner.bottom.store(bottom + 1, Relaxed);
+            return None;
+        }
+        if top == bottom {
+            // The last item: race the thieves for it.
+            let won = (inner.top)
+                .compare_exchange(top, top + 1, SeqCst, Relaxed)
+                .is_ok();
+            inner.bottom.store(bottom + 1, Relaxed);
+            if !won {
+                return None;
+            }
+        }
+        // SAFETY: the item at `bottom` is the worker's, with thieves kept
+        // off it by `bottom` or by winning the race for `top`.
+        Some(unsafe { (*buffer).read(bottom).assume_init() })
+    }
+}
+
+impl<T> Default for Worker<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for Worker<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Worker").field("len", &self.len()).finish()
+    }
+}
+
+impl<T> Stealer<T> {
+    /// Takes the oldest item, unless the deque is empty.
+    pub fn steal(&self) -> Option<T> {
+        let inner = &*self.inner;
+        loop {
+            let top = inner.top.load(Acquire);
+            // Ordering: pairs with the fence in `pop`.
+            fence(SeqCst);
+            let bottom = inner.bottom.load(Acquire);
+            if top >= bottom {
+                return None;
+            }
+            let buffer = inner.buffer.load(Acquire);
+            // SAFETY: the buffer is kept until the deque is dropped. The
+            // copy is only kept if winning the race for `top` shows no one
+            // took the item first, in which case the worker can't have
+            // written over it either.
+            let item = unsafe { (*buffer).read(top) };
+            if (inner.top)
+                .compare_exchange(top, top + 1, SeqCst, Relaxed)
+                .is_ok()
+            {
+                return Some(unsafe { item.assume_init() });
+            }
+        }
+    }
+
+    /// The number of items, which may have changed by the time it's
+    /// returned.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Stealer {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Stealer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Stealer").field("len", &self.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use quickcheck_macros::quickcheck;
+    use std::collections::VecDeque;
+    use std::thread;
+
+    #[test]
+    fn basic_test() {
+        let mut worker = Worker::new();
+        let stealer = worker.stealer();
+        for i in 0..40 {
+            worker.push(i);
+        }
+        assert_eq!(worker.len(), 40);
+        assert_eq!(worker.pop(), Some(39));
+        assert_eq!(stealer.steal(), Some(0));
+        assert_eq!(stealer.clone().steal(), Some(1));
+        while worker.pop().is_some() {}
+        assert_eq!((worker.pop(), stealer.steal()), (None, None));
+
+        // Every item is taken once, by the worker or one of the thieves,
+        // while the worker keeps pushing and popping.
+        let mut worker = Worker::new();
+        let stealer = worker.stealer();
+        let (mine, stolen) = thread::scope(|s| {
+            let thieves: Vec<_> = (0..3)
+                .map(|_| {
+                    let stealer = stealer.clone();
+                    s.spawn(move || {
+                        let mut stolen = vec![];
+                        for _ in 0..2000 {
+                            stolen.extend(stealer.steal());
+                        }
+                        stolen
+                    })
+                })
+                .collect();
+            let mut mine = vec![];
+            for i in 0..3000 {
+                worker.push(i);
+                if i % 3 == 0 {
+                    mine.extend(worker.pop());
+                }
+            }
+            let stolen: Vec<_> = thieves
+                .into_iter()
+                .flat_map(|t| t.join().unwrap())
+                .collect();
+            (mine, stolen)
+        });
+        let mut all: Vec<_> = mine.into_iter().chain(stolen).collect();
+        all.extend(core::iter::from_fn(|| worker.pop()));
+        all.sort_unstable();
+        assert_eq!(all, (0..3000).collect::<Vec<_>>());
+
+        // Items left over are dropped with the last end.
+        let item = Arc::new(());
+        let mut worker = Worker::new();
+        let stealer = worker.stealer();
+        worker.push(item.clone());
+        drop(worker);
+        assert_eq!(Arc::strong_count(&item), 2);
+        drop(stealer);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<Option<u16>>) -> bool {
+        let mut worker = Worker::new();
+        let stealer = worker.stealer();
+        let mut model = VecDeque::new();
+        for op in ops {
+            let ok = match op {
+                Some(item) if item % 4 == 0 => stealer.steal() == model.pop_front(),
+                Some(item) => {
+                    worker.push(item);
+                    model.push_back(item);
+                    true
+                }
+                None => worker.pop() == model.pop_back(),
+            };
+            if !ok || worker.len() != model.len() {
+                return false;
+            }
+        }
+        true
+    }
+}
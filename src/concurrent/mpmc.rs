@@ -0,0 +1,242 @@
+use alloc::boxed::Box;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+
+use super::CachePadded;
+
+struct Slot<T> {
+    // `pos` when free for the push at `pos`, `pos + 1` once holding its
+    // item, for the pop at `pos`.
+    seq: AtomicUsize,
+    item: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A bounded lock-free queue for many producers and consumers (Vyukov's),
+/// failing rather than waiting when it's full or empty.
+pub struct MpmcQueue<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+    // The positions of the next push and the next pop.
+    tail: CachePadded<AtomicUsize>,
+    head: CachePadded<AtomicUsize>,
+}
+
+// SAFETY: items move between threads through the queue, and a slot's item
+// is only touched by the one push or pop which claimed it.
+unsafe impl<T: Send> Send for MpmcQueue<T> {}
+unsafe impl<T: Send> Sync for MpmcQueue<T> {}
+
+impl<T> MpmcQueue<T> {
+    /// A queue of `capacity` slots, rounded up to a power of two, at least two.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(2).next_power_of_two();
+        MpmcQueue {
+            slots: (0..capacity)
+                .map(|pos| Slot {
+                    seq: AtomicUsize::new(pos),
+                    item: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect(),
+            mask: capacity - 1,
+            tail: CachePadded(AtomicUsize::new(0)),
+            head: CachePadded(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The number of items, which other threads may have changed by the
+    /// time it's returned.
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Acquire);
+            let head = self.head.load(Acquire);
+            // Read again to make sure the two were there at the same time.
+            if self.tail.load(Acquire) == tail {
+                return tail.wrapping_sub(head).min(self.capacity());
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Pushes `item` at the back, handing it back if the queue is full.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            // Ordering: acquire pairs with the release store of the pop which
+            // freed the slot, so its read of the old item finishes before the
+            // write below. The position itself is claimed by a relaxed
+            // compare-and-swap, since `seq` carries everything the slot needs.
+            let seq = slot.seq.load(Acquire);
+            match seq.wrapping_sub(pos) as isize {
+                0 => match self.tail.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Relaxed,
+                    Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: the slot is free, as `seq` says, and only
+                        // this push claimed its position.
+                        unsafe { (*slot.item.get()).write(item) };
+                        slot.seq.store(pos.wrapping_add(1), Release);
+                        return Ok(());
+                    }
+                    Err(tail) => pos = tail,
+                },
+                // The slot still holds the item from a lap ago: full.
+                lap if lap < 0 => return Err(item),
+                // Another push got here first.
+                _ => pos = self.tail.load(Relaxed),
+            }
+        }
+    }
+
+    /// Pops the item at the front, if any.
+    pub fn try_pop(&self) -> Option<T> {
+        let mut pos = self.head.load(Relaxed);
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            // Ordering: acquire pairs with the release store of the push which
+            // filled the slot, making its item visible here.
+            let seq = slot.seq.load(Acquire);
+            match seq.wrapping_sub(pos.wrapping_add(1)) as isize {
+                0 => match self.head.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Relaxed,
+                    Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: the push at `pos` wrote the item before
+                        // its release store of `seq`, and only this pop
+                        // claimed its position.
+                        let item = unsafe { (*slot.item.get()).assume_init_read() };
+                        slot.seq.store(pos.wrapping_add(self.mask + 1), Release);
+                        return Some(item);
+                    }
+                    Err(head) => pos = head,
+                },
+                // The push for this position hasn't happened: empty.
+                lap if lap < 0 => return None,
+                // Another pop got here first.
+                _ => pos = self.head.load(Relaxed),
+            }
+        }
+    }
+}
+
+impl<T> Drop for MpmcQueue<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+    }
+}
+
+impl<T> fmt::Debug for MpmcQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MpmcQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use quickcheck_macros::quickcheck;
+    use std::collections::VecDeque;
+    use std::sync::atomic::AtomicU64;
+    use std::thread;
+
+    #[test]
+    fn basic_test() {
+        let queue = MpmcQueue::with_capacity(3);
+        assert_eq!(queue.capacity(), 4);
+        for i in 0..4 {
+            assert_eq!(queue.try_push(i), Ok(()));
+        }
+        assert!(queue.is_full());
+        assert_eq!(queue.try_push(4), Err(4));
+        assert_eq!(queue.try_pop(), Some(0));
+        assert_eq!(queue.try_push(4), Ok(()));
+        assert_eq!(queue.len(), 4);
+        let rest: Vec<_> = core::iter::from_fn(|| queue.try_pop()).collect();
+        assert_eq!(rest, [1, 2, 3, 4]);
+        assert!(queue.is_empty());
+
+        // Every item pushed by four threads is popped by four others, once.
+        let queue = MpmcQueue::with_capacity(16);
+        let (popped, sum) = (AtomicUsize::new(0), AtomicU64::new(0));
+        thread::scope(|s| {
+            for t in 0..4u64 {
+                let queue = &queue;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        let mut item = t * 1000 + i;
+                        while let Err(back) = queue.try_push(item) {
+                            item = back;
+                            thread::yield_now();
+                        }
+                    }
+                });
+            }
+            for _ in 0..4 {
+                s.spawn(|| {
+                    while popped.load(Relaxed) < 4000 {
+                        match queue.try_pop() {
+                            Some(item) => {
+                                sum.fetch_add(item, Relaxed);
+                                popped.fetch_add(1, Relaxed);
+                            }
+                            None => thread::yield_now(),
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(sum.into_inner(), (0..4000).sum::<u64>());
+        assert!(queue.is_empty());
+
+        // Items left over are dropped with the queue.
+        let queue = MpmcQueue::with_capacity(4);
+        let item = std::sync::Arc::new(());
+        queue.try_push(item.clone()).unwrap();
+        drop(queue);
+        assert_eq!(std::sync::Arc::strong_count(&item), 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(capacity: u8, ops: Vec<Option<u16>>) -> bool {
+        let queue = MpmcQueue::with_capacity(capacity as usize % 10);
+        let mut model = VecDeque::new();
+        for op in ops {
+            let ok = match op {
+                Some(item) if model.len() < queue.capacity() => {
+                    model.push_back(item);
+                    queue.try_push(item).is_ok()
+                }
+                Some(item) => queue.try_push(item) == Err(item),
+                None => queue.try_pop() == model.pop_front(),
+            };
+            if !ok || queue.len() != model.len() {
+                return false;
+            }
+        }
+        true
+    }
+}
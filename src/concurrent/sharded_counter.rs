@@ -0,0 +1,126 @@
+use std::fmt;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::thread;
+
+use super::CachePadded;
+
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Threads take shards round robin, in the order they first count.
+    static THREAD: usize = NEXT_THREAD.fetch_add(1, Relaxed);
+}
+
+/// A counter split over per-thread shards on cache lines of their own, so
+/// threads adding to it don't contend. Counts wrap on overflow.
+pub struct ShardedCounter {
+    shards: Box<[CachePadded<AtomicU64>]>,
+}
+
+impl ShardedCounter {
+    /// A counter with a shard for each thread the machine can run at once.
+    pub fn new() -> Self {
+        Self::with_shards(thread::available_parallelism().map_or(1, usize::from))
+    }
+
+    /// A counter with `shards` shards, rounded up to a power of two.
+    pub fn with_shards(shards: usize) -> Self {
+        ShardedCounter {
+            shards: (0..shards.max(1).next_power_of_two())
+                .map(|_| CachePadded(AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard(&self) -> &AtomicU64 {
+        let thread = THREAD.with(|&thread| thread);
+        &self.shards[thread & (self.shards.len() - 1)]
+    }
+
+    pub fn add(&self, n: u64) {
+        // Ordering: relaxed, since the count orders nothing else, and each
+        // shard's adds stay in one modification order for `sum` to read.
+        self.shard().fetch_add(n, Relaxed);
+    }
+
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    /// The count, summed over the shards, so in time linear in their number.
+    /// Adds racing with it may or may not be counted.
+    pub fn sum(&self) -> u64 {
+        self.shards
+            .iter()
+            .fold(0, |sum, shard| sum.wrapping_add(shard.load(Relaxed)))
+    }
+
+    /// Sets the count to zero, returning what it was. No add is lost: each
+    /// is either counted in the result or left for after.
+    pub fn reset(&self) -> u64 {
+        self.shards
+            .iter()
+            .fold(0, |sum, shard| sum.wrapping_add(shard.swap(0, Relaxed)))
+    }
+
+    /// The count, with no other thread adding to it.
+    pub fn into_inner(self) -> u64 {
+        self.sum()
+    }
+}
+
+impl Default for ShardedCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ShardedCounter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ShardedCounter").field(&self.sum()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let counter = ShardedCounter::with_shards(3);
+        assert_eq!(counter.shards(), 4);
+        counter.increment();
+        counter.add(41);
+        assert_eq!(counter.sum(), 42);
+
+        // Adds from many threads at once are all counted, and a reset in
+        // the middle of them loses none.
+        let reset = thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| (0..1000).for_each(|_| counter.increment()));
+            }
+            s.spawn(|| counter.reset()).join().unwrap()
+        });
+        assert_eq!(reset + counter.into_inner(), 42 + 8000);
+    }
+
+    #[quickcheck]
+    #[cfg_attr(miri, ignore = "too slow under Miri")]
+    fn test_quickcheck(shards: u8, adds: Vec<Vec<u32>>) -> bool {
+        let counter = ShardedCounter::with_shards(shards as usize % 8);
+        thread::scope(|s| {
+            for adds in &adds {
+                let counter = &counter;
+                s.spawn(move || adds.iter().for_each(|&n| counter.add(n.into())));
+            }
+        });
+        let total: u64 = adds.iter().flatten().map(|&n| u64::from(n)).sum();
+        counter.sum() == total && counter.reset() == total && counter.sum() == 0
+    }
+}
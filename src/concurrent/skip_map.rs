@@ -0,0 +1,530 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::hint;
+use std::ops::{Bound, RangeBounds};
+use std::sync::atomic::Ordering::{Relaxed, SeqCst};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::rng::split_mix64;
+
+const MAX_HEIGHT: usize = 32;
+
+type Link<K, V> = Option<Arc<Node<K, V>>>;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+struct Node<K, V> {
+    // `None` in the head.
+    key: Option<K>,
+    // Taken by the remove which unlinks the node.
+    value: Mutex<Option<V>>,
+    next: Box<[Mutex<Link<K, V>>]>,
+    // Held to link a node in after this one, or to mark this one.
+    lock: Mutex<()>,
+    // Set once a remove has claimed the node, and once it's linked in at
+    // every level.
+    marked: AtomicBool,
+    linked: AtomicBool,
+}
+
+impl<K, V> Node<K, V> {
+    fn new(entry: Option<(K, V)>, height: usize) -> Self {
+        let (key, value) = entry.unzip();
+        Node {
+            key,
+            value: Mutex::new(value),
+            next: (0..height).map(|_| Mutex::new(None)).collect(),
+            lock: Mutex::new(()),
+            marked: AtomicBool::new(false),
+            linked: AtomicBool::new(false),
+        }
+    }
+
+    fn key(&self) -> &K {
+        self.key.as_ref().expect("the head has no key")
+    }
+
+    fn next(&self, level: usize) -> Link<K, V> {
+        lock(&self.next[level]).clone()
+    }
+
+    fn take_links(&mut self) -> impl Iterator<Item = Arc<Self>> + '_ {
+        (self.next.iter_mut()).filter_map(|link| {
+            link.get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take()
+        })
+    }
+}
+
+impl<K, V> Drop for Node<K, V> {
+    fn drop(&mut self) {
+        // Free the nodes only this one held without recursing down the list.
+        let mut links: Vec<_> = self.take_links().collect();
+        while let Some(link) = links.pop() {
+            if let Some(mut node) = Arc::into_inner(link) {
+                links.extend(node.take_links());
+            }
+        }
+    }
+}
+
+fn same<K, V>(a: &Link<K, V>, b: &Link<K, V>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+/// An ordered map many threads can share through `&self`, a lazy skip list
+/// with a lock per node (Herlihy et al.'s). Values are cloned out.
+pub struct SkipMap<K, V> {
+    head: Arc<Node<K, V>>,
+    len: AtomicUsize,
+    // The height of the tallest node yet, where searches start.
+    height: AtomicUsize,
+    // Splitmix state for node heights.
+    rng: AtomicU64,
+}
+
+impl<K: Ord, V> SkipMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// A map whose node heights only depend on `seed` and the order of its
+    /// inserts. `new` always uses the same seed too, so neither needs any
+    /// entropy from the system.
+    pub fn with_seed(seed: u64) -> Self {
+        SkipMap {
+            head: Arc::new(Node::new(None, MAX_HEIGHT)),
+            len: AtomicUsize::new(0),
+            height: AtomicUsize::new(1),
+            rng: AtomicU64::new(seed),
+        }
+    }
+
+    /// The number of entries, which other threads may have changed by the
+    /// time it's returned.
+    pub fn len(&self) -> usize {
+        self.len.load(SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A height for a new node, each level up half as likely.
+    fn random_height(&self) -> usize {
+        // `fetch_add` hands every caller its own step of the state.
+        let mut state = self.rng.fetch_add(0x9e37_79b9_7f4a_7c15, Relaxed);
+        let x = split_mix64(&mut state);
+        (x.trailing_ones() as usize + 1).min(MAX_HEIGHT)
+    }
+
+    /// Fills `preds` and `succs` with the nodes either side of `key` at
+    /// every level in use, returning the highest level `key` was found at.
+    fn find<Q>(
+        &self,
+        key: &Q,
+        preds: &mut [Arc<Node<K, V>>],
+        succs: &mut [Link<K, V>],
+    ) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut found = None;
+        let mut pred = self.head.clone();
+        for level in (0..self.height.load(SeqCst)).rev() {
+            loop {
+                match pred.next(level) {
+                    Some(node) if node.key().borrow() < key => pred = node,
+                    succ => {
+                        if found.is_none() && succ.as_ref().is_some_and(|n| n.key().borrow() == key)
+                        {
+                            found = Some(level);
+                        }
+                        preds[level] = pred.clone();
+                        succs[level] = succ;
+                        break;
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    /// The node for `key`, linked in at every level, unless there's none.
+    fn node<Q>(&self, key: &Q) -> Option<Arc<Node<K, V>>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut pred = self.head.clone();
+        for level in (0..self.height.load(SeqCst)).rev() {
+            while let Some(node) = pred.next(level) {
+                match node.key().borrow().cmp(key) {
+                    std::cmp::Ordering::Less => pred = node,
+                    std::cmp::Ordering::Equal => {
+                        return node.linked.load(SeqCst).then_some(node);
+                    }
+                    std::cmp::Ordering::Greater => break,
+                }
+            }
+        }
+        None
+    }
+
+    /// Clones out the value for `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        lock(&self.node(key)?.value).clone()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.node(key)
+            .is_some_and(|node| lock(&node.value).is_some())
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let height = self.random_height();
+        // Searches from now on start high enough to find the new node.
+        self.height.fetch_max(height, SeqCst);
+        let mut preds = vec![self.head.clone(); MAX_HEIGHT];
+        let mut succs = vec![None; MAX_HEIGHT];
+        loop {
+            if let Some(level) = self.find(&key, &mut preds, &mut succs) {
+                let node = succs[level].clone().unwrap();
+                while !node.linked.load(SeqCst) && !node.marked.load(SeqCst) {
+                    hint::spin_loop();
+                }
+                // Removed in the meantime unless it still has its value.
+                if let Some(old) = lock(&node.value).as_mut() {
+                    return Some(std::mem::replace(old, value));
+                }
+                continue;
+            }
+            // Lock the nodes before `key` bottom up, and check they still
+            // come right before the ones after it.
+            let mut guards = Vec::with_capacity(height);
+            let mut valid = true;
+            for level in 0..height {
+                let (pred, succ) = (&preds[level], &succs[level]);
+                if level == 0 || !Arc::ptr_eq(pred, &preds[level - 1]) {
+                    guards.push(lock(&pred.lock));
+                }
+                valid = !pred.marked.load(SeqCst)
+                    && succ.as_ref().is_none_or(|s| !s.marked.load(SeqCst))
+                    && same(&pred.next(level), succ);
+                if !valid {
+                    break;
+                }
+            }
+            if !valid {
+                continue;
+            }
+            // A change only locks the nodes before its key, so changes to
+            // keys far apart go on in parallel, and lookups take no node's
+            // lock, only each link's as they follow it.
+            let node = Arc::new(Node::new(Some((key, value)), height));
+            for (link, succ) in node.next.iter().zip(&succs) {
+                *lock(link) = succ.clone();
+            }
+            for (level, pred) in preds[..height].iter().enumerate() {
+                *lock(&pred.next[level]) = Some(node.clone());
+            }
+            // Ordering: the links went in under their mutexes, and storing
+            // `linked` after them makes the node visible to `node` lookups
+            // only once it's reachable at every level.
+            node.linked.store(true, SeqCst);
+            self.len.fetch_add(1, SeqCst);
+            return None;
+        }
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut preds = vec![self.head.clone(); MAX_HEIGHT];
+        let mut succs = vec![None; MAX_HEIGHT];
+        // The node claimed, and its value.
+        let mut claimed: Option<(Arc<Node<K, V>>, V)> = None;
+        loop {
+            let found = self.find(key, &mut preds, &mut succs);
+            if claimed.is_none() {
+                // Only a node linked in at every level, and found at the top
+                // one, is there to remove.
+                let level = found?;
+                let node = succs[level].clone().unwrap();
+                if !node.linked.load(SeqCst) || node.next.len() != level + 1 {
+                    return None;
+                }
+                let value = {
+                    let _guard = lock(&node.lock);
+                    let mut value = lock(&node.value);
+                    if node.marked.load(SeqCst) {
+                        return None;
+                    }
+                    node.marked.store(true, SeqCst);
+                    value.take().unwrap()
+                };
+                claimed = Some((node, value));
+            }
+            let victim = claimed.as_ref().unwrap().0.clone();
+            let height = victim.next.len();
+            let mut guards = Vec::with_capacity(height);
+            let mut valid = true;
+            for level in 0..height {
+                let pred = &preds[level];
+                if level == 0 || !Arc::ptr_eq(pred, &preds[level - 1]) {
+                    guards.push(lock(&pred.lock));
+                }
+                valid = !pred.marked.load(SeqCst) && same(&pred.next(level), &Some(victim.clone()));
+                if !valid {
+                    break;
+                }
+            }
+            if !valid {
+                continue;
+            }
+            // Unlinking top down keeps the node reachable from below until
+            // it's gone, and its `Arc` frees it once no lookup is on it.
+            for level in (0..height).rev() {
+                *lock(&preds[level].next[level]) = victim.next(level);
+            }
+            self.len.fetch_sub(1, SeqCst);
+            return claimed.map(|(_, value)| value);
+        }
+    }
+
+    /// Clones the entries out in key order, weakly consistently: it sees
+    /// every entry there throughout, none removed before it started, and any
+    /// mix of the changes made while it runs.
+    pub fn iter(&self) -> Iter<K, V>
+    where
+        K: Clone,
+    {
+        self.range(..)
+    }
+
+    /// Clones the entries in `range` out in key order, weakly consistently.
+    pub fn range<Q, R>(&self, range: R) -> Iter<K, V, Q>
+    where
+        K: Borrow<Q>,
+        Q: Ord + Clone,
+        R: RangeBounds<Q>,
+    {
+        // The last node before the range.
+        let mut pred = self.head.clone();
+        for level in (0..self.height.load(SeqCst)).rev() {
+            while let Some(node) = pred.next(level) {
+                let before = match range.start_bound() {
+                    Bound::Included(start) => node.key().borrow() < start,
+                    Bound::Excluded(start) => node.key().borrow() <= start,
+                    Bound::Unbounded => false,
+                };
+                if !before {
+                    break;
+                }
+                pred = node;
+            }
+        }
+        Iter {
+            node: pred,
+            end: range.end_bound().cloned(),
+        }
+    }
+}
+
+impl<K: Ord, V> Default for SkipMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone + fmt::Debug, V: Clone + fmt::Debug> fmt::Debug for SkipMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for SkipMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let map = SkipMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+/// Clones of the entries of a [`SkipMap`] in a range, in key order.
+pub struct Iter<K, V, Q = K> {
+    // The last node passed.
+    node: Arc<Node<K, V>>,
+    end: Bound<Q>,
+}
+
+impl<K, V, Q> Iterator for Iter<K, V, Q>
+where
+    K: Borrow<Q> + Clone,
+    V: Clone,
+    Q: Ord,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        loop {
+            let node = self.node.next(0)?;
+            let past = match &self.end {
+                Bound::Included(end) => node.key().borrow() > end,
+                Bound::Excluded(end) => node.key().borrow() >= end,
+                Bound::Unbounded => false,
+            };
+            if past {
+                return None;
+            }
+            self.node = node;
+            // Skip nodes still being linked in, or removed.
+            if self.node.linked.load(SeqCst) {
+                if let Some(value) = lock(&self.node.value).clone() {
+                    return Some((self.node.key().clone(), value));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+    use std::thread;
+
+    #[test]
+    fn basic_test() {
+        let map = SkipMap::new();
+        assert_eq!(map.insert(3, "c"), None);
+        assert_eq!(map.insert(1, "a"), None);
+        assert_eq!(map.insert(2, "b"), None);
+        assert_eq!(map.insert(3, "C"), Some("c"));
+        assert_eq!(map.get(&3), Some("C"));
+        assert_eq!(map.remove(&2), Some("b"));
+        assert_eq!(map.remove(&2), None);
+        assert!(!map.contains_key(&2));
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(1, "a"), (3, "C")]);
+        assert_eq!(map.len(), 2);
+
+        // The same seed and inserts build the same towers.
+        let towers = |seed| {
+            let map = SkipMap::with_seed(seed);
+            (0..100).for_each(|k| _ = map.insert(k, ()));
+            let mut node = map.head.next(0);
+            core::iter::from_fn(|| {
+                let next = node.take()?;
+                node = next.next(0);
+                Some(next.next.len())
+            })
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(towers(7), towers(7));
+        assert_ne!(towers(7), towers(8));
+
+        // Threads changing the map at once, some on the same keys. Each
+        // removes half of its own.
+        let map = SkipMap::new();
+        thread::scope(|s| {
+            for t in 0..4 {
+                let map = &map;
+                s.spawn(move || {
+                    for i in 0..100 {
+                        map.insert(i * 4 + t, t);
+                        map.insert(1000 + i, t);
+                        if i % 2 == 1 {
+                            assert_eq!(map.remove(&(i * 4 + t)), Some(t));
+                        }
+                    }
+                });
+            }
+            // A reader going through at the same time sees keys in order.
+            s.spawn(|| {
+                for _ in 0..10 {
+                    let keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+                    assert!(keys.windows(2).all(|w| w[0] < w[1]));
+                }
+            });
+        });
+        let mut expected: Vec<u32> = (0..100)
+            .step_by(2)
+            .flat_map(|i| (0..4).map(move |t| i * 4 + t))
+            .collect();
+        expected.extend(1000..1100);
+        assert_eq!(map.iter().map(|(k, _)| k).collect::<Vec<_>>(), expected);
+        assert_eq!(map.len(), expected.len());
+
+        // Threads racing on the same few keys leave the map consistent.
+        let map = SkipMap::new();
+        thread::scope(|s| {
+            for t in 0..4u32 {
+                let map = &map;
+                s.spawn(move || {
+                    for i in 0..200 {
+                        let key = (i * 7 + t) % 16;
+                        match i % 3 {
+                            0 => drop(map.remove(&key)),
+                            _ => drop(map.insert(key, t)),
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(map.len(), map.iter().count());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "too slow under Miri")]
+    fn drop_test() {
+        // A long list is dropped without recursing through it.
+        let map: SkipMap<u32, ()> = (0..200_000).map(|i| (i, ())).collect();
+        drop(map);
+    }
+
+    #[quickcheck]
+    #[cfg_attr(miri, ignore = "too slow under Miri")]
+    fn test_quickcheck(ops: Vec<(u8, u8, u8)>) -> bool {
+        let map = SkipMap::new();
+        let mut model = BTreeMap::new();
+        for (op, key, other) in ops {
+            let ok = match op % 4 {
+                0 | 1 => map.insert(key, other) == model.insert(key, other),
+                2 => map.remove(&key) == model.remove(&key),
+                _ => {
+                    let (lo, hi) = (key.min(other), key.max(other));
+                    let range: Vec<_> = model.range(lo..hi).map(|(&k, &v)| (k, v)).collect();
+                    map.range(lo..hi).collect::<Vec<_>>() == range
+                        && map.range(lo..=hi).count() == model.range(lo..=hi).count()
+                        && map.get(&key) == model.get(&key).copied()
+                }
+            };
+            if !ok || map.len() != model.len() {
+                return false;
+            }
+        }
+        map.iter().eq(model.into_iter())
+    }
+}
@@ -0,0 +1,397 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::hint;
+use core::mem::ManuallyDrop;
+use core::ptr;
+use core::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
+use core::sync::atomic::{AtomicPtr, AtomicUsize};
+
+use super::CachePadded;
+
+struct Node<T> {
+    item: ManuallyDrop<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+// How long a push offered for elimination waits for a pop to take it.
+const SPINS: usize = 64;
+
+// The states of an `Exchanger`, in its low two bits. The rest count the
+// offers taken from it, so a push can't mistake another's for its own.
+const EMPTY: usize = 0;
+const WRITING: usize = 1;
+const WAITING: usize = 2;
+const TAKING: usize = 3;
+
+/// A slot where a push which lost a race for the head can hand its node
+/// straight to a pop which lost one too, so neither touches the head again.
+struct Exchanger<T> {
+    state: AtomicUsize,
+    node: UnsafeCell<*mut Node<T>>,
+}
+
+impl<T> Exchanger<T> {
+    /// Offers `node` for a while, returning whether a pop took it.
+    fn offer(&self, node: *mut Node<T>) -> bool {
+        let empty = self.state.load(Relaxed);
+        if empty & 3 != EMPTY
+            || (self.state)
+                .compare_exchange(empty, empty | WRITING, Acquire, Relaxed)
+                .is_err()
+        {
+            return false;
+        }
+        // SAFETY: the `WRITING` state gives this push the slot.
+        unsafe { *self.node.get() = node };
+        let waiting = empty | WAITING;
+        self.state.store(waiting, Release);
+        for _ in 0..SPINS {
+            if self.state.load(Relaxed) != waiting {
+                break;
+            }
+            hint::spin_loop();
+        }
+        // Take the offer back, unless a pop got it first.
+        (self.state)
+            .compare_exchange(waiting, empty, Relaxed, Relaxed)
+            .is_err()
+    }
+
+    /// Takes the node on offer, if any.
+    fn take(&self) -> Option<*mut Node<T>> {
+        let waiting = self.state.load(Relaxed);
+        if waiting & 3 != WAITING
+            || (self.state)
+                .compare_exchange(waiting, waiting | TAKING, Acquire, Relaxed)
+                .is_err()
+        {
+            return None;
+        }
+        // SAFETY: the push wrote the node before its release store of
+        // `WAITING`, and the `TAKING` state gives this pop the slot.
+        let node = unsafe { *self.node.get() };
+        self.state.store((waiting & !3) + 4, Release);
+        Some(node)
+    }
+}
+
+/// A lock-free stack for many threads (Treiber's), with an optional
+/// elimination array for pushes and pops which lose a race for the head.
+pub struct TreiberStack<T> {
+    head: CachePadded<AtomicPtr<Node<T>>>,
+    // The threads in `pop` or `pop_all`, and the nodes taken off which
+    // they might still be reading, their items moved out. The last pop out
+    // frees them, so memory is only held up while pops keep overlapping.
+    poppers: AtomicUsize,
+    pending: AtomicPtr<Node<T>>,
+    slots: Box<[Exchanger<T>]>,
+}
+
+// SAFETY: items move between threads through the stack, and each is moved
+// out by the one pop which unlinked or took its node.
+unsafe impl<T: Send> Send for TreiberStack<T> {}
+unsafe impl<T: Send> Sync for TreiberStack<T> {}
+
+impl<T> TreiberStack<T> {
+    pub fn new() -> Self {
+        Self::with_elimination(0)
+    }
+
+    /// A stack whose pushes and pops which lose a race meet in an
+    /// elimination array of `slots` slots, cancelling out without touching
+    /// the head. A few per core contending is plenty.
+    pub fn with_elimination(slots: usize) -> Self {
+        TreiberStack {
+            head: CachePadded(AtomicPtr::new(ptr::null_mut())),
+            poppers: AtomicUsize::new(0),
+            pending: AtomicPtr::new(ptr::null_mut()),
+            slots: (0..slots)
+                .map(|_| Exchanger {
+                    state: AtomicUsize::new(EMPTY),
+                    node: UnsafeCell::new(ptr::null_mut()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the stack is empty, which other threads may have changed by
+    /// the time it's returned.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(SeqCst).is_null()
+    }
+
+    /// The slot to start at for an operation, picked by hashing `seed`.
+    fn slot(&self, seed: usize) -> usize {
+        let hash = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15_u64 as usize);
+        (hash >> (usize::BITS / 2)) % self.slots.len()
+    }
+
+    pub fn push(&self, item: T) {
+        let node = Box::into_raw(Box::new(Node {
+            item: ManuallyDrop::new(item),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        loop {
+            let head = self.head.load(SeqCst);
+            // SAFETY: `node` is this push's until it's linked in or taken.
+            unsafe { (*node).next.store(head, Relaxed) };
+            if (self.head)
+                .compare_exchange(head, node, SeqCst, SeqCst)
+                .is_ok()
+            {
+                return;
+            }
+            if !self.slots.is_empty() && self.slots[self.slot(node as usize)].offer(node) {
+                return;
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        loop {
+            let seed = match self.try_pop() {
+                Ok(item) => return item,
+                Err(seed) => seed,
+            };
+            if let Some(item) = self.take_offered(seed) {
+                return Some(item);
+            }
+        }
+    }
+
+    /// Pops once, or fails with the head it lost the race to.
+    fn try_pop(&self) -> Result<Option<T>, usize> {
+        // Ordering: counting in and loading the head are both SeqCst, so a
+        // pop which is alone in `release` either sees this one counted or
+        // unlinked the head before this one loaded it.
+        self.poppers.fetch_add(1, SeqCst);
+        let head = self.head.load(SeqCst);
+        if head.is_null() {
+            self.poppers.fetch_sub(1, SeqCst);
+            // A push on offer is as good as one on the stack.
+            return Ok(self.take_offered(0));
+        }
+        // SAFETY: `head` was on the stack after this pop counted itself in,
+        // so it isn't freed until this pop is out again.
+        let next = unsafe { (*head).next.load(SeqCst) };
+        if let Err(now) = self.head.compare_exchange(head, next, SeqCst, SeqCst) {
+            self.poppers.fetch_sub(1, SeqCst);
+            return Err(now as usize);
+        }
+        // SAFETY: unlinking `head` gave this pop its item. Other pops only
+        // read `next`.
+        let item = unsafe { ptr::read(&*(*head).item) };
+        self.release(head, head);
+        Ok(Some(item))
+    }
+
+    /// Takes an item a push has on offer, if any.
+    fn take_offered(&self, seed: usize) -> Option<T> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let start = self.slot(seed);
+        let (before, after) = self.slots.split_at(start);
+        let node = after.iter().chain(before).find_map(Exchanger::take)?;
+        // SAFETY: the node never went on the stack, and its push is done
+        // with it once it's taken.
+        let node = unsafe { Box::from_raw(node) };
+        Some(ManuallyDrop::into_inner(node.item))
+    }
+
+    /// Pops every item at once, newest first, with one swap of the head.
+    pub fn pop_all(&self) -> Vec<T> {
+        self.poppers.fetch_add(1, SeqCst);
+        let first = self.head.swap(ptr::null_mut(), SeqCst);
+        let mut items = Vec::new();
+        let (mut node, mut last) = (first, first);
+        while !node.is_null() {
+            // SAFETY: the swap gave this pop the whole list, as in `try_pop`.
+            unsafe {
+                items.push(ptr::read(&*(*node).item));
+                last = node;
+                node = (*node).next.load(SeqCst);
+            }
+        }
+        self.release(first, last);
+        items
+    }
+
+    /// Counts this pop out, freeing the list from `first` to `last` it took
+    /// off, and any pending, unless other pops might still be reading them.
+    fn release(&self, first: *mut Node<T>, last: *mut Node<T>) {
+        if first.is_null() {
+            self.poppers.fetch_sub(1, SeqCst);
+            return;
+        }
+        if self.poppers.load(SeqCst) > 1 {
+            self.defer(first, last);
+            self.poppers.fetch_sub(1, SeqCst);
+            return;
+        }
+        // Alone, so no other pop saw `first` before it was unlinked, and
+        // those starting now can't.
+        let pending = self.pending.swap(ptr::null_mut(), SeqCst);
+        if self.poppers.fetch_sub(1, SeqCst) == 1 {
+            // Still alone: every pop which might have read `pending` is out.
+            // SAFETY: as above.
+            unsafe { free(pending) };
+        } else if !pending.is_null() {
+            let mut last = pending;
+            // SAFETY: nodes on `pending` aren't freed while it holds them.
+            unsafe {
+                while !(*last).next.load(SeqCst).is_null() {
+                    last = (*last).next.load(SeqCst);
+                }
+            }
+            self.defer(pending, last);
+        }
+        // SAFETY: as above. A single node popped still points into the stack.
+        unsafe {
+            (*last).next.store(ptr::null_mut(), Relaxed);
+            free(first);
+        }
+    }
+
+    /// Adds the list from `first` to `last` to the pending nodes.
+    fn defer(&self, first: *mut Node<T>, last: *mut Node<T>) {
+        let mut pending = self.pending.load(SeqCst);
+        loop {
+            // SAFETY: the list is kept alive by the pops which took it off.
+            unsafe { (*last).next.store(pending, SeqCst) };
+            match (self.pending).compare_exchange(pending, first, SeqCst, SeqCst) {
+                Ok(_) => return,
+                Err(now) => pending = now,
+            }
+        }
+    }
+}
+
+/// Frees the list from `node`, whose items were moved out.
+///
+/// # Safety
+///
+/// Nothing else may reach the list.
+unsafe fn free<T>(mut node: *mut Node<T>) {
+    while !node.is_null() {
+        // SAFETY: as the caller promises.
+        let boxed = unsafe { Box::from_raw(node) };
+        node = boxed.next.load(Relaxed);
+    }
+}
+
+impl<T> Default for TreiberStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for TreiberStack<T> {
+    fn drop(&mut self) {
+        let mut node = *self.head.0.get_mut();
+        while !node.is_null() {
+            // SAFETY: the stack owns its nodes and their items.
+            let mut boxed = unsafe { Box::from_raw(node) };
+            unsafe { ManuallyDrop::drop(&mut boxed.item) };
+            node = *boxed.next.get_mut();
+        }
+        // SAFETY: with no pops left, nothing reaches the pending nodes.
+        unsafe { free(*self.pending.get_mut()) };
+    }
+}
+
+impl<T> fmt::Debug for TreiberStack<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TreiberStack")
+            .field("is_empty", &self.is_empty())
+            .field("elimination", &self.slots.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::sync::Arc;
+    use alloc::vec;
+    use quickcheck_macros::quickcheck;
+    use std::thread;
+
+    #[test]
+    fn basic_test() {
+        let stack = TreiberStack::new();
+        assert_eq!(stack.pop(), None);
+        for i in 0..5 {
+            stack.push(i);
+        }
+        assert_eq!(stack.pop(), Some(4));
+        assert_eq!(stack.pop_all(), [3, 2, 1, 0]);
+        assert!(stack.is_empty() && stack.pop_all().is_empty());
+
+        // Every item pushed is popped once, by `pop` or `pop_all`, eliminated
+        // or not.
+        for slots in [0, 4] {
+            let stack = TreiberStack::with_elimination(slots);
+            let popped: Vec<Vec<u32>> = thread::scope(|s| {
+                for t in 0..4 {
+                    let stack = &stack;
+                    s.spawn(move || (0..500).for_each(|i| stack.push(t * 500 + i)));
+                }
+                let poppers: Vec<_> = (0..4)
+                    .map(|t| {
+                        let stack = &stack;
+                        s.spawn(move || {
+                            let mut popped = vec![];
+                            for _ in 0..300 {
+                                match t {
+                                    0 => popped.extend(stack.pop_all()),
+                                    _ => popped.extend(stack.pop()),
+                                }
+                            }
+                            popped
+                        })
+                    })
+                    .collect();
+                poppers.into_iter().map(|p| p.join().unwrap()).collect()
+            });
+            let mut all: Vec<u32> = popped.into_iter().flatten().collect();
+            all.extend(stack.pop_all());
+            all.sort_unstable();
+            assert_eq!(all, (0..2000).collect::<Vec<_>>());
+        }
+
+        // Items left over are dropped with the stack.
+        let item = Arc::new(());
+        let stack = TreiberStack::new();
+        stack.push(item.clone());
+        stack.push(item.clone());
+        drop(stack);
+        assert_eq!(Arc::strong_count(&item), 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<Option<u16>>) -> bool {
+        let stack = TreiberStack::with_elimination(2);
+        let mut model = vec![];
+        for op in ops {
+            let ok = match op {
+                Some(item) if item % 8 == 0 => {
+                    let all: Vec<_> = model.drain(..).rev().collect();
+                    stack.pop_all() == all
+                }
+                Some(item) => {
+                    model.push(item);
+                    stack.push(item);
+                    true
+                }
+                None => stack.pop() == model.pop(),
+            };
+            if !ok || stack.is_empty() != model.is_empty() {
+                return false;
+            }
+        }
+        true
+    }
+}
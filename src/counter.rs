@@ -0,0 +1,249 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Reverse;
+use core::fmt;
+use core::ops::{AddAssign, SubAssign};
+
+use crate::splay::{Splay, SplayIter};
+
+/// Multiset counting occurrences of each item, iterated in item order.
+#[derive(Clone)]
+pub struct Counter<T> {
+    counts: Splay<T, usize>,
+    total: usize,
+}
+
+impl<T: Ord> Counter<T> {
+    pub fn new() -> Self {
+        Counter {
+            counts: Splay::new(),
+            total: 0,
+        }
+    }
+
+    /// Number of distinct items.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Sum of all counts.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn add(&mut self, item: T) {
+        self.add_n(item, 1)
+    }
+
+    pub fn add_n(&mut self, item: T, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.total += n;
+        match self.counts.get_mut(&item) {
+            Some(count) => *count += n,
+            None => self.counts.set(item, n),
+        }
+    }
+
+    pub fn sub<Q>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.sub_n(item, 1)
+    }
+
+    /// Decreases the count, dropping the item once it reaches zero.
+    pub fn sub_n<Q>(&mut self, item: &Q, n: usize)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let Some(count) = self.counts.get_mut(item) else {
+            return;
+        };
+        if *count > n {
+            *count -= n;
+            self.total -= n;
+        } else {
+            self.total -= *count;
+            self.counts.remove(item);
+        }
+    }
+
+    pub fn get<Q>(&mut self, item: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.counts.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn iter(&self) -> CounterIter<'_, T> {
+        CounterIter(self.counts.iter())
+    }
+
+    /// The `k` most frequent items, ties are broken by item order.
+    pub fn most_common(&self, k: usize) -> Vec<(&T, usize)> {
+        let mut items: Vec<(&T, usize)> = self.iter().collect();
+        items.sort_by_key(|&(_, count)| Reverse(count));
+        items.truncate(k);
+        items
+    }
+}
+
+pub struct CounterIter<'a, T>(SplayIter<'a, T, usize>);
+
+impl<'a, T: Ord> Iterator for CounterIter<'a, T> {
+    type Item = (&'a T, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(item, count)| (item, *count))
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for Counter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> Default for Counter<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> Extend<T> for Counter<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.add(item);
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for Counter<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut counter = Counter::new();
+        counter.extend(iter);
+        counter
+    }
+}
+
+impl<T: Ord + Clone> AddAssign<&Counter<T>> for Counter<T> {
+    fn add_assign(&mut self, other: &Counter<T>) {
+        for (item, count) in other.iter() {
+            self.add_n(item.clone(), count);
+        }
+    }
+}
+
+/// Like Python's `Counter`, only positive counts are kept.
+impl<T: Ord> SubAssign<&Counter<T>> for Counter<T> {
+    fn sub_assign(&mut self, other: &Counter<T>) {
+        for (item, count) in other.iter() {
+            self.sub_n(item, count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut counter: Counter<char> = "abracadabra".chars().collect();
+        assert_eq!(counter.total(), 11);
+        assert_eq!(counter.len(), 5);
+        assert_eq!(counter.get(&'a'), 5);
+        assert_eq!(counter.get(&'z'), 0);
+        assert_eq!(
+            counter.most_common(3),
+            vec![(&'a', 5), (&'b', 2), (&'r', 2)]
+        );
+
+        counter.sub_n(&'b', 5);
+        counter.sub(&'a');
+        assert_eq!(counter.get(&'b'), 0);
+        assert_eq!(counter.get(&'a'), 4);
+        assert_eq!(counter.total(), 8);
+    }
+
+    #[test]
+    fn arithmetic_test() {
+        let a: Counter<i32> = vec![1, 1, 2, 3].into_iter().collect();
+        let b: Counter<i32> = vec![1, 2, 2, 4].into_iter().collect();
+        let mut sum = a.clone();
+        sum += &b;
+        assert_eq!(
+            sum.iter().collect::<Vec<(&i32, usize)>>(),
+            vec![(&1, 3), (&2, 3), (&3, 1), (&4, 1)]
+        );
+        let mut diff = a;
+        diff -= &b;
+        assert_eq!(
+            diff.iter().collect::<Vec<(&i32, usize)>>(),
+            vec![(&1, 1), (&3, 1)]
+        );
+        assert_eq!(diff.total(), 2);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8, u8)>) -> bool {
+        let mut counter: Counter<u8> = Counter::new();
+        let mut model: HashMap<u8, usize> = HashMap::new();
+
+        for (op, item, n) in ops {
+            let item = item % 16;
+            let n = (n % 4) as usize;
+            match op % 3 {
+                0 => {
+                    counter.add_n(item, n);
+                    if n > 0 {
+                        *model.entry(item).or_insert(0) += n;
+                    }
+                }
+                1 => {
+                    counter.sub_n(&item, n);
+                    if let Some(count) = model.get_mut(&item) {
+                        *count = count.saturating_sub(n);
+                        if *count == 0 {
+                            model.remove(&item);
+                        }
+                    }
+                }
+                _ => {
+                    let mut expected: Vec<(&u8, usize)> =
+                        model.iter().map(|(item, &count)| (item, count)).collect();
+                    expected.sort_by_key(|&(item, count)| (Reverse(count), *item));
+                    expected.truncate(n);
+                    if counter.most_common(n) != expected {
+                        return false;
+                    }
+                }
+            }
+            if counter.get(&item) != model.get(&item).copied().unwrap_or(0)
+                || counter.len() != model.len()
+                || counter.total() != model.values().sum::<usize>()
+            {
+                return false;
+            }
+        }
+
+        let mut expected: Vec<(u8, usize)> = model.into_iter().collect();
+        expected.sort();
+        counter
+            .iter()
+            .map(|(item, count)| (*item, count))
+            .eq(expected)
+    }
+}
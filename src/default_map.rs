@@ -0,0 +1,134 @@
+use core::borrow::Borrow;
+use core::ops::{Index, IndexMut};
+
+use crate::splay::{Splay, SplayIter};
+
+/// Splay map which fills in missing keys on mutable access, so `map[k] += 1`
+/// works directly. Reading a missing key through `map[k]` panics, like the
+/// std maps.
+pub struct DefaultMap<K, V, F = fn() -> V> {
+    tree: Splay<K, V>,
+    default: F,
+}
+
+impl<K: Ord, V: Default> DefaultMap<K, V> {
+    pub fn new() -> Self {
+        DefaultMap {
+            tree: Splay::new(),
+            default: V::default,
+        }
+    }
+}
+
+impl<K: Ord, V: Default> Default for DefaultMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V, F: FnMut() -> V> DefaultMap<K, V, F> {
+    pub fn with_default(default: F) -> Self {
+        DefaultMap {
+            tree: Splay::new(),
+            default,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns the value for `key`, inserting the default first if needed.
+    pub fn get_or_default(&mut self, key: K) -> &mut V {
+        self.tree.get_or_insert_with(key, &mut self.default)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.get(key)
+    }
+
+    pub fn set(&mut self, key: K, value: V) {
+        self.tree.set(key, value)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.remove(key)
+    }
+
+    pub fn iter(&self) -> SplayIter<'_, K, V> {
+        self.tree.iter()
+    }
+
+    pub fn into_inner(self) -> Splay<K, V> {
+        self.tree
+    }
+}
+
+impl<K: Ord, V, F> Index<K> for DefaultMap<K, V, F> {
+    type Output = V;
+
+    fn index(&self, key: K) -> &V {
+        self.tree.peek(&key).expect("key not present")
+    }
+}
+
+impl<K: Ord, V, F: FnMut() -> V> IndexMut<K> for DefaultMap<K, V, F> {
+    fn index_mut(&mut self, key: K) -> &mut V {
+        self.get_or_default(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_test() {
+        let mut map: DefaultMap<&str, i32> = DefaultMap::new();
+        for word in "a b a c a b".split(' ') {
+            map[word] += 1;
+        }
+        assert_eq!(map["a"], 3);
+        assert_eq!(
+            map.iter()
+                .map(|(k, v)| (*k, *v))
+                .collect::<Vec<(&str, i32)>>(),
+            vec![("a", 3), ("b", 2), ("c", 1)]
+        );
+        assert_eq!(map.get("d"), None);
+    }
+
+    #[test]
+    fn closure_test() {
+        let mut next = 0;
+        let mut map = DefaultMap::with_default(|| {
+            next += 1;
+            vec![next]
+        });
+        map.get_or_default(10).push(0);
+        map.get_or_default(20);
+        map.get_or_default(10);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&10), Some(&vec![1, 0]));
+        assert_eq!(map.get(&20), Some(&vec![2]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn missing_index_test() {
+        let map: DefaultMap<i32, i32> = DefaultMap::new();
+        let _ = map[1];
+    }
+}
@@ -0,0 +1,200 @@
+use std::io::{self, Write};
+
+use crate::codec::{invalid_data, Checksummed, Codec};
+use crate::splay::Splay;
+
+const MAGIC: &[u8; 4] = b"CBFD";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+
+/// Read-only byte-string map queried in place, so the buffer can be a
+/// memory-mapped file.
+///
+/// Layout: magic, version, entry count, a table of absolute `u64` entry
+/// offsets, the entries as `key_len: u32, value_len: u32, key, value` in key
+/// order, and an FNV-1a checksum of everything before it. All integers are
+/// little-endian.
+pub struct FrozenDiskMap<'a> {
+    bytes: &'a [u8],
+    len: usize,
+}
+
+#[inline]
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+#[inline]
+fn read_u64(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?))
+}
+
+impl<'a> FrozenDiskMap<'a> {
+    /// Writes entries, which have to be strictly sorted by key.
+    pub fn write<I, K, V, W>(entries: I, w: W) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+        W: Write,
+    {
+        let entries: Vec<(K, V)> = entries.into_iter().collect();
+        if !entries
+            .windows(2)
+            .all(|w| w[0].0.as_ref() < w[1].0.as_ref())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "keys are not strictly sorted",
+            ));
+        }
+
+        let mut w = Checksummed::new(w);
+        w.write_all(MAGIC)?;
+        VERSION.encode(&mut w)?;
+        entries.len().encode(&mut w)?;
+
+        let mut offset = (HEADER_LEN + 8 * entries.len()) as u64;
+        for (k, v) in entries.iter() {
+            offset.encode(&mut w)?;
+            offset += 8 + k.as_ref().len() as u64 + v.as_ref().len() as u64;
+        }
+        for (k, v) in entries.iter() {
+            let (k, v) = (k.as_ref(), v.as_ref());
+            let too_long = || io::Error::new(io::ErrorKind::InvalidInput, "entry too long");
+            u32::try_from(k.len())
+                .map_err(|_| too_long())?
+                .encode(&mut w)?;
+            u32::try_from(v.len())
+                .map_err(|_| too_long())?
+                .encode(&mut w)?;
+            w.write_all(k)?;
+            w.write_all(v)?;
+        }
+
+        let checksum = w.checksum();
+        checksum.encode(&mut w.inner)?;
+        w.flush()
+    }
+
+    pub fn write_splay<K, V, W>(tree: &Splay<K, V>, w: W) -> io::Result<()>
+    where
+        K: Ord + AsRef<[u8]>,
+        V: AsRef<[u8]>,
+        W: Write,
+    {
+        Self::write(tree.iter(), w)
+    }
+
+    /// Checks the header and the size of the offset table, entries are
+    /// bounds-checked as they're read. Use `verify` for a full check.
+    pub fn open(bytes: &'a [u8]) -> io::Result<Self> {
+        if bytes.get(..4) != Some(MAGIC) {
+            return Err(invalid_data("not a frozen disk map"));
+        }
+        if read_u32(bytes, 4) != Some(VERSION) {
+            return Err(invalid_data("unsupported frozen disk map version"));
+        }
+        let len = read_u64(bytes, 8)
+            .and_then(|len| usize::try_from(len).ok())
+            .ok_or_else(|| invalid_data("truncated header"))?;
+        let table_end = len
+            .checked_mul(8)
+            .and_then(|table| table.checked_add(HEADER_LEN + 8));
+        if table_end.is_none_or(|end| end > bytes.len()) {
+            return Err(invalid_data("truncated offset table"));
+        }
+        Ok(FrozenDiskMap { bytes, len })
+    }
+
+    /// Recomputes the checksum over the whole buffer.
+    pub fn verify(&self) -> bool {
+        let (body, trailer) = self.bytes.split_at(self.bytes.len() - 8);
+        let mut hasher = Checksummed::new(io::sink());
+        hasher.write_all(body).unwrap();
+        read_u64(trailer, 0) == Some(hasher.checksum())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The `i`-th entry in key order, `None` if out of range or malformed.
+    pub fn entry(&self, i: usize) -> Option<(&'a [u8], &'a [u8])> {
+        if i >= self.len {
+            return None;
+        }
+        let offset = usize::try_from(read_u64(self.bytes, HEADER_LEN + 8 * i)?).ok()?;
+        let key_len = read_u32(self.bytes, offset)? as usize;
+        let value_len = read_u32(self.bytes, offset.checked_add(4)?)? as usize;
+        let key_start = offset.checked_add(8)?;
+        let value_start = key_start.checked_add(key_len)?;
+        let key = self.bytes.get(key_start..value_start)?;
+        let value = self
+            .bytes
+            .get(value_start..value_start.checked_add(value_len)?)?;
+        Some((key, value))
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&'a [u8]> {
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let (k, v) = self.entry(mid)?;
+            match k.cmp(key) {
+                std::cmp::Ordering::Equal => return Some(v),
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+            }
+        }
+        None
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a [u8], &'a [u8])> + '_ {
+        (0..self.len).map_while(|i| self.entry(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_test() {
+        let mut tree: Splay<String, String> = Splay::new();
+        for word in ["pear", "apple", "fig", "banana"] {
+            tree.set(word.to_string(), word.to_uppercase());
+        }
+        let mut buf = Vec::new();
+        FrozenDiskMap::write_splay(&tree, &mut buf).unwrap();
+
+        let map = FrozenDiskMap::open(&buf).unwrap();
+        assert!(map.verify());
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get(b"fig"), Some(&b"FIG"[..]));
+        assert_eq!(map.get(b"grape"), None);
+        assert_eq!(
+            map.iter().map(|(k, _)| k).collect::<Vec<&[u8]>>(),
+            vec![&b"apple"[..], b"banana", b"fig", b"pear"]
+        );
+    }
+
+    #[test]
+    fn invalid_test() {
+        let mut buf = Vec::new();
+        assert!(FrozenDiskMap::write([("b", "1"), ("a", "2")], &mut buf).is_err());
+
+        let mut buf = Vec::new();
+        FrozenDiskMap::write([("a", "1"), ("b", "2")], &mut buf).unwrap();
+        assert!(FrozenDiskMap::open(&buf[..20]).is_err());
+
+        buf[HEADER_LEN + 8] ^= 0xff;
+        let map = FrozenDiskMap::open(&buf).unwrap();
+        assert!(!map.verify());
+        assert_eq!(map.get(b"b"), None);
+    }
+}
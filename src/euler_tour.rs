@@ -0,0 +1,433 @@
+//! Euler tour trees, for connectivity and subtree sums in forests whose
+//! edges come and go.
+
+use alloc::vec::Vec;
+
+use crate::splay::Splay;
+
+#[derive(Clone)]
+struct Node<T> {
+    // `None` for the nodes standing for an edge's arcs.
+    value: Option<T>,
+    // Of the whole splay subtree: its vertex count and combined values.
+    count: usize,
+    total: Option<T>,
+    left: Option<usize>,
+    right: Option<usize>,
+    parent: Option<usize>,
+}
+
+/// A forest on vertices `0..len()`, each with a value, under `link`s and
+/// `cut`s of edges.
+///
+/// Each tree is kept as its Euler tour, the walk around it which passes
+/// each edge once each way, with a node per vertex for its value and a
+/// node per arc. The tours live in splay trees ordered by position, so
+/// rerooting, linking and cutting are a few splits and joins, and every
+/// operation takes O(log n) amortized. A vertex's subtree is one stretch of
+/// its tour, which gives subtree aggregates. Tours rotate as trees are
+/// rerooted, so `combine` must be commutative as well as associative.
+#[derive(Clone)]
+pub struct EulerTourForest<T, F> {
+    // Not a `Splay`: the tours have no keys to order by, and `Splay` moves
+    // entries between slots as it rotates, where a vertex's node must stay
+    // put so it can be splayed up from below by its parent links.
+    nodes: Vec<Node<T>>,
+    // The node of each vertex.
+    vertices: Vec<usize>,
+    // The arcs from the lower vertex of each edge and back.
+    edges: Splay<(usize, usize), (usize, usize)>,
+    free: Vec<usize>,
+    combine: F,
+}
+
+impl<T: Clone, F: Fn(&T, &T) -> T> EulerTourForest<T, F> {
+    pub fn new(combine: F) -> Self {
+        EulerTourForest {
+            nodes: Vec::new(),
+            vertices: Vec::new(),
+            edges: Splay::new(),
+            free: Vec::new(),
+            combine,
+        }
+    }
+
+    /// The number of vertices.
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    fn alloc(&mut self, value: Option<T>) -> usize {
+        let node = Node {
+            count: value.is_some() as usize,
+            total: value.clone(),
+            value,
+            left: None,
+            right: None,
+            parent: None,
+        };
+        match self.free.pop() {
+            Some(x) => {
+                self.nodes[x] = node;
+                x
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Adds a vertex on its own, returning its number.
+    pub fn add_vertex(&mut self, value: T) -> usize {
+        let node = self.alloc(Some(value));
+        self.vertices.push(node);
+        self.vertices.len() - 1
+    }
+
+    pub fn value(&self, v: usize) -> &T {
+        self.nodes[self.vertices[v]].value.as_ref().unwrap()
+    }
+
+    pub fn set_value(&mut self, v: usize, value: T) {
+        let x = self.vertices[v];
+        self.splay(x);
+        self.nodes[x].value = Some(value);
+        self.update(x);
+    }
+
+    fn merge(&self, a: Option<&T>, b: Option<&T>) -> Option<T> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some((self.combine)(a, b)),
+            (a, b) => a.or(b).cloned(),
+        }
+    }
+
+    fn update(&mut self, x: usize) {
+        let node = &self.nodes[x];
+        let (mut count, mut total) = (node.value.is_some() as usize, node.value.clone());
+        for child in [node.left, node.right].into_iter().flatten() {
+            count += self.nodes[child].count;
+            total = self.merge(total.as_ref(), self.nodes[child].total.as_ref());
+        }
+        self.nodes[x].count = count;
+        self.nodes[x].total = total;
+    }
+
+    fn is_left(&self, x: usize) -> bool {
+        let parent = self.nodes[x].parent.unwrap();
+        self.nodes[parent].left == Some(x)
+    }
+
+    fn rotate(&mut self, x: usize) {
+        let p = self.nodes[x].parent.unwrap();
+        let g = self.nodes[p].parent;
+        let inner = if self.is_left(x) {
+            let b = self.nodes[x].right;
+            self.nodes[p].left = b;
+            self.nodes[x].right = Some(p);
+            b
+        } else {
+            let b = self.nodes[x].left;
+            self.nodes[p].right = b;
+            self.nodes[x].left = Some(p);
+            b
+        };
+        if let Some(b) = inner {
+            self.nodes[b].parent = Some(p);
+        }
+        self.nodes[p].parent = Some(x);
+        self.nodes[x].parent = g;
+        match g {
+            None => {}
+            Some(g) if self.nodes[g].left == Some(p) => self.nodes[g].left = Some(x),
+            Some(g) => self.nodes[g].right = Some(x),
+        }
+        self.update(p);
+        self.update(x);
+    }
+
+    /// Makes `x` the root of its splay tree.
+    fn splay(&mut self, x: usize) {
+        while let Some(p) = self.nodes[x].parent {
+            if self.nodes[p].parent.is_some() {
+                if self.is_left(x) == self.is_left(p) {
+                    self.rotate(p);
+                } else {
+                    self.rotate(x);
+                }
+            }
+            self.rotate(x);
+        }
+    }
+
+    /// Whether two nodes are in the same tour.
+    fn same_tour(&mut self, x: usize, y: usize) -> bool {
+        // Splaying `y` to the root of its tree moves `x` off the root when
+        // they share one.
+        self.splay(x);
+        self.splay(y);
+        x == y || self.nodes[x].parent.is_some()
+    }
+
+    /// Takes `x` out of its tour, returning the parts before and after it.
+    fn split_out(&mut self, x: usize) -> (Option<usize>, Option<usize>) {
+        self.splay(x);
+        let (left, right) = (self.nodes[x].left.take(), self.nodes[x].right.take());
+        for child in [left, right].into_iter().flatten() {
+            self.nodes[child].parent = None;
+        }
+        self.update(x);
+        (left, right)
+    }
+
+    /// Appends the tour rooted at `b` to the one rooted at `a`.
+    fn join(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        let Some(mut last) = a else {
+            return b;
+        };
+        while let Some(right) = self.nodes[last].right {
+            last = right;
+        }
+        self.splay(last);
+        self.nodes[last].right = b;
+        if let Some(b) = b {
+            self.nodes[b].parent = Some(last);
+        }
+        self.update(last);
+        Some(last)
+    }
+
+    /// Rotates the tour of `v`'s tree to start at `v`, returning its root.
+    fn reroot(&mut self, v: usize) -> usize {
+        let x = self.vertices[v];
+        self.splay(x);
+        let Some(before) = self.nodes[x].left.take() else {
+            return x;
+        };
+        self.nodes[before].parent = None;
+        self.update(x);
+        self.join(Some(x), Some(before)).unwrap()
+    }
+
+    pub fn connected(&mut self, u: usize, v: usize) -> bool {
+        self.same_tour(self.vertices[u], self.vertices[v])
+    }
+
+    pub fn has_edge(&self, u: usize, v: usize) -> bool {
+        self.edges.contains_key(&(u.min(v), u.max(v)))
+    }
+
+    /// Adds the edge between `u` and `v`, unless they're already connected.
+    /// Returns whether it was added.
+    pub fn link(&mut self, u: usize, v: usize) -> bool {
+        if self.connected(u, v) {
+            return false;
+        }
+        let (tour_u, tour_v) = (self.reroot(u), self.reroot(v));
+        let (there, back) = (self.alloc(None), self.alloc(None));
+        let tour = self.join(Some(tour_u), Some(there));
+        let tour = self.join(tour, Some(tour_v));
+        self.join(tour, Some(back));
+        let arcs = if u < v { (there, back) } else { (back, there) };
+        self.edges.insert((u.min(v), u.max(v)), arcs);
+        true
+    }
+
+    /// The arcs from `u` to `v` and back, if there's an edge.
+    fn arcs(&self, u: usize, v: usize) -> Option<(usize, usize)> {
+        let &(low, high) = self.edges.peek(&(u.min(v), u.max(v)))?;
+        Some(if u < v { (low, high) } else { (high, low) })
+    }
+
+    /// Removes the edge between `u` and `v`, returning whether there was
+    /// one.
+    pub fn cut(&mut self, u: usize, v: usize) -> bool {
+        let Some((there, back)) = self.arcs(u, v) else {
+            return false;
+        };
+        self.edges.remove(&(u.min(v), u.max(v)));
+        // The tour reads `... there, v's side, back ...` up to rotation, so
+        // taking out both arcs leaves v's side whole, and the rest in two
+        // parts to join.
+        let (before, after) = self.split_out(there);
+        let back_after = after.is_some_and(|after| self.same_tour(back, after));
+        let (inner_before, inner_after) = self.split_out(back);
+        if back_after {
+            self.join(before, inner_after);
+        } else {
+            self.join(inner_before, after);
+        }
+        self.free.extend([there, back]);
+        true
+    }
+
+    /// The number of vertices in `v`'s tree.
+    pub fn component_size(&mut self, v: usize) -> usize {
+        let x = self.vertices[v];
+        self.splay(x);
+        self.nodes[x].count
+    }
+
+    /// The values of `v`'s tree combined.
+    pub fn component_aggregate(&mut self, v: usize) -> T {
+        let x = self.vertices[v];
+        self.splay(x);
+        self.nodes[x].total.clone().unwrap()
+    }
+
+    /// The size and combined values of the subtree under `v` when its tree
+    /// hangs from `parent`, then takes the tour apart and back together.
+    fn subtree(&mut self, v: usize, parent: usize) -> Option<(usize, T)> {
+        let (down, up) = self.arcs(parent, v)?;
+        let (before, after) = self.split_out(down);
+        let up_after = after.is_some_and(|after| self.same_tour(up, after));
+        let (inner_before, inner_after) = self.split_out(up);
+        let side = |this: &Self, parts: &[Option<usize>]| {
+            let mut count = 0;
+            let mut total = None;
+            for &part in parts.iter().flatten() {
+                count += this.nodes[part].count;
+                total = this.merge(total.as_ref(), this.nodes[part].total.as_ref());
+            }
+            (count, total.unwrap())
+        };
+        let (result, order) = if up_after {
+            // before, down, inner_before, up, inner_after
+            (
+                side(self, &[inner_before]),
+                [before, Some(down), inner_before, Some(up), inner_after],
+            )
+        } else {
+            // inner_before, up, inner_after, down, after
+            (
+                side(self, &[after, inner_before]),
+                [inner_before, Some(up), inner_after, Some(down), after],
+            )
+        };
+        order
+            .into_iter()
+            .fold(None, |tour, part| self.join(tour, part));
+        Some(result)
+    }
+
+    /// The number of vertices under `v`, itself included, with the tree
+    /// hanging from its neighbour `parent`. `None` if they aren't adjacent.
+    pub fn subtree_size(&mut self, v: usize, parent: usize) -> Option<usize> {
+        self.subtree(v, parent).map(|(count, _)| count)
+    }
+
+    /// The values under `v` combined, `v`'s included, with the tree hanging
+    /// from its neighbour `parent`. `None` if they aren't adjacent.
+    pub fn subtree_aggregate(&mut self, v: usize, parent: usize) -> Option<T> {
+        self.subtree(v, parent).map(|(_, total)| total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut forest = EulerTourForest::new(|a: &u32, b: &u32| a + b);
+        for value in [1, 2, 4, 8, 16] {
+            forest.add_vertex(value);
+        }
+        assert!(forest.link(0, 1));
+        assert!(forest.link(1, 2));
+        assert!(forest.link(1, 3));
+        assert!(!forest.link(0, 3));
+        assert!(forest.connected(3, 0));
+        assert!(!forest.connected(3, 4));
+        assert_eq!(forest.component_size(2), 4);
+        assert_eq!(forest.component_aggregate(2), 15);
+        assert_eq!(forest.subtree_aggregate(1, 0), Some(14));
+        assert_eq!(forest.subtree_size(0, 1), Some(1));
+        assert_eq!(forest.subtree_size(0, 2), None);
+
+        forest.set_value(3, 32);
+        assert!(forest.cut(1, 0));
+        assert!(!forest.cut(1, 0));
+        assert!(forest.link(4, 3));
+        assert_eq!(forest.component_aggregate(4), 2 + 4 + 32 + 16);
+        assert!(!forest.connected(0, 2));
+        assert_eq!(forest.component_size(0), 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(values: Vec<u8>, ops: Vec<(bool, u8, u8)>) -> bool {
+        let n = values.len();
+        if n == 0 {
+            return true;
+        }
+        let mut forest = EulerTourForest::new(|a: &u32, b: &u32| a + b);
+        for &value in &values {
+            forest.add_vertex(value as u32);
+        }
+        let mut edges: Vec<(usize, usize)> = Vec::new();
+        // The vertices reachable from `v` without crossing to `skip`.
+        let reach = |edges: &[(usize, usize)], v: usize, skip: usize| {
+            let (mut seen, mut stack) = (alloc::vec![false; n], alloc::vec![v]);
+            seen[v] = true;
+            if skip != v {
+                seen[skip] = true;
+            }
+            while let Some(u) = stack.pop() {
+                for &(a, b) in edges {
+                    for (x, y) in [(a, b), (b, a)] {
+                        if x == u && !seen[y] {
+                            seen[y] = true;
+                            stack.push(y);
+                        }
+                    }
+                }
+            }
+            if skip != v {
+                seen[skip] = false;
+            }
+            seen
+        };
+        for (link, u, v) in ops {
+            let (u, v) = (u as usize % n, v as usize % n);
+            let adjacent = edges.iter().position(|&e| e == (u.min(v), u.max(v)));
+            let connected = reach(&edges, u, u)[v];
+            let ok = if link {
+                if !connected {
+                    edges.push((u.min(v), u.max(v)));
+                }
+                forest.link(u, v) != connected
+            } else {
+                if let Some(i) = adjacent {
+                    edges.swap_remove(i);
+                }
+                forest.cut(u, v) == adjacent.is_some()
+            };
+            let component = reach(&edges, u, u);
+            let sum = |seen: &[bool]| {
+                (0..n)
+                    .filter(|&i| seen[i])
+                    .map(|i| values[i] as u32)
+                    .sum::<u32>()
+            };
+            let subtree = match edges.contains(&(u.min(v), u.max(v))) {
+                true => Some(sum(&reach(&edges, v, u))),
+                false => None,
+            };
+            if !ok
+                || forest.connected(u, v) != component[v]
+                || forest.component_size(u) != component.iter().filter(|&&c| c).count()
+                || forest.component_aggregate(u) != sum(&component)
+                || forest.subtree_aggregate(v, u) != subtree
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
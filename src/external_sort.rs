@@ -0,0 +1,324 @@
+//! Sorting more items than fit in memory, by way of sorted runs spilled to
+//! temporary files.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::vec;
+
+use crate::codec::Codec;
+use crate::heap::{kmerge_stable, KMerge};
+
+// The most runs merged at once, to keep few files open.
+const FAN_IN: usize = 64;
+
+static RUNS: AtomicU64 = AtomicU64::new(0);
+
+/// A sorted run in a temporary file, deleted on drop.
+struct Run {
+    path: PathBuf,
+    len: usize,
+}
+
+impl Run {
+    fn write<T: Codec, I>(dir: &Path, items: I) -> io::Result<Run>
+    where
+        I: IntoIterator<Item = io::Result<T>>,
+    {
+        let n = RUNS.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("crab-bucket-run-{}-{n}", std::process::id()));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        // From here on an error deletes the file.
+        let mut run = Run { path, len: 0 };
+        let mut w = BufWriter::new(file);
+        for item in items {
+            item?.encode(&mut w)?;
+            run.len += 1;
+        }
+        w.flush()?;
+        Ok(run)
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// The first read error of any run, for `Sorted` to hand on.
+type Error = Arc<Mutex<Option<io::Error>>>;
+
+struct RunReader<T> {
+    reader: BufReader<File>,
+    left: usize,
+    error: Error,
+    _run: Run,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T: Codec> Iterator for RunReader<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        match T::decode(&mut self.reader) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                self.left = 0;
+                self.error.lock().unwrap().get_or_insert(e);
+                None
+            }
+        }
+    }
+}
+
+enum Source<T> {
+    Run(RunReader<T>),
+    Memory(vec::IntoIter<T>),
+}
+
+impl<T: Codec> Iterator for Source<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match self {
+            Source::Run(run) => run.next(),
+            Source::Memory(items) => items.next(),
+        }
+    }
+}
+
+/// Sorts items in runs of at most `budget` at a time, spilling each run to
+/// a temporary file once full, and merges the runs when done.
+///
+/// Items go to disk with their [`Codec`]. The sort is stable: equal items
+/// come out in the order they were pushed. Merging reads one item at a time
+/// from each run through a buffer, and with more than 64 runs merges them
+/// a group at a time into longer ones first, so as not to open too many
+/// files. The files are deleted once read or on drop.
+pub struct ExternalSorter<T> {
+    budget: usize,
+    dir: PathBuf,
+    buffer: Vec<T>,
+    runs: Vec<Run>,
+}
+
+impl<T: Codec + Ord> ExternalSorter<T> {
+    /// A sorter spilling to the system's temporary directory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `budget` is zero.
+    pub fn new(budget: usize) -> Self {
+        Self::in_dir(budget, std::env::temp_dir())
+    }
+
+    /// A sorter spilling to `dir`, which must exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `budget` is zero.
+    pub fn in_dir<P: Into<PathBuf>>(budget: usize, dir: P) -> Self {
+        assert!(budget > 0, "budget must be positive");
+        ExternalSorter {
+            budget,
+            dir: dir.into(),
+            buffer: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len() + self.runs.iter().map(|run| run.len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How many runs have been spilled so far.
+    pub fn runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Adds an item, spilling a run if that fills the budget. The items in
+    /// memory are lost if spilling them fails.
+    pub fn push(&mut self, item: T) -> io::Result<()> {
+        self.buffer.push(item);
+        if self.buffer.len() >= self.budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    pub fn push_all<I: IntoIterator<Item = T>>(&mut self, items: I) -> io::Result<()> {
+        items.into_iter().try_for_each(|item| self.push(item))
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.buffer.sort();
+        let run = Run::write(&self.dir, self.buffer.drain(..).map(Ok))?;
+        self.runs.push(run);
+        Ok(())
+    }
+
+    /// Merges everything pushed into one sorted stream.
+    pub fn finish(mut self) -> io::Result<Sorted<T>> {
+        while self.runs.len() > FAN_IN {
+            let mut runs = mem::take(&mut self.runs).into_iter().peekable();
+            // Neighbouring runs together, which keeps equal items in order.
+            while runs.peek().is_some() {
+                let group = Sorted::<T>::new(runs.by_ref().take(FAN_IN).collect(), Vec::new())?;
+                self.runs.push(Run::write(&self.dir, group)?);
+            }
+        }
+        self.buffer.sort();
+        Sorted::new(self.runs, self.buffer)
+    }
+}
+
+/// The items of an [`ExternalSorter`] in order, from
+/// [`finish`](ExternalSorter::finish).
+///
+/// Reading a run back can fail, which ends the stream with that error.
+pub struct Sorted<T: Codec + Ord> {
+    merge: KMerge<Source<T>>,
+    error: Error,
+    failed: bool,
+}
+
+impl<T: Codec + Ord> Sorted<T> {
+    /// Merges `runs`, then `memory`, which is sorted and pushed last.
+    fn new(runs: Vec<Run>, memory: Vec<T>) -> io::Result<Self> {
+        let error = Error::default();
+        let mut sources = Vec::with_capacity(runs.len() + 1);
+        for run in runs {
+            sources.push(Source::Run(RunReader {
+                reader: BufReader::new(File::open(&run.path)?),
+                left: run.len,
+                error: error.clone(),
+                _run: run,
+                _item: PhantomData,
+            }));
+        }
+        sources.push(Source::Memory(memory.into_iter()));
+        Ok(Sorted {
+            merge: kmerge_stable(sources),
+            error,
+            failed: false,
+        })
+    }
+}
+
+impl<T: Codec + Ord> Iterator for Sorted<T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<io::Result<T>> {
+        if self.failed {
+            return None;
+        }
+        // An item before the error is good, but nothing after it would be.
+        if let Some(e) = self.error.lock().unwrap().take() {
+            self.failed = true;
+            return Some(Err(e));
+        }
+        match self.merge.next() {
+            Some(item) => Some(Ok(item)),
+            None => {
+                let e = self.error.lock().unwrap().take()?;
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::io::Read;
+
+    /// Ordered by the first field only.
+    #[derive(Debug)]
+    struct Tie(u8, u32);
+
+    impl PartialEq for Tie {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Tie {}
+
+    impl PartialOrd for Tie {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Tie {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    impl Codec for Tie {
+        fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+            (self.0, self.1).encode(w)
+        }
+
+        fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+            let (key, seq) = Codec::decode(r)?;
+            Ok(Tie(key, seq))
+        }
+    }
+
+    #[test]
+    fn basic_test() {
+        let dir = std::env::temp_dir().join(format!("crab-bucket-sort-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Enough runs for a pass merging them in groups first.
+        let mut sorter = ExternalSorter::in_dir(3, &dir);
+        sorter
+            .push_all((0..300).map(|i| Tie((i * 7 % 10) as u8, i)))
+            .unwrap();
+        assert_eq!(sorter.runs(), 100);
+        assert_eq!(sorter.len(), 300);
+        let sorted: Vec<Tie> = sorter.finish().unwrap().map(Result::unwrap).collect();
+        assert_eq!(sorted.len(), 300);
+        assert!(sorted
+            .windows(2)
+            .all(|w| (w[0].0, w[0].1) < (w[1].0, w[1].1)));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        let mut sorter = ExternalSorter::in_dir(4, &dir);
+        sorter
+            .push_all(["pear", "fig", "apple"].map(String::from))
+            .unwrap();
+        let sorted: io::Result<Vec<_>> = sorter.finish().unwrap().collect();
+        assert_eq!(sorted.unwrap(), ["apple", "fig", "pear"]);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(budget: u8, items: Vec<i32>) -> bool {
+        let mut sorter = ExternalSorter::new(budget as usize % 16 + 1);
+        sorter.push_all(items.iter().copied()).unwrap();
+        let mut expected = items;
+        expected.sort_unstable();
+        let sorted: io::Result<Vec<_>> = sorter.finish().unwrap().collect();
+        sorted.unwrap() == expected
+    }
+}
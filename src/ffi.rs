@@ -0,0 +1,221 @@
+//! C interface to a splay map from byte strings to byte strings, declared in
+//! `include/crab_bucket.h` (regenerate with `cbindgen --config cbindgen.toml
+//! --output include/crab_bucket.h`). Build a linkable library with
+//! `cargo rustc --lib --release --features ffi --crate-type staticlib`.
+//!
+//! Pointers handed out by `cb_splay_get` and `cb_splay_iter_next` borrow from
+//! the map and stay valid until it is next modified or freed. The map must
+//! not be modified while an iterator over it exists.
+
+use core::slice;
+
+use crate::splay::{Splay, SplayIter};
+
+/// Opaque splay map handle.
+pub struct CbSplay(Splay<Box<[u8]>, Box<[u8]>>);
+
+/// Opaque iterator handle.
+pub struct CbSplayIter(SplayIter<'static, Box<[u8]>, Box<[u8]>>);
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(data, len) }
+    }
+}
+
+unsafe fn write_out(bytes: &[u8], data: *mut *const u8, len: *mut usize) {
+    unsafe {
+        *data = bytes.as_ptr();
+        *len = bytes.len();
+    }
+}
+
+/// Creates an empty map, to be released with `cb_splay_free`.
+#[no_mangle]
+pub extern "C" fn cb_splay_new() -> *mut CbSplay {
+    Box::into_raw(Box::new(CbSplay(Splay::new())))
+}
+
+/// # Safety
+///
+/// `tree` must come from `cb_splay_new` and not have been freed yet, or be
+/// null.
+#[no_mangle]
+pub unsafe extern "C" fn cb_splay_free(tree: *mut CbSplay) {
+    if !tree.is_null() {
+        drop(unsafe { Box::from_raw(tree) });
+    }
+}
+
+/// # Safety
+///
+/// `tree` must be a live map.
+#[no_mangle]
+pub unsafe extern "C" fn cb_splay_len(tree: *const CbSplay) -> usize {
+    unsafe { (*tree).0.len() }
+}
+
+/// Copies the key and value into the map, replacing any previous value.
+///
+/// # Safety
+///
+/// `tree` must be a live map and `key`/`value` must point to `key_len` and
+/// `value_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cb_splay_set(
+    tree: *mut CbSplay,
+    key: *const u8,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+) {
+    unsafe {
+        let key = bytes(key, key_len).into();
+        let value = bytes(value, value_len).into();
+        (*tree).0.set(key, value);
+    }
+}
+
+/// Looks up `key`, storing a pointer to the value and its length in
+/// `value`/`value_len` if it's present. Returns whether it was. Doesn't
+/// splay, so it's safe to call while iterating.
+///
+/// # Safety
+///
+/// `tree` must be a live map, `key` must point to `key_len` readable bytes
+/// and `value`/`value_len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn cb_splay_get(
+    tree: *const CbSplay,
+    key: *const u8,
+    key_len: usize,
+    value: *mut *const u8,
+    value_len: *mut usize,
+) -> bool {
+    unsafe {
+        match (*tree).0.peek(bytes(key, key_len)) {
+            Some(found) => {
+                write_out(found, value, value_len);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Removes `key` from the map, returning whether it was present.
+///
+/// # Safety
+///
+/// `tree` must be a live map and `key` must point to `key_len` readable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cb_splay_remove(
+    tree: *mut CbSplay,
+    key: *const u8,
+    key_len: usize,
+) -> bool {
+    unsafe { (*tree).0.remove(bytes(key, key_len)).is_some() }
+}
+
+/// Starts iterating over the map in key order, to be released with
+/// `cb_splay_iter_free`.
+///
+/// # Safety
+///
+/// `tree` must be a live map, which must outlive the iterator and stay
+/// unmodified while it's in use.
+#[no_mangle]
+pub unsafe extern "C" fn cb_splay_iter_new(tree: *const CbSplay) -> *mut CbSplayIter {
+    let tree = unsafe { &(*tree).0 };
+    Box::into_raw(Box::new(CbSplayIter(tree.iter())))
+}
+
+/// Advances the iterator, storing the next entry in the out parameters.
+/// Returns false, leaving them untouched, once the map is exhausted.
+///
+/// # Safety
+///
+/// `iter` must be a live iterator and all out parameters must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn cb_splay_iter_next(
+    iter: *mut CbSplayIter,
+    key: *mut *const u8,
+    key_len: *mut usize,
+    value: *mut *const u8,
+    value_len: *mut usize,
+) -> bool {
+    unsafe {
+        match (*iter).0.next() {
+            Some((k, v)) => {
+                write_out(k, key, key_len);
+                write_out(v, value, value_len);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// # Safety
+///
+/// `iter` must come from `cb_splay_iter_new` and not have been freed yet, or
+/// be null.
+#[no_mangle]
+pub unsafe extern "C" fn cb_splay_iter_free(iter: *mut CbSplayIter) {
+    if !iter.is_null() {
+        drop(unsafe { Box::from_raw(iter) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    #[test]
+    fn basic_test() {
+        unsafe {
+            let tree = cb_splay_new();
+            cb_splay_set(tree, b"b".as_ptr(), 1, b"2".as_ptr(), 1);
+            cb_splay_set(tree, b"a".as_ptr(), 1, ptr::null(), 0);
+            assert_eq!(cb_splay_len(tree), 2);
+
+            let (mut value, mut value_len) = (ptr::null(), 0);
+            assert!(cb_splay_get(
+                tree,
+                b"b".as_ptr(),
+                1,
+                &mut value,
+                &mut value_len
+            ));
+            assert_eq!(bytes(value, value_len), b"2");
+            assert!(!cb_splay_get(
+                tree,
+                b"c".as_ptr(),
+                1,
+                &mut value,
+                &mut value_len
+            ));
+
+            let iter = cb_splay_iter_new(tree);
+            let (mut key, mut key_len) = (ptr::null(), 0);
+            let mut keys = Vec::new();
+            while cb_splay_iter_next(iter, &mut key, &mut key_len, &mut value, &mut value_len) {
+                keys.push(bytes(key, key_len).to_vec());
+                // Lookups leave the iterator alone.
+                let (mut found, mut found_len) = (ptr::null(), 0);
+                assert!(cb_splay_get(tree, key, key_len, &mut found, &mut found_len));
+                assert_eq!(bytes(found, found_len), bytes(value, value_len));
+            }
+            cb_splay_iter_free(iter);
+            assert_eq!(keys, [b"a".to_vec(), b"b".to_vec()]);
+
+            assert!(cb_splay_remove(tree, b"a".as_ptr(), 1));
+            assert!(!cb_splay_remove(tree, b"a".as_ptr(), 1));
+            cb_splay_free(tree);
+        }
+    }
+}
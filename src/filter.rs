@@ -0,0 +1,8 @@
+//! Approximate membership filters: compact sets which may answer "maybe"
+//! for items never added, but never "no" for items which were.
+
+mod cuckoo;
+mod quotient;
+
+pub use cuckoo::CuckooFilter;
+pub use quotient::QuotientFilter;
@@ -0,0 +1,292 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+
+use crate::rng::split_mix64;
+
+// Zero marks an empty entry, so fingerprints are never zero.
+const EMPTY: u16 = 0;
+const MAX_KICKS: usize = 500;
+
+/// A cuckoo filter of Fan et al. with 16-bit fingerprints, around 0.01%
+/// false positives with buckets of four.
+///
+/// Each item has two candidate buckets, the second derived from the first
+/// and the fingerprint alone, so entries can be moved between them without
+/// the item. Unlike a Bloom filter, items can be removed again, as long as
+/// only items which were inserted are ever removed.
+#[derive(Clone)]
+pub struct CuckooFilter<S = RandomState> {
+    // `bucket_size` entries per bucket, the bucket count is a power of two.
+    entries: Vec<u16>,
+    bucket_size: usize,
+    len: usize,
+    // An entry kicked out of both its buckets, held here so an insert
+    // which fails doesn't lose an older item.
+    victim: Option<(usize, u16)>,
+    rng: u64,
+    hasher: S,
+}
+
+impl CuckooFilter {
+    /// A filter for about `capacity` items, with buckets of four entries
+    /// filled to 95% at most.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_params(capacity, 4, 0.95)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `bucket_size` is zero or `load_factor` isn't within
+    /// `0.0..=1.0`, excluding zero.
+    pub fn with_params(capacity: usize, bucket_size: usize, load_factor: f64) -> Self {
+        Self::with_params_and_hasher(capacity, bucket_size, load_factor, RandomState::new())
+    }
+}
+
+impl<S: BuildHasher> CuckooFilter<S> {
+    pub fn with_params_and_hasher(
+        capacity: usize,
+        bucket_size: usize,
+        load_factor: f64,
+        hasher: S,
+    ) -> Self {
+        assert!(bucket_size > 0, "bucket size must be positive");
+        assert!(
+            load_factor > 0.0 && load_factor <= 1.0,
+            "load factor must be within 0..=1"
+        );
+        let slots = (capacity as f64 / load_factor).ceil() as usize;
+        let buckets = slots.div_ceil(bucket_size).next_power_of_two();
+        CuckooFilter {
+            entries: vec![EMPTY; buckets * bucket_size],
+            bucket_size,
+            len: 0,
+            victim: None,
+            rng: 0,
+            hasher,
+        }
+    }
+
+    /// Makes the entries kicked out of full buckets only depend on `seed`
+    /// and the items, given a hasher which doesn't change between runs.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = seed;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of entries, the most items which could ever fit.
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True once an insert has failed, nothing more can be added until
+    /// something is removed.
+    pub fn is_full(&self) -> bool {
+        self.victim.is_some()
+    }
+
+    fn buckets(&self) -> usize {
+        self.entries.len() / self.bucket_size
+    }
+
+    fn bucket(&mut self, idx: usize) -> &mut [u16] {
+        let start = idx * self.bucket_size;
+        &mut self.entries[start..start + self.bucket_size]
+    }
+
+    /// The fingerprint and first bucket of `item`.
+    fn locate<T: Hash + ?Sized>(&self, item: &T) -> (u16, usize) {
+        let hash = self.hasher.hash_one(item);
+        let fingerprint = ((hash >> 48) as u16).max(1);
+        (fingerprint, hash as usize & (self.buckets() - 1))
+    }
+
+    /// The other bucket of `fingerprint`, given one of them. The mapping
+    /// is its own inverse.
+    fn alternate(&self, idx: usize, fingerprint: u16) -> usize {
+        let mixed = (fingerprint as u64).wrapping_mul(0x5bd1_e995) as usize;
+        (idx ^ mixed) & (self.buckets() - 1)
+    }
+
+    fn try_place(&mut self, idx: usize, fingerprint: u16) -> bool {
+        match self.bucket(idx).iter_mut().find(|entry| **entry == EMPTY) {
+            Some(entry) => {
+                *entry = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn next_random(&mut self) -> usize {
+        // Only picks which entry to kick out.
+        split_mix64(&mut self.rng) as usize
+    }
+
+    /// Adds `item`, returning false if the filter is full. An item added
+    /// twice takes two entries and has to be removed twice.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        if self.victim.is_some() {
+            return false;
+        }
+        let (mut fingerprint, first) = self.locate(item);
+        let second = self.alternate(first, fingerprint);
+        self.len += 1;
+        if self.try_place(first, fingerprint) || self.try_place(second, fingerprint) {
+            return true;
+        }
+        let mut idx = if self.next_random() & 1 == 0 {
+            first
+        } else {
+            second
+        };
+        for _ in 0..MAX_KICKS {
+            let slot = self.next_random() % self.bucket_size;
+            fingerprint = mem::replace(&mut self.bucket(idx)[slot], fingerprint);
+            idx = self.alternate(idx, fingerprint);
+            if self.try_place(idx, fingerprint) {
+                return true;
+            }
+        }
+        // The item is in, but whichever entry was kicked out last has
+        // nowhere to go.
+        self.victim = Some((idx, fingerprint));
+        true
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let (fingerprint, first) = self.locate(item);
+        let second = self.alternate(first, fingerprint);
+        let in_bucket = |idx: usize| {
+            let start = idx * self.bucket_size;
+            self.entries[start..start + self.bucket_size].contains(&fingerprint)
+        };
+        let in_victim = self
+            .victim
+            .is_some_and(|(idx, f)| f == fingerprint && (idx == first || idx == second));
+        in_victim || in_bucket(first) || in_bucket(second)
+    }
+
+    /// Removes one copy of `item`, returning whether one was found. Only
+    /// remove items which were inserted: removing anything else may take
+    /// out another item sharing its fingerprint.
+    pub fn remove<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        let (fingerprint, first) = self.locate(item);
+        let second = self.alternate(first, fingerprint);
+        if let Some((idx, f)) = self.victim {
+            if f == fingerprint && (idx == first || idx == second) {
+                self.victim = None;
+                self.len -= 1;
+                return true;
+            }
+        }
+        for idx in [first, second] {
+            if let Some(entry) = self.bucket(idx).iter_mut().find(|e| **e == fingerprint) {
+                *entry = EMPTY;
+                self.len -= 1;
+                // The freed entry may give the victim a home.
+                if let Some((idx, f)) = self.victim.take() {
+                    if !self.try_place(idx, f) && !self.try_place(self.alternate(idx, f), f) {
+                        self.victim = Some((idx, f));
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(EMPTY);
+        self.victim = None;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+
+    #[test]
+    fn basic_test() {
+        let mut filter = CuckooFilter::new(1000);
+        for i in 0..1000 {
+            assert!(filter.insert(&i));
+        }
+        assert!((0..1000).all(|i| filter.contains(&i)));
+        let false_positives = (1000..11000).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 20, "{false_positives} false positives");
+
+        for i in 0..500 {
+            assert!(filter.remove(&i));
+        }
+        assert_eq!(filter.len(), 500);
+        assert!((500..1000).all(|i| filter.contains(&i)));
+        filter.clear();
+        assert!(filter.is_empty() && !filter.contains(&600));
+
+        // With a fixed hasher, the same seed fills the same entries.
+        let [left, right] = [(); 2].map(|_| {
+            let hasher = BuildHasherDefault::<DefaultHasher>::default();
+            let mut filter = CuckooFilter::with_params_and_hasher(64, 4, 1.0, hasher).with_seed(9);
+            (0..80).for_each(|i| _ = filter.insert(&i));
+            filter.entries
+        });
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn full_test() {
+        let mut filter = CuckooFilter::with_params(8, 2, 1.0);
+        let mut inserted = 0;
+        while filter.insert(&inserted) {
+            inserted += 1;
+        }
+        assert!(filter.is_full() && inserted <= filter.capacity() + 1);
+        // A failed insert doesn't lose anything.
+        assert!((0..inserted).all(|i| filter.contains(&i)));
+        assert!((0..inserted).all(|i| filter.remove(&i)));
+        assert!(filter.is_empty() && !filter.is_full());
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(bool, u8)>) -> bool {
+        let mut filter = CuckooFilter::with_params(16, 2, 1.0);
+        let mut model = std::collections::BTreeMap::<u8, usize>::new();
+        for (insert, item) in ops {
+            let item = item % 32;
+            if insert {
+                if filter.insert(&item) {
+                    *model.entry(item).or_default() += 1;
+                }
+            } else if model.get(&item).is_some_and(|&n| n > 0) {
+                if !filter.remove(&item) {
+                    return false;
+                }
+                *model.get_mut(&item).unwrap() -= 1;
+            }
+            if filter.len() != model.values().sum::<usize>() {
+                return false;
+            }
+            if model
+                .iter()
+                .any(|(item, &n)| n > 0 && !filter.contains(item))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Read, Write};
+
+use crate::codec::{invalid_data, Codec};
+
+const OCCUPIED: u64 = 1;
+const CONTINUATION: u64 = 2;
+const SHIFTED: u64 = 4;
+const METADATA: u64 = 7;
+
+/// A quotient filter of Bender et al.
+///
+/// The top `q` bits of an item's fingerprint pick its slot, and the low `r`
+/// bits, the remainder, are stored there or shifted along to the next free
+/// slot. Slots are `r + 3` bits packed into one vector, so lookups stay
+/// within a few cache lines and the whole filter encodes as it's stored.
+/// False positives occur about once in `2^r` lookups.
+///
+/// Fingerprints of all sizes fit in `q + r` bits, so the filter can double
+/// its slots by moving a bit from the remainder to the quotient, without
+/// the original items.
+#[derive(Clone)]
+pub struct QuotientFilter<S = RandomState> {
+    q: u32,
+    r: u32,
+    len: usize,
+    words: Vec<u64>,
+    hasher: S,
+}
+
+impl QuotientFilter {
+    /// A filter hashing with a fresh `RandomState`, see
+    /// [`with_hasher`](QuotientFilter::with_hasher) for a reproducible one.
+    pub fn new(q: u32, r: u32) -> Self {
+        Self::with_hasher(q, r, RandomState::new())
+    }
+}
+
+impl<S: BuildHasher> QuotientFilter<S> {
+    /// A filter with `2^q` slots and remainders of `r` bits. The hasher has
+    /// to agree between processes for an encoded filter to be useful.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` or `r` is zero, `r` is over 61 or `q + r` is over 64.
+    pub fn with_hasher(q: u32, r: u32, hasher: S) -> Self {
+        assert!(q > 0 && r > 0, "quotient and remainder need a bit each");
+        assert!(r <= 61 && q + r <= 64, "fingerprints are at most 64 bits");
+        let bits = (1usize << q) * (r as usize + 3);
+        QuotientFilter {
+            q,
+            r,
+            len: 0,
+            words: vec![0; bits.div_ceil(64)],
+            hasher,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn slots(&self) -> usize {
+        1 << self.q
+    }
+
+    pub fn remainder_bits(&self) -> u32 {
+        self.r
+    }
+
+    fn width(&self) -> usize {
+        self.r as usize + 3
+    }
+
+    fn get(&self, slot: usize) -> u64 {
+        let bit = slot * self.width();
+        let (word, offset) = (bit / 64, bit % 64);
+        let mut value = self.words[word] >> offset;
+        if offset + self.width() > 64 {
+            value |= self.words[word + 1] << (64 - offset);
+        }
+        value & self.slot_mask()
+    }
+
+    fn set(&mut self, slot: usize, value: u64) {
+        let bit = slot * self.width();
+        let (word, offset) = (bit / 64, bit % 64);
+        let mask = self.slot_mask();
+        self.words[word] = self.words[word] & !(mask << offset) | value << offset;
+        if offset + self.width() > 64 {
+            let shift = 64 - offset;
+            self.words[word + 1] = self.words[word + 1] & !(mask >> shift) | value >> shift;
+        }
+    }
+
+    fn slot_mask(&self) -> u64 {
+        u64::MAX >> (64 - self.width())
+    }
+
+    fn next(&self, slot: usize) -> usize {
+        (slot + 1) & (self.slots() - 1)
+    }
+
+    fn fingerprint<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        let hash = self.hasher.hash_one(item);
+        hash & (u64::MAX >> (64 - self.q - self.r))
+    }
+
+    fn split(&self, fingerprint: u64) -> (usize, u64) {
+        let remainder = fingerprint & ((1 << self.r) - 1);
+        ((fingerprint >> self.r) as usize, remainder)
+    }
+
+    /// The slot where the run of `quotient` starts, or would start.
+    /// Counts the runs from the start of the cluster up to `quotient` and
+    /// skips as many in the slots.
+    fn run_start(&self, quotient: usize) -> usize {
+        let mut b = quotient;
+        while self.get(b) & SHIFTED != 0 {
+            b = (b + self.slots() - 1) & (self.slots() - 1);
+        }
+        let mut s = b;
+        while b != quotient {
+            loop {
+                s = self.next(s);
+                if self.get(s) & CONTINUATION == 0 {
+                    break;
+                }
+            }
+            loop {
+                b = self.next(b);
+                if self.get(b) & OCCUPIED != 0 {
+                    break;
+                }
+            }
+        }
+        s
+    }
+
+    /// Adds `item`, returning false if its fingerprint was there already.
+    /// Doubles the slots once they're three quarters full, while there are
+    /// remainder bits to spare.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every slot is taken and the remainder is down to one bit.
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        let fingerprint = self.fingerprint(item);
+        self.add(fingerprint)
+    }
+
+    fn add(&mut self, fingerprint: u64) -> bool {
+        if self.len * 4 >= self.slots() * 3 && self.r > 1 {
+            self.grow();
+        }
+        let fingerprint = fingerprint & (u64::MAX >> (64 - self.q - self.r));
+        assert!(self.len < self.slots(), "quotient filter is full");
+        let (quotient, remainder) = self.split(fingerprint);
+        let home = self.get(quotient);
+        let mut entry = remainder << 3;
+        if home & METADATA == 0 {
+            self.set(quotient, entry | OCCUPIED);
+            self.len += 1;
+            return true;
+        }
+        self.set(quotient, home | OCCUPIED);
+
+        let start = self.run_start(quotient);
+        let mut s = start;
+        if home & OCCUPIED != 0 {
+            // Runs are sorted, find where the remainder goes in this one.
+            loop {
+                let existing = self.get(s) >> 3;
+                if existing == remainder {
+                    return false;
+                }
+                if existing > remainder {
+                    break;
+                }
+                s = self.next(s);
+                if self.get(s) & CONTINUATION == 0 {
+                    break;
+                }
+            }
+            if s == start {
+                // The old head is pushed along and continues the run.
+                self.set(start, self.get(start) | CONTINUATION);
+            } else {
+                entry |= CONTINUATION;
+            }
+        }
+        if s != quotient {
+            entry |= SHIFTED;
+        }
+        self.shift_in(s, entry);
+        self.len += 1;
+        true
+    }
+
+    /// Writes `entry` at `slot`, moving everything up to the next empty
+    /// slot along by one. Occupied bits belong to the slot, not the entry,
+    /// so they stay put.
+    fn shift_in(&mut self, mut slot: usize, mut entry: u64) {
+        loop {
+            let mut prev = self.get(slot);
+            let empty = prev & METADATA == 0;
+            if !empty {
+                prev |= SHIFTED;
+                if prev & OCCUPIED != 0 {
+                    entry |= OCCUPIED;
+                    prev &= !OCCUPIED;
+                }
+            }
+            self.set(slot, entry);
+            if empty {
+                return;
+            }
+            entry = prev;
+            slot = self.next(slot);
+        }
+    }
+
+    pub fn contains<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        self.contains_fingerprint(self.fingerprint(item))
+    }
+
+    fn contains_fingerprint(&self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        if self.get(quotient) & OCCUPIED == 0 {
+            return false;
+        }
+        let mut s = self.run_start(quotient);
+        loop {
+            let existing = self.get(s) >> 3;
+            if existing >= remainder {
+                return existing == remainder;
+            }
+            s = self.next(s);
+            if self.get(s) & CONTINUATION == 0 {
+                return false;
+            }
+        }
+    }
+
+    /// Every stored fingerprint, walking the clusters in slot order.
+    fn fingerprints(&self) -> Vec<u64> {
+        let mut out = Vec::with_capacity(self.len);
+        if self.len == 0 {
+            return out;
+        }
+        let is_cluster_start = |e: u64| e & METADATA == OCCUPIED;
+        let mut slot = (0..self.slots())
+            .find(|&s| is_cluster_start(self.get(s)))
+            .unwrap();
+        let mut quotient = slot;
+        while out.len() < self.len {
+            let entry = self.get(slot);
+            if is_cluster_start(entry) {
+                quotient = slot;
+            } else if entry & SHIFTED != 0 && entry & CONTINUATION == 0 {
+                // A new run, for the next occupied quotient.
+                loop {
+                    quotient = self.next(quotient);
+                    if self.get(quotient) & OCCUPIED != 0 {
+                        break;
+                    }
+                }
+            }
+            if entry & METADATA != 0 {
+                out.push((quotient as u64) << self.r | entry >> 3);
+            }
+            slot = self.next(slot);
+        }
+        out
+    }
+
+    /// Doubles the slots by turning a remainder bit into a quotient bit,
+    /// which also doubles the false positive rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the remainder is down to one bit.
+    pub fn grow(&mut self) {
+        assert!(self.r > 1, "no remainder bits left to grow into");
+        let fingerprints = self.fingerprints();
+        self.q += 1;
+        self.r -= 1;
+        self.words = vec![0; (self.slots() * self.width()).div_ceil(64)];
+        self.len = 0;
+        for fingerprint in fingerprints {
+            self.add(fingerprint);
+        }
+    }
+
+    /// Adds every fingerprint of `other`, which has to use the same hasher.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filters' fingerprints have different widths.
+    pub fn merge(&mut self, other: &Self) {
+        assert_eq!(
+            self.q + self.r,
+            other.q + other.r,
+            "merged filters need fingerprints of the same width"
+        );
+        for fingerprint in other.fingerprints() {
+            self.add(fingerprint);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+        self.len = 0;
+    }
+}
+
+impl<S: BuildHasher + Default> Codec for QuotientFilter<S> {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.q.encode(w)?;
+        self.r.encode(w)?;
+        self.len.encode(w)?;
+        self.words.encode(w)
+    }
+
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        let (q, rem) = (u32::decode(r)?, u32::decode(r)?);
+        if q == 0 || rem == 0 || rem > 61 || q + rem > 64 || q >= usize::BITS {
+            return Err(invalid_data("invalid quotient filter widths"));
+        }
+        let len = usize::decode(r)?;
+        let words = Vec::<u64>::decode(r)?;
+        let slots = 1usize << q;
+        let bits = slots.checked_mul(rem as usize + 3);
+        if bits.map(|bits| bits.div_ceil(64)) != Some(words.len()) || len > slots {
+            return Err(invalid_data("quotient filter doesn't match its widths"));
+        }
+        Ok(QuotientFilter {
+            q,
+            r: rem,
+            len,
+            words,
+            hasher: S::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::BTreeSet;
+    use std::hash::BuildHasherDefault;
+
+    type Fixed = BuildHasherDefault<DefaultHasher>;
+
+    #[test]
+    fn basic_test() {
+        let mut filter = QuotientFilter::new(10, 12);
+        for i in 0..700 {
+            filter.insert(&i);
+        }
+        assert_eq!(filter.slots(), 1024);
+        assert!((0..700).all(|i| filter.contains(&i)));
+        let false_positives = (700..10700).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 20, "{false_positives} false positives");
+        assert!(!filter.insert(&0));
+
+        // Growing keeps every item.
+        for i in 700..1000 {
+            filter.insert(&i);
+        }
+        assert_eq!((filter.slots(), filter.remainder_bits()), (2048, 11));
+        assert!((0..1000).all(|i| filter.contains(&i)));
+        // Less any fingerprints which collided.
+        assert!(filter.len() > 990);
+    }
+
+    #[test]
+    fn merge_test() {
+        let mut a = QuotientFilter::with_hasher(6, 10, Fixed::default());
+        let mut b = QuotientFilter::with_hasher(8, 8, Fixed::default());
+        for i in 0..40 {
+            a.insert(&i);
+        }
+        for i in 20..100 {
+            b.insert(&i);
+        }
+        a.merge(&b);
+        assert!((0..100).all(|i| a.contains(&i)));
+        assert_eq!(a.len(), 100);
+    }
+
+    #[test]
+    fn codec_test() {
+        let mut filter = QuotientFilter::with_hasher(6, 13, Fixed::default());
+        for i in 0..40 {
+            filter.insert(&i);
+        }
+        let mut buf = Vec::new();
+        filter.encode(&mut buf).unwrap();
+        let decoded = QuotientFilter::<Fixed>::decode(&mut &buf[..]).unwrap();
+        assert_eq!(decoded.len(), 40);
+        assert!((0..40).all(|i| decoded.contains(&i)));
+        assert!(QuotientFilter::<Fixed>::decode(&mut &buf[..20]).is_err());
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(fingerprints: Vec<u8>) -> bool {
+        // Four bits each way, so clusters wrap around and collide often.
+        let mut filter = QuotientFilter::with_hasher(4, 4, Fixed::default());
+        let mut model = BTreeSet::new();
+        for fingerprint in fingerprints.into_iter().take(15) {
+            let fingerprint = fingerprint as u64;
+            if filter.add(fingerprint) != model.insert(fingerprint) {
+                return false;
+            }
+        }
+        let mut stored = filter.fingerprints();
+        stored.sort_unstable();
+        stored.len() == filter.len()
+            && stored.iter().eq(model.iter())
+            && model.iter().all(|&f| filter.contains_fingerprint(f))
+            && (0..256).all(|f| filter.contains_fingerprint(f) == model.contains(&f))
+    }
+}
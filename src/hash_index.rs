@@ -0,0 +1,131 @@
+//! The open-addressing table behind the hash maps which keep their entries
+//! in a vector, mapping keys to positions in it.
+
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+
+const EMPTY: usize = usize::MAX;
+
+/// Positions in a slice of entries, found by linear probing on the hash of
+/// their keys. The entries themselves are passed in by the owning map.
+#[derive(Clone)]
+pub(crate) struct HashIndex<S> {
+    // Indices into the entries, length is a power of two.
+    slots: Vec<usize>,
+    hasher: S,
+}
+
+impl<S: BuildHasher> HashIndex<S> {
+    pub(crate) fn new(hasher: S) -> Self {
+        HashIndex {
+            slots: Vec::new(),
+            hasher,
+        }
+    }
+
+    #[inline]
+    fn home<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        self.hasher.hash_one(key) as usize & (self.slots.len() - 1)
+    }
+
+    /// The slot holding the position of `key` in `entries`.
+    pub(crate) fn find<K, V, Q>(&self, entries: &[(K, V)], key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let mask = self.slots.len() - 1;
+        let mut slot = self.home(key);
+        while self.slots[slot] != EMPTY {
+            if entries[self.slots[slot]].0.borrow() == key {
+                return Some(slot);
+            }
+            slot = (slot + 1) & mask;
+        }
+        None
+    }
+
+    /// The position held in `slot`.
+    #[inline]
+    pub(crate) fn get(&self, slot: usize) -> usize {
+        self.slots[slot]
+    }
+
+    fn place<K: Hash, V>(&mut self, entries: &[(K, V)], idx: usize) {
+        let mask = self.slots.len() - 1;
+        let mut slot = self.home(&entries[idx].0);
+        while self.slots[slot] != EMPTY {
+            slot = (slot + 1) & mask;
+        }
+        self.slots[slot] = idx;
+    }
+
+    /// Indexes `entries[idx]`, which is new, keeping the table at most half
+    /// full.
+    pub(crate) fn insert<K: Hash, V>(&mut self, entries: &[(K, V)], idx: usize) {
+        if entries.len() * 2 <= self.slots.len() {
+            self.place(entries, idx);
+            return;
+        }
+        let size = std::cmp::max(8, self.slots.len() * 2);
+        self.slots = vec![EMPTY; size];
+        for idx in 0..entries.len() {
+            self.place(entries, idx);
+        }
+    }
+
+    /// Empties `slot`, by backward-shift deletion, which keeps probe
+    /// sequences intact without tombstones.
+    pub(crate) fn remove<K: Hash, V>(&mut self, entries: &[(K, V)], slot: usize) {
+        let mask = self.slots.len() - 1;
+        let mut hole = slot;
+        let mut next = (slot + 1) & mask;
+        while self.slots[next] != EMPTY {
+            let home = self.home(&entries[self.slots[next]].0);
+            if next.wrapping_sub(home) & mask >= next.wrapping_sub(hole) & mask {
+                self.slots[hole] = self.slots[next];
+                hole = next;
+            }
+            next = (next + 1) & mask;
+        }
+        self.slots[hole] = EMPTY;
+    }
+
+    /// Points the slot of `entries[from]` at `to`, where it's about to move.
+    pub(crate) fn repoint<K: Hash, V>(&mut self, entries: &[(K, V)], from: usize, to: usize) {
+        let mask = self.slots.len() - 1;
+        let mut slot = self.home(&entries[from].0);
+        while self.slots[slot] != from {
+            slot = (slot + 1) & mask;
+        }
+        self.slots[slot] = to;
+    }
+
+    /// Moves every position after `idx` down one, for a removal which
+    /// shifts the entries.
+    pub(crate) fn shift_down(&mut self, idx: usize) {
+        for slot in self.slots.iter_mut() {
+            if *slot != EMPTY && *slot > idx {
+                *slot -= 1;
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    /// The number of slots holding a position.
+    #[cfg(test)]
+    pub(crate) fn occupied(&self) -> usize {
+        self.slots.iter().filter(|&&idx| idx != EMPTY).count()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn slots(&self) -> &[usize] {
+        &self.slots
+    }
+}
@@ -0,0 +1,292 @@
+//! Priority queues. The addressable ones hand out a [`Handle`] from `push`
+//! which can later find the item again, to lower its priority in place.
+
+mod binomial;
+mod bucket;
+mod calendar;
+mod fibonacci;
+mod interval;
+mod kmerge;
+mod quantile;
+mod skew;
+mod splay_heap;
+mod top_k;
+
+pub use binomial::BinomialHeap;
+pub use bucket::BucketQueue;
+pub use calendar::CalendarQueue;
+pub use fibonacci::FibonacciHeap;
+pub use interval::IntervalHeap;
+pub use kmerge::{kmerge, kmerge_stable, KMerge, WithSource};
+pub use quantile::{QuantileTracker, Ticket};
+pub use skew::SkewHeap;
+pub use splay_heap::SplayHeap;
+pub use top_k::TopK;
+
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+const NONE: usize = usize::MAX;
+
+/// Refers to an item pushed into a heap, until it's popped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    slot: usize,
+    generation: u32,
+}
+
+/// The operations every heap here shares, so they can be swapped for one
+/// another. The caveats of each, like what `meld` costs, are on the types.
+pub trait AddressableHeap<T: Ord>: Default {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn push(&mut self, item: T) -> Handle;
+
+    fn peek_min(&self) -> Option<&T>;
+
+    fn pop_min(&mut self) -> Option<T>;
+
+    /// The item behind `handle`, `None` once it was popped.
+    fn get(&self, handle: Handle) -> Option<&T>;
+
+    /// Replaces the item behind `handle` with a smaller one, panicking if
+    /// it's greater or was popped already.
+    fn decrease_key(&mut self, handle: Handle, item: T);
+
+    /// Moves every item of `other` in. Handles into `other` don't carry
+    /// over.
+    fn meld(&mut self, other: Self);
+}
+
+macro_rules! addressable_heap {
+    ($($heap:ident),*) => {
+        $(
+            impl<T: Ord> AddressableHeap<T> for $heap<T> {
+                fn len(&self) -> usize {
+                    $heap::len(self)
+                }
+
+                fn push(&mut self, item: T) -> Handle {
+                    $heap::push(self, item)
+                }
+
+                fn peek_min(&self) -> Option<&T> {
+                    $heap::peek_min(self)
+                }
+
+                fn pop_min(&mut self) -> Option<T> {
+                    $heap::pop_min(self)
+                }
+
+                fn get(&self, handle: Handle) -> Option<&T> {
+                    $heap::get(self, handle)
+                }
+
+                fn decrease_key(&mut self, handle: Handle, item: T) {
+                    $heap::decrease_key(self, handle, item)
+                }
+
+                fn meld(&mut self, other: Self) {
+                    $heap::meld(self, other)
+                }
+            }
+        )*
+    };
+}
+
+addressable_heap!(BinomialHeap, FibonacciHeap);
+
+#[derive(Clone)]
+struct Slot<N> {
+    generation: u32,
+    node: Option<N>,
+}
+
+/// The nodes of a heap, linked by slot index. Slots are reused after a pop,
+/// bumping their generation so handles to the old item stop resolving.
+#[derive(Clone)]
+struct Slab<N> {
+    slots: Vec<Slot<N>>,
+    free: Vec<usize>,
+}
+
+impl<N> Slab<N> {
+    fn new() -> Self {
+        Slab {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    fn insert(&mut self, node: N) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot].node = Some(node);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    fn remove(&mut self, slot: usize) -> N {
+        let entry = &mut self.slots[slot];
+        entry.generation = entry.generation.wrapping_add(1);
+        self.free.push(slot);
+        entry.node.take().unwrap()
+    }
+
+    fn pair_mut(&mut self, a: usize, b: usize) -> (&mut N, &mut N) {
+        let [x, y] = self.slots.get_disjoint_mut([a, b]).unwrap();
+        (x.node.as_mut().unwrap(), y.node.as_mut().unwrap())
+    }
+
+    fn handle(&self, slot: usize) -> Handle {
+        Handle {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    fn resolve(&self, handle: Handle) -> Option<usize> {
+        let entry = self.slots.get(handle.slot)?;
+        (entry.generation == handle.generation && entry.node.is_some()).then_some(handle.slot)
+    }
+
+    /// Moves every node of `other` in after the current slots, returning
+    /// the offset its slot indices moved by. Links inside the moved nodes
+    /// are for the caller to shift.
+    fn append(&mut self, other: Slab<N>) -> usize {
+        let offset = self.slots.len();
+        self.slots.extend(other.slots);
+        self.free
+            .extend(other.free.into_iter().map(|slot| slot + offset));
+        offset
+    }
+
+    fn clear(&mut self) {
+        self.slots.clear();
+        self.free.clear();
+    }
+}
+
+impl<N> Index<usize> for Slab<N> {
+    type Output = N;
+
+    fn index(&self, slot: usize) -> &N {
+        self.slots[slot].node.as_ref().unwrap()
+    }
+}
+
+impl<N> IndexMut<usize> for Slab<N> {
+    fn index_mut(&mut self, slot: usize) -> &mut N {
+        self.slots[slot].node.as_mut().unwrap()
+    }
+}
+
+/// Shifts a link by `offset`, leaving `NONE` alone.
+fn shift(link: &mut usize, offset: usize) {
+    if *link != NONE {
+        *link += offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Push(u16),
+        Pop,
+        Decrease(usize, u16),
+        Meld(Vec<u16>),
+    }
+
+    impl quickcheck::Arbitrary for Op {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            match u8::arbitrary(g) % 8 {
+                0..=2 => Op::Push(u16::arbitrary(g)),
+                3..=4 => Op::Pop,
+                5..=6 => Op::Decrease(usize::arbitrary(g), u16::arbitrary(g)),
+                _ => Op::Meld(Vec::arbitrary(g)),
+            }
+        }
+    }
+
+    /// Runs `ops` against a model keyed by unique tags, which the heap
+    /// orders ties by too.
+    fn check<H: AddressableHeap<(u16, usize)>>(ops: Vec<Op>) -> bool {
+        let mut heap = H::default();
+        let mut model = BTreeMap::new();
+        let mut handles = Vec::new();
+        for (tag, op) in ops.into_iter().enumerate() {
+            match op {
+                Op::Push(priority) => {
+                    handles.push((heap.push((priority, tag)), tag));
+                    model.insert(tag, priority);
+                }
+                Op::Pop => {
+                    let expected = model.iter().map(|(&t, &p)| (p, t)).min();
+                    if heap.pop_min() != expected {
+                        return false;
+                    }
+                    if let Some((_, tag)) = expected {
+                        model.remove(&tag);
+                    }
+                }
+                Op::Decrease(which, by) => {
+                    if handles.is_empty() {
+                        continue;
+                    }
+                    let (handle, tag) = handles[which % handles.len()];
+                    if let Some(priority) = model.get_mut(&tag) {
+                        *priority = priority.saturating_sub(by);
+                        heap.decrease_key(handle, (*priority, tag));
+                        if heap.get(handle) != Some(&(*priority, tag)) {
+                            return false;
+                        }
+                    } else if heap.get(handle).is_some() {
+                        return false;
+                    }
+                }
+                Op::Meld(priorities) => {
+                    let mut other = H::default();
+                    for (i, priority) in priorities.into_iter().take(256).enumerate() {
+                        // Tags past any `ops` index, never decreased.
+                        let tag = usize::MAX - tag * 256 - i;
+                        other.push((priority, tag));
+                        model.insert(tag, priority);
+                    }
+                    heap.meld(other);
+                }
+            }
+            let min = model.iter().map(|(&t, &p)| (p, t)).min();
+            if heap.len() != model.len() || heap.peek_min() != min.as_ref() {
+                return false;
+            }
+        }
+        true
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_binomial(ops: Vec<Op>) -> bool {
+        check::<BinomialHeap<_>>(ops)
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_fibonacci(ops: Vec<Op>) -> bool {
+        check::<FibonacciHeap<_>>(ops)
+    }
+}
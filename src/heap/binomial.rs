@@ -0,0 +1,302 @@
+use core::mem;
+
+use super::{shift, Handle, Slab, NONE};
+
+#[derive(Clone)]
+struct Node<T> {
+    item: T,
+    // Slot in `handles` pointing back here.
+    handle: usize,
+    parent: usize,
+    // Children go from the highest degree down, roots from the lowest up.
+    child: usize,
+    sibling: usize,
+    degree: usize,
+}
+
+/// A binomial heap: a list of heap-ordered binomial trees, at most one of
+/// each degree, like the bits of `len`. Two heaps meld like binary addition,
+/// in O(log n) links.
+///
+/// `decrease_key` moves items up their tree, so handles resolve through a
+/// table of their own rather than to a fixed node.
+#[derive(Clone)]
+pub struct BinomialHeap<T> {
+    nodes: Slab<Node<T>>,
+    handles: Slab<usize>,
+    head: usize,
+    len: usize,
+}
+
+impl<T: Ord> BinomialHeap<T> {
+    pub fn new() -> Self {
+        BinomialHeap {
+            nodes: Slab::new(),
+            handles: Slab::new(),
+            head: NONE,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, item: T) -> Handle {
+        let handle = self.handles.insert(NONE);
+        let idx = self.nodes.insert(Node {
+            item,
+            handle,
+            parent: NONE,
+            child: NONE,
+            sibling: NONE,
+            degree: 0,
+        });
+        self.handles[handle] = idx;
+        self.head = self.union(self.head, idx);
+        self.len += 1;
+        self.handles.handle(handle)
+    }
+
+    /// Merges two root lists by degree, then links trees of equal degree
+    /// until at most one of each is left.
+    fn union(&mut self, a: usize, b: usize) -> usize {
+        let head = self.merge_lists(a, b);
+        let (mut prev, mut x) = (NONE, head);
+        let mut head = head;
+        while x != NONE && self.nodes[x].sibling != NONE {
+            let next = self.nodes[x].sibling;
+            let after = self.nodes[next].sibling;
+            let degree = self.nodes[x].degree;
+            if degree != self.nodes[next].degree
+                || (after != NONE && self.nodes[after].degree == degree)
+            {
+                // Either nothing to link, or three in a row and the last two
+                // go first.
+                prev = x;
+                x = next;
+            } else if self.nodes[x].item <= self.nodes[next].item {
+                self.nodes[x].sibling = after;
+                self.link(next, x);
+            } else {
+                if prev == NONE {
+                    head = next;
+                } else {
+                    self.nodes[prev].sibling = next;
+                }
+                self.link(x, next);
+                x = next;
+            }
+        }
+        head
+    }
+
+    fn merge_lists(&mut self, mut a: usize, mut b: usize) -> usize {
+        let mut head = NONE;
+        let mut tail = NONE;
+        while a != NONE || b != NONE {
+            let take_a = b == NONE || (a != NONE && self.nodes[a].degree <= self.nodes[b].degree);
+            let idx = if take_a { a } else { b };
+            let next = self.nodes[idx].sibling;
+            if take_a {
+                a = next;
+            } else {
+                b = next;
+            }
+            if tail == NONE {
+                head = idx;
+            } else {
+                self.nodes[tail].sibling = idx;
+            }
+            tail = idx;
+        }
+        if tail != NONE {
+            self.nodes[tail].sibling = NONE;
+        }
+        head
+    }
+
+    /// Makes the root `child` the first child of the root `parent`.
+    fn link(&mut self, child: usize, parent: usize) {
+        self.nodes[child].parent = parent;
+        self.nodes[child].sibling = self.nodes[parent].child;
+        self.nodes[parent].child = child;
+        self.nodes[parent].degree += 1;
+    }
+
+    /// The smallest root and the one before it.
+    fn min_root(&self) -> Option<(usize, usize)> {
+        let (mut best, mut best_prev) = (self.head, NONE);
+        if best == NONE {
+            return None;
+        }
+        let (mut prev, mut x) = (self.head, self.nodes[self.head].sibling);
+        while x != NONE {
+            if self.nodes[x].item < self.nodes[best].item {
+                (best, best_prev) = (x, prev);
+            }
+            (prev, x) = (x, self.nodes[x].sibling);
+        }
+        Some((best, best_prev))
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        let (min, _) = self.min_root()?;
+        Some(&self.nodes[min].item)
+    }
+
+    /// The item behind `handle`, `None` once it was popped.
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        let slot = self.handles.resolve(handle)?;
+        Some(&self.nodes[self.handles[slot]].item)
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let (min, prev) = self.min_root()?;
+        Some(self.remove_root(min, prev))
+    }
+
+    /// Removes the item behind `handle` from anywhere in the heap, `None`
+    /// if it was popped already.
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let slot = self.handles.resolve(handle)?;
+        // Carry the item up to its root as if it were the smallest.
+        let mut idx = self.handles[slot];
+        while self.nodes[idx].parent != NONE {
+            let parent = self.nodes[idx].parent;
+            self.swap_items(idx, parent);
+            idx = parent;
+        }
+        let (mut prev, mut x) = (NONE, self.head);
+        while x != idx {
+            (prev, x) = (x, self.nodes[x].sibling);
+        }
+        Some(self.remove_root(idx, prev))
+    }
+
+    /// Unlinks the root `root`, which follows `prev` in the root list, and
+    /// merges its children back in.
+    fn remove_root(&mut self, root: usize, prev: usize) -> T {
+        let after = self.nodes[root].sibling;
+        if prev == NONE {
+            self.head = after;
+        } else {
+            self.nodes[prev].sibling = after;
+        }
+        // The children, reversed, are a root list of their own.
+        let (mut children, mut child) = (NONE, self.nodes[root].child);
+        while child != NONE {
+            let next = self.nodes[child].sibling;
+            self.nodes[child].parent = NONE;
+            self.nodes[child].sibling = children;
+            children = child;
+            child = next;
+        }
+        self.head = self.union(self.head, children);
+        self.len -= 1;
+        let node = self.nodes.remove(root);
+        self.handles.remove(node.handle);
+        node.item
+    }
+
+    /// Replaces the item behind `handle` with a smaller one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the item was popped already or `item` is greater.
+    pub fn decrease_key(&mut self, handle: Handle, item: T) {
+        let slot = self.handles.resolve(handle).expect("item was popped");
+        let mut idx = self.handles[slot];
+        assert!(item <= self.nodes[idx].item, "new item is greater");
+        self.nodes[idx].item = item;
+        loop {
+            let parent = self.nodes[idx].parent;
+            if parent == NONE || self.nodes[parent].item <= self.nodes[idx].item {
+                return;
+            }
+            self.swap_items(idx, parent);
+            idx = parent;
+        }
+    }
+
+    fn swap_items(&mut self, a: usize, b: usize) {
+        let (x, y) = self.nodes.pair_mut(a, b);
+        mem::swap(&mut x.item, &mut y.item);
+        mem::swap(&mut x.handle, &mut y.handle);
+        self.handles[self.nodes[a].handle] = a;
+        self.handles[self.nodes[b].handle] = b;
+    }
+
+    /// Moves every item of `other` into this heap, in O(log n) links after
+    /// a single copy of `other`'s arena onto the end of this one. Handles
+    /// into `other` don't carry over.
+    pub fn meld(&mut self, other: BinomialHeap<T>) {
+        if other.head == NONE {
+            return;
+        }
+        let offset = self.nodes.append(other.nodes);
+        let handle_offset = self.handles.append(other.handles);
+        for slot in &mut self.nodes.slots[offset..] {
+            if let Some(node) = &mut slot.node {
+                for link in [&mut node.parent, &mut node.child, &mut node.sibling] {
+                    shift(link, offset);
+                }
+                node.handle += handle_offset;
+            }
+        }
+        for slot in &mut self.handles.slots[handle_offset..] {
+            if let Some(idx) = &mut slot.node {
+                *idx += offset;
+            }
+        }
+        self.len += other.len;
+        self.head = self.union(self.head, other.head + offset);
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.handles.clear();
+        self.head = NONE;
+        self.len = 0;
+    }
+}
+
+impl<T: Ord> Default for BinomialHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_test() {
+        let mut heap = BinomialHeap::new();
+        let handles: Vec<_> = (0..7).map(|i| heap.push(i * 10)).collect();
+        // Seven is 0b111: one tree each of degrees 0, 1 and 2.
+        assert_eq!(heap.peek_min(), Some(&0));
+        heap.decrease_key(handles[6], 5);
+        assert_eq!(heap.pop_min(), Some(0));
+        assert_eq!(heap.pop_min(), Some(5));
+        assert_eq!(heap.get(handles[1]), Some(&10));
+        assert_eq!(heap.remove(handles[3]), Some(30));
+        assert_eq!(heap.remove(handles[3]), None);
+        heap.push(30);
+
+        let mut other = BinomialHeap::new();
+        other.push(15);
+        heap.meld(other);
+        assert_eq!(heap.len(), 6);
+        let mut out = Vec::new();
+        while let Some(item) = heap.pop_min() {
+            out.push(item);
+        }
+        assert_eq!(out, [10, 15, 20, 30, 40, 50]);
+    }
+}
@@ -0,0 +1,151 @@
+use alloc::vec::Vec;
+
+/// A priority queue for priorities in `0..=max_priority`, one bucket each.
+///
+/// `push` is O(1). `pop_min` scans forward from the last bucket it popped
+/// from, so when priorities only go up from there, as in Dial's shortest
+/// paths or a tick scheduler, a whole run of pops costs O(n + max_priority).
+/// Items of equal priority come out last in, first out.
+#[derive(Clone, Debug)]
+pub struct BucketQueue<T> {
+    buckets: Vec<Vec<T>>,
+    // No item has a priority below `cursor`.
+    cursor: usize,
+    len: usize,
+}
+
+impl<T> BucketQueue<T> {
+    pub fn new(max_priority: usize) -> Self {
+        BucketQueue {
+            buckets: (0..=max_priority).map(|_| Vec::new()).collect(),
+            cursor: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn max_priority(&self) -> usize {
+        self.buckets.len() - 1
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `priority` is above the maximum.
+    pub fn push(&mut self, priority: usize, item: T) {
+        assert!(priority < self.buckets.len(), "priority out of range");
+        self.buckets[priority].push(item);
+        self.cursor = self.cursor.min(priority);
+        self.len += 1;
+    }
+
+    /// Moves the cursor onto the lowest nonempty bucket.
+    fn seek(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        while self.buckets[self.cursor].is_empty() {
+            self.cursor += 1;
+        }
+        Some(self.cursor)
+    }
+
+    /// The lowest priority of any item.
+    pub fn min_priority(&mut self) -> Option<usize> {
+        self.seek()
+    }
+
+    pub fn peek_min(&mut self) -> Option<(usize, &T)> {
+        let priority = self.seek()?;
+        Some((priority, self.buckets[priority].last()?))
+    }
+
+    pub fn pop_min(&mut self) -> Option<(usize, T)> {
+        let priority = self.seek()?;
+        self.len -= 1;
+        Some((priority, self.buckets[priority].pop()?))
+    }
+
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        self.cursor = 0;
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BinaryHeap;
+    use core::cmp::Reverse;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut queue = BucketQueue::new(10);
+        queue.push(3, 'a');
+        queue.push(1, 'b');
+        queue.push(3, 'c');
+        assert_eq!(queue.pop_min(), Some((1, 'b')));
+        assert_eq!(queue.peek_min(), Some((3, &'c')));
+        queue.push(0, 'd');
+        assert_eq!(queue.pop_min(), Some((0, 'd')));
+        assert_eq!(queue.pop_min(), Some((3, 'c')));
+        assert_eq!(queue.pop_min(), Some((3, 'a')));
+        assert_eq!(queue.pop_min(), None);
+        assert_eq!(queue.max_priority(), 10);
+    }
+
+    /// Dial's algorithm on random graphs against a plain Dijkstra.
+    #[quickcheck]
+    fn test_quickcheck(edges: Vec<(u8, u8, u8)>) -> bool {
+        const NODES: usize = 16;
+        const MAX_WEIGHT: usize = 7;
+        let mut adjacent = vec![Vec::new(); NODES];
+        for (from, to, weight) in edges {
+            let weight = weight as usize % (MAX_WEIGHT + 1);
+            adjacent[from as usize % NODES].push((to as usize % NODES, weight));
+        }
+
+        let mut dial = vec![usize::MAX; NODES];
+        let mut queue = BucketQueue::new(MAX_WEIGHT * NODES);
+        dial[0] = 0;
+        queue.push(0, 0);
+        while let Some((distance, node)) = queue.pop_min() {
+            if distance > dial[node] {
+                continue;
+            }
+            for &(next, weight) in &adjacent[node] {
+                if distance + weight < dial[next] {
+                    dial[next] = distance + weight;
+                    queue.push(distance + weight, next);
+                }
+            }
+        }
+
+        let mut dijkstra = vec![usize::MAX; NODES];
+        let mut heap = BinaryHeap::new();
+        dijkstra[0] = 0;
+        heap.push(Reverse((0, 0)));
+        while let Some(Reverse((distance, node))) = heap.pop() {
+            if distance > dijkstra[node] {
+                continue;
+            }
+            for &(next, weight) in &adjacent[node] {
+                if distance + weight < dijkstra[next] {
+                    dijkstra[next] = distance + weight;
+                    heap.push(Reverse((distance + weight, next)));
+                }
+            }
+        }
+        dial == dijkstra
+    }
+}
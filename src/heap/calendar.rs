@@ -0,0 +1,238 @@
+use alloc::vec::Vec;
+
+const MIN_BUCKETS: usize = 2;
+// How many of the earliest events set the bucket width on a resize.
+const SAMPLE: usize = 25;
+
+/// A calendar queue (Brown, 1988), for the pending events of a simulation.
+///
+/// Times hash into a ring of buckets, each a day of `width` in a year of
+/// as many days as there are buckets. `pop_min` looks through today's
+/// bucket for an event of this year and moves on a day if there isn't one,
+/// so with event times spread roughly evenly both `push` and `pop_min` are
+/// O(1) on average. The ring doubles or halves as the queue grows or
+/// shrinks, and each time the width is set again from the gaps between the
+/// earliest events, to keep a few per day. Events at equal times come out
+/// first in, first out.
+#[derive(Clone, Debug)]
+pub struct CalendarQueue<T> {
+    // Each sorted by time descending, so the earliest is at the end.
+    buckets: Vec<Vec<(f64, T)>>,
+    width: f64,
+    // The day being looked at, counted from time zero; no event is on an
+    // earlier one.
+    day: i64,
+    len: usize,
+}
+
+impl<T> CalendarQueue<T> {
+    pub fn new() -> Self {
+        CalendarQueue {
+            buckets: (0..MIN_BUCKETS).map(|_| Vec::new()).collect(),
+            width: 1.0,
+            day: i64::MIN,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The span of time each bucket covers at the moment.
+    pub fn bucket_width(&self) -> f64 {
+        self.width
+    }
+
+    fn day_of(&self, time: f64) -> i64 {
+        // `floor`, which isn't in `core`; the cast saturates far out.
+        let days = time / self.width;
+        let day = days as i64;
+        day - ((day as f64) > days) as i64
+    }
+
+    fn bucket_of(&self, day: i64) -> usize {
+        day.rem_euclid(self.buckets.len() as i64) as usize
+    }
+
+    fn place(&mut self, time: f64, item: T) {
+        let day = self.day_of(time);
+        let b = self.bucket_of(day);
+        let bucket = &mut self.buckets[b];
+        // Ahead of any at the same time, which leave first.
+        let i = bucket.partition_point(|&(t, _)| t > time);
+        bucket.insert(i, (time, item));
+        self.day = self.day.min(day);
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `time` is NaN.
+    pub fn push(&mut self, time: f64, item: T) {
+        assert!(!time.is_nan(), "event time is NaN");
+        if self.len == 0 {
+            self.day = i64::MAX;
+        }
+        self.place(time, item);
+        self.len += 1;
+        if self.len > 2 * self.buckets.len() {
+            self.resize(2 * self.buckets.len());
+        }
+    }
+
+    /// Moves on to the day of the earliest event, returning its bucket.
+    fn seek(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        for _ in 0..self.buckets.len() {
+            let b = self.bucket_of(self.day);
+            if let Some(&(time, _)) = self.buckets[b].last() {
+                if self.day_of(time) <= self.day {
+                    return Some(b);
+                }
+            }
+            self.day += 1;
+        }
+        // A whole year went by empty, so the events are sparse: jump
+        // straight to the earliest.
+        let (b, time) = self
+            .buckets
+            .iter()
+            .enumerate()
+            .filter_map(|(b, bucket)| Some((b, bucket.last()?.0)))
+            .min_by(|x, y| x.1.total_cmp(&y.1))?;
+        self.day = self.day_of(time);
+        Some(b)
+    }
+
+    /// The earliest event, with its time.
+    pub fn peek_min(&mut self) -> Option<(f64, &T)> {
+        let b = self.seek()?;
+        let (time, item) = self.buckets[b].last()?;
+        Some((*time, item))
+    }
+
+    pub fn pop_min(&mut self) -> Option<(f64, T)> {
+        let b = self.seek()?;
+        let event = self.buckets[b].pop();
+        self.len -= 1;
+        if self.len < self.buckets.len() / 2 && self.buckets.len() > MIN_BUCKETS {
+            self.resize(self.buckets.len() / 2);
+        }
+        event
+    }
+
+    /// Rebuilds the ring with `buckets` days, each about three times as
+    /// wide as the typical gap between the earliest events.
+    fn resize(&mut self, buckets: usize) {
+        // Bucket by bucket, each earliest first, so equal times keep their
+        // order.
+        let mut events: Vec<(f64, T)> = Vec::with_capacity(self.len);
+        for bucket in &mut self.buckets {
+            events.extend(bucket.drain(..).rev());
+        }
+        let mut times: Vec<f64> = events.iter().map(|&(time, _)| time).collect();
+        let k = times.len().min(SAMPLE);
+        if k >= 2 {
+            times.select_nth_unstable_by(k - 1, f64::total_cmp);
+            times[..k].sort_unstable_by(f64::total_cmp);
+            let gaps: Vec<f64> = times[..k].windows(2).map(|w| w[1] - w[0]).collect();
+            let mean = gaps.iter().sum::<f64>() / gaps.len() as f64;
+            // Leave out the odd large gap, as Brown does.
+            let typical: Vec<f64> = gaps.into_iter().filter(|&gap| gap <= 2.0 * mean).collect();
+            let width = 3.0 * typical.iter().sum::<f64>() / typical.len() as f64;
+            if width.is_finite() && width > 0.0 {
+                self.width = width;
+            }
+        }
+        self.buckets = (0..buckets).map(|_| Vec::new()).collect();
+        self.day = i64::MAX;
+        for (time, item) in events {
+            self.place(time, item);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.iter_mut().for_each(Vec::clear);
+        self.len = 0;
+    }
+}
+
+impl<T> Default for CalendarQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeSet;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut queue = CalendarQueue::new();
+        for (i, time) in [3.0, 1.5, 1.5, 100.0, -2.0, 7.25].into_iter().enumerate() {
+            queue.push(time, i);
+        }
+        assert_eq!(queue.peek_min(), Some((-2.0, &4)));
+        let order: Vec<_> = core::iter::from_fn(|| queue.pop_min()).collect();
+        assert_eq!(
+            order,
+            [
+                (-2.0, 4),
+                (1.5, 1),
+                (1.5, 2),
+                (3.0, 0),
+                (7.25, 5),
+                (100.0, 3)
+            ]
+        );
+
+        // A steady stream of events a tenth apart, each scheduling the next.
+        let mut now = 0.0;
+        for i in 0..1000 {
+            queue.push(i as f64 * 0.1, i);
+        }
+        while let Some((time, i)) = queue.pop_min() {
+            assert!(time >= now);
+            now = time;
+            if i < 5000 {
+                queue.push(time + 100.0, i + 1000);
+            }
+        }
+        assert!(queue.bucket_width() < 1.0);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<Option<(u8, u8)>>) -> bool {
+        let mut queue = CalendarQueue::new();
+        let mut model = BTreeSet::new();
+        for (i, op) in ops.into_iter().enumerate() {
+            match op {
+                // Times of very different spreads, to make it resize.
+                Some((time, scale)) => {
+                    let time = time as f64 * [0.01, 1.0, 1000.0][scale as usize % 3];
+                    queue.push(time, i);
+                    model.insert((time.to_bits(), i));
+                }
+                None => {
+                    let expected = model.pop_first().map(|(time, i)| (f64::from_bits(time), i));
+                    if queue.pop_min() != expected {
+                        return false;
+                    }
+                }
+            }
+            if queue.len() != model.len() {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,385 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use super::{shift, Handle, Slab, NONE};
+
+#[derive(Clone)]
+struct Node<T> {
+    item: T,
+    parent: usize,
+    child: usize,
+    // Siblings, in a circular list.
+    left: usize,
+    right: usize,
+    degree: usize,
+    // Lost a child since it last became a child itself.
+    marked: bool,
+}
+
+/// A Fibonacci heap of Fredman and Tarjan: O(1) `push` and amortized
+/// `decrease_key`, O(log n) amortized `pop_min`.
+///
+/// All the work is deferred to `pop_min`, which links the roots into trees
+/// of distinct degrees. The constant factors are high, it pays off when
+/// `decrease_key` dominates, as in Dijkstra's or Prim's algorithm on dense
+/// graphs.
+#[derive(Clone)]
+pub struct FibonacciHeap<T> {
+    nodes: Slab<Node<T>>,
+    // Somewhere in the root list, the smallest root.
+    min: usize,
+    len: usize,
+}
+
+impl<T: Ord> FibonacciHeap<T> {
+    pub fn new() -> Self {
+        FibonacciHeap {
+            nodes: Slab::new(),
+            min: NONE,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, item: T) -> Handle {
+        let idx = self.nodes.insert(Node {
+            item,
+            parent: NONE,
+            child: NONE,
+            left: NONE,
+            right: NONE,
+            degree: 0,
+            marked: false,
+        });
+        self.add_root(idx);
+        self.len += 1;
+        self.nodes.handle(idx)
+    }
+
+    /// Splices `idx` into the root list as a singleton, updating `min`.
+    fn add_root(&mut self, idx: usize) {
+        self.nodes[idx].parent = NONE;
+        if self.min == NONE {
+            self.nodes[idx].left = idx;
+            self.nodes[idx].right = idx;
+            self.min = idx;
+            return;
+        }
+        self.splice(self.min, idx);
+        if self.nodes[idx].item < self.nodes[self.min].item {
+            self.min = idx;
+        }
+    }
+
+    /// Inserts `idx` to the right of `at`.
+    fn splice(&mut self, at: usize, idx: usize) {
+        let right = self.nodes[at].right;
+        self.nodes[idx].left = at;
+        self.nodes[idx].right = right;
+        self.nodes[at].right = idx;
+        self.nodes[right].left = idx;
+    }
+
+    /// Takes `idx` out of its sibling list.
+    fn unlink(&mut self, idx: usize) {
+        let Node { left, right, .. } = self.nodes[idx];
+        self.nodes[left].right = right;
+        self.nodes[right].left = left;
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        (self.min != NONE).then(|| &self.nodes[self.min].item)
+    }
+
+    /// The item behind `handle`, `None` once it was popped.
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        let idx = self.nodes.resolve(handle)?;
+        Some(&self.nodes[idx].item)
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let min = self.min;
+        if min == NONE {
+            return None;
+        }
+        let mut child = self.nodes[min].child;
+        for _ in 0..self.nodes[min].degree {
+            let next = self.nodes[child].right;
+            self.nodes[child].parent = NONE;
+            self.nodes[child].marked = false;
+            self.splice(min, child);
+            child = next;
+        }
+        let next = self.nodes[min].right;
+        self.unlink(min);
+        self.min = if next == min { NONE } else { next };
+        self.len -= 1;
+        let item = self.nodes.remove(min).item;
+        if self.min != NONE {
+            self.consolidate();
+        }
+        Some(item)
+    }
+
+    /// Links roots of equal degree until all degrees differ, then rebuilds
+    /// the root list from what's left.
+    fn consolidate(&mut self) {
+        let mut roots = Vec::new();
+        let mut idx = self.min;
+        loop {
+            roots.push(idx);
+            idx = self.nodes[idx].right;
+            if idx == self.min {
+                break;
+            }
+        }
+        let mut by_degree: Vec<usize> =
+            vec![NONE; 2 * (usize::BITS - self.len.leading_zeros()) as usize + 2];
+        for mut root in roots {
+            let mut degree = self.nodes[root].degree;
+            while by_degree[degree] != NONE {
+                let mut other = mem::replace(&mut by_degree[degree], NONE);
+                if self.nodes[other].item < self.nodes[root].item {
+                    mem::swap(&mut root, &mut other);
+                }
+                self.adopt(root, other);
+                degree += 1;
+            }
+            by_degree[degree] = root;
+        }
+        self.min = NONE;
+        for root in by_degree.into_iter().filter(|&root| root != NONE) {
+            self.add_root(root);
+        }
+    }
+
+    /// Makes `child`, a root, a child of the root `parent`.
+    fn adopt(&mut self, parent: usize, child: usize) {
+        self.nodes[child].parent = parent;
+        self.nodes[child].marked = false;
+        match self.nodes[parent].child {
+            NONE => {
+                self.nodes[child].left = child;
+                self.nodes[child].right = child;
+                self.nodes[parent].child = child;
+            }
+            first => self.splice(first, child),
+        }
+        self.nodes[parent].degree += 1;
+    }
+
+    /// Replaces the item behind `handle` with a smaller one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the item was popped already or `item` is greater.
+    pub fn decrease_key(&mut self, handle: Handle, item: T) {
+        let idx = self.nodes.resolve(handle).expect("item was popped");
+        assert!(item <= self.nodes[idx].item, "new item is greater");
+        self.nodes[idx].item = item;
+        let parent = self.nodes[idx].parent;
+        if parent != NONE && self.nodes[idx].item < self.nodes[parent].item {
+            self.cut(idx);
+            self.cascading_cut(parent);
+        }
+        if self.nodes[idx].item < self.nodes[self.min].item {
+            self.min = idx;
+        }
+    }
+
+    /// Moves `idx` from its parent's children to the root list.
+    fn cut(&mut self, idx: usize) {
+        let parent = self.nodes[idx].parent;
+        let right = self.nodes[idx].right;
+        self.unlink(idx);
+        let node = &mut self.nodes[parent];
+        node.degree -= 1;
+        if node.child == idx {
+            node.child = if right == idx { NONE } else { right };
+        }
+        self.nodes[idx].marked = false;
+        self.add_root(idx);
+    }
+
+    /// Cuts marked ancestors loose, so no node loses more than one child
+    /// without being cut itself. That bounds degrees by O(log n).
+    fn cascading_cut(&mut self, mut idx: usize) {
+        loop {
+            let parent = self.nodes[idx].parent;
+            if parent == NONE {
+                return;
+            }
+            if !self.nodes[idx].marked {
+                self.nodes[idx].marked = true;
+                return;
+            }
+            self.cut(idx);
+            idx = parent;
+        }
+    }
+
+    /// Moves every item of `other` into this heap. The root lists are
+    /// spliced in O(1), after a single copy of `other`'s arena onto the
+    /// end of this one. Handles into `other` don't carry over.
+    pub fn meld(&mut self, other: FibonacciHeap<T>) {
+        if other.min == NONE {
+            return;
+        }
+        let offset = self.nodes.append(other.nodes);
+        for slot in &mut self.nodes.slots[offset..] {
+            if let Some(node) = &mut slot.node {
+                for link in [
+                    &mut node.parent,
+                    &mut node.child,
+                    &mut node.left,
+                    &mut node.right,
+                ] {
+                    shift(link, offset);
+                }
+            }
+        }
+        self.len += other.len;
+        let other_min = other.min + offset;
+        if self.min == NONE {
+            self.min = other_min;
+            return;
+        }
+        // Join the two circular lists into one.
+        let (a, b) = (self.nodes[self.min].right, self.nodes[other_min].left);
+        self.nodes[self.min].right = other_min;
+        self.nodes[other_min].left = self.min;
+        self.nodes[b].right = a;
+        self.nodes[a].left = b;
+        if self.nodes[other_min].item < self.nodes[self.min].item {
+            self.min = other_min;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.min = NONE;
+        self.len = 0;
+    }
+}
+
+impl<T: Ord> Default for FibonacciHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basic_test() {
+        let mut heap = FibonacciHeap::new();
+        let handles: Vec<_> = [5, 3, 8, 1, 9].into_iter().map(|i| heap.push(i)).collect();
+        assert_eq!(heap.peek_min(), Some(&1));
+        assert_eq!(heap.pop_min(), Some(1));
+        assert_eq!(heap.get(handles[3]), None);
+
+        heap.decrease_key(handles[4], 2);
+        assert_eq!(heap.pop_min(), Some(2));
+
+        let mut other = FibonacciHeap::new();
+        other.push(4);
+        other.push(0);
+        heap.meld(other);
+        let mut out = Vec::new();
+        while let Some(item) = heap.pop_min() {
+            out.push(item);
+        }
+        assert_eq!(out, [0, 3, 4, 5, 8]);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "new item is greater")]
+    fn increase_test() {
+        let mut heap = FibonacciHeap::new();
+        let handle = heap.push(1);
+        heap.decrease_key(handle, 2);
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Push(u16),
+        Pop,
+        Decrease(usize, u16),
+        Meld(Vec<u16>),
+    }
+
+    impl quickcheck::Arbitrary for Op {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            match u8::arbitrary(g) % 8 {
+                0..=2 => Op::Push(u16::arbitrary(g)),
+                3..=4 => Op::Pop,
+                5..=6 => Op::Decrease(usize::arbitrary(g), u16::arbitrary(g)),
+                _ => Op::Meld(Vec::arbitrary(g)),
+            }
+        }
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<Op>) -> bool {
+        let mut heap = FibonacciHeap::new();
+        // Live items by a unique tag, which the heap orders them by too.
+        let mut model = BTreeMap::new();
+        let mut handles = Vec::new();
+        for (tag, op) in ops.into_iter().enumerate() {
+            match op {
+                Op::Push(priority) => {
+                    handles.push((heap.push((priority, tag)), tag));
+                    model.insert(tag, priority);
+                }
+                Op::Pop => {
+                    let expected = model.iter().map(|(&t, &p)| (p, t)).min();
+                    if heap.pop_min() != expected {
+                        return false;
+                    }
+                    if let Some((_, tag)) = expected {
+                        model.remove(&tag);
+                    }
+                }
+                Op::Decrease(which, by) => {
+                    if handles.is_empty() {
+                        continue;
+                    }
+                    let (handle, tag) = handles[which % handles.len()];
+                    if let Some(priority) = model.get_mut(&tag) {
+                        *priority = priority.saturating_sub(by);
+                        heap.decrease_key(handle, (*priority, tag));
+                    } else if heap.get(handle).is_some() {
+                        return false;
+                    }
+                }
+                Op::Meld(priorities) => {
+                    let mut other = FibonacciHeap::new();
+                    for (i, priority) in priorities.into_iter().take(256).enumerate() {
+                        // Tags past any `ops` index, never decreased.
+                        let tag = usize::MAX - tag * 256 - i;
+                        other.push((priority, tag));
+                        model.insert(tag, priority);
+                    }
+                    heap.meld(other);
+                }
+            }
+            if heap.len() != model.len() {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,228 @@
+use alloc::vec::Vec;
+
+/// A double-ended priority queue, the interval heap of van Leeuwen and
+/// Wood. `push`, `pop_min` and `pop_max` take O(log n).
+///
+/// Node `k` holds the pair at `data[2k]` and `data[2k + 1]`, an interval
+/// which contains the intervals of its children. The lows form a min-heap
+/// and the highs a max-heap, both in one contiguous vector; the last node
+/// may hold a single item, which counts as both ends.
+#[derive(Clone, Debug)]
+pub struct IntervalHeap<T> {
+    data: Vec<T>,
+}
+
+impl<T: Ord> IntervalHeap<T> {
+    pub fn new() -> Self {
+        IntervalHeap { data: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        IntervalHeap {
+            data: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    pub fn peek_max(&self) -> Option<&T> {
+        self.data.get(1).or(self.data.first())
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.data.push(item);
+        let i = self.data.len() - 1;
+        if i % 2 == 1 {
+            if self.data[i] < self.data[i - 1] {
+                self.data.swap(i, i - 1);
+                self.sift_up_min(i - 1);
+            } else {
+                self.sift_up_max(i);
+            }
+        } else if i > 0 {
+            // A lone item, which may be out of either end of its parent.
+            let parent = (i / 2 - 1) / 2;
+            if self.data[i] < self.data[2 * parent] {
+                self.sift_up_min(i);
+            } else {
+                self.sift_up_max(i);
+            }
+        }
+    }
+
+    fn sift_up_min(&mut self, mut i: usize) {
+        while i >= 2 {
+            let parent = 2 * ((i / 2 - 1) / 2);
+            if self.data[i] >= self.data[parent] {
+                break;
+            }
+            self.data.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_up_max(&mut self, mut i: usize) {
+        while i >= 2 {
+            let parent = 2 * ((i / 2 - 1) / 2) + 1;
+            if self.data[i] <= self.data[parent] {
+                break;
+            }
+            self.data.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        if self.data.len() <= 2 {
+            return (!self.data.is_empty()).then(|| self.data.remove(0));
+        }
+        let item = self.data.swap_remove(0);
+        let len = self.data.len();
+        let mut k = 0;
+        loop {
+            if 2 * k + 1 < len && self.data[2 * k] > self.data[2 * k + 1] {
+                self.data.swap(2 * k, 2 * k + 1);
+            }
+            let child = 2 * k + 1;
+            if 2 * child >= len {
+                break;
+            }
+            let m = if 2 * child + 2 < len && self.data[2 * child + 2] < self.data[2 * child] {
+                child + 1
+            } else {
+                child
+            };
+            if self.data[2 * m] >= self.data[2 * k] {
+                break;
+            }
+            self.data.swap(2 * k, 2 * m);
+            k = m;
+        }
+        Some(item)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        if self.data.len() <= 2 {
+            return self.data.pop();
+        }
+        let item = self.data.swap_remove(1);
+        let len = self.data.len();
+        // The high end of node `k`, or its only item.
+        let high = |k: usize| if 2 * k + 1 < len { 2 * k + 1 } else { 2 * k };
+        let mut k = 0;
+        loop {
+            if 2 * k + 1 < len && self.data[2 * k] > self.data[2 * k + 1] {
+                self.data.swap(2 * k, 2 * k + 1);
+            }
+            let child = 2 * k + 1;
+            if 2 * child >= len {
+                break;
+            }
+            let m = if 2 * child + 2 < len && self.data[high(child + 1)] > self.data[high(child)] {
+                child + 1
+            } else {
+                child
+            };
+            if self.data[high(m)] <= self.data[2 * k + 1] {
+                break;
+            }
+            self.data.swap(2 * k + 1, high(m));
+            k = m;
+        }
+        Some(item)
+    }
+
+    /// The items in no particular order.
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.data.iter()
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+}
+
+impl<T: Ord> Default for IntervalHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for IntervalHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = IntervalHeap::new();
+        heap.extend(iter);
+        heap
+    }
+}
+
+impl<T: Ord> Extend<T> for IntervalHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut heap: IntervalHeap<_> = [5, 1, 9, 3, 7].into_iter().collect();
+        assert_eq!((heap.peek_min(), heap.peek_max()), (Some(&1), Some(&9)));
+        assert_eq!(heap.pop_max(), Some(9));
+        assert_eq!(heap.pop_min(), Some(1));
+        assert_eq!(heap.pop_max(), Some(7));
+        assert_eq!(heap.pop_min(), Some(3));
+        assert_eq!((heap.peek_min(), heap.peek_max()), (Some(&5), Some(&5)));
+        assert_eq!(heap.pop_max(), Some(5));
+        assert_eq!(heap.pop_min(), None);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>) -> bool {
+        let mut heap = IntervalHeap::new();
+        let mut model = Vec::new();
+        for (op, item) in ops {
+            match op % 3 {
+                0 => {
+                    heap.push(item);
+                    model.push(item);
+                    model.sort_unstable();
+                }
+                1 => {
+                    let expected = (!model.is_empty()).then(|| model.remove(0));
+                    if heap.pop_min() != expected {
+                        return false;
+                    }
+                }
+                _ => {
+                    if heap.pop_max() != model.pop() {
+                        return false;
+                    }
+                }
+            }
+            if heap.peek_min() != model.first() || heap.peek_max() != model.last() {
+                return false;
+            }
+        }
+        true
+    }
+}
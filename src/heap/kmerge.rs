@@ -0,0 +1,193 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use super::SplayHeap;
+
+/// The next item of one input, with the input's index.
+struct Head<T> {
+    item: T,
+    source: usize,
+    // The source if ties go by input, else zero, leaving them to the heap.
+    rank: usize,
+}
+
+impl<T: Ord> PartialEq for Head<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Head<T> {}
+
+impl<T: Ord> PartialOrd for Head<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Head<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.item.cmp(&other.item).then(self.rank.cmp(&other.rank))
+    }
+}
+
+/// The items of several sorted iterators as one sorted iterator, from
+/// [`kmerge`] or [`kmerge_stable`].
+///
+/// The next item of each input waits in a [`SplayHeap`], so each item out
+/// costs O(log k) amortized for k inputs, and less while one input keeps
+/// winning, as its next item lands beside the one just popped. Inputs which
+/// aren't sorted are merged all the same, just not into sorted order.
+pub struct KMerge<I: Iterator> {
+    iters: Vec<I>,
+    heap: SplayHeap<Head<I::Item>>,
+}
+
+/// Merges sorted iterators into one. Equal items from different inputs
+/// come out in no particular order; see [`kmerge_stable`].
+pub fn kmerge<I>(iters: I) -> KMerge<<I::Item as IntoIterator>::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    <I::Item as IntoIterator>::Item: Ord,
+{
+    KMerge::new(iters, false)
+}
+
+/// Merges sorted iterators into one, giving equal items in the order of
+/// their inputs, so with runs listed oldest first the newest of each key
+/// comes last.
+pub fn kmerge_stable<I>(iters: I) -> KMerge<<I::Item as IntoIterator>::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    <I::Item as IntoIterator>::Item: Ord,
+{
+    KMerge::new(iters, true)
+}
+
+impl<I: Iterator> KMerge<I>
+where
+    I::Item: Ord,
+{
+    fn new<J>(iters: J, stable: bool) -> Self
+    where
+        J: IntoIterator,
+        J::Item: IntoIterator<IntoIter = I>,
+    {
+        let mut iters: Vec<I> = iters.into_iter().map(IntoIterator::into_iter).collect();
+        let mut heap = SplayHeap::new();
+        for (source, iter) in iters.iter_mut().enumerate() {
+            if let Some(item) = iter.next() {
+                let rank = if stable { source } else { 0 };
+                heap.push(Head { item, source, rank });
+            }
+        }
+        KMerge { iters, heap }
+    }
+
+    /// The next item with the index of the input it came from.
+    fn pop(&mut self) -> Option<(I::Item, usize)> {
+        let Head { item, source, rank } = self.heap.pop_min()?;
+        if let Some(next) = self.iters[source].next() {
+            self.heap.push(Head {
+                item: next,
+                source,
+                rank,
+            });
+        }
+        Some((item, source))
+    }
+
+    /// Yields each item with the index of the input it came from, as
+    /// `(item, source)`.
+    pub fn with_source(self) -> WithSource<I> {
+        WithSource(self)
+    }
+
+    fn bounds(&self) -> (usize, Option<usize>) {
+        let waiting = self.heap.len();
+        self.iters.iter().map(Iterator::size_hint).fold(
+            (waiting, Some(waiting)),
+            |(lo, hi), (l, h)| {
+                (
+                    lo.saturating_add(l),
+                    hi.zip(h).and_then(|(a, b)| a.checked_add(b)),
+                )
+            },
+        )
+    }
+}
+
+impl<I: Iterator> Iterator for KMerge<I>
+where
+    I::Item: Ord,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.pop().map(|(item, _)| item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.bounds()
+    }
+}
+
+/// A [`KMerge`] yielding `(item, source)`, from [`KMerge::with_source`].
+pub struct WithSource<I: Iterator>(KMerge<I>);
+
+impl<I: Iterator> Iterator for WithSource<I>
+where
+    I::Item: Ord,
+{
+    type Item = (I::Item, usize);
+
+    fn next(&mut self) -> Option<(I::Item, usize)> {
+        self.0.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let runs = [vec![1, 4, 7], vec![2, 5], vec![], vec![0, 4, 9]];
+        let merged = kmerge(runs.iter().map(|run| run.iter().copied()));
+        assert_eq!(merged.size_hint(), (8, Some(8)));
+        assert_eq!(merged.collect::<Vec<_>>(), [0, 1, 2, 4, 4, 5, 7, 9]);
+
+        // Compaction: the newest run last, so for each key the last wins.
+        let runs = [vec!["a", "c"], vec!["a", "b"], vec!["c"]];
+        let merged: Vec<_> = kmerge_stable(runs).with_source().collect();
+        assert_eq!(merged, [("a", 0), ("a", 1), ("b", 1), ("c", 0), ("c", 2)]);
+        assert_eq!(kmerge(Vec::<Vec<u8>>::new()).next(), None);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(mut runs: Vec<Vec<(u8, u8)>>) -> bool {
+        runs.iter_mut().for_each(|run| run.sort_unstable());
+        let mut expected: Vec<_> = runs.iter().flatten().copied().collect();
+        expected.sort_unstable();
+        if !kmerge(runs.clone()).eq(expected.iter().copied()) {
+            return false;
+        }
+
+        // By key only, equal keys in input order.
+        let keys = || runs.iter().map(|run| run.iter().map(|&(key, _)| key));
+        let mut expected: Vec<_> = keys()
+            .enumerate()
+            .flat_map(|(source, run)| run.map(move |key| (key, source)))
+            .collect();
+        expected.sort_by_key(|&(key, source)| (key, source));
+        kmerge_stable(keys()).with_source().eq(expected)
+    }
+}
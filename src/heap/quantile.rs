@@ -0,0 +1,160 @@
+use core::cmp::Reverse;
+
+use super::{BinomialHeap, Handle};
+use crate::splay::Splay;
+
+/// Identifies an item pushed into a [`QuantileTracker`], to expire it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ticket(u64);
+
+#[derive(Clone, Copy)]
+enum Side {
+    Lower(Handle),
+    Upper(Handle),
+}
+
+/// The exact running `p`-quantile of a stream, with two heaps split at it.
+///
+/// `lower` holds the smallest `floor(p * (n - 1)) + 1` items as a max-heap,
+/// `upper` the rest as a min-heap, and the quantile is the top of `lower`.
+/// Pushing and expiring both take O(log n); expiry removes from the middle
+/// of a heap, so both are [`BinomialHeap`]s.
+pub struct QuantileTracker<T> {
+    p: f64,
+    lower: BinomialHeap<Reverse<(T, Ticket)>>,
+    upper: BinomialHeap<(T, Ticket)>,
+    sides: Splay<Ticket, Side>,
+    next: u64,
+}
+
+impl<T: Ord> QuantileTracker<T> {
+    /// # Panics
+    ///
+    /// Panics if `p` isn't within `0.0..=1.0`.
+    pub fn new(p: f64) -> Self {
+        assert!((0.0..=1.0).contains(&p), "quantile must be within 0..=1");
+        QuantileTracker {
+            p,
+            lower: BinomialHeap::new(),
+            upper: BinomialHeap::new(),
+            sides: Splay::new(),
+            next: 0,
+        }
+    }
+
+    /// Tracks the median, the lower one of an even number of items.
+    pub fn median() -> Self {
+        Self::new(0.5)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lower.len() + self.upper.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lower.is_empty()
+    }
+
+    /// The item of rank `floor(p * (n - 1))` among the `n` live items.
+    pub fn quantile(&self) -> Option<&T> {
+        self.lower.peek_min().map(|Reverse((item, _))| item)
+    }
+
+    pub fn push(&mut self, item: T) -> Ticket {
+        let ticket = Ticket(self.next);
+        self.next += 1;
+        if self.quantile().is_none_or(|q| item <= *q) {
+            self.push_lower(item, ticket);
+        } else {
+            self.push_upper(item, ticket);
+        }
+        self.rebalance();
+        ticket
+    }
+
+    fn push_lower(&mut self, item: T, ticket: Ticket) {
+        let handle = self.lower.push(Reverse((item, ticket)));
+        self.sides.set(ticket, Side::Lower(handle));
+    }
+
+    fn push_upper(&mut self, item: T, ticket: Ticket) {
+        let handle = self.upper.push((item, ticket));
+        self.sides.set(ticket, Side::Upper(handle));
+    }
+
+    /// Moves items across the split until `lower` has its share.
+    fn rebalance(&mut self) {
+        let len = self.len();
+        let target = match len {
+            0 => 0,
+            _ => (self.p * (len - 1) as f64) as usize + 1,
+        };
+        while self.lower.len() > target {
+            let Reverse((item, ticket)) = self.lower.pop_min().unwrap();
+            self.push_upper(item, ticket);
+        }
+        while self.lower.len() < target {
+            let (item, ticket) = self.upper.pop_min().unwrap();
+            self.push_lower(item, ticket);
+        }
+    }
+
+    /// Drops the item pushed as `ticket`, returning it unless it expired
+    /// already.
+    pub fn expire(&mut self, ticket: Ticket) -> Option<T> {
+        let (item, _) = match self.sides.remove(&ticket)? {
+            Side::Lower(handle) => self.lower.remove(handle)?.0,
+            Side::Upper(handle) => self.upper.remove(handle)?,
+        };
+        self.rebalance();
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut median = QuantileTracker::median();
+        assert_eq!(median.quantile(), None);
+        let tickets: Vec<_> = [5, 1, 9, 3].into_iter().map(|i| median.push(i)).collect();
+        assert_eq!(median.quantile(), Some(&3));
+        median.push(7);
+        assert_eq!(median.quantile(), Some(&5));
+        assert_eq!(median.expire(tickets[0]), Some(5));
+        assert_eq!(median.expire(tickets[0]), None);
+        assert_eq!(median.quantile(), Some(&3));
+
+        let mut p90 = QuantileTracker::new(0.9);
+        for i in 0..=100 {
+            p90.push(i);
+        }
+        assert_eq!(p90.quantile(), Some(&90));
+    }
+
+    /// A sliding window of `window` items over `items`, checked against
+    /// sorting the window every step.
+    #[quickcheck]
+    fn test_quickcheck(items: Vec<u8>, window: u8, p: u8) -> bool {
+        let window = window as usize % 16 + 1;
+        let p = (p % 11) as f64 / 10.0;
+        let mut tracker = QuantileTracker::new(p);
+        let mut tickets = Vec::new();
+        for (i, &item) in items.iter().enumerate() {
+            tickets.push(tracker.push(item));
+            if i >= window && tracker.expire(tickets[i - window]) != Some(items[i - window]) {
+                return false;
+            }
+            let mut live = items[(i + 1).saturating_sub(window)..=i].to_vec();
+            live.sort_unstable();
+            let rank = (p * (live.len() - 1) as f64) as usize;
+            if tracker.len() != live.len() || tracker.quantile() != Some(&live[rank]) {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,227 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    item: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+/// A skew heap, Sleator and Tarjan's self-adjusting heap: a heap-ordered
+/// binary tree with no balance information, whose children are swapped
+/// along every merge path.
+///
+/// `push`, `pop_min` and `meld` are O(log n) amortized, `meld` included,
+/// since they all merge two right spines, which stay short on the whole.
+/// Nodes are boxed, so `meld` relinks the other heap rather than copying
+/// it. Equal items come out in no particular order. For a heap which also
+/// pops the greatest item, see [`SplayHeap`](super::SplayHeap).
+pub struct SkewHeap<T> {
+    root: Link<T>,
+    len: usize,
+}
+
+/// Merges two heaps along their right spines, swapping the children of
+/// every node on the way. Iterative, since the spines can be long before
+/// they amortize.
+fn merge<T: Ord>(mut a: Link<T>, mut b: Link<T>) -> Link<T> {
+    let mut path: Vec<Box<Node<T>>> = Vec::new();
+    let mut tail = loop {
+        match (a, b) {
+            (None, rest) | (rest, None) => break rest,
+            (Some(x), Some(y)) => {
+                let (mut top, other) = if y.item < x.item { (y, x) } else { (x, y) };
+                a = top.right.take();
+                b = Some(other);
+                path.push(top);
+            }
+        }
+    };
+    while let Some(mut node) = path.pop() {
+        node.right = node.left.take();
+        node.left = tail;
+        tail = Some(node);
+    }
+    tail
+}
+
+impl<T: Ord> SkewHeap<T> {
+    pub fn new() -> Self {
+        SkewHeap { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, item: T) {
+        let node = Box::new(Node {
+            item,
+            left: None,
+            right: None,
+        });
+        self.root = merge(self.root.take(), Some(node));
+        self.len += 1;
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.root.as_ref().map(|node| &node.item)
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        let node = *self.root.take()?;
+        self.root = merge(node.left, node.right);
+        self.len -= 1;
+        Some(node.item)
+    }
+
+    /// Moves every item of `other` in, in O(log n) amortized.
+    pub fn meld(&mut self, mut other: Self) {
+        self.root = merge(self.root.take(), other.root.take());
+        self.len += mem::take(&mut other.len);
+    }
+
+    /// The items in no particular order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            stack: self.root.iter().map(|node| &**node).collect(),
+        }
+    }
+
+    /// The items, smallest first.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut items = Vec::with_capacity(self.len);
+        items.extend(core::iter::from_fn(|| self.pop_min()));
+        items
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// The items of a [`SkewHeap`], in no particular order.
+pub struct Iter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.left.as_deref());
+        self.stack.extend(node.right.as_deref());
+        Some(&node.item)
+    }
+}
+
+impl<T> Drop for SkewHeap<T> {
+    fn drop(&mut self) {
+        // Without recursing down what may be a long path.
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+impl<T: Ord + Clone> Clone for SkewHeap<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: Ord> Default for SkewHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for SkewHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> Extend<T> for SkewHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SkewHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = SkewHeap::new();
+        heap.extend(iter);
+        heap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut heap: SkewHeap<_> = [5, 1, 3].into_iter().collect();
+        assert_eq!(heap.peek_min(), Some(&1));
+        let mut other = SkewHeap::new();
+        other.extend([2, 0, 9]);
+        heap.meld(other);
+        assert_eq!(heap.len(), 6);
+        assert_eq!(heap.pop_min(), Some(0));
+        assert_eq!(heap.iter().count(), 5);
+        assert_eq!(heap.clone().into_sorted_vec(), [1, 2, 3, 5, 9]);
+
+        // Sorted pushes make a long left path, which mustn't overflow the
+        // stack when melded or dropped.
+        let mut long: SkewHeap<_> = (0..100_000).rev().collect();
+        long.meld((0..100_000).collect());
+        assert_eq!((long.pop_min(), long.len()), (Some(0), 199_999));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, i16)>, melds: Vec<Vec<i16>>) -> bool {
+        let mut heap = SkewHeap::new();
+        let mut model: Vec<i16> = Vec::new();
+        let mut melds = melds.into_iter();
+        for (op, item) in ops {
+            let ok = match op % 4 {
+                0 | 1 => {
+                    heap.push(item);
+                    model.push(item);
+                    true
+                }
+                2 => {
+                    model.sort_unstable();
+                    let expected = (!model.is_empty()).then(|| model.remove(0));
+                    heap.pop_min() == expected
+                }
+                _ => {
+                    let items = melds.next().unwrap_or_default();
+                    model.extend(&items);
+                    heap.meld(items.into_iter().collect());
+                    heap.peek_min() == model.iter().min()
+                }
+            };
+            if !ok || heap.len() != model.len() {
+                return false;
+            }
+        }
+        model.sort_unstable();
+        heap.into_sorted_vec() == model
+    }
+}
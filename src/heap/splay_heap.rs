@@ -0,0 +1,215 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::splay::Splay;
+
+/// A double-ended priority queue on a [`Splay`] ordered by item.
+///
+/// `push`, `pop_min` and `pop_max` are O(log n) amortized and splay the
+/// items they touch to the root, so a run of pops, or pushes near the last
+/// one popped as in event simulation, costs far less. Equal items come out
+/// in the order they were pushed.
+///
+/// A search tree can't meld two heaps whose items interleave in O(log n),
+/// since the result has to sort them together: `meld` here takes
+/// O(min(m log n, n + m)). [`SkewHeap`](super::SkewHeap) melds in O(log n)
+/// amortized, but only pops the smallest item.
+#[derive(Clone)]
+pub struct SplayHeap<T> {
+    // Each item with its push count, to keep equal ones apart and in order.
+    tree: Splay<(T, u64), ()>,
+    pushes: u64,
+}
+
+impl<T: Ord> SplayHeap<T> {
+    pub fn new() -> Self {
+        SplayHeap {
+            tree: Splay::new(),
+            pushes: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.tree.insert((item, self.pushes), ());
+        self.pushes += 1;
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.tree.first_key_value().map(|((item, _), ())| item)
+    }
+
+    pub fn peek_max(&self) -> Option<&T> {
+        self.tree.last_key_value().map(|((item, _), ())| item)
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        self.tree.pop_first().map(|((item, _), ())| item)
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        self.tree.pop_last().map(|((item, _), ())| item)
+    }
+
+    /// Moves every item of `other` in. Its items count as pushed after all
+    /// of these.
+    pub fn meld(&mut self, other: Self) {
+        let (n, m) = (self.len(), other.len());
+        let offset = self.pushes;
+        self.pushes += other.pushes;
+        let items = other
+            .tree
+            .into_keys()
+            .map(|(item, pushed)| ((item, pushed + offset), ()));
+        if m.saturating_mul((usize::BITS - n.leading_zeros()) as usize) < n + m {
+            self.tree.extend(items);
+        } else {
+            let other: Splay<_, _> = items.collect();
+            self.tree.merge_with(other, |_, (), ()| ());
+        }
+    }
+
+    /// The items in order, smallest first.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.tree.iter().map(|((item, _), ())| item)
+    }
+
+    /// The items, smallest first.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        self.tree.into_keys().map(|(item, _)| item).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+}
+
+impl<T: Ord> Default for SplayHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for SplayHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> Extend<T> for SplayHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SplayHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut heap = SplayHeap::new();
+        heap.extend(iter);
+        heap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut heap: SplayHeap<_> = [(5, 'a'), (1, 'b'), (3, 'c')].into_iter().collect();
+        assert_eq!(heap.peek_min(), Some(&(1, 'b')));
+        assert_eq!(heap.pop_max(), Some((5, 'a')));
+
+        let mut other = SplayHeap::new();
+        other.extend([(2, 'd'), (0, 'e'), (9, 'f')]);
+        heap.meld(other);
+        assert_eq!(heap.len(), 5);
+        assert_eq!(heap.pop_min(), Some((0, 'e')));
+        assert_eq!(heap.peek_max(), Some(&(9, 'f')));
+        assert_eq!(
+            heap.into_sorted_vec(),
+            [(1, 'b'), (2, 'd'), (3, 'c'), (9, 'f')]
+        );
+
+        // Equal items keep their push order, across a meld too.
+        let mut ties = SplayHeap::new();
+        let mut later = SplayHeap::new();
+        for i in 0..50 {
+            ties.push(Tie(i % 3, i));
+            later.push(Tie(i % 3, 50 + i));
+        }
+        ties.meld(later);
+        let order: Vec<_> = core::iter::from_fn(|| ties.pop_min())
+            .map(|Tie(_, i)| i)
+            .collect();
+        let zeros: Vec<_> = (0..50).step_by(3).chain((50..100).step_by(3)).collect();
+        assert_eq!(order[..34], zeros);
+    }
+
+    /// Ordered by the first field only.
+    #[derive(Debug)]
+    struct Tie(u32, u32);
+
+    impl PartialEq for Tie {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Tie {}
+
+    impl PartialOrd for Tie {
+        fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Tie {
+        fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, i16)>, melds: Vec<Vec<i16>>) -> bool {
+        let mut heap = SplayHeap::new();
+        let mut model: Vec<i16> = Vec::new();
+        let mut melds = melds.into_iter();
+        for (op, item) in ops {
+            let ok = match op % 4 {
+                0 | 1 => {
+                    heap.push(item);
+                    model.push(item);
+                    true
+                }
+                2 => {
+                    model.sort_unstable();
+                    let expected = (!model.is_empty()).then(|| model.remove(0));
+                    heap.pop_min() == expected
+                }
+                _ => {
+                    let items = melds.next().unwrap_or_default();
+                    model.extend(&items);
+                    heap.meld(items.into_iter().collect());
+                    model.sort_unstable();
+                    heap.peek_max() == model.last()
+                }
+            };
+            if !ok || heap.len() != model.len() {
+                return false;
+            }
+        }
+        model.sort_unstable();
+        heap.into_sorted_vec() == model
+    }
+}
@@ -0,0 +1,151 @@
+use alloc::vec::Vec;
+
+use super::IntervalHeap;
+
+/// Collects the `k` largest, or smallest, items of a stream in O(k) space,
+/// O(log k) per item.
+///
+/// Items sit in an [`IntervalHeap`], so whichever end would be dropped
+/// next is at hand either way.
+#[derive(Clone, Debug)]
+pub struct TopK<T> {
+    k: usize,
+    largest: bool,
+    heap: IntervalHeap<T>,
+}
+
+impl<T: Ord> TopK<T> {
+    pub fn largest(k: usize) -> Self {
+        TopK {
+            k,
+            largest: true,
+            heap: IntervalHeap::with_capacity(k),
+        }
+    }
+
+    pub fn smallest(k: usize) -> Self {
+        TopK {
+            k,
+            largest: false,
+            heap: IntervalHeap::with_capacity(k),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// The item which the next better one would replace, once there are
+    /// `k` of them.
+    pub fn threshold(&self) -> Option<&T> {
+        if self.largest {
+            self.heap.peek_min()
+        } else {
+            self.heap.peek_max()
+        }
+    }
+
+    /// Offers `item`, returning whichever item was dropped for it, or the
+    /// item itself if it doesn't make the cut. Ties keep the older item.
+    pub fn push(&mut self, item: T) -> Option<T> {
+        if self.heap.len() < self.k {
+            self.heap.push(item);
+            return None;
+        }
+        let better = match self.threshold() {
+            None => return Some(item),
+            Some(worst) if self.largest => item > *worst,
+            Some(worst) => item < *worst,
+        };
+        if !better {
+            return Some(item);
+        }
+        let dropped = if self.largest {
+            self.heap.pop_min()
+        } else {
+            self.heap.pop_max()
+        };
+        self.heap.push(item);
+        dropped
+    }
+
+    /// Folds in the items of `other`, as if they'd been pushed here.
+    pub fn merge(&mut self, other: TopK<T>) {
+        for item in other.heap.into_vec() {
+            self.push(item);
+        }
+    }
+
+    /// The items, best first.
+    pub fn into_sorted_vec(self) -> Vec<T> {
+        let mut items = self.heap.into_vec();
+        items.sort_unstable();
+        if self.largest {
+            items.reverse();
+        }
+        items
+    }
+}
+
+impl<T: Ord> Extend<T> for TopK<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.push(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut top = TopK::largest(3);
+        top.extend([4, 1, 7, 3]);
+        assert_eq!(top.threshold(), Some(&3));
+        assert_eq!(top.push(2), Some(2));
+        assert_eq!(top.push(9), Some(3));
+
+        let mut other = TopK::largest(3);
+        other.extend([8, 5]);
+        top.merge(other);
+        assert_eq!(top.into_sorted_vec(), [9, 8, 7]);
+
+        let mut bottom = TopK::smallest(2);
+        bottom.extend([4, 1, 7, 3]);
+        assert_eq!(bottom.into_sorted_vec(), [1, 3]);
+        assert_eq!(TopK::largest(0).push(1), Some(1));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(items: Vec<i32>, k: u8, largest: bool) -> bool {
+        let k = k as usize % 10;
+        let (left, right) = items.split_at(items.len() / 2);
+        let new = |k| {
+            if largest {
+                TopK::largest(k)
+            } else {
+                TopK::smallest(k)
+            }
+        };
+        let mut top = new(k);
+        top.extend(left.iter().copied());
+        let mut other = new(k);
+        other.extend(right.iter().copied());
+        top.merge(other);
+
+        let mut expected = items.clone();
+        expected.sort_unstable();
+        if largest {
+            expected.reverse();
+        }
+        expected.truncate(k);
+        top.into_sorted_vec() == expected
+    }
+}
@@ -0,0 +1,266 @@
+//! A map indexed both by hash and in key order, like an in-memory
+//! database table with a hash index and an ordered one over the same rows.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+use std::ops::RangeBounds;
+
+use crate::hash_index::HashIndex;
+use crate::splay::{self, Splay};
+
+/// Map with O(1) point lookups through a hash index and ordered scans
+/// through a splay tree, both over one vector of entries.
+///
+/// The entries live densely in a vector. The hash index is the same
+/// open-addressing table of positions in it as
+/// [`OrderedMap`](crate::ordered_map::OrderedMap)'s, and the ordered index is
+/// a [`Splay`] from each key to its position, so keys are kept twice and
+/// values once. Lookups only touch the table and leave the tree's shape
+/// alone. Inserts and removes update both; a remove moves the last entry
+/// into the hole it leaves and repoints both indexes at it.
+#[derive(Clone)]
+pub struct HybridMap<K, V, S = RandomState> {
+    entries: Vec<(K, V)>,
+    index: HashIndex<S>,
+    ordered: Splay<K, usize>,
+}
+
+/// The entries of a [`HybridMap`] within a range, in key order.
+pub struct Range<'a, K, V> {
+    range: splay::Range<'a, K, usize>,
+    entries: &'a [(K, V)],
+}
+
+impl<'a, K: Ord, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, &idx) = self.range.next()?;
+        let (key, value) = &self.entries[idx];
+        Some((key, value))
+    }
+}
+
+impl<K: Hash + Ord + Clone, V> HybridMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K: Hash + Ord + Clone, V, S: BuildHasher> HybridMap<K, V, S> {
+    /// An empty map hashing keys with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        HybridMap {
+            entries: Vec::new(),
+            index: HashIndex::new(hasher),
+            ordered: Splay::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.index.find(&self.entries, key)?;
+        Some(&self.entries[self.index.get(slot)].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.index.find(&self.entries, key)?;
+        Some(&mut self.entries[self.index.get(slot)].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.find(&self.entries, key).is_some()
+    }
+
+    /// Inserts or updates an entry, returning the previous value for `key`.
+    /// An update only goes through the hash index.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(mem::replace(old, value));
+        }
+        let idx = self.entries.len();
+        self.ordered.set(key.clone(), idx);
+        self.entries.push((key, value));
+        self.index.insert(&self.entries, idx);
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let slot = self.index.find(&self.entries, key)?;
+        let idx = self.index.get(slot);
+        self.index.remove(&self.entries, slot);
+        self.ordered.remove(key);
+        let last = self.entries.len() - 1;
+        if idx != last {
+            // The last entry moves into the hole.
+            self.index.repoint(&self.entries, last, idx);
+            *self.ordered.get_mut::<K>(&self.entries[last].0).unwrap() = idx;
+        }
+        Some(self.entries.swap_remove(idx).1)
+    }
+
+    /// The entries in key order.
+    pub fn iter(&self) -> Range<'_, K, V> {
+        self.range::<K, _>(..)
+    }
+
+    /// The entries within `range` in key order.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same malformed ranges as `BTreeMap::range`.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        Range {
+            range: self.ordered.range(range),
+            entries: &self.entries,
+        }
+    }
+
+    /// The entries in the order they sit in the vector, which is insertion
+    /// order until something is removed. Faster than `iter`.
+    pub fn entries(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+        self.ordered.clear();
+    }
+}
+
+impl<K: Hash + Ord + Clone, V, S: BuildHasher + Default> Default for HybridMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Hash + Ord + Clone, V, S: BuildHasher> Extend<(K, V)> for HybridMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Hash + Ord + Clone, V, S: BuildHasher + Default> FromIterator<(K, V)>
+    for HybridMap<K, V, S>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Hash + Ord + Clone + fmt::Debug, V: fmt::Debug, S: BuildHasher> fmt::Debug
+    for HybridMap<K, V, S>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::BTreeMap;
+    use std::hash::BuildHasherDefault;
+
+    impl<K: Hash + Ord + Clone, V, S: BuildHasher> HybridMap<K, V, S> {
+        /// Whether both indexes hold every entry once, at its position.
+        fn is_consistent(&self) -> bool {
+            self.index.occupied() == self.entries.len()
+                && self.ordered.len() == self.entries.len()
+                && self.entries.iter().enumerate().all(|(idx, (key, _))| {
+                    self.index
+                        .find(&self.entries, key)
+                        .map(|slot| self.index.get(slot))
+                        == Some(idx)
+                        && self.ordered.peek(key) == Some(&idx)
+                })
+        }
+    }
+
+    #[test]
+    fn basic_test() {
+        let mut map: HybridMap<_, _> = (0..10).map(|k| (k, k * 10)).collect();
+        assert_eq!(map.get(&3), Some(&30));
+        assert_eq!(map.insert(3, 31), Some(30));
+        *map.get_mut(&4).unwrap() += 1;
+        assert_eq!(map.remove(&0), Some(0));
+        assert_eq!(map.remove(&0), None);
+        assert!(map.is_consistent());
+        assert_eq!(
+            map.range(2..5).collect::<Vec<_>>(),
+            [(&2, &20), (&3, &31), (&4, &41)]
+        );
+        // The last entry took the removed one's place.
+        assert_eq!(map.entries().next(), Some((&9, &90)));
+        assert_eq!(map.len(), 9);
+        map.clear();
+        assert!(map.is_empty() && map.get(&5).is_none());
+
+        // A fixed hasher lays the hash index out the same every run.
+        let seeded = || {
+            let mut map = HybridMap::with_hasher(BuildHasherDefault::<DefaultHasher>::default());
+            map.extend((0..20).map(|k| (k, ())));
+            map.index.slots().to_vec()
+        };
+        assert_eq!(seeded(), seeded());
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8, u8)>) -> bool {
+        let mut map = HybridMap::new();
+        let mut model = BTreeMap::new();
+        for (i, (op, a, b)) in ops.into_iter().enumerate() {
+            let a = a % 64;
+            let ok = match op % 4 {
+                0 | 1 => map.insert(a, i) == model.insert(a, i),
+                2 => map.remove(&a) == model.remove(&a),
+                _ => {
+                    let range = a.min(b)..=a.max(b);
+                    map.get(&a) == model.get(&a) && map.range(range.clone()).eq(model.range(range))
+                }
+            };
+            if !ok || map.len() != model.len() || !map.is_consistent() {
+                return false;
+            }
+        }
+        map.iter().eq(model.iter())
+    }
+}
@@ -0,0 +1,111 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::splay::Splay;
+
+/// Dense handle for an interned string, symbols are handed out as 0, 1, 2...
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+/// Maps strings to symbols and back. Each string is allocated once and shared
+/// between the lookup tree and the symbol table.
+pub struct Interner {
+    lookup: Splay<Arc<str>, Symbol>,
+    strings: Vec<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Interner {
+            lookup: Splay::new(),
+            strings: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    pub fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&symbol) = self.lookup.get(s) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.strings.len()).expect("too many symbols"));
+        let s: Arc<str> = Arc::from(s);
+        self.strings.push(s.clone());
+        self.lookup.set(s, symbol);
+        symbol
+    }
+
+    pub fn intern_all<I, S>(&mut self, strings: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for s in strings {
+            self.intern(s.as_ref());
+        }
+    }
+
+    /// Looks up a string without interning it, never allocates.
+    pub fn get(&mut self, s: &str) -> Option<Symbol> {
+        self.lookup.get(s).copied()
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.0 as usize).map(|s| &**s)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> {
+        self.strings
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (Symbol(i as u32), &**s))
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut interner = Interner::new();
+        interner.intern_all(["foo", "bar"]);
+        let foo = interner.intern("foo");
+        let baz = interner.intern("baz");
+        assert_eq!(foo.as_u32(), 0);
+        assert_eq!(baz.as_u32(), 2);
+        assert_eq!(interner.get("bar"), Some(Symbol(1)));
+        assert_eq!(interner.get("qux"), None);
+        assert_eq!(interner.resolve(baz), Some("baz"));
+        assert_eq!(interner.resolve(Symbol(3)), None);
+        assert_eq!(interner.len(), 3);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(strings: Vec<String>) -> bool {
+        let mut interner = Interner::new();
+        let symbols: Vec<Symbol> = strings.iter().map(|s| interner.intern(s)).collect();
+        strings.iter().zip(symbols).all(|(s, symbol)| {
+            interner.resolve(symbol) == Some(s) && interner.get(s) == Some(symbol)
+        })
+    }
+}
@@ -0,0 +1,369 @@
+//! Longest-prefix matching over addresses, as in a routing table.
+
+use alloc::vec::Vec;
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+const NONE: usize = usize::MAX;
+
+/// A fixed-width address whose bits are read from the most significant.
+pub trait Address: Copy + Eq {
+    const BITS: u8;
+
+    /// All zeros, the address every empty prefix masks to.
+    const UNSPECIFIED: Self;
+
+    /// Bit `i`, counting from the top.
+    fn bit(self, i: u8) -> bool;
+
+    /// Just the top `len` bits, the rest cleared.
+    fn mask(self, len: u8) -> Self;
+
+    /// How many top bits the two have in common.
+    fn common_prefix(self, other: Self) -> u8;
+}
+
+macro_rules! int_address {
+    ($($int:ty),*) => {
+        $(
+            impl Address for $int {
+                const BITS: u8 = <$int>::BITS as u8;
+                const UNSPECIFIED: Self = 0;
+
+                fn bit(self, i: u8) -> bool {
+                    self >> (<Self as Address>::BITS - 1 - i) & 1 == 1
+                }
+
+                fn mask(self, len: u8) -> Self {
+                    match len {
+                        0 => 0,
+                        _ => self & (<$int>::MAX << (<Self as Address>::BITS - len)),
+                    }
+                }
+
+                fn common_prefix(self, other: Self) -> u8 {
+                    (self ^ other).leading_zeros() as u8
+                }
+            }
+        )*
+    };
+}
+
+int_address!(u32, u128);
+
+macro_rules! ip_address {
+    ($($ip:ty => $int:ty),*) => {
+        $(
+            impl Address for $ip {
+                const BITS: u8 = <$int>::BITS as u8;
+                const UNSPECIFIED: Self = <$ip>::UNSPECIFIED;
+
+                fn bit(self, i: u8) -> bool {
+                    <$int>::from(self).bit(i)
+                }
+
+                fn mask(self, len: u8) -> Self {
+                    <$int>::from(self).mask(len).into()
+                }
+
+                fn common_prefix(self, other: Self) -> u8 {
+                    <$int>::from(self).common_prefix(other.into())
+                }
+            }
+        )*
+    };
+}
+
+ip_address!(Ipv4Addr => u32, Ipv6Addr => u128);
+
+#[derive(Clone, Debug)]
+struct Node<A, V> {
+    prefix: A,
+    len: u8,
+    value: Option<V>,
+    children: [usize; 2],
+}
+
+/// A map from address prefixes, like `10.0.0.0/8`, answering which of them
+/// is the longest to match an address.
+///
+/// A path-compressed binary trie: a node only exists where a prefix is
+/// stored or two of them part ways, so a lookup visits at most one node per
+/// distinct stored prefix length above the match, and never more than the
+/// address has bits.
+#[derive(Clone, Debug)]
+pub struct IpPrefixMap<A, V> {
+    // The root is node 0, the empty prefix.
+    nodes: Vec<Node<A, V>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<A: Address, V> IpPrefixMap<A, V> {
+    pub fn new() -> Self {
+        IpPrefixMap {
+            nodes: alloc::vec![Node {
+                prefix: A::UNSPECIFIED,
+                len: 0,
+                value: None,
+                children: [NONE; 2],
+            }],
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn alloc(&mut self, node: Node<A, V>) -> usize {
+        match self.free.pop() {
+            Some(x) => {
+                self.nodes[x] = node;
+                x
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Stores `value` under the prefix of `len` bits of `addr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is longer than the address.
+    pub fn insert(&mut self, addr: A, len: u8, value: V) -> Option<V> {
+        assert!(len <= A::BITS, "prefix longer than the address");
+        let key = addr.mask(len);
+        let mut x = 0;
+        loop {
+            let at = self.nodes[x].len;
+            if at == len {
+                let old = self.nodes[x].value.replace(value);
+                self.len += old.is_none() as usize;
+                return old;
+            }
+            let side = key.bit(at) as usize;
+            let c = self.nodes[x].children[side];
+            self.len += 1;
+            if c == NONE {
+                let leaf = self.alloc(Node {
+                    prefix: key,
+                    len,
+                    value: Some(value),
+                    children: [NONE; 2],
+                });
+                self.nodes[x].children[side] = leaf;
+                return None;
+            }
+            let child = &self.nodes[c];
+            let common = key.common_prefix(child.prefix).min(child.len).min(len);
+            if common == child.len {
+                self.len -= 1;
+                x = c;
+                continue;
+            }
+            // The new prefix parts from the child's at `common`, so a node
+            // goes in between: the new prefix itself, or a branch.
+            let child_side = child.prefix.bit(common) as usize;
+            let mut between = Node {
+                prefix: key.mask(common),
+                len: common,
+                value: None,
+                children: [NONE; 2],
+            };
+            between.children[child_side] = c;
+            if common == len {
+                between.value = Some(value);
+            } else {
+                let leaf = self.alloc(Node {
+                    prefix: key,
+                    len,
+                    value: Some(value),
+                    children: [NONE; 2],
+                });
+                between.children[1 - child_side] = leaf;
+            }
+            let between = self.alloc(between);
+            self.nodes[x].children[side] = between;
+            return None;
+        }
+    }
+
+    /// The node holding exactly the prefix, and the path down to it.
+    fn find(&self, addr: A, len: u8) -> Option<(usize, Vec<usize>)> {
+        assert!(len <= A::BITS, "prefix longer than the address");
+        let key = addr.mask(len);
+        let (mut x, mut path) = (0, Vec::new());
+        loop {
+            let node = &self.nodes[x];
+            if node.len == len {
+                return (node.prefix == key).then_some((x, path));
+            }
+            let c = node.children[key.bit(node.len) as usize];
+            if c == NONE
+                || self.nodes[c].len > len
+                || key.mask(self.nodes[c].len) != self.nodes[c].prefix
+            {
+                return None;
+            }
+            path.push(x);
+            x = c;
+        }
+    }
+
+    /// The value stored under exactly this prefix.
+    pub fn get(&self, addr: A, len: u8) -> Option<&V> {
+        let (x, _) = self.find(addr, len)?;
+        self.nodes[x].value.as_ref()
+    }
+
+    pub fn get_mut(&mut self, addr: A, len: u8) -> Option<&mut V> {
+        let (x, _) = self.find(addr, len)?;
+        self.nodes[x].value.as_mut()
+    }
+
+    pub fn remove(&mut self, addr: A, len: u8) -> Option<V> {
+        let (mut x, mut path) = self.find(addr, len)?;
+        let value = self.nodes[x].value.take()?;
+        self.len -= 1;
+        // Splice out nodes left holding nothing and at most one child: the
+        // removed one, then maybe a branch above it.
+        while let Some(parent) = path.pop() {
+            let node = &self.nodes[x];
+            let [left, right] = node.children;
+            if node.value.is_some() || (left != NONE && right != NONE) {
+                break;
+            }
+            let side = (self.nodes[parent].children[1] == x) as usize;
+            self.nodes[parent].children[side] = if left == NONE { right } else { left };
+            self.free.push(x);
+            x = parent;
+        }
+        Some(value)
+    }
+
+    /// The longest stored prefix which `addr` starts with, as the prefix,
+    /// its length and its value.
+    pub fn longest_match(&self, addr: A) -> Option<(A, u8, &V)> {
+        let mut x = 0;
+        let mut best = None;
+        loop {
+            let node = &self.nodes[x];
+            if let Some(value) = &node.value {
+                best = Some((node.prefix, node.len, value));
+            }
+            if node.len == A::BITS {
+                return best;
+            }
+            let c = node.children[addr.bit(node.len) as usize];
+            if c == NONE || addr.mask(self.nodes[c].len) != self.nodes[c].prefix {
+                return best;
+            }
+            x = c;
+        }
+    }
+
+    /// Every prefix, its length and its value, shorter prefixes before the
+    /// longer ones they contain.
+    pub fn iter(&self) -> impl Iterator<Item = (A, u8, &V)> {
+        let mut stack = alloc::vec![0];
+        core::iter::from_fn(move || {
+            while let Some(x) = stack.pop() {
+                let node = &self.nodes[x];
+                stack.extend(node.children.iter().rev().copied().filter(|&c| c != NONE));
+                if let Some(value) = &node.value {
+                    return Some((node.prefix, node.len, value));
+                }
+            }
+            None
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[0].value = None;
+        self.nodes[0].children = [NONE; 2];
+        self.free.clear();
+        self.len = 0;
+    }
+}
+
+impl<A: Address, V> Default for IpPrefixMap<A, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut routes = IpPrefixMap::new();
+        routes.insert(Ipv4Addr::new(10, 0, 0, 0), 8, "corp");
+        routes.insert(Ipv4Addr::new(10, 1, 0, 0), 16, "lab");
+        routes.insert(Ipv4Addr::new(0, 0, 0, 0), 0, "default");
+        let hop = |a, b, c, d| {
+            routes
+                .longest_match(Ipv4Addr::new(a, b, c, d))
+                .map(|(_, _, v)| *v)
+        };
+        assert_eq!(hop(10, 1, 2, 3), Some("lab"));
+        assert_eq!(hop(10, 2, 0, 1), Some("corp"));
+        assert_eq!(hop(192, 168, 0, 1), Some("default"));
+
+        assert_eq!(routes.remove(Ipv4Addr::new(10, 0, 0, 0), 8), Some("corp"));
+        assert_eq!(routes.get(Ipv4Addr::new(10, 1, 9, 9), 16), Some(&"lab"));
+        assert_eq!(
+            routes
+                .longest_match(Ipv4Addr::new(10, 2, 0, 1))
+                .map(|(_, len, _)| len),
+            Some(0)
+        );
+        assert_eq!(routes.len(), 2);
+
+        let mut v6 = IpPrefixMap::new();
+        v6.insert(0x2001_0db8_u128 << 96, 32, ());
+        assert!(v6
+            .longest_match(0x2001_0db8_0000_0000_0000_0000_0000_0001)
+            .is_some());
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(bool, u16, u8)>, queries: Vec<u16>) -> bool {
+        // Prefixes of the top 16 bits, so that plenty of them nest.
+        let addr = |bits: u16| (bits as u32) << 16;
+        let mut map = IpPrefixMap::new();
+        let mut model = BTreeMap::new();
+        for (i, (insert, bits, len)) in ops.into_iter().enumerate() {
+            let len = len % 17;
+            let key = (addr(bits).mask(len), len);
+            let ok = if insert {
+                map.insert(addr(bits), len, i) == model.insert(key, i)
+            } else {
+                map.remove(addr(bits), len) == model.remove(&key)
+            };
+            if !ok || map.len() != model.len() {
+                return false;
+            }
+        }
+        queries.into_iter().all(|bits| {
+            let expected = model
+                .iter()
+                .filter(|((prefix, len), _)| addr(bits).mask(*len) == *prefix)
+                .max_by_key(|((_, len), _)| *len)
+                .map(|(&(prefix, len), v)| (prefix, len, v));
+            map.longest_match(addr(bits)) == expected
+        }) && map.iter().count() == model.len()
+    }
+}
@@ -0,0 +1,183 @@
+use core::borrow::Borrow;
+use core::ops::RangeBounds;
+
+use crate::splay::{Range, Splay, Values};
+
+/// Values kept in the order of a key extracted from them, like
+/// `Vec::sort_by_key`. At most one value is kept per key.
+///
+/// The extracted key is cached next to each value, so `modify` can tell when
+/// a change moved the value and reposition it.
+pub struct KeyedSet<T, K, F = fn(&T) -> K> {
+    tree: Splay<K, T>,
+    key: F,
+}
+
+impl<T, K: Ord, F: Fn(&T) -> K> KeyedSet<T, K, F> {
+    pub fn new(key: F) -> Self {
+        KeyedSet {
+            tree: Splay::new(),
+            key,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Inserts `value`, returning the value it displaced if one with the same
+    /// key was present.
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        self.tree.insert((self.key)(&value), value)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&T>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.get(key)
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&T>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.peek(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.contains_key(key)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.remove(key)
+    }
+
+    /// Applies `f` to the value under `key` and moves it if its key changed.
+    /// A value already present under the new key is displaced and returned
+    /// alongside `f`'s result.
+    pub fn modify<Q, R, G>(&mut self, key: &Q, f: G) -> Option<(R, Option<T>)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        G: FnOnce(&mut T) -> R,
+    {
+        let value = self.tree.get_mut(key)?;
+        let result = f(value);
+        let new_key = (self.key)(value);
+        if new_key.borrow() == key {
+            return Some((result, None));
+        }
+        let value = self.tree.remove(key).unwrap();
+        Some((result, self.tree.insert(new_key, value)))
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.tree.first_key_value().map(|(_, v)| v)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.tree.last_key_value().map(|(_, v)| v)
+    }
+
+    /// The values in key order.
+    pub fn iter(&self) -> Values<'_, K, T> {
+        self.tree.values()
+    }
+
+    /// The values whose keys fall within `range`, with their keys.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, T>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.tree.range(range)
+    }
+}
+
+impl<T, K: Ord, F: Fn(&T) -> K> Extend<T> for KeyedSet<T, K, F> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Player {
+        name: &'static str,
+        score: u32,
+    }
+
+    #[test]
+    fn basic_test() {
+        let mut players = KeyedSet::new(|p: &Player| p.score);
+        players.extend([
+            Player {
+                name: "a",
+                score: 30,
+            },
+            Player {
+                name: "b",
+                score: 10,
+            },
+            Player {
+                name: "c",
+                score: 20,
+            },
+        ]);
+        let names: Vec<_> = players.iter().map(|p| p.name).collect();
+        assert_eq!(names, ["b", "c", "a"]);
+        assert_eq!(players.get(&20).map(|p| p.name), Some("c"));
+
+        assert_eq!(players.modify(&10, |p| p.score = 40), Some(((), None)));
+        assert_eq!(players.last().map(|p| p.name), Some("b"));
+        assert!(!players.contains_key(&10));
+
+        let (_, displaced) = players.modify(&40, |p| p.score = 30).unwrap();
+        assert_eq!(displaced.map(|p| p.name), Some("a"));
+        assert_eq!(players.len(), 2);
+        assert_eq!(players.range(25..).count(), 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(values: Vec<(u8, u8)>, moves: Vec<(u8, u8)>) -> bool {
+        let mut set = KeyedSet::new(|&(k, _): &(u8, u8)| k);
+        let mut model = BTreeMap::new();
+        for (k, v) in values {
+            set.insert((k, v));
+            model.insert(k, v);
+        }
+        for (from, to) in moves {
+            let got = set.modify(&from, |e| e.0 = to).map(|(_, d)| d);
+            let expected = model
+                .remove(&from)
+                .map(|v| model.insert(to, v).map(|d| (to, d)));
+            if got != expected {
+                return false;
+            }
+        }
+        set.iter().copied().eq(model.into_iter())
+    }
+}
@@ -1 +1,58 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+extern crate alloc;
+
+pub mod adaptive;
+#[cfg(feature = "quickcheck")]
+mod arbitrary;
+pub mod burst_trie;
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod codec;
+pub mod compare;
+pub mod concurrent;
+pub mod counter;
+pub mod default_map;
+#[cfg(feature = "std")]
+pub mod disk_map;
+pub mod euler_tour;
+#[cfg(feature = "std")]
+pub mod external_sort;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod filter;
+#[cfg(feature = "std")]
+mod hash_index;
+pub mod heap;
+#[cfg(feature = "std")]
+pub mod hybrid_map;
+pub mod interner;
+pub mod ip_prefix_map;
+pub mod keyed_set;
+mod macros;
+pub mod monotonic_queue;
+#[cfg(feature = "std")]
+pub mod ordered_map;
+pub mod range_set;
+#[cfg(feature = "std")]
+pub mod rcu_map;
+pub mod ring_buffer;
+mod rng;
+#[cfg(feature = "std")]
+pub mod sharding;
+pub mod shift_map;
+#[cfg(feature = "std")]
+pub mod sketch;
+pub mod small_str_map;
+pub mod sorted_map;
+pub mod spatial;
 pub mod splay;
+pub mod splay_set;
+pub mod static_splay;
+pub mod suffix_array;
+pub mod versioned_map;
+pub mod weak_value_map;
+#[cfg(feature = "std")]
+pub mod workload;
@@ -0,0 +1,53 @@
+/// Builds a [`Splay`](crate::splay::Splay) from `key => value` pairs. Like
+/// the `From` conversions, literals written in key order are linked into a
+/// balanced tree without sorting, and later duplicates win.
+///
+/// ```
+/// let mut tree = crab_bucket::splay! { "a" => 1, "b" => 2 };
+/// assert_eq!(tree.get("b"), Some(&2));
+/// ```
+#[macro_export]
+macro_rules! splay {
+    ($($key:expr => $value:expr),* $(,)?) => {
+        $crate::splay::Splay::from([$(($key, $value)),*])
+    };
+}
+
+/// Builds a [`SplaySet`](crate::splay_set::SplaySet) from its elements, the
+/// set counterpart of [`splay!`].
+///
+/// ```
+/// let set = crab_bucket::splay_set! { 3, 1, 2 };
+/// assert_eq!(set.first(), Some(&1));
+/// ```
+#[macro_export]
+macro_rules! splay_set {
+    ($($value:expr),* $(,)?) => {
+        <$crate::splay_set::SplaySet<_> as ::core::iter::FromIterator<_>>::from_iter([$($value),*])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::splay::Splay;
+    use crate::splay_set::SplaySet;
+
+    #[test]
+    fn basic_test() {
+        let tree = splay! {
+            1 => "one",
+            2 => "two",
+            3 => "three",
+        };
+        assert!(tree.depth() <= 2);
+        assert!(tree.keys().copied().eq(1..=3));
+
+        let empty: Splay<u8, ()> = splay! {};
+        assert!(empty.is_empty());
+
+        let set = splay_set! { "b", "a", "b" };
+        assert_eq!(set.iter().collect::<Vec<_>>(), [&"a", &"b"]);
+        let empty: SplaySet<u8> = splay_set! {};
+        assert!(empty.is_empty());
+    }
+}
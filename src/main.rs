@@ -1,5 +1,3 @@
-mod splay;
-
 fn main() {
     println!("Hello, world!");
 }
@@ -0,0 +1,179 @@
+//! A FIFO queue which knows its minimum and maximum, for sliding windows.
+
+use alloc::collections::VecDeque;
+
+/// A queue with amortized O(1) `push_back`, `pop_front`, `min` and `max`.
+///
+/// Besides the items, it keeps the positions of the ones which could still
+/// become the minimum, increasing from the front, and likewise for the
+/// maximum. An item is dropped from those as soon as a later one beats it,
+/// since it will leave the queue first.
+#[derive(Clone, Debug)]
+pub struct MonotonicQueue<T> {
+    items: VecDeque<T>,
+    // Position of `items[0]` in the stream, for the ones in `mins`/`maxs`.
+    front: u64,
+    mins: VecDeque<u64>,
+    maxs: VecDeque<u64>,
+}
+
+impl<T: Ord> MonotonicQueue<T> {
+    pub fn new() -> Self {
+        MonotonicQueue {
+            items: VecDeque::new(),
+            front: 0,
+            mins: VecDeque::new(),
+            maxs: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn at(&self, position: u64) -> &T {
+        &self.items[(position - self.front) as usize]
+    }
+
+    pub fn push_back(&mut self, item: T) {
+        let position = self.front + self.items.len() as u64;
+        while self.mins.back().is_some_and(|&p| *self.at(p) > item) {
+            self.mins.pop_back();
+        }
+        while self.maxs.back().is_some_and(|&p| *self.at(p) < item) {
+            self.maxs.pop_back();
+        }
+        self.mins.push_back(position);
+        self.maxs.push_back(position);
+        self.items.push_back(item);
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let item = self.items.pop_front()?;
+        if self.mins.front() == Some(&self.front) {
+            self.mins.pop_front();
+        }
+        if self.maxs.front() == Some(&self.front) {
+            self.maxs.pop_front();
+        }
+        self.front += 1;
+        Some(item)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.items.front()
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.items.back()
+    }
+
+    pub fn min(&self) -> Option<&T> {
+        self.mins.front().map(|&p| self.at(p))
+    }
+
+    pub fn max(&self) -> Option<&T> {
+        self.maxs.front().map(|&p| self.at(p))
+    }
+
+    /// The items, front to back.
+    pub fn iter(&self) -> alloc::collections::vec_deque::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.mins.clear();
+        self.maxs.clear();
+    }
+}
+
+impl<T: Ord> Default for MonotonicQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The minimum and maximum of every run of `width` consecutive items,
+/// from [`sliding_min_max`].
+pub struct SlidingMinMax<I: Iterator> {
+    iter: I,
+    width: usize,
+    queue: MonotonicQueue<I::Item>,
+}
+
+/// Slides a window of `width` items over `iter`, yielding its minimum and
+/// maximum at each step once it's full. A shorter `iter` yields nothing.
+///
+/// # Panics
+///
+/// Panics if `width` is zero.
+pub fn sliding_min_max<I>(iter: I, width: usize) -> SlidingMinMax<I::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: Ord + Clone,
+{
+    assert!(width > 0, "window width must be positive");
+    SlidingMinMax {
+        iter: iter.into_iter(),
+        width,
+        queue: MonotonicQueue::new(),
+    }
+}
+
+impl<I: Iterator> Iterator for SlidingMinMax<I>
+where
+    I::Item: Ord + Clone,
+{
+    type Item = (I::Item, I::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.queue.len() == self.width {
+            self.queue.pop_front();
+        }
+        while self.queue.len() < self.width {
+            self.queue.push_back(self.iter.next()?);
+        }
+        Some((self.queue.min()?.clone(), self.queue.max()?.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut queue = MonotonicQueue::new();
+        for item in [3, 1, 4, 1, 5] {
+            queue.push_back(item);
+        }
+        assert_eq!((queue.min(), queue.max()), (Some(&1), Some(&5)));
+        queue.pop_front();
+        queue.pop_front();
+        assert_eq!((queue.min(), queue.max()), (Some(&1), Some(&5)));
+        queue.pop_front();
+        queue.pop_front();
+        assert_eq!((queue.min(), queue.front()), (Some(&5), Some(&5)));
+        assert_eq!(queue.pop_front(), Some(5));
+        assert_eq!(queue.max(), None);
+
+        let windows: Vec<_> = sliding_min_max([1, 3, 2, 5, 4], 3).collect();
+        assert_eq!(windows, [(1, 3), (2, 5), (2, 5)]);
+        assert_eq!(sliding_min_max([1], 2).next(), None);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(items: Vec<u8>, width: u8) -> bool {
+        let width = width as usize % 8 + 1;
+        let expected = items
+            .windows(width)
+            .map(|w| (*w.iter().min().unwrap(), *w.iter().max().unwrap()));
+        sliding_min_max(items.iter().copied(), width).eq(expected)
+    }
+}
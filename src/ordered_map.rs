@@ -0,0 +1,248 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+use crate::hash_index::HashIndex;
+
+/// Hash map which remembers insertion order. Entries live in a vector and a
+/// small open-addressing table maps keys to their position in it.
+#[derive(Clone)]
+pub struct OrderedMap<K, V, S = RandomState> {
+    entries: Vec<(K, V)>,
+    index: HashIndex<S>,
+}
+
+impl<K: Hash + Eq, V> OrderedMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> OrderedMap<K, V, S> {
+    /// An empty map hashing keys with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        OrderedMap {
+            entries: Vec::new(),
+            index: HashIndex::new(hasher),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Inserts a new entry at the end, or replaces the value in place if the
+    /// key is already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(slot) = self.index.find(&self.entries, &key) {
+            let idx = self.index.get(slot);
+            return Some(std::mem::replace(&mut self.entries[idx].1, value));
+        }
+        self.entries.push((key, value));
+        self.index.insert(&self.entries, self.entries.len() - 1);
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.index.find(&self.entries, key)?;
+        Some(&self.entries[self.index.get(slot)].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.index.find(&self.entries, key)?;
+        Some(&mut self.entries[self.index.get(slot)].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.find(&self.entries, key).is_some()
+    }
+
+    pub fn get_index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.index.find(&self.entries, key)?;
+        Some(self.index.get(slot))
+    }
+
+    pub fn get_index(&self, idx: usize) -> Option<(&K, &V)> {
+        self.entries.get(idx).map(|(k, v)| (k, v))
+    }
+
+    /// Removes the entry by moving the last entry into its place, O(1) but
+    /// disturbs the order.
+    pub fn swap_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.index.find(&self.entries, key)?;
+        let idx = self.index.get(slot);
+        self.index.remove(&self.entries, slot);
+
+        let last = self.entries.len() - 1;
+        if idx != last {
+            self.index.repoint(&self.entries, last, idx);
+        }
+        Some(self.entries.swap_remove(idx).1)
+    }
+
+    /// Removes the entry by shifting all following entries, O(n) but keeps
+    /// the order.
+    pub fn shift_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.index.find(&self.entries, key)?;
+        let idx = self.index.get(slot);
+        self.index.remove(&self.entries, slot);
+        self.index.shift_down(idx);
+        Some(self.entries.remove(idx).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K: Hash + Eq + fmt::Debug, V: fmt::Debug, S: BuildHasher> fmt::Debug for OrderedMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for OrderedMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickcheck::{Arbitrary, Gen};
+
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+
+    #[test]
+    fn basic_test() {
+        let mut map: OrderedMap<&str, i32> = OrderedMap::new();
+        map.insert("c", 1);
+        map.insert("a", 2);
+        map.insert("b", 3);
+        assert_eq!(map.insert("a", 4), Some(2));
+        assert_eq!(
+            map.keys().copied().collect::<Vec<&str>>(),
+            vec!["c", "a", "b"]
+        );
+
+        assert_eq!(map.swap_remove("c"), Some(1));
+        assert_eq!(map.keys().copied().collect::<Vec<&str>>(), vec!["b", "a"]);
+
+        map.insert("d", 5);
+        assert_eq!(map.shift_remove("b"), Some(3));
+        assert_eq!(map.keys().copied().collect::<Vec<&str>>(), vec!["a", "d"]);
+        assert_eq!(map.get("d"), Some(&5));
+        assert_eq!(map.get_index_of("d"), Some(1));
+        assert_eq!(map.get("b"), None);
+
+        // A fixed hasher lays the slots out the same every run.
+        let seeded = || {
+            let mut map = OrderedMap::with_hasher(BuildHasherDefault::<DefaultHasher>::default());
+            (0..20).for_each(|k| _ = map.insert(k, ()));
+            map.index.slots().to_vec()
+        };
+        assert_eq!(seeded(), seeded());
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Insert(u8, i32),
+        SwapRemove(u8),
+        ShiftRemove(u8),
+    }
+
+    impl Arbitrary for Op {
+        fn arbitrary(g: &mut Gen) -> Self {
+            match *g.choose(&[0, 1, 2]).unwrap() {
+                0 => Op::Insert(u8::arbitrary(g), i32::arbitrary(g)),
+                1 => Op::SwapRemove(u8::arbitrary(g)),
+                2 => Op::ShiftRemove(u8::arbitrary(g)),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<Op>) -> bool {
+        let mut map: OrderedMap<u8, i32> = OrderedMap::new();
+        let mut model: Vec<(u8, i32)> = Vec::new();
+
+        for op in ops.iter() {
+            match *op {
+                Op::Insert(k, v) => {
+                    let expected = match model.iter_mut().find(|(key, _)| *key == k) {
+                        Some(entry) => Some(std::mem::replace(&mut entry.1, v)),
+                        None => {
+                            model.push((k, v));
+                            None
+                        }
+                    };
+                    if map.insert(k, v) != expected {
+                        return false;
+                    }
+                }
+                Op::SwapRemove(k) => {
+                    let expected = model
+                        .iter()
+                        .position(|(key, _)| *key == k)
+                        .map(|i| model.swap_remove(i).1);
+                    if map.swap_remove(&k) != expected {
+                        return false;
+                    }
+                }
+                Op::ShiftRemove(k) => {
+                    let expected = model
+                        .iter()
+                        .position(|(key, _)| *key == k)
+                        .map(|i| model.remove(i).1);
+                    if map.shift_remove(&k) != expected {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        model.iter().all(|(k, v)| map.get(k) == Some(v))
+            && map.iter().map(|(k, v)| (*k, *v)).eq(model.iter().copied())
+    }
+}
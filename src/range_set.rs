@@ -0,0 +1,250 @@
+//! Sets of values kept as disjoint ranges, like block allocations or free
+//! extents.
+
+use core::fmt;
+use core::ops::{Bound, Range};
+
+use crate::splay::Splay;
+
+/// A set of half-open ranges, kept disjoint and coalesced: overlapping or
+/// adjacent ranges merge as they're inserted, and removing a range from the
+/// middle of one splits it. Stored as a [`Splay`] from start to end, so
+/// each operation costs a few tree lookups plus the number of ranges it
+/// merges or drops.
+#[derive(Clone)]
+pub struct RangeSet<T> {
+    ranges: Splay<T, T>,
+}
+
+impl<T: Ord + Copy> RangeSet<T> {
+    pub fn new() -> Self {
+        RangeSet {
+            ranges: Splay::new(),
+        }
+    }
+
+    /// The number of disjoint ranges.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The last range starting within `bound`, taken as an upper bound.
+    fn last_starting(&self, bound: Bound<&T>) -> Option<Range<T>> {
+        let (&start, &end) = self.ranges.upper_bound(bound).peek_prev()?;
+        Some(start..end)
+    }
+
+    pub fn contains(&self, point: &T) -> bool {
+        self.range_containing(point).is_some()
+    }
+
+    /// The range `point` falls in, if any.
+    pub fn range_containing(&self, point: &T) -> Option<Range<T>> {
+        self.last_starting(Bound::Included(point))
+            .filter(|range| *point < range.end)
+    }
+
+    /// Adds every value in `range`, returning whether any was missing.
+    pub fn insert(&mut self, range: Range<T>) -> bool {
+        let Range { mut start, mut end } = range;
+        if start >= end {
+            return false;
+        }
+        if let Some(before) = self.last_starting(Bound::Included(&start)) {
+            if before.end >= end {
+                return false;
+            }
+            if before.end >= start {
+                start = before.start;
+            }
+        }
+        // Of the ranges starting up to `end`, which all merge, the last one
+        // reaches furthest.
+        if let Some(last) = self.last_starting(Bound::Included(&end)) {
+            if last.start >= start {
+                end = end.max(last.end);
+            }
+        }
+        self.ranges.remove_range(start..=end);
+        self.ranges.insert(start, end);
+        true
+    }
+
+    /// Drops every value in `range`, returning whether any was there.
+    pub fn remove(&mut self, range: Range<T>) -> bool {
+        let Range { start, end } = range;
+        if start >= end {
+            return false;
+        }
+        let mut changed = false;
+        // A range from before `start` which reaches into it keeps its head,
+        // and maybe its tail too.
+        if let Some(before) = self.last_starting(Bound::Excluded(&start)) {
+            if before.end > start {
+                self.ranges.insert(before.start, start);
+                if before.end > end {
+                    self.ranges.insert(end, before.end);
+                    return true;
+                }
+                changed = true;
+            }
+        }
+        // Of the ranges starting inside, the last one may keep its tail.
+        let mut tail = None;
+        if let Some(last) = self.last_starting(Bound::Excluded(&end)) {
+            if last.start >= start && last.end > end {
+                tail = Some(last.end);
+            }
+        }
+        changed |= self.ranges.remove_range(start..end) > 0;
+        if let Some(tail) = tail {
+            self.ranges.insert(end, tail);
+        }
+        changed
+    }
+
+    /// The ranges, in order.
+    pub fn iter(&self) -> impl Iterator<Item = Range<T>> + '_ {
+        self.ranges.iter().map(|(&start, &end)| start..end)
+    }
+
+    /// The stretches of `within` which aren't in the set, in order.
+    pub fn gaps(&self, within: Range<T>) -> impl Iterator<Item = Range<T>> + '_ {
+        let end = within.end;
+        let mut cursor = within.start.min(end);
+        if let Some(before) = self.last_starting(Bound::Included(&cursor)) {
+            cursor = cursor.max(before.end);
+        }
+        let mut ranges = self.ranges.range(within.start.min(end)..end);
+        let mut done = false;
+        core::iter::from_fn(move || {
+            while !done {
+                match ranges.next() {
+                    Some((&start, &next)) if start > cursor => {
+                        let gap = cursor..start;
+                        cursor = next;
+                        return Some(gap);
+                    }
+                    Some((_, &next)) => cursor = cursor.max(next),
+                    None => {
+                        done = true;
+                        if cursor < end {
+                            return Some(cursor..end);
+                        }
+                    }
+                }
+            }
+            None
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+    }
+}
+
+impl<T: Ord + Copy> Default for RangeSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Copy + fmt::Debug> fmt::Debug for RangeSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> PartialEq for RangeSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ranges == other.ranges
+    }
+}
+
+impl<T: Ord> Eq for RangeSet<T> {}
+
+impl<T: Ord + Copy> Extend<Range<T>> for RangeSet<T> {
+    fn extend<I: IntoIterator<Item = Range<T>>>(&mut self, iter: I) {
+        for range in iter {
+            self.insert(range);
+        }
+    }
+}
+
+impl<T: Ord + Copy> FromIterator<Range<T>> for RangeSet<T> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut set = RangeSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut set: RangeSet<u32> = [0..10, 20..30, 10..15].into_iter().collect();
+        assert_eq!(set.iter().collect::<Vec<_>>(), [0..15, 20..30]);
+        assert!(!set.insert(3..7));
+        assert!(set.insert(14..21));
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.range_containing(&0), Some(0..30));
+
+        assert!(set.remove(5..8));
+        assert!(!set.remove(5..8));
+        assert_eq!(set.range_containing(&9), Some(8..30));
+        assert!(!set.contains(&7));
+        assert_eq!(set.gaps(0..40).collect::<Vec<_>>(), [5..8, 30..40]);
+        assert_eq!(set.gaps(10..20).count(), 0);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(bool, u8, u8)>, within: (u8, u8)) -> bool {
+        let mut set = RangeSet::new();
+        let mut model = [false; 256];
+        for (insert, a, b) in ops {
+            let range = a.min(b)..a.max(b);
+            let was = model[range.start as usize..range.end as usize].to_vec();
+            let changed = if insert {
+                set.insert(range.clone())
+            } else {
+                set.remove(range.clone())
+            };
+            let expected = was.iter().any(|&x| x != insert);
+            model[range.start as usize..range.end as usize].fill(insert);
+            if changed != expected {
+                return false;
+            }
+        }
+
+        // Maximal runs of the model, then of its complement within bounds.
+        let runs = |value: bool, from: u8, to: u8| {
+            let mut runs = Vec::new();
+            let mut i = from;
+            while i < to {
+                if model[i as usize] == value {
+                    let start = i;
+                    while i < to && model[i as usize] == value {
+                        i += 1;
+                    }
+                    runs.push(start..i);
+                } else {
+                    i += 1;
+                }
+            }
+            runs
+        };
+        let (from, to) = (within.0.min(within.1), within.0.max(within.1));
+        (0..=255).all(|i| set.contains(&i) == model[i as usize])
+            && set.iter().collect::<Vec<_>>() == runs(true, 0, 255)
+            && set.gaps(from..to).collect::<Vec<_>>() == runs(false, from, to)
+    }
+}
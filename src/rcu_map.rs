@@ -0,0 +1,150 @@
+//! Read-mostly map: readers take the currently published `FrozenSplay`
+//! without locking, writers build a new version and swap it in.
+
+use std::borrow::Borrow;
+use std::hint;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::splay::{FrozenSplay, Splay};
+
+pub struct RcuMap<K, V> {
+    current: AtomicPtr<FrozenSplay<K, V>>,
+    // Readers register in `readers[epoch]` while they pick up `current`. A
+    // writer flips the epoch after swapping and waits for the old half to
+    // drain before dropping its reference to the previous version.
+    epoch: AtomicUsize,
+    readers: [AtomicUsize; 2],
+    writer: Mutex<()>,
+    _owns: PhantomData<Arc<FrozenSplay<K, V>>>,
+}
+
+impl<K: Ord, V> RcuMap<K, V> {
+    pub fn new(tree: Splay<K, V>) -> Self {
+        RcuMap {
+            current: AtomicPtr::new(Arc::into_raw(Arc::new(tree.freeze())).cast_mut()),
+            epoch: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            writer: Mutex::new(()),
+            _owns: PhantomData,
+        }
+    }
+
+    /// The currently published version. Never blocks, even during writes.
+    pub fn load(&self) -> Arc<FrozenSplay<K, V>> {
+        let epoch = loop {
+            let epoch = self.epoch.load(SeqCst);
+            self.readers[epoch].fetch_add(1, SeqCst);
+            if self.epoch.load(SeqCst) == epoch {
+                break epoch;
+            }
+            // A writer flipped the epoch in between and might not wait for us.
+            self.readers[epoch].fetch_sub(1, SeqCst);
+        };
+        let ptr = self.current.load(SeqCst);
+        // SAFETY: the writer which replaces `ptr` waits for `readers[epoch]`
+        // to drain before releasing its reference, so `ptr` is still alive.
+        let current = unsafe {
+            Arc::increment_strong_count(ptr);
+            Arc::from_raw(ptr)
+        };
+        self.readers[epoch].fetch_sub(1, SeqCst);
+        current
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        self.load().get(key).cloned()
+    }
+
+    /// Applies a batch of writes to a copy of the current version and
+    /// publishes the result. Writers are serialised; readers keep seeing the
+    /// previous version until the swap.
+    pub fn update<F: FnOnce(&mut Splay<K, V>)>(&self, f: F)
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let _guard = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut tree = FrozenSplay::clone(&self.load()).thaw();
+        f(&mut tree);
+
+        let new = Arc::into_raw(Arc::new(tree.freeze())).cast_mut();
+        let old = self.current.swap(new, SeqCst);
+        let epoch = self.epoch.fetch_xor(1, SeqCst);
+        while self.readers[epoch].load(SeqCst) != 0 {
+            hint::spin_loop();
+        }
+        // SAFETY: `old` came from `Arc::into_raw` and no reader is about to
+        // take a new reference to it.
+        drop(unsafe { Arc::from_raw(old) });
+    }
+}
+
+impl<K: Ord, V> Default for RcuMap<K, V> {
+    fn default() -> Self {
+        Self::new(Splay::new())
+    }
+}
+
+impl<K, V> Drop for RcuMap<K, V> {
+    fn drop(&mut self) {
+        // SAFETY: the map owns one reference to the published version.
+        drop(unsafe { Arc::from_raw(*self.current.get_mut()) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn basic_test() {
+        let map = RcuMap::default();
+        map.update(|tree| {
+            tree.set("a", 1);
+            tree.set("b", 2);
+        });
+        let before = map.load();
+        map.update(|tree| {
+            tree.remove("a");
+        });
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.get("b"), Some(2));
+        assert_eq!(before.get("a"), Some(&1));
+    }
+
+    #[test]
+    fn concurrent_test() {
+        // Every version maps all keys to the same value, so a reader can tell
+        // if it ever sees a torn or freed one.
+        let map = RcuMap::new((0..64).map(|k| (k, 0)).collect());
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..2000 {
+                        let version = map.load();
+                        let first = *version.get(&0).unwrap();
+                        assert!(version.iter().all(|(_, v)| *v == first));
+                    }
+                });
+            }
+            s.spawn(|| {
+                for i in 1..=200 {
+                    map.update(|tree| {
+                        for (_, v) in tree.iter_mut() {
+                            *v = i;
+                        }
+                    });
+                }
+            });
+        });
+        assert_eq!(map.get(&63), Some(200));
+    }
+}
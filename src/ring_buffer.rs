@@ -0,0 +1,396 @@
+//! Ring buffers of a fixed capacity, inline or on the heap.
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::iter::Chain;
+use core::mem::MaybeUninit;
+use core::slice;
+
+/// A double-ended queue of at most `N` items, stored inline.
+///
+/// Pushing onto a full ring fails, handing the item back, unless it's one
+/// of the `_overwrite` pushes, which evict the item at the other end to
+/// make room and return it. The items are at most two slices, see [`as_slices`], and
+/// [`make_contiguous`] turns them into one.
+///
+/// [`as_slices`]: RingBuffer::as_slices
+/// [`make_contiguous`]: RingBuffer::make_contiguous
+pub struct RingBuffer<T, const N: usize> {
+    buf: [MaybeUninit<T>; N],
+    head: usize,
+    len: usize,
+}
+
+/// A [`RingBuffer`] whose capacity is picked at runtime, stored on the heap.
+pub struct HeapRingBuffer<T> {
+    buf: Box<[MaybeUninit<T>]>,
+    head: usize,
+    len: usize,
+}
+
+/// The items of a ring, front to back.
+pub type Iter<'a, T> = Chain<slice::Iter<'a, T>, slice::Iter<'a, T>>;
+
+pub type IterMut<'a, T> = Chain<slice::IterMut<'a, T>, slice::IterMut<'a, T>>;
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    pub const fn new() -> Self {
+        RingBuffer {
+            buf: [const { MaybeUninit::uninit() }; N],
+            head: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for RingBuffer<T, N> {
+    fn clone(&self) -> Self {
+        let mut ring = Self::new();
+        for item in self.iter() {
+            ring.write_back(item.clone());
+        }
+        ring
+    }
+}
+
+impl<T> HeapRingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        HeapRingBuffer {
+            buf: (0..capacity).map(|_| MaybeUninit::uninit()).collect(),
+            head: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<T: Clone> Clone for HeapRingBuffer<T> {
+    fn clone(&self) -> Self {
+        let mut ring = Self::new(self.capacity());
+        for item in self.iter() {
+            ring.write_back(item.clone());
+        }
+        ring
+    }
+}
+
+/// # Safety
+///
+/// Every slot must be initialized.
+unsafe fn assume_init<T>(slots: &[MaybeUninit<T>]) -> &[T] {
+    unsafe { &*(slots as *const [MaybeUninit<T>] as *const [T]) }
+}
+
+/// # Safety
+///
+/// Every slot must be initialized.
+unsafe fn assume_init_mut<T>(slots: &mut [MaybeUninit<T>]) -> &mut [T] {
+    unsafe { &mut *(slots as *mut [MaybeUninit<T>] as *mut [T]) }
+}
+
+// Both rings keep their `len` items in the slots from `head` on, wrapping
+// around the end of `buf`; the other slots are uninitialized.
+macro_rules! ring_buffer {
+    ([$($generics:tt)*] $ring:ty) => {
+        impl<$($generics)*> $ring {
+            pub fn len(&self) -> usize {
+                self.len
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.len == 0
+            }
+
+            pub fn is_full(&self) -> bool {
+                self.len == self.buf.len()
+            }
+
+            pub fn capacity(&self) -> usize {
+                self.buf.len()
+            }
+
+            /// The slot of the `i`th item, for `i` up to the capacity.
+            fn slot(&self, i: usize) -> usize {
+                let slot = self.head + i;
+                if slot >= self.buf.len() {
+                    slot - self.buf.len()
+                } else {
+                    slot
+                }
+            }
+
+            fn write_back(&mut self, item: T) {
+                let slot = self.slot(self.len);
+                self.buf[slot].write(item);
+                self.len += 1;
+            }
+
+            fn write_front(&mut self, item: T) {
+                self.head = self.slot(self.buf.len() - 1);
+                self.buf[self.head].write(item);
+                self.len += 1;
+            }
+
+            /// Pushes `item` onto the back, or hands it back if the ring is
+            /// full.
+            pub fn push_back(&mut self, item: T) -> Result<(), T> {
+                if self.is_full() {
+                    return Err(item);
+                }
+                self.write_back(item);
+                Ok(())
+            }
+
+            pub fn push_front(&mut self, item: T) -> Result<(), T> {
+                if self.is_full() {
+                    return Err(item);
+                }
+                self.write_front(item);
+                Ok(())
+            }
+
+            /// Pushes `item` onto the back, evicting the front item to make
+            /// room if the ring is full and returning it.
+            pub fn push_back_overwrite(&mut self, item: T) -> Option<T> {
+                if self.buf.is_empty() {
+                    return Some(item);
+                }
+                let dropped = if self.is_full() { self.pop_front() } else { None };
+                self.write_back(item);
+                dropped
+            }
+
+            pub fn push_front_overwrite(&mut self, item: T) -> Option<T> {
+                if self.buf.is_empty() {
+                    return Some(item);
+                }
+                let dropped = if self.is_full() { self.pop_back() } else { None };
+                self.write_front(item);
+                dropped
+            }
+
+            pub fn pop_front(&mut self) -> Option<T> {
+                if self.len == 0 {
+                    return None;
+                }
+                // SAFETY: the front item is at `head`, which moves past it.
+                let item = unsafe { self.buf[self.head].assume_init_read() };
+                self.head = self.slot(1);
+                self.len -= 1;
+                Some(item)
+            }
+
+            pub fn pop_back(&mut self) -> Option<T> {
+                if self.len == 0 {
+                    return None;
+                }
+                self.len -= 1;
+                let slot = self.slot(self.len);
+                // SAFETY: the back item was in `slot`, which `len` now excludes.
+                Some(unsafe { self.buf[slot].assume_init_read() })
+            }
+
+            /// The `i`th item from the front.
+            pub fn get(&self, i: usize) -> Option<&T> {
+                if i >= self.len {
+                    return None;
+                }
+                // SAFETY: the first `len` items are initialized.
+                Some(unsafe { self.buf[self.slot(i)].assume_init_ref() })
+            }
+
+            pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+                if i >= self.len {
+                    return None;
+                }
+                let slot = self.slot(i);
+                // SAFETY: the first `len` items are initialized.
+                Some(unsafe { self.buf[slot].assume_init_mut() })
+            }
+
+            pub fn front(&self) -> Option<&T> {
+                self.get(0)
+            }
+
+            pub fn back(&self) -> Option<&T> {
+                self.get(self.len.checked_sub(1)?)
+            }
+
+            /// The items, front to back, as the part up to the end of the
+            /// buffer and the part which wrapped around to its start.
+            pub fn as_slices(&self) -> (&[T], &[T]) {
+                let first = self.len.min(self.buf.len() - self.head);
+                let (wrapped, from_head) = self.buf.split_at(self.head);
+                // SAFETY: these are the slots of the `len` items.
+                unsafe {
+                    (
+                        assume_init(&from_head[..first]),
+                        assume_init(&wrapped[..self.len - first]),
+                    )
+                }
+            }
+
+            pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+                let first = self.len.min(self.buf.len() - self.head);
+                let (wrapped, from_head) = self.buf.split_at_mut(self.head);
+                // SAFETY: these are the slots of the `len` items.
+                unsafe {
+                    (
+                        assume_init_mut(&mut from_head[..first]),
+                        assume_init_mut(&mut wrapped[..self.len - first]),
+                    )
+                }
+            }
+
+            /// Rotates the items to the start of the buffer, in O(capacity),
+            /// so that they're one slice.
+            pub fn make_contiguous(&mut self) -> &mut [T] {
+                self.buf.rotate_left(self.head);
+                self.head = 0;
+                // SAFETY: the `len` items are now at the start.
+                unsafe { assume_init_mut(&mut self.buf[..self.len]) }
+            }
+
+            pub fn iter(&self) -> Iter<'_, T> {
+                let (first, second) = self.as_slices();
+                first.iter().chain(second)
+            }
+
+            pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+                let (first, second) = self.as_mut_slices();
+                first.iter_mut().chain(second)
+            }
+
+            pub fn clear(&mut self) {
+                while self.pop_front().is_some() {}
+                self.head = 0;
+            }
+        }
+
+        impl<$($generics)*> Drop for $ring {
+            fn drop(&mut self) {
+                self.clear();
+            }
+        }
+
+        impl<$($generics)*> fmt::Debug for $ring
+        where
+            T: fmt::Debug,
+        {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_list().entries(self.iter()).finish()
+            }
+        }
+
+        impl<'a, $($generics)*> IntoIterator for &'a $ring {
+            type Item = &'a T;
+            type IntoIter = Iter<'a, T>;
+
+            fn into_iter(self) -> Iter<'a, T> {
+                self.iter()
+            }
+        }
+    };
+}
+
+ring_buffer!([T, const N: usize] RingBuffer<T, N>);
+ring_buffer!([T] HeapRingBuffer<T>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::VecDeque;
+    use alloc::rc::Rc;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut ring = RingBuffer::<_, 3>::new();
+        assert_eq!(ring.push_back(1), Ok(()));
+        assert_eq!(ring.push_back(2), Ok(()));
+        assert_eq!(ring.push_front(0), Ok(()));
+        assert_eq!(ring.as_slices(), (&[0][..], &[1, 2][..]));
+        assert_eq!(ring.make_contiguous(), [0, 1, 2]);
+        assert_eq!(ring.push_back(3), Err(3));
+        assert_eq!(ring.push_back_overwrite(3), Some(0));
+        assert_eq!(ring.push_front_overwrite(0), Some(3));
+        assert_eq!((ring.front(), ring.back()), (Some(&0), Some(&2)));
+        assert_eq!(format!("{ring:?}"), "[0, 1, 2]");
+
+        let mut empty = HeapRingBuffer::new(0);
+        assert_eq!(empty.push_back_overwrite(1), Some(1));
+        assert_eq!(empty.pop_front(), None);
+
+        let item = Rc::new(());
+        let mut ring = HeapRingBuffer::new(2);
+        for _ in 0..3 {
+            ring.push_back_overwrite(item.clone());
+        }
+        assert_eq!(Rc::strong_count(&item), 3);
+        ring.clone().pop_back();
+        drop(ring);
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>, capacity: u8) -> bool {
+        let capacity = capacity as usize % 6;
+        let mut ring = HeapRingBuffer::new(capacity);
+        let mut model = VecDeque::new();
+        for (op, item) in ops {
+            let full = model.len() == capacity;
+            let ok = match op % 7 {
+                0 => {
+                    let expected = if full {
+                        Err(item)
+                    } else {
+                        model.push_back(item);
+                        Ok(())
+                    };
+                    ring.push_back(item) == expected
+                }
+                1 => {
+                    let expected = if full {
+                        Err(item)
+                    } else {
+                        model.push_front(item);
+                        Ok(())
+                    };
+                    ring.push_front(item) == expected
+                }
+                2 => {
+                    let expected = if capacity == 0 {
+                        Some(item)
+                    } else {
+                        let dropped = if full { model.pop_front() } else { None };
+                        model.push_back(item);
+                        dropped
+                    };
+                    ring.push_back_overwrite(item) == expected
+                }
+                3 => {
+                    let expected = if capacity == 0 {
+                        Some(item)
+                    } else {
+                        let dropped = if full { model.pop_back() } else { None };
+                        model.push_front(item);
+                        dropped
+                    };
+                    ring.push_front_overwrite(item) == expected
+                }
+                4 => ring.pop_front() == model.pop_front(),
+                5 => ring.pop_back() == model.pop_back(),
+                _ => ring.make_contiguous() == model.make_contiguous(),
+            };
+            if !ok || !ring.iter().eq(&model) {
+                return false;
+            }
+        }
+        true
+    }
+}
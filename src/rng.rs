@@ -0,0 +1,16 @@
+//! SplitMix64, the small seeded generator behind every randomized
+//! structure in the crate, so none of them needs entropy from the system.
+
+/// Steps `state` and returns the next output. Not fit for anything an
+/// adversary might want to predict.
+pub(crate) fn split_mix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    mix64(*state)
+}
+
+/// The finalizer of SplitMix64, a bijection which scrambles every bit.
+pub(crate) fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
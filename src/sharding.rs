@@ -0,0 +1,20 @@
+//! Spreading keys over a changing set of members, such as cache servers or
+//! database shards, so that few keys move when the members change.
+
+mod hash_ring;
+mod rendezvous;
+
+pub use hash_ring::HashRing;
+pub use rendezvous::Rendezvous;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A hash which doesn't change between runs, or processes, so that they
+/// agree on where each key goes; SipHash with fixed keys, so not safe
+/// against crafted inputs.
+fn stable_hash<T: Hash + ?Sized>(item: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Bound;
+
+use super::stable_hash;
+use crate::splay::Splay;
+
+/// Consistent hashing: members and keys hash onto a ring of `u64`, and each
+/// key belongs to the first member point at or after its own.
+///
+/// Every member gets `replicas` points per unit of weight, so its share of
+/// the keys follows its weight and evens out across many small arcs. Adding
+/// a member only takes keys over from the others, and removing one only
+/// hands its own keys on, about `1 / n` of them either way.
+#[derive(Clone, Debug)]
+pub struct HashRing<N> {
+    points: Splay<u64, N>,
+    weights: HashMap<N, u32>,
+    replicas: u32,
+}
+
+impl<N: Hash + Eq + Clone> HashRing<N> {
+    /// A ring with 160 points per unit of weight, as in ketama.
+    pub fn new() -> Self {
+        Self::with_replicas(160)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `replicas` is zero.
+    pub fn with_replicas(replicas: u32) -> Self {
+        assert!(replicas > 0, "replicas must be positive");
+        HashRing {
+            points: Splay::new(),
+            weights: HashMap::new(),
+            replicas,
+        }
+    }
+
+    /// The number of members.
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    pub fn contains(&self, node: &N) -> bool {
+        self.weights.contains_key(node)
+    }
+
+    pub fn weight(&self, node: &N) -> Option<u32> {
+        self.weights.get(node).copied()
+    }
+
+    /// The members and their weights, in no particular order.
+    pub fn nodes(&self) -> impl Iterator<Item = (&N, u32)> {
+        self.weights.iter().map(|(node, &weight)| (node, weight))
+    }
+
+    /// The ring positions of a member of this weight.
+    fn points_of<'a>(&self, node: &'a N, weight: u32) -> impl Iterator<Item = u64> + 'a {
+        (0..self.replicas as u64 * weight as u64).map(move |i| stable_hash(&(node, i)))
+    }
+
+    /// Adds a member, or changes its weight, returning the old one. A
+    /// member of weight zero gets no keys.
+    pub fn insert(&mut self, node: N, weight: u32) -> Option<u32> {
+        let old = self.remove(&node);
+        for point in self.points_of(&node, weight).collect::<Vec<_>>() {
+            // On the rare collision the member already there keeps it.
+            if !self.points.contains_key(&point) {
+                self.points.insert(point, node.clone());
+            }
+        }
+        self.weights.insert(node, weight);
+        old
+    }
+
+    pub fn remove(&mut self, node: &N) -> Option<u32> {
+        let weight = self.weights.remove(node)?;
+        for point in self.points_of(node, weight).collect::<Vec<_>>() {
+            if self.points.peek(&point) == Some(node) {
+                self.points.remove(&point);
+            }
+        }
+        Some(weight)
+    }
+
+    /// The member `key` belongs to, `None` if there are no points.
+    pub fn node_for<K: Hash + ?Sized>(&self, key: &K) -> Option<&N> {
+        let hash = stable_hash(key);
+        let (_, node) = self
+            .points
+            .lower_bound(Bound::Included(&hash))
+            .peek_next()
+            .or_else(|| self.points.first_key_value())?;
+        Some(node)
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.weights.clear();
+    }
+}
+
+impl<N: Hash + Eq + Clone> Default for HashRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut ring = HashRing::new();
+        assert_eq!(ring.node_for("key"), None);
+        ring.insert("a", 1);
+        ring.insert("b", 1);
+        ring.insert("c", 2);
+        assert_eq!(ring.insert("c", 2), Some(2));
+        assert_eq!(ring.len(), 3);
+
+        let mut counts = HashMap::new();
+        for key in 0..10_000 {
+            *counts.entry(*ring.node_for(&key).unwrap()).or_insert(0) += 1;
+        }
+        // Roughly 2500, 2500 and 5000.
+        assert!((2000..3000).contains(&counts["a"]));
+        assert!((2000..3000).contains(&counts["b"]));
+        assert!((4500..5500).contains(&counts["c"]));
+
+        assert_eq!(ring.remove(&"c"), Some(2));
+        assert_eq!(ring.remove(&"c"), None);
+        assert!((0..100).all(|key| ring.node_for(&key) != Some(&"c")));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(nodes: Vec<(u8, u8)>, new: u8, keys: Vec<u32>) -> bool {
+        let mut ring = HashRing::with_replicas(4);
+        for (node, weight) in nodes {
+            ring.insert(node, weight as u32 % 4);
+        }
+        ring.remove(&new);
+        let before: Vec<_> = keys.iter().map(|key| ring.node_for(key).copied()).collect();
+        // Adding a member only moves keys to it, and removing it again puts
+        // them back.
+        ring.insert(new, 3);
+        let moved = keys.iter().zip(&before).all(|(key, &was)| {
+            let now = ring.node_for(key).copied();
+            now == was || now == Some(new)
+        });
+        ring.remove(&new);
+        let after: Vec<_> = keys.iter().map(|key| ring.node_for(key).copied()).collect();
+        moved && before == after
+    }
+}
@@ -0,0 +1,163 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::stable_hash;
+
+/// Rendezvous, or highest random weight, hashing: every member scores every
+/// key by hashing the pair, and the key goes to the highest scores.
+///
+/// Scores are `weight / -ln(u)` for the pair's hash `u` in `(0, 1)`, which
+/// gives each member a share of the keys in proportion to its weight, with
+/// no replicas needed to even it out. Removing a member only moves the keys
+/// it owned, each to its own next best, so the next `k - 1` owners of a key
+/// make ready replicas. Lookups cost a hash per member rather than a tree
+/// search, fine for the tens or hundreds of members a cluster has.
+#[derive(Clone, Debug)]
+pub struct Rendezvous<N> {
+    weights: HashMap<N, u32>,
+}
+
+impl<N: Hash + Eq> Rendezvous<N> {
+    pub fn new() -> Self {
+        Rendezvous {
+            weights: HashMap::new(),
+        }
+    }
+
+    /// The number of members.
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    pub fn contains(&self, node: &N) -> bool {
+        self.weights.contains_key(node)
+    }
+
+    pub fn weight(&self, node: &N) -> Option<u32> {
+        self.weights.get(node).copied()
+    }
+
+    /// The members and their weights, in no particular order.
+    pub fn nodes(&self) -> impl Iterator<Item = (&N, u32)> {
+        self.weights.iter().map(|(node, &weight)| (node, weight))
+    }
+
+    /// Adds a member, or changes its weight, returning the old one. A
+    /// member of weight zero gets no keys.
+    pub fn insert(&mut self, node: N, weight: u32) -> Option<u32> {
+        self.weights.insert(node, weight)
+    }
+
+    pub fn remove(&mut self, node: &N) -> Option<u32> {
+        self.weights.remove(node)
+    }
+
+    /// The members of positive weight with their scores for `key`.
+    fn scores<K: Hash + ?Sized>(&self, key: &K) -> impl Iterator<Item = (&N, f64)> {
+        let key = stable_hash(key);
+        self.weights
+            .iter()
+            .filter(|(_, &weight)| weight > 0)
+            .map(move |(node, &weight)| {
+                let hash = stable_hash(&(key, node));
+                // The top 53 bits, centred in their interval so never 0 or 1.
+                let u = ((hash >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+                (node, weight as f64 / -u.ln())
+            })
+    }
+
+    /// The member `key` belongs to, `None` if none has positive weight.
+    pub fn node_for<K: Hash + ?Sized>(&self, key: &K) -> Option<&N> {
+        let (node, _) = self.scores(key).max_by(by_score)?;
+        Some(node)
+    }
+
+    /// The `k` best members for `key`, best first, or all of positive
+    /// weight if there are fewer.
+    pub fn top_k<K: Hash + ?Sized>(&self, key: &K, k: usize) -> Vec<&N> {
+        let mut scores: Vec<_> = self.scores(key).collect();
+        let best_first = |a: &(&N, f64), b: &(&N, f64)| by_score(b, a);
+        if k < scores.len() {
+            if k == 0 {
+                return Vec::new();
+            }
+            scores.select_nth_unstable_by(k - 1, best_first);
+            scores.truncate(k);
+        }
+        scores.sort_unstable_by(best_first);
+        scores.into_iter().map(|(node, _)| node).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.weights.clear();
+    }
+}
+
+/// Scores ascending. Equal scores are as rare as equal 53-bit hashes, and
+/// then the order depends on the members' order in the map.
+fn by_score<N>(a: &(&N, f64), b: &(&N, f64)) -> Ordering {
+    a.1.total_cmp(&b.1)
+}
+
+impl<N: Hash + Eq> Default for Rendezvous<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut members = Rendezvous::new();
+        assert_eq!(members.node_for("key"), None);
+        members.insert("a", 1);
+        members.insert("b", 1);
+        members.insert("c", 2);
+        members.insert("idle", 0);
+        assert_eq!(members.insert("c", 2), Some(2));
+        assert_eq!(members.len(), 4);
+
+        let mut counts = HashMap::new();
+        for key in 0..10_000 {
+            *counts.entry(*members.node_for(&key).unwrap()).or_insert(0) += 1;
+        }
+        // Roughly 2500, 2500 and 5000.
+        assert!((2200..2800).contains(&counts["a"]));
+        assert!((2200..2800).contains(&counts["b"]));
+        assert!((4700..5300).contains(&counts["c"]));
+        assert!(!counts.contains_key("idle"));
+
+        let owners = members.top_k(&7, 5);
+        assert_eq!(owners.len(), 3);
+        assert_eq!(owners[0], members.node_for(&7).unwrap());
+        assert!(members.top_k(&7, 0).is_empty());
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(nodes: Vec<(u8, u8)>, gone: u8, keys: Vec<u32>) -> bool {
+        let mut members = Rendezvous::new();
+        for (node, weight) in nodes {
+            members.insert(node, weight as u32 % 4);
+        }
+        let before: Vec<Vec<u8>> = keys
+            .iter()
+            .map(|key| members.top_k(key, 4).into_iter().copied().collect())
+            .collect();
+        // Dropping a member leaves everyone else's order as it was.
+        members.remove(&gone);
+        keys.iter().zip(before).all(|(key, was)| {
+            let now: Vec<u8> = members.top_k(key, 3).into_iter().copied().collect();
+            let expected: Vec<u8> = was.into_iter().filter(|&n| n != gone).take(3).collect();
+            now == expected && members.node_for(key) == now.first()
+        })
+    }
+}
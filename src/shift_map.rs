@@ -0,0 +1,432 @@
+//! A splay tree over `i64` keys which can shift every key from some point on
+//! in O(log n) amortized, for sequence indexes and editors which insert and
+//! remove blocks.
+//!
+//! Each node stores its key relative to its parent's (the root's is
+//! absolute), so moving a whole subtree only touches the link at its top.
+
+use alloc::vec::Vec;
+use core::cmp::Ordering::{Equal, Greater, Less};
+use core::iter::FusedIterator;
+use core::mem;
+
+struct Node<V> {
+    rel: i64,
+    value: V,
+    left: Option<usize>,
+    right: Option<usize>,
+    parent: Option<usize>,
+}
+
+pub struct ShiftMap<V> {
+    root: Option<usize>,
+    nodes: Vec<Node<V>>,
+}
+
+impl<V> ShiftMap<V> {
+    pub fn new() -> Self {
+        ShiftMap {
+            root: None,
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn is_left(&self, idx: usize) -> bool {
+        let parent = self.nodes[idx].parent.unwrap();
+        self.nodes[parent].left == Some(idx)
+    }
+
+    /// Moves `x` above its parent, preserving every absolute key.
+    fn rotate(&mut self, x: usize) {
+        let p = self.nodes[x].parent.unwrap();
+        let g = self.nodes[p].parent;
+        let rx = self.nodes[x].rel;
+        let inner = if self.is_left(x) {
+            let b = self.nodes[x].right;
+            self.nodes[p].left = b;
+            self.nodes[x].right = Some(p);
+            b
+        } else {
+            let b = self.nodes[x].left;
+            self.nodes[p].right = b;
+            self.nodes[x].left = Some(p);
+            b
+        };
+        if let Some(b) = inner {
+            self.nodes[b].parent = Some(p);
+            self.nodes[b].rel = self.nodes[b].rel.wrapping_add(rx);
+        }
+        self.nodes[x].rel = self.nodes[p].rel.wrapping_add(rx);
+        self.nodes[p].rel = rx.wrapping_neg();
+        self.nodes[p].parent = Some(x);
+        self.nodes[x].parent = g;
+        match g {
+            None => self.root = Some(x),
+            Some(g) if self.nodes[g].left == Some(p) => self.nodes[g].left = Some(x),
+            Some(g) => self.nodes[g].right = Some(x),
+        }
+    }
+
+    fn splay(&mut self, x: usize) {
+        while let Some(p) = self.nodes[x].parent {
+            if self.nodes[p].parent.is_some() {
+                if self.is_left(x) == self.is_left(p) {
+                    self.rotate(p);
+                } else {
+                    self.rotate(x);
+                }
+            }
+            self.rotate(x);
+        }
+    }
+
+    /// Descends towards `key`, returning the node holding it or else the
+    /// last one visited. Nothing is splayed.
+    fn seek(&self, key: i64) -> Option<(usize, Result<(), i64>)> {
+        let mut idx = self.root?;
+        let mut base = 0i64;
+        loop {
+            let here = base.wrapping_add(self.nodes[idx].rel);
+            let next = match key.cmp(&here) {
+                Equal => return Some((idx, Ok(()))),
+                Less => self.nodes[idx].left,
+                Greater => self.nodes[idx].right,
+            };
+            match next {
+                Some(next) => {
+                    base = here;
+                    idx = next;
+                }
+                None => return Some((idx, Err(here))),
+            }
+        }
+    }
+
+    fn root_value(&mut self) -> &mut V {
+        let root = self.root.unwrap();
+        &mut self.nodes[root].value
+    }
+
+    pub fn insert(&mut self, key: i64, value: V) -> Option<V> {
+        let Some((idx, found)) = self.seek(key) else {
+            self.nodes.push(Node {
+                rel: key,
+                value,
+                left: None,
+                right: None,
+                parent: None,
+            });
+            self.root = Some(0);
+            return None;
+        };
+        match found {
+            Ok(()) => {
+                self.splay(idx);
+                Some(mem::replace(self.root_value(), value))
+            }
+            Err(here) => {
+                let new = self.nodes.len();
+                self.nodes.push(Node {
+                    rel: key.wrapping_sub(here),
+                    value,
+                    left: None,
+                    right: None,
+                    parent: Some(idx),
+                });
+                if key < here {
+                    self.nodes[idx].left = Some(new);
+                } else {
+                    self.nodes[idx].right = Some(new);
+                }
+                self.splay(new);
+                None
+            }
+        }
+    }
+
+    pub fn get(&mut self, key: i64) -> Option<&V> {
+        let (idx, found) = self.seek(key)?;
+        self.splay(idx);
+        found.ok().map(|()| &*self.root_value())
+    }
+
+    pub fn get_mut(&mut self, key: i64) -> Option<&mut V> {
+        let (idx, found) = self.seek(key)?;
+        self.splay(idx);
+        found.ok().map(|()| self.root_value())
+    }
+
+    /// Detaches the subtree under `idx` as a tree of its own, turning its
+    /// relative key absolute.
+    fn detach(&mut self, idx: Option<usize>, base: i64) -> Option<usize> {
+        let idx = idx?;
+        let node = &mut self.nodes[idx];
+        node.parent = None;
+        node.rel = node.rel.wrapping_add(base);
+        Some(idx)
+    }
+
+    pub fn remove(&mut self, key: i64) -> Option<V> {
+        let (idx, found) = self.seek(key)?;
+        self.splay(idx);
+        found.ok()?;
+
+        let base = self.nodes[idx].rel;
+        let left = self.detach(self.nodes[idx].left, base);
+        let right = self.detach(self.nodes[idx].right, base);
+        self.root = left;
+        match left {
+            None => self.root = right,
+            Some(mut max) => {
+                while let Some(r) = self.nodes[max].right {
+                    max = r;
+                }
+                self.splay(max);
+                if let Some(r) = right {
+                    self.nodes[r].parent = Some(max);
+                    self.nodes[r].rel = self.nodes[r].rel.wrapping_sub(self.nodes[max].rel);
+                }
+                self.nodes[max].right = right;
+            }
+        }
+
+        // Keep the arena dense: the last node takes over the freed slot.
+        let node = self.nodes.swap_remove(idx);
+        let last = self.nodes.len();
+        if idx != last {
+            let moved = &self.nodes[idx];
+            let (parent, left, right) = (moved.parent, moved.left, moved.right);
+            match parent {
+                None => self.root = Some(idx),
+                Some(p) if self.nodes[p].left == Some(last) => self.nodes[p].left = Some(idx),
+                Some(p) => self.nodes[p].right = Some(idx),
+            }
+            for child in [left, right].into_iter().flatten() {
+                self.nodes[child].parent = Some(idx);
+            }
+        }
+        Some(node.value)
+    }
+
+    /// Adds `delta` to every key not less than `from`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a negative `delta` would move a shifted key onto or below a
+    /// key smaller than `from`, or if a shifted key overflows.
+    pub fn shift(&mut self, from: i64, delta: i64) {
+        let Some((idx, found)) = self.seek(from) else {
+            return;
+        };
+        self.splay(idx);
+        // The first shifted key is the root or, if the search ended just
+        // below `from`, the root's successor.
+        if matches!(found, Err(here) if here < from) {
+            let Some(mut first) = self.nodes[idx].right else {
+                return;
+            };
+            while let Some(l) = self.nodes[first].left {
+                first = l;
+            }
+            self.splay(first);
+        }
+        let first = self.root.unwrap();
+
+        // Both ends of the shifted keys have to fit, checked before any
+        // link changes.
+        let first_key = self.nodes[first].rel;
+        let (mut last, mut last_key) = (first, first_key);
+        while let Some(r) = self.nodes[last].right {
+            last = r;
+            last_key = last_key.wrapping_add(self.nodes[r].rel);
+        }
+        let shifted = first_key.checked_add(delta);
+        let (Some(shifted), Some(_)) = (shifted, last_key.checked_add(delta)) else {
+            panic!("shifted key overflows");
+        };
+
+        // With the largest smaller key splayed above it, `first` becomes its
+        // right child without a left subtree, so its link carries everything
+        // that moves.
+        let Some(mut pred) = self.nodes[first].left else {
+            self.nodes[first].rel = shifted;
+            return;
+        };
+        while let Some(r) = self.nodes[pred].right {
+            pred = r;
+        }
+        self.splay(pred);
+        let pred_key = self.nodes[pred].rel;
+        assert!(shifted > pred_key, "shift would reorder keys");
+        self.nodes[first].rel = shifted.wrapping_sub(pred_key);
+    }
+
+    pub fn iter(&self) -> Iter<'_, V> {
+        let mut iter = Iter {
+            map: self,
+            stack: Vec::new(),
+        };
+        iter.push_left(self.root, 0);
+        iter
+    }
+}
+
+impl<V> Default for ShiftMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entries of a [`ShiftMap`] in key order, with their absolute keys.
+pub struct Iter<'a, V> {
+    map: &'a ShiftMap<V>,
+    stack: Vec<(usize, i64)>,
+}
+
+impl<V> Iter<'_, V> {
+    fn push_left(&mut self, mut idx: Option<usize>, mut base: i64) {
+        while let Some(i) = idx {
+            base = base.wrapping_add(self.map.nodes[i].rel);
+            self.stack.push((i, base));
+            idx = self.map.nodes[i].left;
+        }
+    }
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (i64, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (idx, key) = self.stack.pop()?;
+        let node = &self.map.nodes[idx];
+        self.push_left(node.right, key);
+        Some((key, &node.value))
+    }
+}
+
+impl<V> FusedIterator for Iter<'_, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basic_test() {
+        let mut lines = ShiftMap::new();
+        for (i, line) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            lines.insert(i as i64 * 10, line);
+        }
+        lines.shift(15, 100);
+        let entries: Vec<_> = lines.iter().map(|(k, v)| (k, *v)).collect();
+        assert_eq!(entries, [(0, "a"), (10, "b"), (120, "c"), (130, "d")]);
+        assert_eq!(lines.get(120), Some(&"c"));
+
+        assert_eq!(lines.remove(10), Some("b"));
+        lines.shift(120, -119);
+        assert_eq!(lines.get(1), Some(&"c"));
+        assert_eq!(lines.get(11), Some(&"d"));
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "shift would reorder keys")]
+    fn reorder_test() {
+        let mut map: ShiftMap<()> = ShiftMap::new();
+        map.insert(0, ());
+        map.insert(5, ());
+        map.shift(5, -5);
+    }
+
+    #[test]
+    fn overflow_test() {
+        let mut map = ShiftMap::new();
+        map.insert(1, "a");
+        map.insert(i64::MAX, "b");
+        let shift = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| map.shift(1, 1)));
+        assert!(shift.is_err());
+        // Nothing moved.
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            [(1, &"a"), (i64::MAX, &"b")]
+        );
+        map.shift(2, -1);
+        assert_eq!(map.get(i64::MAX - 1), Some(&"b"));
+
+        map.insert(i64::MIN, "c");
+        let shift =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| map.shift(i64::MIN, -1)));
+        assert!(shift.is_err());
+        map.shift(i64::MIN, 1);
+        assert_eq!(map.get(i64::MIN + 1), Some(&"c"));
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Insert(i8),
+        Remove(i8),
+        Get(i8),
+        Shift(i8, i8),
+    }
+
+    impl quickcheck::Arbitrary for Op {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            let key = i8::arbitrary(g) / 4;
+            match u8::arbitrary(g) % 4 {
+                0 => Op::Insert(key),
+                1 => Op::Remove(key),
+                2 => Op::Get(key),
+                _ => Op::Shift(key, i8::arbitrary(g) / 8),
+            }
+        }
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<Op>) -> bool {
+        let mut map = ShiftMap::new();
+        let mut model = BTreeMap::new();
+        for (i, op) in ops.into_iter().enumerate() {
+            match op {
+                Op::Insert(k) => {
+                    if map.insert(k as i64, i) != model.insert(k as i64, i) {
+                        return false;
+                    }
+                }
+                Op::Remove(k) => {
+                    if map.remove(k as i64) != model.remove(&(k as i64)) {
+                        return false;
+                    }
+                }
+                Op::Get(k) => {
+                    if map.get(k as i64) != model.get(&(k as i64)) {
+                        return false;
+                    }
+                }
+                Op::Shift(from, delta) => {
+                    let (from, delta) = (from as i64, delta as i64);
+                    let moved = model.split_off(&from);
+                    let below = model.last_key_value().map(|(&k, _)| k);
+                    let first = moved.first_key_value().map(|(&k, _)| k);
+                    if let (Some(below), Some(first)) = (below, first) {
+                        if first + delta <= below {
+                            model.extend(moved);
+                            continue;
+                        }
+                    }
+                    model.extend(moved.into_iter().map(|(k, v)| (k + delta, v)));
+                    map.shift(from, delta);
+                }
+            }
+        }
+        map.iter().map(|(k, &v)| (k, v)).eq(model.into_iter())
+    }
+}
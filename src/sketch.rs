@@ -0,0 +1,10 @@
+//! Streaming summaries which answer approximately in a fraction of the
+//! space the whole stream would take.
+
+mod minhash;
+mod reservoir;
+mod tdigest;
+
+pub use minhash::MinHash;
+pub use reservoir::ReservoirSampler;
+pub use tdigest::TDigest;
@@ -0,0 +1,153 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::rng::{mix64, split_mix64};
+
+/// A MinHash signature of a set, for estimating Jaccard similarity.
+///
+/// Each of the `k` entries is the minimum over the set of one permutation
+/// of the items' hashes, and two sets agree on an entry with probability
+/// equal to their Jaccard similarity. Signatures are only comparable when
+/// built with the same `k` and seed; item hashes use a fixed key for that
+/// reason, so they aren't safe against crafted inputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MinHash {
+    seed: u64,
+    // One key per permutation, `mix64(hash ^ key)`.
+    keys: Vec<u64>,
+    signature: Vec<u64>,
+}
+
+impl MinHash {
+    pub fn new(k: usize) -> Self {
+        Self::with_seed(k, 0)
+    }
+
+    pub fn with_seed(k: usize, seed: u64) -> Self {
+        let mut state = seed;
+        MinHash {
+            seed,
+            keys: (0..k).map(|_| split_mix64(&mut state)).collect(),
+            signature: vec![u64::MAX; k],
+        }
+    }
+
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        let hash = hasher.finish();
+        for (min, key) in self.signature.iter_mut().zip(&self.keys) {
+            *min = (*min).min(mix64(hash ^ key));
+        }
+    }
+
+    pub fn signature(&self) -> &[u64] {
+        &self.signature
+    }
+
+    fn check_compatible(&self, other: &MinHash) {
+        assert!(
+            self.seed == other.seed && self.keys.len() == other.keys.len(),
+            "signatures use different permutations"
+        );
+    }
+
+    /// Estimated Jaccard similarity, the share of entries which agree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the signatures differ in `k` or seed.
+    pub fn jaccard(&self, other: &MinHash) -> f64 {
+        self.check_compatible(other);
+        let same = self
+            .signature
+            .iter()
+            .zip(&other.signature)
+            .filter(|(a, b)| a == b)
+            .count();
+        same as f64 / self.signature.len() as f64
+    }
+
+    /// Turns this into the signature of the union of both sets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the signatures differ in `k` or seed.
+    pub fn merge(&mut self, other: &MinHash) {
+        self.check_compatible(other);
+        for (min, &theirs) in self.signature.iter_mut().zip(&other.signature) {
+            *min = (*min).min(theirs);
+        }
+    }
+
+    /// Hashes each band of `rows` consecutive entries, for locality
+    /// sensitive hashing: sets which share any band's hash are candidate
+    /// near duplicates. Bands are numbered into their hash, so equal
+    /// buckets only match for the same band.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rows` is zero or doesn't divide `k`.
+    pub fn bands(&self, rows: usize) -> impl Iterator<Item = u64> + '_ {
+        assert!(
+            rows > 0 && self.signature.len().is_multiple_of(rows),
+            "rows have to divide the signature into equal bands"
+        );
+        self.signature.chunks(rows).enumerate().map(|(band, rows)| {
+            let mut hasher = DefaultHasher::new();
+            (band, rows).hash(&mut hasher);
+            hasher.finish()
+        })
+    }
+
+    /// The similarity at which sets become likely candidates with `bands`
+    /// bands of `rows` rows, about `(1/bands)^(1/rows)`.
+    pub fn threshold(bands: usize, rows: usize) -> f64 {
+        (1.0 / bands as f64).powf(1.0 / rows as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(items: impl IntoIterator<Item = u32>) -> MinHash {
+        let mut minhash = MinHash::new(256);
+        for item in items {
+            minhash.insert(&item);
+        }
+        minhash
+    }
+
+    #[test]
+    fn jaccard_test() {
+        let a = signature(0..150);
+        let b = signature(50..200);
+        assert!((a.jaccard(&b) - 0.5).abs() < 0.1, "{}", a.jaccard(&b));
+        assert_eq!(a.jaccard(&signature(0..150)), 1.0);
+        assert!(a.jaccard(&signature(1000..1150)) < 0.05);
+
+        let mut union = a.clone();
+        union.merge(&b);
+        assert_eq!(union, signature(0..200));
+    }
+
+    #[test]
+    fn bands_test() {
+        let a = signature(0..100);
+        let near = signature(0..99);
+        let far = signature(500..600);
+        let shared =
+            |x: &MinHash, y: &MinHash| x.bands(4).zip(y.bands(4)).filter(|(p, q)| p == q).count();
+        assert_eq!(a.bands(4).count(), 64);
+        assert!(shared(&a, &near) > 32);
+        assert_eq!(shared(&a, &far), 0);
+        assert!((MinHash::threshold(64, 4) - 0.3536).abs() < 0.001);
+    }
+
+    #[test]
+    #[should_panic(expected = "different permutations")]
+    fn incompatible_test() {
+        MinHash::new(8).jaccard(&MinHash::with_seed(8, 1));
+    }
+}
@@ -0,0 +1,203 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::RandomState;
+use std::collections::BinaryHeap;
+use std::hash::BuildHasher;
+
+use crate::rng::split_mix64;
+
+struct Keyed<T> {
+    key: f64,
+    item: T,
+}
+
+impl<T> PartialEq for Keyed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Keyed<T> {}
+
+impl<T> PartialOrd for Keyed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Keyed<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.total_cmp(&other.key)
+    }
+}
+
+/// A random sample of at most `capacity` items from a stream, with the
+/// A-Res algorithm of Efraimidis and Spirakis.
+///
+/// Every item gets the key `u^(1/w)` for a uniform `u` and its weight `w`,
+/// and the sample is the items with the largest keys. With equal weights
+/// that's a uniform sample. Since keys don't depend on the rest of the
+/// stream, samples of disjoint streams merge into a sample of both.
+pub struct ReservoirSampler<T> {
+    capacity: usize,
+    // Smallest key on top, the first to go.
+    heap: BinaryHeap<Reverse<Keyed<T>>>,
+    seen: u64,
+    rng: u64,
+}
+
+impl<T> ReservoirSampler<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_seed(capacity, RandomState::new().hash_one(0))
+    }
+
+    /// A sampler whose choices only depend on `seed` and the stream.
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        ReservoirSampler {
+            capacity,
+            heap: BinaryHeap::with_capacity(capacity),
+            seen: 0,
+            rng: seed,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of items offered so far.
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    /// Offers an item with the same chance as every other unweighted one.
+    pub fn add(&mut self, item: T) {
+        self.add_weighted(item, 1.0);
+    }
+
+    /// Offers an item, more likely to be kept the higher its weight.
+    /// Items of weight zero are never kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weight` is negative or NaN.
+    pub fn add_weighted(&mut self, item: T, weight: f64) {
+        assert!(weight >= 0.0, "weights can't be negative");
+        self.seen += 1;
+        if weight == 0.0 {
+            return;
+        }
+        // `ln(u) / w` orders like `u^(1/w)` without underflowing.
+        let u = ((split_mix64(&mut self.rng) >> 11) + 1) as f64 / (1u64 << 53) as f64;
+        self.offer(Keyed {
+            key: u.ln() / weight,
+            item,
+        });
+    }
+
+    fn offer(&mut self, keyed: Keyed<T>) {
+        if self.heap.len() < self.capacity {
+            self.heap.push(Reverse(keyed));
+        } else if let Some(mut smallest) = self.heap.peek_mut() {
+            if keyed > smallest.0 {
+                smallest.0 = keyed;
+            }
+        }
+    }
+
+    /// Folds in the sample of another stream, as if its items had been
+    /// offered here.
+    pub fn merge(&mut self, other: ReservoirSampler<T>) {
+        self.seen += other.seen;
+        for Reverse(keyed) in other.heap {
+            self.offer(keyed);
+        }
+    }
+
+    /// The sampled items, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.heap.iter().map(|Reverse(keyed)| &keyed.item)
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.heap
+            .into_iter()
+            .map(|Reverse(keyed)| keyed.item)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_test() {
+        let mut sampler = ReservoirSampler::with_seed(10, 1);
+        for i in 0..5 {
+            sampler.add(i);
+        }
+        assert_eq!(sampler.len(), 5);
+        for i in 5..1000 {
+            sampler.add(i);
+        }
+        assert_eq!((sampler.len(), sampler.seen()), (10, 1000));
+        let mut sample = sampler.into_vec();
+        sample.sort_unstable();
+        sample.dedup();
+        assert_eq!(sample.len(), 10);
+    }
+
+    #[test]
+    fn uniform_test() {
+        // Each of 10 items should be picked in about 3 of 10 samples.
+        let mut hits = [0; 10];
+        for seed in 0..10_000 {
+            let mut sampler = ReservoirSampler::with_seed(3, seed);
+            for i in 0..10 {
+                sampler.add(i);
+            }
+            sampler.iter().for_each(|&i| hits[i] += 1);
+        }
+        assert!(hits.iter().all(|&n| (2700..3300).contains(&n)), "{hits:?}");
+    }
+
+    #[test]
+    fn weighted_test() {
+        let mut heavy = 0;
+        for seed in 0..1000 {
+            let mut sampler = ReservoirSampler::with_seed(1, seed);
+            sampler.add_weighted("light", 1.0);
+            sampler.add_weighted("heavy", 9.0);
+            sampler.add_weighted("never", 0.0);
+            match sampler.into_vec()[..] {
+                ["heavy"] => heavy += 1,
+                ["light"] => {}
+                ref other => panic!("{other:?}"),
+            }
+        }
+        assert!((850..950).contains(&heavy), "{heavy}");
+    }
+
+    #[test]
+    fn merge_test() {
+        let mut hits = [0; 4];
+        for seed in 0..4000 {
+            let mut left = ReservoirSampler::with_seed(1, seed);
+            let mut right = ReservoirSampler::with_seed(1, seed + 1_000_000);
+            left.add(0);
+            (1..4).for_each(|i| right.add(i));
+            left.merge(right);
+            assert_eq!(left.seen(), 4);
+            left.iter().for_each(|&i| hits[i] += 1);
+        }
+        assert!(hits.iter().all(|&n| (850..1150).contains(&n)), "{hits:?}");
+    }
+}
@@ -0,0 +1,227 @@
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// The merging t-digest of Dunning, for quantiles of a stream of samples.
+///
+/// Samples are summarised by centroids, a mean and a weight each, kept small
+/// near the tails so extreme quantiles stay accurate. New samples are
+/// buffered and merged in batches. `compression` bounds the number of
+/// centroids at about twice its value; 100 keeps the error of p99 well
+/// under a percent.
+#[derive(Clone, Debug)]
+pub struct TDigest {
+    compression: f64,
+    // Sorted by mean.
+    centroids: Vec<Centroid>,
+    buffer: Vec<Centroid>,
+    samples: u64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    /// # Panics
+    ///
+    /// Panics if `compression` isn't positive.
+    pub fn new(compression: f64) -> Self {
+        assert!(compression > 0.0, "compression must be positive");
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            samples: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples == 0
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (!self.is_empty()).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (!self.is_empty()).then_some(self.max)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `sample` is NaN.
+    pub fn add(&mut self, sample: f64) {
+        assert!(!sample.is_nan(), "can't add NaN to a t-digest");
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+        self.samples += 1;
+        self.push(Centroid {
+            mean: sample,
+            weight: 1.0,
+        });
+    }
+
+    fn push(&mut self, centroid: Centroid) {
+        self.buffer.push(centroid);
+        if self.buffer.len() as f64 >= 5.0 * self.compression {
+            self.flush();
+        }
+    }
+
+    /// The scale function k1: centroids may span one unit of it, which is
+    /// a narrow range of quantiles near 0 and 1.
+    fn scale(&self, q: f64) -> f64 {
+        self.compression / (2.0 * PI) * (2.0 * q - 1.0).clamp(-1.0, 1.0).asin()
+    }
+
+    /// Merges the buffer into the centroids.
+    fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut all = std::mem::take(&mut self.buffer);
+        all.append(&mut self.centroids);
+        all.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+        let total: f64 = all.iter().map(|c| c.weight).sum();
+
+        let mut merged = Vec::with_capacity(all.len());
+        let mut before = 0.0;
+        let mut current = all[0];
+        for &next in &all[1..] {
+            let q = (before + current.weight + next.weight) / total;
+            if self.scale(q) - self.scale(before / total) <= 1.0 {
+                let weight = current.weight + next.weight;
+                current.mean += (next.mean - current.mean) * next.weight / weight;
+                current.weight = weight;
+            } else {
+                before += current.weight;
+                merged.push(current);
+                current = next;
+            }
+        }
+        merged.push(current);
+        self.centroids = merged;
+    }
+
+    /// Estimates the value below which a fraction `q` of the samples falls,
+    /// interpolating between centroids. `None` if there are no samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` isn't within `0.0..=1.0`.
+    pub fn quantile(&mut self, q: f64) -> Option<f64> {
+        assert!((0.0..=1.0).contains(&q), "quantile must be within 0..=1");
+        self.flush();
+        let first = self.centroids.first()?;
+        let last = self.centroids.last()?;
+        let total: f64 = self.centroids.iter().map(|c| c.weight).sum();
+        let target = q * total;
+
+        // Each centroid sits at the middle of its weight.
+        if target < first.weight / 2.0 {
+            let t = target / (first.weight / 2.0);
+            return Some(self.min + (first.mean - self.min) * t);
+        }
+        if target > total - last.weight / 2.0 {
+            let t = (total - target) / (last.weight / 2.0);
+            return Some(self.max - (self.max - last.mean) * t);
+        }
+        let mut center = first.weight / 2.0;
+        for pair in self.centroids.windows(2) {
+            let next = center + (pair[0].weight + pair[1].weight) / 2.0;
+            if target <= next {
+                let t = (target - center) / (next - center);
+                return Some(pair[0].mean + (pair[1].mean - pair[0].mean) * t);
+            }
+            center = next;
+        }
+        Some(last.mean)
+    }
+
+    /// Adds every sample summarised by `other`.
+    pub fn merge(&mut self, other: &TDigest) {
+        if other.is_empty() {
+            return;
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.samples += other.samples;
+        for &centroid in other.centroids.iter().chain(&other.buffer) {
+            self.push(centroid);
+        }
+    }
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self::new(100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn basic_test() {
+        let mut digest = TDigest::default();
+        assert_eq!(digest.quantile(0.5), None);
+        let mut samples: Vec<f64> = (0..100_000).map(|i| i as f64).collect();
+        samples.shuffle(&mut rand::rng());
+        for &sample in &samples {
+            digest.add(sample);
+        }
+        assert_eq!(digest.quantile(0.0), Some(0.0));
+        assert_eq!(digest.quantile(1.0), Some(99_999.0));
+        for (q, tolerance) in [(0.5, 500.0), (0.99, 100.0), (0.999, 50.0)] {
+            let estimate = digest.quantile(q).unwrap();
+            let exact = q * 99_999.0;
+            assert!((estimate - exact).abs() < tolerance, "{q}: {estimate}");
+        }
+        assert!(digest.centroids.len() <= 200);
+    }
+
+    #[test]
+    fn merge_test() {
+        let mut low = TDigest::new(50.0);
+        let mut high = TDigest::new(50.0);
+        for i in 0..1000 {
+            low.add(i as f64);
+            high.add((i + 1000) as f64);
+        }
+        low.merge(&high);
+        assert_eq!(low.len(), 2000);
+        assert_eq!((low.min(), low.max()), (Some(0.0), Some(1999.0)));
+        assert!((low.quantile(0.5).unwrap() - 1000.0).abs() < 20.0);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(samples: Vec<i16>, q: u8) -> bool {
+        let mut digest = TDigest::new(20.0);
+        for &sample in &samples {
+            digest.add(sample as f64);
+        }
+        let q = q as f64 / 255.0;
+        match digest.quantile(q) {
+            None => samples.is_empty(),
+            Some(estimate) => {
+                // Always within the range, and monotone in `q`.
+                let lower = digest.quantile(q * 0.5).unwrap();
+                digest.min().unwrap() <= lower
+                    && lower <= estimate
+                    && estimate <= digest.max().unwrap()
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! A map keyed by strings which keeps the short ones inline, for very many
+//! small keys.
+
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::mem;
+use core::ptr::NonNull;
+use core::slice;
+use core::str;
+
+use crate::splay::Splay;
+
+/// The longest key kept inline, which makes a key as big as a `String`.
+pub const INLINE: usize = 22;
+const CHUNK: usize = 4096;
+
+/// A key of a [`SmallStrMap`]: its bytes in place, or a pointer into one of
+/// the map's chunks, which only the map can make and which never leaves it.
+enum Key {
+    Inline { len: u8, bytes: [u8; INLINE] },
+    Spilled { ptr: NonNull<u8>, len: usize },
+}
+
+// SAFETY: a spilled key only reads bytes owned by its map, which nothing
+// writes while the key is in it, so it's as good as a `&str` into the map.
+unsafe impl Send for Key {}
+unsafe impl Sync for Key {}
+
+impl Key {
+    fn as_str(&self) -> &str {
+        let bytes = match self {
+            Key::Inline { len, bytes } => &bytes[..*len as usize],
+            // SAFETY: the chunk outlives the key and those bytes don't
+            // change, see `SmallStrMap::key`.
+            Key::Spilled { ptr, len } => unsafe { slice::from_raw_parts(ptr.as_ptr(), *len) },
+        };
+        // SAFETY: the bytes were copied from a `str`.
+        unsafe { str::from_utf8_unchecked(bytes) }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// A map from strings to `V` on a [`Splay`], which keeps keys of up to
+/// [`INLINE`] bytes in the node itself and the longer ones in chunks of
+/// bytes the map shares out between them.
+///
+/// A `Splay<String, V>` allocates each key on its own and reads it through
+/// a pointer in every comparison; here short keys cost neither, and long
+/// ones one allocation per few kilobytes. The bytes of removed long keys
+/// stay in their chunk until they're about half the total, when the map
+/// copies the live keys into fresh chunks, in O(n) amortized over the
+/// removes.
+pub struct SmallStrMap<V> {
+    tree: Splay<Key, V>,
+    // Never grown past their capacity, so the bytes in them don't move.
+    chunks: Vec<Vec<u8>>,
+    // Bytes of long keys in the chunks, and how many are of removed ones.
+    spilled: usize,
+    garbage: usize,
+}
+
+impl<V> SmallStrMap<V> {
+    pub fn new() -> Self {
+        SmallStrMap {
+            tree: Splay::new(),
+            chunks: Vec::new(),
+            spilled: 0,
+            garbage: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// The bytes taken up in the chunks by long keys, removed ones included.
+    pub fn spilled_bytes(&self) -> usize {
+        self.spilled
+    }
+
+    fn key(&mut self, s: &str) -> Key {
+        if s.len() <= INLINE {
+            let mut bytes = [0; INLINE];
+            bytes[..s.len()].copy_from_slice(s.as_bytes());
+            return Key::Inline {
+                len: s.len() as u8,
+                bytes,
+            };
+        }
+        let fits = self
+            .chunks
+            .last()
+            .is_some_and(|chunk| chunk.capacity() - chunk.len() >= s.len());
+        if !fits {
+            self.chunks.push(Vec::with_capacity(s.len().max(CHUNK)));
+        }
+        let chunk = self.chunks.last_mut().unwrap();
+        let start = chunk.len();
+        // Within capacity, so the chunk isn't moved and its bytes so far
+        // are left alone.
+        chunk.extend_from_slice(s.as_bytes());
+        self.spilled += s.len();
+        Key::Spilled {
+            ptr: NonNull::from(&chunk[start..]).cast(),
+            len: s.len(),
+        }
+    }
+
+    /// Inserts or updates an entry. Only a new key is copied in.
+    pub fn insert(&mut self, key: &str, value: V) -> Option<V> {
+        if let Some(old) = self.tree.get_mut(key) {
+            return Some(mem::replace(old, value));
+        }
+        let key = self.key(key);
+        self.tree.set(key, value);
+        None
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<&V> {
+        self.tree.get(key)
+    }
+
+    /// Looks `key` up without splaying.
+    pub fn peek(&self, key: &str) -> Option<&V> {
+        self.tree.peek(key)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        self.tree.get_mut(key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.tree.contains_key(key)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let value = self.tree.remove(key)?;
+        if key.len() > INLINE {
+            self.garbage += key.len();
+            if self.garbage > CHUNK && 2 * self.garbage > self.spilled {
+                self.compact();
+            }
+        }
+        Some(value)
+    }
+
+    /// Copies the live long keys into fresh chunks and frees the old ones.
+    fn compact(&mut self) {
+        let old = mem::take(&mut self.chunks);
+        self.spilled = 0;
+        self.garbage = 0;
+        let entries: Vec<(Key, V)> = mem::take(&mut self.tree)
+            .into_iter()
+            .map(|(key, value)| (self.key(key.as_str()), value))
+            .collect();
+        // Still in order, so this doesn't compare them again.
+        self.tree = entries.into_iter().collect();
+        drop(old);
+    }
+
+    /// The entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &V)> {
+        self.tree.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.chunks.clear();
+        self.spilled = 0;
+        self.garbage = 0;
+    }
+}
+
+impl<V> Default for SmallStrMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Clone> Clone for SmallStrMap<V> {
+    fn clone(&self) -> Self {
+        // Not field by field, since the keys point into these chunks.
+        self.iter()
+            .map(|(key, value)| (key, value.clone()))
+            .collect()
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for SmallStrMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<S: AsRef<str>, V> Extend<(S, V)> for SmallStrMap<V> {
+    fn extend<I: IntoIterator<Item = (S, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key.as_ref(), value);
+        }
+    }
+}
+
+impl<S: AsRef<str>, V> FromIterator<(S, V)> for SmallStrMap<V> {
+    fn from_iter<I: IntoIterator<Item = (S, V)>>(iter: I) -> Self {
+        let mut map = SmallStrMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use alloc::string::String;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        assert_eq!(mem::size_of::<Key>(), mem::size_of::<String>());
+        let long = "a key well past the inline length";
+        let mut map = SmallStrMap::new();
+        assert_eq!(map.insert("short", 1), None);
+        assert_eq!(map.insert(long, 2), None);
+        assert_eq!(map.insert("", 3), None);
+        assert_eq!(map.insert(long, 4), Some(2));
+        assert_eq!(map.spilled_bytes(), long.len());
+        assert_eq!(map.get(long), Some(&4));
+        assert_eq!(map.peek("short"), Some(&1));
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["", long, "short"]);
+
+        let copy = map.clone();
+        assert_eq!(map.remove(long), Some(4));
+        assert!(!map.contains_key(long));
+        assert_eq!(copy.peek(long), Some(&4));
+
+        // Removing most of many long keys frees their chunks.
+        let keys: Vec<String> = (0..200).map(|i| alloc::format!("{long} {i:03}")).collect();
+        map.extend(keys.iter().map(|key| (key, 0)));
+        for key in &keys[..180] {
+            map.remove(key);
+        }
+        assert!(map.spilled_bytes() < 100 * keys[0].len());
+        assert!(keys[180..].iter().all(|key| map.contains_key(key)));
+        assert_eq!(map.len(), 22);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8, u8)>) -> bool {
+        let mut map = SmallStrMap::new();
+        let mut model = BTreeMap::new();
+        for (i, (op, key, len)) in ops.into_iter().enumerate() {
+            // Keys either side of the inline length, some sharing prefixes.
+            let key: String = core::iter::repeat_n(char::from(b'a' + key % 4), len as usize % 40)
+                .chain(char::from_digit(key as u32 % 3, 10))
+                .collect();
+            let ok = match op % 4 {
+                0 | 1 => map.insert(&key, i) == model.insert(key, i),
+                2 => map.get(&key) == model.get(&key),
+                _ => map.remove(&key) == model.remove(&key),
+            };
+            if !ok || map.len() != model.len() {
+                return false;
+            }
+        }
+        map.iter().eq(model.iter().map(|(k, v)| (k.as_str(), v)))
+    }
+}
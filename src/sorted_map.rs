@@ -0,0 +1,237 @@
+//! A trait over the crate's ordered maps, so code can take its balancing
+//! strategy as a type parameter:
+//!
+//! ```
+//! use crab_bucket::sorted_map::SortedMap;
+//! use crab_bucket::splay::Splay;
+//! use crab_bucket::versioned_map::VersionedMap;
+//!
+//! fn top_scores<M: SortedMap<u32, &'static str> + Default>() -> Vec<u32> {
+//!     let mut scores = M::default();
+//!     scores.insert(70, "b");
+//!     scores.insert(90, "a");
+//!     scores.insert(50, "c");
+//!     scores.range(60..).map(|(&score, _)| score).collect()
+//! }
+//!
+//! assert_eq!(top_scores::<Splay<_, _>>(), [70, 90]);
+//! assert_eq!(top_scores::<VersionedMap<_, _>>(), [70, 90]);
+//! ```
+//!
+//! It's `SortedMap` rather than `OrderedMap`, which is the hash map in
+//! [`ordered_map`](crate::ordered_map) keeping insertion order.
+
+use alloc::collections::btree_map::{self, BTreeMap};
+use core::ops::RangeBounds;
+
+use crate::compare::Natural;
+use crate::splay::{self, Allocator, Splay, SplayIter};
+use crate::versioned_map::{self, VersionedMap};
+
+/// A map keeping its keys in order.
+///
+/// `get` takes `&mut self` so that self-adjusting maps can restructure on
+/// reads; the others just look the key up.
+pub trait SortedMap<K: Ord, V> {
+    type Iter<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+    type Range<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V>;
+
+    fn contains_key(&mut self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts or updates an entry, returning the previous value for `key`.
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+
+    fn remove(&mut self, key: &K) -> Option<V>;
+
+    /// The entries in key order.
+    fn iter(&self) -> Self::Iter<'_>;
+
+    /// The entries within `range` in key order.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same malformed ranges as `BTreeMap::range`.
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Self::Range<'_>;
+}
+
+impl<K: Ord, V, A: Allocator> SortedMap<K, V> for Splay<K, V, Natural, A> {
+    type Iter<'a>
+        = SplayIter<'a, K, V, Natural, A>
+    where
+        Self: 'a;
+    type Range<'a>
+        = splay::Range<'a, K, V, Natural, A>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize {
+        Splay::len(self)
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        Splay::get(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        Splay::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        Splay::remove(self, key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        Splay::iter(self)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Self::Range<'_> {
+        Splay::range(self, range)
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> SortedMap<K, V> for VersionedMap<K, V> {
+    type Iter<'a>
+        = versioned_map::Iter<'a, K, V>
+    where
+        Self: 'a;
+    type Range<'a>
+        = versioned_map::Range<'a, K, V>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize {
+        VersionedMap::len(self)
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        VersionedMap::get(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        VersionedMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        VersionedMap::remove(self, key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        VersionedMap::iter(self)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Self::Range<'_> {
+        VersionedMap::range(self, range)
+    }
+}
+
+impl<K: Ord, V> SortedMap<K, V> for BTreeMap<K, V> {
+    type Iter<'a>
+        = btree_map::Iter<'a, K, V>
+    where
+        Self: 'a;
+    type Range<'a>
+        = btree_map::Range<'a, K, V>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        BTreeMap::insert(self, key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        BTreeMap::remove(self, key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        BTreeMap::iter(self)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Self::Range<'_> {
+        BTreeMap::range(self, range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use quickcheck_macros::quickcheck;
+
+    fn basic<M: SortedMap<&'static str, i32> + Default>() {
+        let mut map = M::default();
+        assert!(map.is_empty());
+        assert_eq!(map.insert("b", 2), None);
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("c", 3), None);
+        assert_eq!(map.insert("b", 20), Some(2));
+        assert_eq!(map.get(&"b"), Some(&20));
+        assert_eq!(map.remove(&"a"), Some(1));
+        assert!(!map.contains_key(&"a"));
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&"b", &20), (&"c", &3)]);
+        assert_eq!(map.range(.."c").collect::<Vec<_>>(), [(&"b", &20)]);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn basic_test() {
+        basic::<Splay<_, _>>();
+        basic::<VersionedMap<_, _>>();
+        basic::<BTreeMap<_, _>>();
+    }
+
+    /// Runs `ops` on an `M` and a `BTreeMap`, checking that they agree on
+    /// every answer.
+    fn differential<M: SortedMap<u8, usize> + Default>(ops: &[(u8, u8, u8)]) -> bool {
+        let mut map = M::default();
+        let mut model = BTreeMap::new();
+        for (i, &(op, a, b)) in ops.iter().enumerate() {
+            let (a, b) = (a % 32, b % 32);
+            let ok = match op % 5 {
+                0 | 1 => map.insert(a, i) == model.insert(a, i),
+                2 => map.remove(&a) == model.remove(&a),
+                3 => map.get(&a) == model.get(&a),
+                _ => {
+                    let range = a.min(b)..=a.max(b);
+                    map.range(range.clone()).eq(model.range(range))
+                }
+            };
+            if !ok || map.len() != model.len() {
+                return false;
+            }
+        }
+        map.iter().eq(model.iter())
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8, u8)>) -> bool {
+        differential::<Splay<_, _>>(&ops)
+            && differential::<VersionedMap<_, _>>(&ops)
+            && differential::<BTreeMap<_, _>>(&ops)
+    }
+}
@@ -0,0 +1,15 @@
+//! Indexes over points in space, for lookups by distance or region which
+//! don't fit a one-dimensional order.
+
+mod kd_tree;
+mod region_tree;
+mod z_order;
+
+pub use kd_tree::{KdTree, KdTreeRange};
+pub use region_tree::{Key, Octree, Quadtree, RegionTree, RegionTreeNeighbors, RegionTreeQuery};
+pub use z_order::{morton_decode, morton_encode, ZOrderMap, ZOrderQuery};
+
+/// The squared Euclidean distance between `a` and `b`.
+fn distance2<const D: usize>(a: &[f64; D], b: &[f64; D]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
@@ -0,0 +1,336 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use super::distance2;
+
+const NONE: usize = usize::MAX;
+
+#[derive(Clone, Debug)]
+struct Node<const D: usize, V> {
+    point: [f64; D],
+    value: V,
+    left: usize,
+    right: usize,
+}
+
+/// A k-d tree over points in `D` dimensions, each with a value.
+///
+/// Nodes at depth `d` split space on axis `d % D`: points below the split
+/// are on the left, points above it on the right, ties on either side.
+/// [`KdTree::build`] splits at the median so the tree is balanced; `insert` adds leaves and doesn't rebalance, so a tree
+/// grown from sorted points degrades towards a list. Rebuild it then.
+#[derive(Clone, Debug)]
+pub struct KdTree<const D: usize, V = ()> {
+    nodes: Vec<Node<D, V>>,
+    root: usize,
+}
+
+/// A point found at some squared distance, ordered by the distance.
+struct Candidate {
+    distance2: f64,
+    idx: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance2.total_cmp(&other.distance2)
+    }
+}
+
+impl<const D: usize, V> KdTree<D, V> {
+    pub fn new() -> Self {
+        const { assert!(D > 0, "a k-d tree needs at least one dimension") };
+        KdTree {
+            nodes: Vec::new(),
+            root: NONE,
+        }
+    }
+
+    /// Builds a balanced tree in O(n log n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if a coordinate is NaN.
+    pub fn build<I: IntoIterator<Item = ([f64; D], V)>>(points: I) -> Self {
+        let mut tree = Self::new();
+        tree.nodes = points
+            .into_iter()
+            .map(|(point, value)| Node::new(point, value))
+            .collect();
+        let mut order: Vec<usize> = (0..tree.nodes.len()).collect();
+        tree.root = build(&mut tree.nodes, &mut order, 0);
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a coordinate is NaN.
+    pub fn insert(&mut self, point: [f64; D], value: V) {
+        let idx = self.nodes.len();
+        self.nodes.push(Node::new(point, value));
+        let (mut x, mut depth) = (self.root, 0);
+        if x == NONE {
+            self.root = idx;
+            return;
+        }
+        loop {
+            let node = &mut self.nodes[x];
+            let link = if point[depth % D] < node.point[depth % D] {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+            if *link == NONE {
+                *link = idx;
+                return;
+            }
+            x = *link;
+            depth += 1;
+        }
+    }
+
+    /// The closest point to `point` and its value.
+    pub fn nearest(&self, point: &[f64; D]) -> Option<(&[f64; D], &V)> {
+        self.nearest_k(point, 1).pop()
+    }
+
+    /// The `k` closest points to `point`, closest first. Ties at the cut
+    /// are broken arbitrarily.
+    pub fn nearest_k(&self, point: &[f64; D], k: usize) -> Vec<(&[f64; D], &V)> {
+        if k == 0 {
+            return Vec::new();
+        }
+        // The best so far, farthest on top.
+        let mut best: BinaryHeap<Candidate> = BinaryHeap::with_capacity(k + 1);
+        // Subtrees still to visit, each with a lower bound on how close its
+        // points can be.
+        let mut stack = Vec::new();
+        if self.root != NONE {
+            stack.push((self.root, 0, 0.0));
+        }
+        while let Some((x, depth, bound)) = stack.pop() {
+            if best.len() == k && best.peek().is_some_and(|worst| bound >= worst.distance2) {
+                continue;
+            }
+            let node = &self.nodes[x];
+            best.push(Candidate {
+                distance2: distance2(&node.point, point),
+                idx: x,
+            });
+            if best.len() > k {
+                best.pop();
+            }
+            let diff = point[depth % D] - node.point[depth % D];
+            let (near, far) = if diff < 0.0 {
+                (node.left, node.right)
+            } else {
+                (node.right, node.left)
+            };
+            // The far side is pushed first so that the near one goes next.
+            if far != NONE {
+                stack.push((far, depth + 1, diff * diff));
+            }
+            if near != NONE {
+                stack.push((near, depth + 1, bound));
+            }
+        }
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|candidate| {
+                let node = &self.nodes[candidate.idx];
+                (&node.point, &node.value)
+            })
+            .collect()
+    }
+
+    /// The points within the box from `min` to `max`, bounds included, in
+    /// no particular order.
+    pub fn range<'a>(&'a self, min: &'a [f64; D], max: &'a [f64; D]) -> KdTreeRange<'a, D, V> {
+        let mut stack = Vec::new();
+        if self.root != NONE {
+            stack.push((self.root, 0));
+        }
+        KdTreeRange {
+            tree: self,
+            min,
+            max,
+            stack,
+        }
+    }
+
+    /// Every point and its value, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[f64; D], &V)> {
+        self.nodes.iter().map(|node| (&node.point, &node.value))
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.root = NONE;
+    }
+}
+
+impl<const D: usize, V> Node<D, V> {
+    fn new(point: [f64; D], value: V) -> Self {
+        assert!(point.iter().all(|x| !x.is_nan()), "coordinate is NaN");
+        Node {
+            point,
+            value,
+            left: NONE,
+            right: NONE,
+        }
+    }
+}
+
+/// Links the nodes in `order` into a subtree at `depth`, split at the
+/// median, and returns its root.
+fn build<const D: usize, V>(nodes: &mut [Node<D, V>], order: &mut [usize], depth: usize) -> usize {
+    if order.is_empty() {
+        return NONE;
+    }
+    let axis = depth % D;
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by(mid, |&a, &b| {
+        nodes[a].point[axis].total_cmp(&nodes[b].point[axis])
+    });
+    let root = order[mid];
+    let (left, right) = order.split_at_mut(mid);
+    nodes[root].left = build(nodes, left, depth + 1);
+    nodes[root].right = build(nodes, &mut right[1..], depth + 1);
+    root
+}
+
+impl<const D: usize, V> Default for KdTree<D, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const D: usize, V> FromIterator<([f64; D], V)> for KdTree<D, V> {
+    fn from_iter<I: IntoIterator<Item = ([f64; D], V)>>(iter: I) -> Self {
+        Self::build(iter)
+    }
+}
+
+/// The points in a box, from [`KdTree::range`].
+pub struct KdTreeRange<'a, const D: usize, V> {
+    tree: &'a KdTree<D, V>,
+    min: &'a [f64; D],
+    max: &'a [f64; D],
+    stack: Vec<(usize, usize)>,
+}
+
+impl<'a, const D: usize, V> Iterator for KdTreeRange<'a, D, V> {
+    type Item = (&'a [f64; D], &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((x, depth)) = self.stack.pop() {
+            let node = &self.tree.nodes[x];
+            let axis = depth % D;
+            // Points equal to the split may be on either side.
+            if node.left != NONE && self.min[axis] <= node.point[axis] {
+                self.stack.push((node.left, depth + 1));
+            }
+            if node.right != NONE && self.max[axis] >= node.point[axis] {
+                self.stack.push((node.right, depth + 1));
+            }
+            let inside = (0..D).all(|i| (self.min[i]..=self.max[i]).contains(&node.point[i]));
+            if inside {
+                return Some((&node.point, &node.value));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut tree: KdTree<2, &str> = [
+            ([0.0, 0.0], "origin"),
+            ([5.0, 5.0], "middle"),
+            ([9.0, 1.0], "corner"),
+        ]
+        .into_iter()
+        .collect();
+        tree.insert([4.0, 6.0], "near middle");
+        assert_eq!(tree.nearest(&[1.0, 1.0]), Some((&[0.0, 0.0], &"origin")));
+        let nearest: Vec<_> = tree
+            .nearest_k(&[5.0, 6.0], 2)
+            .into_iter()
+            .map(|(_, v)| *v)
+            .collect();
+        assert_eq!(nearest, ["near middle", "middle"]);
+        let mut inside: Vec<_> = tree
+            .range(&[4.0, 0.0], &[10.0, 5.0])
+            .map(|(_, v)| *v)
+            .collect();
+        inside.sort_unstable();
+        assert_eq!(inside, ["corner", "middle"]);
+        assert_eq!(KdTree::<3>::new().nearest(&[0.0; 3]), None);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(
+        built: Vec<(i8, i8)>,
+        inserted: Vec<(i8, i8)>,
+        query: (i8, i8),
+        k: u8,
+    ) -> bool {
+        let point = |(x, y): (i8, i8)| [x as f64, y as f64];
+        let mut points: Vec<_> = built.into_iter().map(point).collect();
+        let mut tree: KdTree<2, usize> = points.iter().copied().zip(0..).collect();
+        for p in inserted.into_iter().map(point) {
+            tree.insert(p, points.len());
+            points.push(p);
+        }
+        let query = point(query);
+        let k = k as usize % 8;
+
+        let mut distances: Vec<_> = points.iter().map(|p| distance2(p, &query)).collect();
+        distances.sort_unstable_by(f64::total_cmp);
+        distances.truncate(k);
+        let found: Vec<_> = tree
+            .nearest_k(&query, k)
+            .into_iter()
+            .map(|(p, _)| distance2(p, &query))
+            .collect();
+
+        let (min, max) = (
+            [query[0] - 20.0, query[1] - 40.0],
+            [query[0] + 30.0, query[1] + 10.0],
+        );
+        let mut inside: Vec<_> = tree.range(&min, &max).map(|(_, &i)| i).collect();
+        inside.sort_unstable();
+        let expected: Vec<_> = (0..points.len())
+            .filter(|&i| (0..2).all(|d| min[d] <= points[i][d] && points[i][d] <= max[d]))
+            .collect();
+        found == distances && inside == expected && tree.len() == points.len()
+    }
+}
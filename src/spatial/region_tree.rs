@@ -0,0 +1,516 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+use super::distance2;
+
+const NONE: usize = usize::MAX;
+
+/// Leaves this deep don't split any further, however full, so that many
+/// points in the same spot can't recurse forever.
+const MAX_DEPTH: usize = 24;
+
+/// Refers to a point inserted into a [`RegionTree`], until it's removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Key {
+    slot: usize,
+    generation: u32,
+}
+
+#[derive(Clone, Debug)]
+struct Entry<const D: usize, V> {
+    point: [f64; D],
+    value: V,
+}
+
+#[derive(Clone, Debug)]
+struct Slot<const D: usize, V> {
+    generation: u32,
+    entry: Option<Entry<D, V>>,
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    // The first of `2^D` consecutive children, or `NONE` for a leaf.
+    children: usize,
+    // The slots of the points in a leaf.
+    items: Vec<usize>,
+}
+
+/// A region tree over a box in `D` dimensions: a [`Quadtree`] in two, an
+/// [`Octree`] in three.
+///
+/// Every node is a box. A leaf holds up to `bucket` points; one more and it
+/// splits into `2^D` children, halving the box along each axis. Removing
+/// points merges children back once they'd fit in their parent again.
+#[derive(Clone, Debug)]
+pub struct RegionTree<const D: usize, V = ()> {
+    min: [f64; D],
+    max: [f64; D],
+    bucket: usize,
+    // The root is node 0.
+    nodes: Vec<Node>,
+    free_nodes: Vec<usize>,
+    slots: Vec<Slot<D, V>>,
+    free_slots: Vec<usize>,
+    len: usize,
+}
+
+pub type Quadtree<V = ()> = RegionTree<2, V>;
+
+pub type Octree<V = ()> = RegionTree<3, V>;
+
+impl<const D: usize, V> RegionTree<D, V> {
+    /// A tree over the box from `min` to `max`, with buckets of 8 points.
+    pub fn new(min: [f64; D], max: [f64; D]) -> Self {
+        Self::with_bucket(min, max, 8)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `bucket` is zero or the box is empty.
+    pub fn with_bucket(min: [f64; D], max: [f64; D], bucket: usize) -> Self {
+        const { assert!(D > 0 && D < usize::BITS as usize, "unsupported dimension") };
+        assert!(bucket > 0, "bucket must be positive");
+        assert!((0..D).all(|i| min[i] <= max[i]), "box is empty");
+        RegionTree {
+            min,
+            max,
+            bucket,
+            nodes: alloc::vec![Node {
+                children: NONE,
+                items: Vec::new(),
+            }],
+            free_nodes: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn entry(&self, slot: usize) -> &Entry<D, V> {
+        self.slots[slot].entry.as_ref().unwrap()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `point` is outside the tree's box.
+    pub fn insert(&mut self, point: [f64; D], value: V) -> Key {
+        assert!(
+            (0..D).all(|i| (self.min[i]..=self.max[i]).contains(&point[i])),
+            "point outside the tree"
+        );
+        let entry = Some(Entry { point, value });
+        let slot = match self.free_slots.pop() {
+            Some(slot) => {
+                self.slots[slot].entry = entry;
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry,
+                });
+                self.slots.len() - 1
+            }
+        };
+        self.len += 1;
+
+        let (mut x, mut min, mut max) = (0, self.min, self.max);
+        let mut depth = 0;
+        while self.nodes[x].children != NONE {
+            let child = child_of(&min, &max, &point);
+            (min, max) = child_box(&min, &max, child);
+            x = self.nodes[x].children + child;
+            depth += 1;
+        }
+        self.nodes[x].items.push(slot);
+        // Only the child with the new point can be over, if all went there.
+        while self.nodes[x].items.len() > self.bucket && depth < MAX_DEPTH {
+            self.split(x, &min, &max);
+            let child = child_of(&min, &max, &point);
+            (min, max) = child_box(&min, &max, child);
+            x = self.nodes[x].children + child;
+            depth += 1;
+        }
+        Key {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    /// Splits the leaf `x`, whose box is from `min` to `max`, into children.
+    fn split(&mut self, x: usize, min: &[f64; D], max: &[f64; D]) {
+        let children = match self.free_nodes.pop() {
+            Some(first) => first,
+            None => {
+                let first = self.nodes.len();
+                self.nodes.extend((0..1 << D).map(|_| Node {
+                    children: NONE,
+                    items: Vec::new(),
+                }));
+                first
+            }
+        };
+        for slot in core::mem::take(&mut self.nodes[x].items) {
+            let child = child_of(min, max, &self.entry(slot).point);
+            self.nodes[children + child].items.push(slot);
+        }
+        self.nodes[x].children = children;
+    }
+
+    fn resolve(&self, key: Key) -> Option<usize> {
+        let slot = self.slots.get(key.slot)?;
+        (slot.generation == key.generation && slot.entry.is_some()).then_some(key.slot)
+    }
+
+    pub fn get(&self, key: Key) -> Option<(&[f64; D], &V)> {
+        let entry = self.entry(self.resolve(key)?);
+        Some((&entry.point, &entry.value))
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut V> {
+        let slot = self.resolve(key)?;
+        Some(&mut self.slots[slot].entry.as_mut().unwrap().value)
+    }
+
+    /// Removes the point behind `key`, returning its value unless it was
+    /// removed already.
+    pub fn remove(&mut self, key: Key) -> Option<V> {
+        let slot = self.resolve(key)?;
+        let point = self.entry(slot).point;
+        let (mut x, mut min, mut max) = (0, self.min, self.max);
+        let mut path = Vec::new();
+        while self.nodes[x].children != NONE {
+            path.push(x);
+            let child = child_of(&min, &max, &point);
+            (min, max) = child_box(&min, &max, child);
+            x = self.nodes[x].children + child;
+        }
+        self.nodes[x].items.retain(|&s| s != slot);
+        // Merge back up while the children would fit in their parent.
+        while let Some(parent) = path.pop() {
+            let children = self.nodes[parent].children;
+            let block = children..children + (1 << D);
+            let leaves = block.clone().all(|c| self.nodes[c].children == NONE);
+            let total: usize = block.clone().map(|c| self.nodes[c].items.len()).sum();
+            if !leaves || total > self.bucket {
+                break;
+            }
+            let mut items = Vec::with_capacity(total);
+            for c in block {
+                items.append(&mut self.nodes[c].items);
+            }
+            self.nodes[parent] = Node {
+                children: NONE,
+                items,
+            };
+            self.free_nodes.push(children);
+        }
+
+        let slot = &mut self.slots[slot];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(key.slot);
+        self.len -= 1;
+        slot.entry.take().map(|entry| entry.value)
+    }
+
+    /// The points within the box from `min` to `max`, bounds included, in
+    /// no particular order.
+    pub fn query(&self, min: [f64; D], max: [f64; D]) -> RegionTreeQuery<'_, D, V> {
+        RegionTreeQuery {
+            tree: self,
+            min,
+            max,
+            stack: alloc::vec![(0, self.min, self.max)],
+            items: Vec::new(),
+        }
+    }
+
+    /// Every point, nearest to `point` first.
+    pub fn neighbors(&self, point: [f64; D]) -> RegionTreeNeighbors<'_, D, V> {
+        let mut pending = BinaryHeap::new();
+        pending.push(Pending {
+            distance2: box_distance2(&self.min, &self.max, &point),
+            next: Next::Node(0, self.min, self.max),
+        });
+        RegionTreeNeighbors {
+            tree: self,
+            point,
+            pending,
+        }
+    }
+
+    /// Every point, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Key, &[f64; D], &V)> {
+        self.slots.iter().enumerate().filter_map(|(slot, s)| {
+            let entry = s.entry.as_ref()?;
+            let key = Key {
+                slot,
+                generation: s.generation,
+            };
+            Some((key, &entry.point, &entry.value))
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[0] = Node {
+            children: NONE,
+            items: Vec::new(),
+        };
+        self.free_nodes.clear();
+        for (slot, s) in self.slots.iter_mut().enumerate() {
+            if s.entry.take().is_some() {
+                s.generation = s.generation.wrapping_add(1);
+                self.free_slots.push(slot);
+            }
+        }
+        self.len = 0;
+    }
+}
+
+/// Which child of the box from `min` to `max` holds `point`: bit `i` is
+/// set for the upper half along axis `i`.
+fn child_of<const D: usize>(min: &[f64; D], max: &[f64; D], point: &[f64; D]) -> usize {
+    (0..D)
+        .filter(|&i| point[i] >= (min[i] + max[i]) / 2.0)
+        .map(|i| 1 << i)
+        .sum()
+}
+
+fn child_box<const D: usize>(min: &[f64; D], max: &[f64; D], child: usize) -> ([f64; D], [f64; D]) {
+    let (mut lo, mut hi) = (*min, *max);
+    for i in 0..D {
+        let mid = (min[i] + max[i]) / 2.0;
+        if child >> i & 1 == 1 {
+            lo[i] = mid;
+        } else {
+            hi[i] = mid;
+        }
+    }
+    (lo, hi)
+}
+
+/// The squared distance from `point` to the nearest point of the box.
+fn box_distance2<const D: usize>(min: &[f64; D], max: &[f64; D], point: &[f64; D]) -> f64 {
+    let mut nearest = *point;
+    for i in 0..D {
+        nearest[i] = point[i].clamp(min[i], max[i]);
+    }
+    distance2(&nearest, point)
+}
+
+fn overlaps<const D: usize>(a: (&[f64; D], &[f64; D]), b: (&[f64; D], &[f64; D])) -> bool {
+    (0..D).all(|i| a.0[i] <= b.1[i] && b.0[i] <= a.1[i])
+}
+
+/// The points in a box, from [`RegionTree::query`].
+pub struct RegionTreeQuery<'a, const D: usize, V> {
+    tree: &'a RegionTree<D, V>,
+    min: [f64; D],
+    max: [f64; D],
+    stack: Vec<(usize, [f64; D], [f64; D])>,
+    // Slots of the last leaf, still to be checked.
+    items: Vec<usize>,
+}
+
+impl<'a, const D: usize, V> Iterator for RegionTreeQuery<'a, D, V> {
+    type Item = (Key, &'a [f64; D], &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while let Some(slot) = self.items.pop() {
+                let entry = self.tree.entry(slot);
+                if (0..D).all(|i| (self.min[i]..=self.max[i]).contains(&entry.point[i])) {
+                    let key = Key {
+                        slot,
+                        generation: self.tree.slots[slot].generation,
+                    };
+                    return Some((key, &entry.point, &entry.value));
+                }
+            }
+            let (x, min, max) = self.stack.pop()?;
+            if !overlaps((&min, &max), (&self.min, &self.max)) {
+                continue;
+            }
+            let node = &self.tree.nodes[x];
+            if node.children == NONE {
+                self.items.extend_from_slice(&node.items);
+                continue;
+            }
+            for child in 0..1 << D {
+                let (lo, hi) = child_box(&min, &max, child);
+                self.stack.push((node.children + child, lo, hi));
+            }
+        }
+    }
+}
+
+enum Next<const D: usize> {
+    Node(usize, [f64; D], [f64; D]),
+    Slot(usize),
+}
+
+/// A node or point still to visit, nearest first.
+struct Pending<const D: usize> {
+    distance2: f64,
+    next: Next<D>,
+}
+
+impl<const D: usize> PartialEq for Pending<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<const D: usize> Eq for Pending<D> {}
+
+impl<const D: usize> PartialOrd for Pending<D> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const D: usize> Ord for Pending<D> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance2.total_cmp(&self.distance2)
+    }
+}
+
+/// The points by distance, from [`RegionTree::neighbors`]. A best-first
+/// search, so taking the nearest few only opens the boxes around them.
+pub struct RegionTreeNeighbors<'a, const D: usize, V> {
+    tree: &'a RegionTree<D, V>,
+    point: [f64; D],
+    pending: BinaryHeap<Pending<D>>,
+}
+
+impl<'a, const D: usize, V> Iterator for RegionTreeNeighbors<'a, D, V> {
+    type Item = (Key, &'a [f64; D], &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.pending.pop()?.next {
+                Next::Slot(slot) => {
+                    let entry = self.tree.entry(slot);
+                    let key = Key {
+                        slot,
+                        generation: self.tree.slots[slot].generation,
+                    };
+                    return Some((key, &entry.point, &entry.value));
+                }
+                Next::Node(x, min, max) => {
+                    let node = &self.tree.nodes[x];
+                    if node.children == NONE {
+                        for &slot in &node.items {
+                            self.pending.push(Pending {
+                                distance2: distance2(&self.tree.entry(slot).point, &self.point),
+                                next: Next::Slot(slot),
+                            });
+                        }
+                        continue;
+                    }
+                    for child in 0..1 << D {
+                        let (lo, hi) = child_box(&min, &max, child);
+                        self.pending.push(Pending {
+                            distance2: box_distance2(&lo, &hi, &self.point),
+                            next: Next::Node(node.children + child, lo, hi),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut tree = Quadtree::with_bucket([0.0, 0.0], [100.0, 100.0], 2);
+        let keys: Vec<_> = [[10.0, 10.0], [20.0, 20.0], [80.0, 80.0], [90.0, 10.0]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| tree.insert(p, i))
+            .collect();
+        let mut found: Vec<_> = tree
+            .query([0.0, 0.0], [50.0, 50.0])
+            .map(|(_, _, &v)| v)
+            .collect();
+        found.sort_unstable();
+        assert_eq!(found, [0, 1]);
+        let nearest: Vec<_> = tree
+            .neighbors([85.0, 20.0])
+            .map(|(_, _, &v)| v)
+            .take(2)
+            .collect();
+        assert_eq!(nearest, [3, 2]);
+
+        assert_eq!(tree.remove(keys[3]), Some(3));
+        assert_eq!(tree.remove(keys[3]), None);
+        assert_eq!(tree.get(keys[3]), None);
+        assert_eq!(tree.get(keys[2]), Some((&[80.0, 80.0], &2)));
+        assert_eq!(
+            tree.neighbors([85.0, 20.0]).next().map(|(k, _, _)| k),
+            Some(keys[2])
+        );
+
+        let mut octree = Octree::new([0.0; 3], [1.0; 3]);
+        octree.insert([1.0, 1.0, 1.0], ());
+        assert_eq!(octree.query([0.5; 3], [1.0; 3]).count(), 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(bool, (u8, u8))>, query: ((u8, u8), (u8, u8))) -> bool {
+        let point = |(x, y): (u8, u8)| [x as f64 / 4.0, y as f64 / 4.0];
+        let mut tree = Quadtree::with_bucket([0.0, 0.0], [64.0, 64.0], 3);
+        let mut model = Vec::new();
+        for (i, (insert, p)) in ops.into_iter().enumerate() {
+            if insert || model.is_empty() {
+                let p = point(p);
+                model.push((tree.insert(p, i), p, i));
+            } else {
+                let (key, _, value) = model.swap_remove(p.0 as usize % model.len());
+                if tree.remove(key) != Some(value) {
+                    return false;
+                }
+            }
+        }
+
+        let (a, b) = (point(query.0), point(query.1));
+        let (min, max) = (
+            [a[0].min(b[0]), a[1].min(b[1])],
+            [a[0].max(b[0]), a[1].max(b[1])],
+        );
+        let mut found: Vec<_> = tree.query(min, max).map(|(_, _, &v)| v).collect();
+        found.sort_unstable();
+        let mut expected: Vec<_> = model
+            .iter()
+            .filter(|(_, p, _)| (0..2).all(|i| min[i] <= p[i] && p[i] <= max[i]))
+            .map(|&(_, _, v)| v)
+            .collect();
+        expected.sort_unstable();
+
+        let distances: Vec<_> = tree
+            .neighbors(a)
+            .map(|(_, p, _)| distance2(p, &a))
+            .collect();
+        let mut expected_distances: Vec<_> =
+            model.iter().map(|(_, p, _)| distance2(p, &a)).collect();
+        expected_distances.sort_unstable_by(f64::total_cmp);
+
+        found == expected && distances == expected_distances && tree.len() == model.len()
+    }
+}
@@ -0,0 +1,211 @@
+use alloc::vec::Vec;
+
+use crate::splay::{self, Splay};
+
+/// Bits per coordinate.
+const LEVELS: u32 = u32::BITS;
+
+/// Interleaves the bits of `point`, bit `b` of coordinate `i` going to bit
+/// `b * D + i` of the code. Codes sort points along the Z-shaped curve which
+/// visits each quadrant, recursively, before the next.
+pub fn morton_encode<const D: usize>(point: [u32; D]) -> u128 {
+    let mut code = 0;
+    for b in 0..LEVELS as usize {
+        for (i, &x) in point.iter().enumerate() {
+            code |= ((x as u128 >> b) & 1) << (b * D + i);
+        }
+    }
+    code
+}
+
+pub fn morton_decode<const D: usize>(code: u128) -> [u32; D] {
+    let mut point = [0; D];
+    for b in 0..LEVELS as usize {
+        for (i, x) in point.iter_mut().enumerate() {
+            *x |= (((code >> (b * D + i)) & 1) as u32) << b;
+        }
+    }
+    point
+}
+
+/// A map from points on a `D`-dimensional integer grid, for `D` up to 3,
+/// kept in a [`Splay`] by their Morton code.
+///
+/// Nearby points mostly get nearby codes, so a box query turns into a few
+/// runs of consecutive codes: [`query`](ZOrderMap::query) walks the implicit
+/// `2^D`-ary tree of aligned boxes, reads off every box the query covers as
+/// one range of the splay tree, and skips boxes with nothing in them.
+#[derive(Clone, Debug)]
+pub struct ZOrderMap<const D: usize, V> {
+    tree: Splay<u128, V>,
+}
+
+impl<const D: usize, V> ZOrderMap<D, V> {
+    pub fn new() -> Self {
+        const { assert!(D > 0 && D <= 3, "Morton codes hold up to 3 dimensions") };
+        ZOrderMap { tree: Splay::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn insert(&mut self, point: [u32; D], value: V) -> Option<V> {
+        self.tree.insert(morton_encode(point), value)
+    }
+
+    pub fn get(&self, point: &[u32; D]) -> Option<&V> {
+        self.tree.peek(&morton_encode(*point))
+    }
+
+    pub fn get_mut(&mut self, point: &[u32; D]) -> Option<&mut V> {
+        self.tree.get_mut(&morton_encode(*point))
+    }
+
+    pub fn contains_key(&self, point: &[u32; D]) -> bool {
+        self.tree.contains_key(&morton_encode(*point))
+    }
+
+    pub fn remove(&mut self, point: &[u32; D]) -> Option<V> {
+        self.tree.remove(&morton_encode(*point))
+    }
+
+    /// Every point and its value, in Z-order.
+    pub fn iter(&self) -> impl Iterator<Item = ([u32; D], &V)> {
+        self.tree
+            .iter()
+            .map(|(&code, value)| (morton_decode(code), value))
+    }
+
+    /// The points within the box from `min` to `max`, bounds included, in
+    /// Z-order.
+    pub fn query(&self, min: [u32; D], max: [u32; D]) -> ZOrderQuery<'_, D, V> {
+        ZOrderQuery {
+            tree: &self.tree,
+            min,
+            max,
+            stack: alloc::vec![(0, LEVELS)],
+            run: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+}
+
+impl<const D: usize, V> Default for ZOrderMap<D, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const D: usize, V> FromIterator<([u32; D], V)> for ZOrderMap<D, V> {
+    fn from_iter<I: IntoIterator<Item = ([u32; D], V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.tree = iter
+            .into_iter()
+            .map(|(point, value)| (morton_encode(point), value))
+            .collect();
+        map
+    }
+}
+
+/// The points in a box, from [`ZOrderMap::query`].
+pub struct ZOrderQuery<'a, const D: usize, V> {
+    tree: &'a Splay<u128, V>,
+    min: [u32; D],
+    max: [u32; D],
+    // Aligned boxes still to visit, by their first code and how many low
+    // bits of each coordinate they span, last to visit first.
+    stack: Vec<(u128, u32)>,
+    // The codes of a box inside the query, being read off.
+    run: Option<splay::Range<'a, u128, V>>,
+}
+
+impl<'a, const D: usize, V> Iterator for ZOrderQuery<'a, D, V> {
+    type Item = ([u32; D], &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(run) = &mut self.run {
+                if let Some((&code, value)) = run.next() {
+                    return Some((morton_decode(code), value));
+                }
+                self.run = None;
+            }
+            let (first, level) = self.stack.pop()?;
+            let corner: [u32; D] = morton_decode(first);
+            let span = (1u64 << level) - 1;
+            let (mut inside, mut disjoint) = (true, false);
+            for (i, &c) in corner.iter().enumerate() {
+                let (lo, hi) = (c as u64, c as u64 + span);
+                inside &= self.min[i] as u64 <= lo && hi <= self.max[i] as u64;
+                disjoint |= hi < self.min[i] as u64 || (self.max[i] as u64) < lo;
+            }
+            if disjoint {
+                continue;
+            }
+            let last = first + ((1u128 << (level as usize * D)) - 1);
+            if inside {
+                self.run = Some(self.tree.range(first..=last));
+                continue;
+            }
+            if self.tree.range(first..=last).next().is_none() {
+                continue;
+            }
+            // A single cell is either inside or not, so `level` is positive.
+            let child_bits = (level - 1) as usize * D;
+            for child in (0..1u128 << D).rev() {
+                self.stack.push((first + (child << child_bits), level - 1));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        assert_eq!(morton_encode([0b11, 0b01]), 0b0111);
+        assert_eq!(
+            morton_decode::<3>(morton_encode([7, u32::MAX, 0])),
+            [7, u32::MAX, 0]
+        );
+
+        let mut map: ZOrderMap<2, _> = [([1, 1], 'a'), ([2, 3], 'b'), ([6, 0], 'c')]
+            .into_iter()
+            .collect();
+        assert_eq!(map.insert([1, 1], 'A'), Some('a'));
+        let found: Vec<_> = map.query([0, 0], [3, 3]).collect();
+        assert_eq!(found, [([1, 1], &'A'), ([2, 3], &'b')]);
+        assert_eq!(map.remove(&[2, 3]), Some('b'));
+        assert_eq!(map.query([2, 0], [u32::MAX, u32::MAX]).count(), 1);
+        assert!(map.contains_key(&[6, 0]));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(points: Vec<(u8, u8, u8)>, query: ((u8, u8, u8), (u8, u8, u8))) -> bool {
+        let point = |(x, y, z): (u8, u8, u8)| [x as u32 % 32, y as u32 * 1000, z as u32];
+        let map: ZOrderMap<3, usize> = points.iter().map(|&p| point(p)).zip(0..).collect();
+        let (a, b) = (point(query.0), point(query.1));
+        let (min, max): ([u32; 3], [u32; 3]) = (
+            core::array::from_fn(|i| a[i].min(b[i])),
+            core::array::from_fn(|i| a[i].max(b[i])),
+        );
+        let found: Vec<_> = map.query(min, max).map(|(p, _)| p).collect();
+        let expected: Vec<_> = map
+            .iter()
+            .map(|(p, _)| p)
+            .filter(|p| (0..3).all(|i| min[i] <= p[i] && p[i] <= max[i]))
+            .collect();
+        found == expected
+    }
+}
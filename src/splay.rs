@@ -1,4 +1,69 @@
-use std::cmp::Ordering::{Equal, Greater, Less};
+use alloc::collections::{BTreeMap, TryReserveError};
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering::{Equal, Greater, Less};
+use core::fmt;
+use core::iter::FusedIterator;
+use core::mem;
+use core::ops::{Bound, RangeBounds};
+
+use crate::compare::{Compare, Natural};
+
+mod arena;
+mod bounded;
+mod builder;
+mod checkpoint;
+mod compat;
+mod cow;
+mod cursor;
+mod dot;
+#[cfg(feature = "std")]
+mod durable;
+mod entry;
+mod forest;
+mod frozen;
+mod ingest;
+mod invariants;
+mod merge;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod patch;
+mod pretty;
+#[cfg(feature = "std")]
+mod snapshot;
+mod stats;
+#[cfg(feature = "stream")]
+mod stream;
+mod undo;
+
+pub use arena::{Allocator, Global};
+pub use bounded::BoundedSplay;
+pub use builder::SplayBuilder;
+pub use checkpoint::CheckpointSplay;
+pub(crate) use compat::check_range;
+pub use compat::{
+    CompatSplay, IntoIter, IntoKeys, IntoValues, IterMut, Keys, Range, RangeMut, Values, ValuesMut,
+};
+pub use cow::{CowSplay, SplaySnapshot};
+pub use cursor::Cursor;
+pub use dot::DotOptions;
+#[cfg(feature = "std")]
+pub use durable::DurableSplay;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use forest::{SplayForest, TreeId};
+pub use frozen::{FrozenIter, FrozenSplay};
+pub use ingest::IngestSplay;
+pub use invariants::InvariantError;
+pub use merge::{Diff, DiffOp, Difference, JoinIter, JoinMode, SymmetricDifference};
+#[cfg(feature = "rayon")]
+pub use parallel::{ParIter, ParIterMut};
+pub use pretty::TreeDisplay;
+pub use stats::Stats;
+#[cfg(feature = "stream")]
+pub use stream::SnapshotStream;
+pub use undo::UndoSplay;
+
+use arena::Nodes;
 
 type Idx = usize;
 
@@ -17,71 +82,128 @@ impl OptionIdx {
     }
 }
 
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 struct Node<K, V> {
     key: K,
     value: V,
     left: OptionIdx,
     right: OptionIdx,
+    // Times the key was found through `&mut self`, see `Splay::hottest`.
+    #[cfg(feature = "stats")]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    hits: u64,
 }
 
-pub struct Splay<K, V> {
+/// A splay tree ordered by `C`, the keys' own `Ord` impl unless the tree
+/// is made [`with_comparator`](Splay::with_comparator).
+#[derive(Clone)]
+pub struct Splay<K, V, C = Natural, A: Allocator = Global> {
     root: OptionIdx,
-    nodes: Vec<Node<K, V>>,
+    nodes: Nodes<Node<K, V>, A>,
+    cmp: C,
+    stats: stats::Recorder,
 }
 
-pub struct SplayIter<'a, K, V> {
-    tree: &'a Splay<K, V>,
-    path: Vec<(Idx, bool)>,
+pub struct SplayIter<'a, K, V, C = Natural, A: Allocator = Global> {
+    tree: &'a Splay<K, V, C, A>,
+    ends: Ends,
+    len: usize,
 }
 
-impl<'a, K: Ord, V> SplayIter<'a, K, V> {
-    fn new(tree: &'a Splay<K, V>) -> Self {
-        let path = Vec::new();
-        let mut t = SplayIter { tree, path };
-        if let Some(root) = tree.root.to_option() {
-            t.towards_min(root);
-        }
-        t
+impl<'a, K, V, C, A: Allocator> SplayIter<'a, K, V, C, A> {
+    fn new(tree: &'a Splay<K, V, C, A>) -> Self {
+        Self::subtree(tree, tree.root, tree.nodes.len())
     }
 
-    fn towards_min(&mut self, idx: Idx) {
-        let mut idx = Some(idx);
-
-        while let Some(i) = idx {
-            self.path.push((i, false));
-            idx = self.tree.nodes[i].left.to_option();
+    /// Iterates over the `len` entries under `root`.
+    fn subtree(tree: &'a Splay<K, V, C, A>, root: OptionIdx, len: usize) -> Self {
+        let mut ends = Ends {
+            front: Vec::new(),
+            back: Vec::new(),
+        };
+        for (stack, dir) in [(&mut ends.front, Dir::Left), (&mut ends.back, Dir::Right)] {
+            let mut next = root.to_option();
+            while let Some(idx) = next {
+                stack.push(idx);
+                next = link(&tree.nodes[idx], dir).to_option();
+            }
         }
+        SplayIter { tree, ends, len }
     }
 
-    fn upwards(&mut self) {
-        while let Some((_, right_subtree_visited)) = self.path.last() {
-            if !right_subtree_visited {
-                break;
-            }
-            self.path.pop();
-        }
+    fn step(&mut self, from: Dir) -> Option<(&'a K, &'a V)> {
+        let nodes = &self.tree.nodes;
+        let idx = self.ends.step(from, |idx, dir| link(&nodes[idx], dir))?;
+        self.len -= 1;
+        let node = &nodes[idx];
+        Some((&node.key, &node.value))
     }
 }
 
-impl<'a, K: Ord, V> Iterator for SplayIter<'a, K, V> {
+impl<'a, K, V, C, A: Allocator> Iterator for SplayIter<'a, K, V, C, A> {
     type Item = (&'a K, &'a V);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (node_idx, visited_right_subtree) = self.path.last_mut()?;
-        let node = &self.tree.nodes[*node_idx];
+        self.step(Dir::Left)
+    }
 
-        match (&visited_right_subtree, node.right.to_option()) {
-            (false, Some(right)) => {
-                *visited_right_subtree = true;
-                self.towards_min(right);
-            }
-            _ => {
-                *visited_right_subtree = true;
-                self.upwards()
-            }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V, C, A: Allocator> DoubleEndedIterator for SplayIter<'_, K, V, C, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(Dir::Right)
+    }
+}
+
+impl<K, V, C, A: Allocator> ExactSizeIterator for SplayIter<'_, K, V, C, A> {}
+impl<K, V, C, A: Allocator> FusedIterator for SplayIter<'_, K, V, C, A> {}
+
+/// Where a walk over part of a tree has got to from either end: each stack
+/// is the path down to the next entry from its end. It's over once both
+/// ends have come to the same entry.
+struct Ends {
+    front: Vec<Idx>,
+    back: Vec<Idx>,
+}
+
+impl Ends {
+    /// Steps from the front (`Dir::Left`) or back (`Dir::Right`), with
+    /// `child` reading links.
+    fn step(&mut self, from: Dir, child: impl Fn(Idx, Dir) -> OptionIdx) -> Option<Idx> {
+        let (this, other) = match from {
+            Dir::Left => (&mut self.front, &self.back),
+            Dir::Right => (&mut self.back, &self.front),
         };
+        let idx = this.pop()?;
+        if other.last() == Some(&idx) {
+            self.finish();
+            return Some(idx);
+        }
+        let mut next = child(idx, from.flip()).to_option();
+        while let Some(i) = next {
+            this.push(i);
+            next = child(i, from).to_option();
+        }
+        Some(idx)
+    }
 
-        Some((&node.key, &node.value))
+    fn finish(&mut self) {
+        self.front.clear();
+        self.back.clear();
+    }
+}
+
+fn link<K, V>(node: &Node<K, V>, dir: Dir) -> OptionIdx {
+    match dir {
+        Dir::Left => node.left,
+        Dir::Right => node.right,
     }
 }
 
@@ -119,17 +241,25 @@ impl Path {
     }
 }
 
-enum OrCreate<'a, K, V> {
-    Lookup(&'a K),
+/// Where the link to a node lives: at the top of the subtree searched, or in
+/// a child slot of the given node.
+#[derive(Clone, Copy)]
+enum Link {
+    Top,
+    Child(Idx, Dir),
+}
+
+enum OrCreate<'a, Q: ?Sized, K, V> {
+    Lookup(&'a Q),
     Create(K, V),
 }
 
-impl<'a, K, V> OrCreate<'a, K, V> {
+impl<'a, Q: ?Sized, K: Borrow<Q>, V> OrCreate<'a, Q, K, V> {
     #[inline]
-    fn key(&self) -> &K {
+    fn key(&self) -> &Q {
         match self {
             OrCreate::Lookup(k) => k,
-            OrCreate::Create(k, _) => &k,
+            OrCreate::Create(k, _) => k.borrow(),
         }
     }
 
@@ -144,9 +274,67 @@ impl<'a, K, V> OrCreate<'a, K, V> {
 
 impl<K: Ord, V> Splay<K, V> {
     pub fn new() -> Self {
+        Self::new_in(Global)
+    }
+}
+
+impl<K: Ord, V, A: Allocator> Splay<K, V, Natural, A> {
+    /// Tree whose nodes are allocated with `alloc`, custom allocators need
+    /// the nightly `allocator_api` feature.
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_comparator_in(Natural, alloc)
+    }
+}
+
+impl<K, V, C: Compare<K>> Splay<K, V, C> {
+    /// Tree ordering its keys with `cmp`, which every lookup goes through
+    /// too: looking up by a borrowed `Q` needs `C: Compare<Q>`.
+    pub fn with_comparator(cmp: C) -> Self {
+        Self::with_comparator_in(cmp, Global)
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> Splay<K, V, C, A> {
+    pub fn with_comparator_in(cmp: C, alloc: A) -> Self {
         Splay {
             root: IDX_NONE,
-            nodes: Vec::new(),
+            nodes: Nodes::new_in(alloc),
+            cmp,
+            stats: Default::default(),
+        }
+    }
+
+    pub fn comparator(&self) -> &C {
+        &self.cmp
+    }
+
+    /// Applies `f` to every value in place. Values are visited in arena
+    /// order, not key order.
+    pub fn map_values<F: FnMut(&mut V)>(&mut self, mut f: F) {
+        for node in self.nodes.iter_mut() {
+            f(&mut node.value);
+        }
+    }
+
+    /// Maps every value through `f`, keeping the keys, the tree shape, the
+    /// comparator and the allocator, so nothing is compared or relinked.
+    pub fn into_map_values<U, F: FnMut(V) -> U>(self, mut f: F) -> Splay<K, U, C, A>
+    where
+        A: Clone,
+    {
+        let nodes = arena::map(self.nodes, |node| Node {
+            key: node.key,
+            value: f(node.value),
+            left: node.left,
+            right: node.right,
+            #[cfg(feature = "stats")]
+            hits: node.hits,
+        });
+        Splay {
+            root: self.root,
+            nodes,
+            cmp: self.cmp,
+            stats: self.stats,
         }
     }
 
@@ -154,7 +342,7 @@ impl<K: Ord, V> Splay<K, V> {
         match idx.to_option() {
             None => 0,
             Some(idx) => {
-                1 + std::cmp::max(
+                1 + core::cmp::max(
                     self.node_depth(self.nodes[idx].left),
                     self.node_depth(self.nodes[idx].right),
                 )
@@ -166,37 +354,191 @@ impl<K: Ord, V> Splay<K, V> {
         self.node_depth(self.root)
     }
 
-    pub fn iter(&self) -> SplayIter<K, V> {
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn iter(&self) -> SplayIter<'_, K, V, C, A> {
         SplayIter::new(self)
     }
 
+    /// How many entries fit before the node arena has to grow.
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+
+    /// Like `reserve`, but reports allocation failure instead of aborting.
+    /// Once it succeeds, the next `additional` insertions won't allocate.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.nodes.try_reserve(additional)
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+    }
+
+    /// The node in slot `idx`, where `idx` came from a link or the root.
+    ///
+    /// With the `unchecked` feature the bounds check is skipped. That relies
+    /// on every link pointing at an occupied slot, which holds because:
+    ///
+    /// - `new_node` only hands out the slot it just pushed, and
+    ///   `link_balanced` only links slots below the length it's given;
+    /// - rotations swap node contents and move existing links around;
+    /// - removals repoint the link to the last node at the slot it moves
+    ///   into before the arena shrinks (`remove_root`, `remove_range`), and
+    ///   find that link before changing anything, so a panicking `Ord`
+    ///   can't leave a stale index behind. Should an inconsistent comparator
+    ///   hide the link from the search, they look through every node;
+    /// - trees read back from rkyv archives or snapshots have their links
+    ///   checked on the way in.
+    #[inline(always)]
+    fn node(&self, idx: Idx) -> &Node<K, V> {
+        #[cfg(feature = "unchecked")]
+        {
+            debug_assert!(idx < self.nodes.len());
+            // SAFETY: `idx < self.nodes.len()`, see above.
+            unsafe { self.nodes.get_unchecked(idx) }
+        }
+        #[cfg(not(feature = "unchecked"))]
+        &self.nodes[idx]
+    }
+
+    #[inline(always)]
+    fn node_mut(&mut self, idx: Idx) -> &mut Node<K, V> {
+        #[cfg(feature = "unchecked")]
+        {
+            debug_assert!(idx < self.nodes.len());
+            // SAFETY: as for `node`.
+            unsafe { self.nodes.get_unchecked_mut(idx) }
+        }
+        #[cfg(not(feature = "unchecked"))]
+        &mut self.nodes[idx]
+    }
+
+    #[inline(always)]
+    fn swap_nodes(&mut self, a: Idx, b: Idx) {
+        #[cfg(feature = "unchecked")]
+        {
+            debug_assert!(a < self.nodes.len() && b < self.nodes.len());
+            let nodes = self.nodes.as_mut_ptr();
+            // SAFETY: both slots are in bounds as for `node`, and `ptr::swap`
+            // allows them to be the same.
+            unsafe { core::ptr::swap(nodes.add(a), nodes.add(b)) }
+        }
+        #[cfg(not(feature = "unchecked"))]
+        self.nodes.swap(a, b);
+    }
+
     #[inline]
     fn child(&self, idx: Idx, dir: Dir) -> OptionIdx {
         match dir {
-            Dir::Left => self.nodes[idx as usize].left,
-            Dir::Right => self.nodes[idx as usize].right,
+            Dir::Left => self.node(idx).left,
+            Dir::Right => self.node(idx).right,
         }
     }
 
     #[inline]
     fn set_child(&mut self, idx: Idx, dir: Dir, to: OptionIdx) {
         match dir {
-            Dir::Left => self.nodes[idx as usize].left = to,
-            Dir::Right => self.nodes[idx as usize].right = to,
+            Dir::Left => self.node_mut(idx).left = to,
+            Dir::Right => self.node_mut(idx).right = to,
         };
     }
 
-    pub fn get(&mut self, key: K) -> Option<&V> {
-        self.visit(OrCreate::Lookup(&key));
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.visit(OrCreate::Lookup(key));
         self.root.to_option().and_then(|root| {
-            if self.nodes[root as usize].key == key {
-                Some(&self.nodes[root as usize].value)
+            if self.cmp.compare(self.nodes[root].key.borrow(), key) == Equal {
+                Some(&self.nodes[root].value)
             } else {
                 None
             }
         })
     }
 
+    /// Lookup which leaves the tree shape alone, usable through `&self`.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.find(key).map(|idx| &self.nodes[idx].value)
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<Idx>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        let mut idx = self.root.to_option();
+        while let Some(i) = idx {
+            let node = self.node(i);
+            idx = match self.cmp.compare(key, node.key.borrow()) {
+                Equal => return Some(i),
+                Less => node.left.to_option(),
+                Greater => node.right.to_option(),
+            };
+        }
+        None
+    }
+
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, f: F) -> &mut V {
+        self.visit(OrCreate::Lookup(&key));
+        match self.root.to_option() {
+            Some(root) if self.cmp.compare(&self.nodes[root].key, &key) == Equal => {}
+            _ => self.set(key, f()),
+        }
+        let root = self.root.to_option().unwrap();
+        &mut self.nodes[root].value
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.visit(OrCreate::Lookup(key));
+        let root = self.root.to_option()?;
+        if self.cmp.compare(self.nodes[root].key.borrow(), key) == Equal {
+            Some(&mut self.nodes[root].value)
+        } else {
+            None
+        }
+    }
+
+    /// Mutable references to the values of several distinct keys at once.
+    /// Returns `None` if any key is missing or appears twice. Doesn't splay.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        let mut indices = [0; N];
+        for (idx, key) in indices.iter_mut().zip(keys) {
+            *idx = self.find(key)?;
+        }
+        let nodes = self.nodes.get_disjoint_mut(indices).ok()?;
+        Some(nodes.map(|node| &mut node.value))
+    }
+
     #[inline]
     fn new_node(&mut self, key: K, value: V) -> Idx {
         let node = Node {
@@ -204,20 +546,26 @@ impl<K: Ord, V> Splay<K, V> {
             value,
             left: IDX_NONE,
             right: IDX_NONE,
+            #[cfg(feature = "stats")]
+            hits: 0,
         };
+        if self.nodes.len() == self.nodes.capacity() {
+            self.stats.allocation();
+        }
         self.nodes.push(node);
-        (self.nodes.len() - 1) as Idx
+        self.nodes.len() - 1
     }
 
     #[inline]
     /// Swaps upper with lower.
     fn rotate(&mut self, upper: Idx, dir: Dir) {
         let lower = self.child(upper, dir).to_option().unwrap();
+        self.stats.rotation();
 
         self.set_child(upper, dir, self.child(lower, dir.flip()));
         self.set_child(lower, dir.flip(), OptionIdx(lower));
 
-        self.nodes.swap(upper, lower);
+        self.swap_nodes(upper, lower);
     }
 
     #[inline]
@@ -225,6 +573,7 @@ impl<K: Ord, V> Splay<K, V> {
         match *path {
             Path::Empty | Path::One(_) => {}
             Path::Two(dir1, dir2) => {
+                self.stats.splay_step();
                 let next_node = self.child(idx, dir1).to_option().unwrap();
                 self.rotate(next_node, dir2);
                 self.rotate(idx, dir1);
@@ -234,25 +583,30 @@ impl<K: Ord, V> Splay<K, V> {
     }
 
     #[inline]
-    fn splay_finish(&mut self, path: &Path) {
+    fn splay_finish(&mut self, root: Idx, path: &Path) {
         match path {
             Path::Empty => {}
             Path::Two(..) => unreachable!(),
             Path::One(dir) => {
-                let root = self.root.to_option().unwrap();
+                self.stats.splay_step();
                 self.rotate(root, *dir)
             }
         }
     }
 
     #[inline]
-    fn visit_inner_helper(
+    fn visit_inner_helper<Q>(
         &mut self,
         node_idx: Idx,
-        create: OrCreate<K, V>,
+        create: OrCreate<Q, K, V>,
         dir: Dir,
         path: &mut Path,
-    ) -> Option<V> {
+    ) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
         match self.child(node_idx, dir).to_option() {
             Some(idx) => {
                 let value = self.visit_inner(idx, create, path);
@@ -271,11 +625,26 @@ impl<K: Ord, V> Splay<K, V> {
     }
 
     #[inline]
-    fn visit_inner(&mut self, node_idx: Idx, create: OrCreate<K, V>, path: &mut Path) -> Option<V> {
+    fn visit_inner<Q>(
+        &mut self,
+        node_idx: Idx,
+        create: OrCreate<Q, K, V>,
+        path: &mut Path,
+    ) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
         let key = create.key();
+        self.stats.comparison();
 
-        let value = match key.cmp(&self.nodes[node_idx as usize].key) {
+        let value = match self.cmp.compare(key, self.node(node_idx).key.borrow()) {
             Equal => {
+                #[cfg(feature = "stats")]
+                {
+                    self.node_mut(node_idx).hits += 1;
+                }
                 *path = Path::Empty;
                 create.value()
             }
@@ -287,12 +656,17 @@ impl<K: Ord, V> Splay<K, V> {
         value
     }
 
-    fn visit(&mut self, create: OrCreate<K, V>) -> Option<V> {
+    fn visit<Q>(&mut self, create: OrCreate<Q, K, V>) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
         match self.root.to_option() {
             Some(root) => {
                 let mut path = Path::Empty;
                 let value = self.visit_inner(root, create, &mut path);
-                self.splay_finish(&path);
+                self.splay_finish(root, &path);
                 value
             }
             None => match create {
@@ -307,12 +681,703 @@ impl<K: Ord, V> Splay<K, V> {
     }
 
     pub fn set(&mut self, key: K, value: V) {
-        if let Some(value) = self.visit(OrCreate::Create(key, value)) {
-            self.nodes[self.root.to_option().unwrap() as usize].value = value;
+        if let Some(value) = self.visit::<K>(OrCreate::Create(key, value)) {
+            self.nodes[self.root.to_option().unwrap()].value = value;
+        }
+    }
+
+    /// Finds the link to `idx` under `subtree`, searching by its key.
+    fn find_link(&mut self, subtree: OptionIdx, idx: Idx) -> Option<Link> {
+        if subtree == OptionIdx(idx) {
+            return Some(Link::Top);
+        }
+        let mut link = subtree;
+        while let Some(parent) = link.to_option() {
+            self.stats.comparison();
+            let dir = match self
+                .cmp
+                .compare(&self.node(idx).key, &self.node(parent).key)
+            {
+                Less => Dir::Left,
+                _ => Dir::Right,
+            };
+            link = self.child(parent, dir);
+            if link == OptionIdx(idx) {
+                return Some(Link::Child(parent, dir));
+            }
+        }
+        None
+    }
+
+    /// Finds the link to `idx` by looking at every node but `skip`, for when
+    /// `find_link` misses it. `Link::Top` if no node has it as a child.
+    fn scan_link(&self, idx: Idx, skip: Idx) -> Link {
+        (0..self.nodes.len())
+            .filter(|&parent| parent != skip)
+            .find_map(|parent| {
+                [Dir::Left, Dir::Right]
+                    .into_iter()
+                    .find(|&dir| self.child(parent, dir) == OptionIdx(idx))
+                    .map(|dir| Link::Child(parent, dir))
+            })
+            .unwrap_or(Link::Top)
+    }
+
+    /// Joins two trees where every key in `left` is less than every key in
+    /// `right`. Makes no comparisons, so it can't panic halfway.
+    fn join(&mut self, left: OptionIdx, right: OptionIdx) -> OptionIdx {
+        let Some(l) = left.to_option() else {
+            return right;
+        };
+        self.splay_extreme(l, Dir::Right);
+        self.set_child(l, Dir::Right, right);
+        left
+    }
+
+    /// Splays the minimum (`Dir::Left`) or maximum (`Dir::Right`) of the
+    /// subtree to its top.
+    fn splay_extreme(&mut self, root: Idx, dir: Dir) {
+        let mut path = Path::Empty;
+        self.visit_extreme(root, dir, &mut path);
+        self.splay_finish(root, &path);
+    }
+
+    fn visit_extreme(&mut self, idx: Idx, dir: Dir, path: &mut Path) {
+        match self.child(idx, dir).to_option() {
+            Some(child) => {
+                self.visit_extreme(child, dir, path);
+                path.extend(dir);
+            }
+            None => *path = Path::Empty,
+        }
+        self.splay_step(idx, path);
+    }
+
+    /// Position in key order of the node in each slot.
+    fn ranks(&self) -> Vec<usize> {
+        let mut rank = alloc::vec![0; self.nodes.len()];
+        let mut stack = Vec::new();
+        let mut next = self.root.to_option();
+        let mut pos = 0;
+        while next.is_some() || !stack.is_empty() {
+            while let Some(idx) = next {
+                stack.push(idx);
+                next = self.nodes[idx].left.to_option();
+            }
+            let idx = stack.pop().unwrap();
+            rank[idx] = pos;
+            pos += 1;
+            next = self.nodes[idx].right.to_option();
+        }
+        rank
+    }
+
+    /// Consumes the tree, returning its entries in key order in O(n).
+    fn into_entries(mut self) -> Vec<(K, V)> {
+        let rank = self.ranks();
+        let mut slots: Vec<Option<(K, V)>> = Vec::new();
+        slots.resize_with(self.nodes.len(), || None);
+        for (idx, node) in self.nodes.drain(..).enumerate() {
+            slots[rank[idx]] = Some((node.key, node.value));
+        }
+        slots.into_iter().map(Option::unwrap).collect()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.visit(OrCreate::Lookup(key));
+        let root = self.root.to_option()?;
+        if self.cmp.compare(self.nodes[root].key.borrow(), key) != Equal {
+            return None;
+        }
+        Some(self.remove_root(&mut []).value)
+    }
+
+    /// Unlinks the root and frees its slot. `others` are the roots of other
+    /// trees living in the same arena, one of which may own the node moved
+    /// into the slot.
+    fn remove_root(&mut self, others: &mut [OptionIdx]) -> Node<K, V> {
+        let root = self.root.to_option().unwrap();
+        let (mut left, mut right) = (self.nodes[root].left, self.nodes[root].right);
+        let last = self.nodes.len() - 1;
+
+        // Keep the arena dense: the last node takes over the freed slot. Its
+        // link is found before anything changes, so a panicking comparison
+        // leaves the tree as it was.
+        let mut moved = None;
+        if root != last {
+            let in_left = self
+                .cmp
+                .compare(&self.nodes[last].key, &self.nodes[root].key)
+                == Less;
+            let subtree = if in_left { left } else { right };
+            moved = self.find_link(subtree, last);
+            for &other in others.iter() {
+                if moved.is_some() {
+                    break;
+                }
+                moved = self.find_link(other, last);
+            }
+            // An inconsistent comparator can send the searches astray, and
+            // a stale link would outlive the slot.
+            moved = moved.or_else(|| Some(self.scan_link(last, root)));
+        }
+
+        let node = self.nodes.swap_remove(root);
+        match moved {
+            None => {}
+            Some(Link::Child(parent, dir)) => self.set_child(parent, dir, OptionIdx(root)),
+            Some(Link::Top) => {
+                let mut tops = [&mut left, &mut right].into_iter().chain(others.iter_mut());
+                if let Some(top) = tops.find(|top| **top == OptionIdx(last)) {
+                    *top = OptionIdx(root);
+                }
+            }
+        }
+        // Everything on the left is smaller than the removed key.
+        self.root = self.join(left, right);
+        node
+    }
+
+    /// Removes every entry with a key in `range` and returns how many there
+    /// were. The range is cut out as one subtree by splaying its two ends, so
+    /// the only per-entry work is freeing the slots, which moves a surviving
+    /// node into each freed slot below the new length.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same malformed ranges as `range`.
+    pub fn remove_range<Q, R>(&mut self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+        R: RangeBounds<Q>,
+    {
+        compat::check_range_by(&range, &self.cmp);
+
+        // Cut off everything before the range, then everything after it,
+        // leaving the range itself at the root. Should a comparison panic,
+        // `cut` puts the pieces back together on the way out.
+        let mut cut = Reassemble {
+            tree: self,
+            left: IDX_NONE,
+            mid: IDX_NONE,
+            seam: IDX_NONE,
+        };
+        let tree = &mut *cut.tree;
+        if let Bound::Included(start) | Bound::Excluded(start) = range.start_bound() {
+            tree.visit(OrCreate::Lookup(start));
+            let Some(root) = tree.root.to_option() else {
+                return 0;
+            };
+            let order = tree.cmp.compare(tree.nodes[root].key.borrow(), start);
+            let within = match range.start_bound() {
+                Bound::Included(_) => order != Less,
+                _ => order == Greater,
+            };
+            cut.left = if within {
+                let left = tree.child(root, Dir::Left);
+                tree.set_child(root, Dir::Left, IDX_NONE);
+                left
+            } else {
+                let right = tree.child(root, Dir::Right);
+                tree.set_child(root, Dir::Right, IDX_NONE);
+                mem::replace(&mut tree.root, right)
+            };
+        }
+        let right = match (range.end_bound(), tree.root.to_option()) {
+            (Bound::Included(end) | Bound::Excluded(end), Some(_)) => {
+                tree.visit(OrCreate::Lookup(end));
+                let root = tree.root.to_option().unwrap();
+                let order = tree.cmp.compare(tree.nodes[root].key.borrow(), end);
+                let within = match range.end_bound() {
+                    Bound::Included(_) => order != Greater,
+                    _ => order == Less,
+                };
+                if within {
+                    let right = tree.child(root, Dir::Right);
+                    tree.set_child(root, Dir::Right, IDX_NONE);
+                    right
+                } else {
+                    let left = tree.child(root, Dir::Left);
+                    tree.set_child(root, Dir::Left, IDX_NONE);
+                    mem::replace(&mut tree.root, left)
+                }
+            }
+            _ => IDX_NONE,
+        };
+        cut.seam = mem::replace(&mut cut.left, IDX_NONE);
+        cut.mid = mem::replace(&mut tree.root, IDX_NONE);
+        tree.root = tree.join(cut.seam, right);
+
+        let mut dead = Vec::new();
+        let mut stack: Vec<Idx> = cut.mid.to_option().into_iter().collect();
+        while let Some(idx) = stack.pop() {
+            dead.push(idx);
+            let node = &tree.nodes[idx];
+            stack.extend(node.left.to_option());
+            stack.extend(node.right.to_option());
+        }
+        dead.sort_unstable();
+
+        // Fill the dead slots below the new length with the live nodes above
+        // it, keeping the arena dense. Their links are all found first, while
+        // the removed entries can still be put back.
+        let (len, count) = (tree.nodes.len(), dead.len());
+        let (holes, dead_above) = dead.split_at(dead.partition_point(|&idx| idx < len - count));
+        let movers: Vec<Idx> = (len - count..len)
+            .filter(|idx| dead_above.binary_search(idx).is_err())
+            .collect();
+        let mut links = Vec::with_capacity(movers.len());
+        for &from in &movers {
+            let link = tree.find_link(tree.root, from);
+            links.push(link.unwrap_or_else(|| tree.scan_link(from, from)));
+        }
+        cut.mid = IDX_NONE;
+        drop(cut);
+
+        for (&hole, &from) in holes.iter().zip(&movers) {
+            self.nodes.swap(hole, from);
+        }
+        let moved_to = |idx| movers.binary_search(&idx).map_or(idx, |i| holes[i]);
+        for (&hole, link) in holes.iter().zip(links) {
+            match link {
+                Link::Top => self.root = OptionIdx(hole),
+                Link::Child(parent, dir) => self.set_child(moved_to(parent), dir, OptionIdx(hole)),
+            }
+        }
+        self.nodes.truncate(len - count);
+        count
+    }
+}
+
+/// The pieces of a tree being cut apart by `remove_range`, reassembled when
+/// dropped. `left` is everything before the range while the end is still
+/// being looked for; once it's joined with everything after the range,
+/// `seam` is its top and the range `mid` belongs right of it.
+struct Reassemble<'a, K, V, C: Compare<K>, A: Allocator> {
+    tree: &'a mut Splay<K, V, C, A>,
+    left: OptionIdx,
+    mid: OptionIdx,
+    seam: OptionIdx,
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> Drop for Reassemble<'_, K, V, C, A> {
+    fn drop(&mut self) {
+        let tree = &mut *self.tree;
+        tree.root = tree.join(self.left, tree.root);
+        if self.mid.to_option().is_some() {
+            match self.seam.to_option() {
+                Some(seam) => {
+                    let right = tree.child(seam, Dir::Right);
+                    let right = tree.join(self.mid, right);
+                    tree.set_child(seam, Dir::Right, right);
+                }
+                None => tree.root = tree.join(self.mid, tree.root),
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> Splay<K, V> {
+    /// Links `nodes[lo..hi]`, which are in key order, into a balanced tree.
+    fn link_balanced(&mut self, lo: Idx, hi: Idx) -> OptionIdx {
+        if lo == hi {
+            return IDX_NONE;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = self.link_balanced(lo, mid);
+        let right = self.link_balanced(mid + 1, hi);
+        self.set_child(mid, Dir::Left, left);
+        self.set_child(mid, Dir::Right, right);
+        OptionIdx(mid)
+    }
+
+    /// Builds a balanced tree in O(n) if the entries are already sorted and
+    /// falls back to sorting them otherwise. Later duplicates win.
+    fn from_entries(mut entries: Vec<(K, V)>) -> Self {
+        if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+            // Reversing first makes the stable sort put the last duplicate
+            // first, which is the one `dedup_by` keeps.
+            entries.reverse();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries.dedup_by(|a, b| a.0 == b.0);
+        }
+        Self::from_sorted_unique(entries)
+    }
+
+    fn from_sorted_unique(entries: Vec<(K, V)>) -> Self {
+        let nodes: Vec<Node<K, V>> = entries
+            .into_iter()
+            .map(|(key, value)| Node {
+                key,
+                value,
+                left: IDX_NONE,
+                right: IDX_NONE,
+                #[cfg(feature = "stats")]
+                hits: 0,
+            })
+            .collect();
+        let mut tree = Splay {
+            root: IDX_NONE,
+            nodes: arena::from_vec(nodes),
+            cmp: Natural,
+            stats: Default::default(),
+        };
+        tree.root = tree.link_balanced(0, tree.nodes.len());
+        tree
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Splay<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self::from_entries(iter.into_iter().collect())
+    }
+}
+
+impl<K: Ord, V> From<Vec<(K, V)>> for Splay<K, V> {
+    fn from(entries: Vec<(K, V)>) -> Self {
+        Self::from_entries(entries)
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for Splay<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        Self::from_entries(Vec::from(entries))
+    }
+}
+
+impl<K: Ord, V> From<BTreeMap<K, V>> for Splay<K, V> {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Self::from_sorted_unique(map.into_iter().collect())
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> From<Splay<K, V, C, A>> for Vec<(K, V)> {
+    fn from(tree: Splay<K, V, C, A>) -> Self {
+        tree.into_entries()
+    }
+}
+
+impl<K: Ord, V, A: Allocator> From<Splay<K, V, Natural, A>> for BTreeMap<K, V> {
+    fn from(tree: Splay<K, V, Natural, A>) -> Self {
+        tree.into_entries().into_iter().collect()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C: Compare<K>, A: Allocator> fmt::Debug for Splay<K, V, C, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V> Default for Splay<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use alloc::vec::Vec;
+    use core::marker::PhantomData;
+
+    use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::Splay;
+
+    /// Serialized as a sequence of `(key, value)` pairs in key order.
+    impl<K: Ord + Serialize, V: Serialize> Serialize for Splay<K, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    struct SplayVisitor<K, V>(PhantomData<(K, V)>);
+
+    impl<'de, K: Ord + Deserialize<'de>, V: Deserialize<'de>> Visitor<'de> for SplayVisitor<K, V> {
+        type Value = Splay<K, V>;
+
+        fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+            f.write_str("a sequence of key-value pairs")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut entries = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(entry) = seq.next_element()? {
+                entries.push(entry);
+            }
+            Ok(Splay::from_entries(entries))
+        }
+    }
+
+    impl<'de, K: Ord + Deserialize<'de>, V: Deserialize<'de>> Deserialize<'de> for Splay<K, V> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_seq(SplayVisitor(PhantomData))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn roundtrip_test() {
+            let tree: Splay<String, u32> = [("b", 2), ("a", 1), ("c", 3)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect();
+            let json = serde_json::to_string(&tree).unwrap();
+            assert_eq!(json, r#"[["a",1],["b",2],["c",3]]"#);
+
+            let mut tree: Splay<String, u32> = serde_json::from_str(&json).unwrap();
+            assert_eq!(tree.len(), 3);
+            assert_eq!(tree.get("b"), Some(&2));
         }
     }
 }
 
+#[cfg(feature = "rkyv")]
+mod rkyv_impl {
+    use alloc::vec::Vec;
+    use core::cmp::Ordering::{Equal, Greater, Less};
+
+    use rkyv::bytecheck::CheckBytes;
+    use rkyv::munge::munge;
+    use rkyv::rancor::Fallible;
+    use rkyv::ser::{Allocator, Writer};
+    use rkyv::vec::{ArchivedVec, VecResolver};
+    use rkyv::{Archive, Archived, Deserialize, Place, Portable, Serialize};
+
+    use super::{arena, ArchivedNode, Node, OptionIdx, Splay, IDX_NONE};
+    use crate::compare::Natural;
+
+    /// Links are archived as fixed 64-bit indices so the empty marker
+    /// survives on any pointer width.
+    #[derive(Portable, CheckBytes)]
+    #[bytecheck(crate = rkyv::bytecheck)]
+    #[repr(transparent)]
+    pub struct ArchivedOptionIdx(Archived<u64>);
+
+    impl ArchivedOptionIdx {
+        #[inline]
+        fn to_option(&self) -> Option<usize> {
+            match self.0.to_native() {
+                u64::MAX => None,
+                idx => Some(idx as usize),
+            }
+        }
+    }
+
+    impl Archive for OptionIdx {
+        type Archived = ArchivedOptionIdx;
+        type Resolver = ();
+
+        fn resolve(&self, _: (), out: Place<Self::Archived>) {
+            let idx = match self.to_option() {
+                None => u64::MAX,
+                Some(idx) => idx as u64,
+            };
+            munge!(let ArchivedOptionIdx(out) = out);
+            out.write(Archived::<u64>::from_native(idx));
+        }
+    }
+
+    impl<S: Fallible + ?Sized> Serialize<S> for OptionIdx {
+        fn serialize(&self, _: &mut S) -> Result<(), S::Error> {
+            Ok(())
+        }
+    }
+
+    impl<D: Fallible + ?Sized> Deserialize<OptionIdx, D> for ArchivedOptionIdx {
+        fn deserialize(&self, _: &mut D) -> Result<OptionIdx, D::Error> {
+            Ok(self.to_option().map_or(IDX_NONE, OptionIdx))
+        }
+    }
+
+    #[derive(Portable, CheckBytes)]
+    #[bytecheck(crate = rkyv::bytecheck)]
+    #[repr(C)]
+    pub struct ArchivedSplay<K: Archive, V: Archive> {
+        root: ArchivedOptionIdx,
+        nodes: ArchivedVec<ArchivedNode<K, V>>,
+    }
+
+    impl<K: Archive, V: Archive> Archive for Splay<K, V> {
+        type Archived = ArchivedSplay<K, V>;
+        type Resolver = VecResolver;
+
+        fn resolve(&self, resolver: VecResolver, out: Place<Self::Archived>) {
+            munge!(let ArchivedSplay { root, nodes } = out);
+            self.root.resolve((), root);
+            ArchivedVec::resolve_from_slice(&self.nodes, resolver, nodes);
+        }
+    }
+
+    impl<K, V, S> Serialize<S> for Splay<K, V>
+    where
+        K: Serialize<S>,
+        V: Serialize<S>,
+        S: Fallible + Allocator + Writer + ?Sized,
+    {
+        fn serialize(&self, serializer: &mut S) -> Result<VecResolver, S::Error> {
+            ArchivedVec::serialize_from_slice(&self.nodes, serializer)
+        }
+    }
+
+    impl<K, V, D> Deserialize<Splay<K, V>, D> for ArchivedSplay<K, V>
+    where
+        K: Archive,
+        V: Archive,
+        ArchivedVec<ArchivedNode<K, V>>: Deserialize<Vec<Node<K, V>>, D>,
+        D: Fallible + ?Sized,
+    {
+        fn deserialize(&self, deserializer: &mut D) -> Result<Splay<K, V>, D::Error> {
+            let tree = Splay {
+                root: self.root.deserialize(deserializer)?,
+                nodes: arena::from_vec(self.nodes.deserialize(deserializer)?),
+                cmp: Natural,
+                stats: Default::default(),
+            };
+            // The only property the rest of the code can't recover from,
+            // and which lookups skip checking with the `unchecked` feature.
+            let len = tree.nodes.len();
+            let in_bounds = |link: OptionIdx| link.to_option().is_none_or(|idx| idx < len);
+            assert!(
+                in_bounds(tree.root)
+                    && tree
+                        .nodes
+                        .iter()
+                        .all(|n| in_bounds(n.left) && in_bounds(n.right)),
+                "archived tree links past its nodes"
+            );
+            Ok(tree)
+        }
+    }
+
+    /// Read-only queries straight on the archived bytes. `rkyv::access` only
+    /// validates the layout, so a corrupted buffer can make these panic, but
+    /// they always terminate.
+    impl<K: Archive, V: Archive> ArchivedSplay<K, V> {
+        pub fn len(&self) -> usize {
+            self.nodes.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.nodes.is_empty()
+        }
+
+        fn node(&self, idx: usize) -> &ArchivedNode<K, V> {
+            &self.nodes[idx]
+        }
+
+        pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V::Archived>
+        where
+            K::Archived: PartialOrd<Q>,
+        {
+            let mut idx = self.root.to_option();
+            for _ in 0..self.nodes.len() {
+                let node = self.node(idx?);
+                idx = match node.key.partial_cmp(key)? {
+                    Equal => return Some(&node.value),
+                    Greater => node.left.to_option(),
+                    Less => node.right.to_option(),
+                };
+            }
+            None
+        }
+
+        pub fn iter(&self) -> ArchivedSplayIter<'_, K, V> {
+            let mut iter = ArchivedSplayIter {
+                tree: self,
+                stack: Vec::new(),
+                remaining: self.nodes.len(),
+            };
+            iter.towards_min(self.root.to_option());
+            iter
+        }
+    }
+
+    pub struct ArchivedSplayIter<'a, K: Archive, V: Archive> {
+        tree: &'a ArchivedSplay<K, V>,
+        stack: Vec<usize>,
+        // Nodes not yielded yet, bounds the walk even if links form a cycle.
+        remaining: usize,
+    }
+
+    impl<K: Archive, V: Archive> ArchivedSplayIter<'_, K, V> {
+        fn towards_min(&mut self, mut idx: Option<usize>) {
+            while let Some(i) = idx {
+                if self.stack.len() >= self.remaining {
+                    break;
+                }
+                self.stack.push(i);
+                idx = self.tree.node(i).left.to_option();
+            }
+        }
+    }
+
+    impl<'a, K: Archive, V: Archive> Iterator for ArchivedSplayIter<'a, K, V> {
+        type Item = (&'a K::Archived, &'a V::Archived);
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.remaining == 0 {
+                return None;
+            }
+            self.remaining -= 1;
+            let node = self.tree.node(self.stack.pop()?);
+            self.towards_min(node.right.to_option());
+            Some((&node.key, &node.value))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use rkyv::rancor::Error;
+
+        use super::*;
+        use crate::splay::Splay;
+
+        #[test]
+        fn archive_test() {
+            let mut tree: Splay<u32, String> = Splay::new();
+            for i in [5, 3, 8, 1, 4] {
+                tree.set(i, i.to_string());
+            }
+            tree.get(&4);
+
+            let bytes = rkyv::to_bytes::<Error>(&tree).unwrap();
+            let archived = rkyv::access::<ArchivedSplay<u32, String>, Error>(&bytes).unwrap();
+            assert_eq!(archived.len(), 5);
+            assert_eq!(archived.get(&8).map(|s| s.as_str()), Some("8"));
+            assert_eq!(archived.get(&2), None);
+            assert_eq!(
+                archived
+                    .iter()
+                    .map(|(k, _)| k.to_native())
+                    .collect::<Vec<u32>>(),
+                vec![1, 3, 4, 5, 8]
+            );
+
+            let mut tree = rkyv::deserialize::<Splay<u32, String>, Error>(archived).unwrap();
+            assert_eq!(tree.get(&1).map(|s| s.as_str()), Some("1"));
+            let empty: Splay<u32, String> = Splay::new();
+            let bytes = rkyv::to_bytes::<Error>(&empty).unwrap();
+            let archived = rkyv::access::<ArchivedSplay<u32, String>, Error>(&bytes).unwrap();
+            assert_eq!(archived.iter().count(), 0);
+        }
+    }
+}
+
+#[cfg(feature = "rkyv")]
+pub use rkyv_impl::{ArchivedSplay, ArchivedSplayIter};
+
 #[cfg(test)]
 mod tests {
     use quickcheck::{Arbitrary, Gen};
@@ -327,11 +1392,16 @@ mod tests {
         let mut tree: Splay<i32, i32> = Splay::new();
         tree.set(1, 1);
         tree.set(2, 2);
-        assert_eq!(tree.get(1), Some(&1));
-        assert_eq!(tree.get(2), Some(&2));
-        assert_eq!(tree.get(3), None);
+        assert_eq!(tree.get(&1), Some(&1));
+        assert_eq!(tree.get(&2), Some(&2));
+        assert_eq!(tree.get(&3), None);
+        assert_eq!(tree.peek(&1), Some(&1));
+        assert_eq!(tree.peek(&3), None);
+        *tree.get_or_insert_with(3, || 0) += 3;
+        *tree.get_or_insert_with(3, || 0) += 3;
+        assert_eq!(tree.remove(&3), Some(6));
         tree.set(2, 1);
-        assert_eq!(tree.get(2), Some(&1));
+        assert_eq!(tree.get(&2), Some(&1));
         assert_eq!(
             tree.iter()
                 .map(|(x, y)| (*x, *y))
@@ -341,6 +1411,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore = "too slow under Miri")]
     fn depth_test() {
         let mut rng = rand::rng();
         let mut tree: Splay<i32, i32> = Splay::new();
@@ -359,15 +1430,17 @@ mod tests {
     enum Op {
         Set(i32, i32),
         Get(i32),
+        Remove(i32),
         CompareSorted,
     }
 
     impl Arbitrary for Op {
         fn arbitrary(g: &mut Gen) -> Self {
-            match *g.choose(&[0, 1, 2]).unwrap() {
+            match *g.choose(&[0, 1, 2, 3]).unwrap() {
                 0 => Op::Set(i32::arbitrary(g), i32::arbitrary(g)),
                 1 => Op::Get(i32::arbitrary(g)),
-                2 => Op::CompareSorted,
+                2 => Op::Remove(i32::arbitrary(g)),
+                3 => Op::CompareSorted,
                 _ => unreachable!(),
             }
         }
@@ -385,19 +1458,264 @@ mod tests {
                     map.insert(k, v);
                 }
                 Op::Get(k) => {
-                    if tree.get(k) != map.get(&k) {
+                    if tree.get(&k) != map.get(&k) {
+                        return false;
+                    }
+                }
+                Op::Remove(k) => {
+                    if tree.remove(&k) != map.remove(&k) {
                         return false;
                     }
                 }
                 Op::CompareSorted => {
                     let tree_vec: Vec<i32> = tree.iter().map(|(k, _)| *k).collect();
-                    let mut map_vec: Vec<i32> = map.iter().map(|(k, _)| *k).collect();
+                    let mut map_vec: Vec<i32> = map.keys().copied().collect();
                     map_vec.sort();
                     assert_eq!(tree_vec, map_vec);
                 }
             }
         }
 
-        return true;
+        true
+    }
+
+    #[test]
+    fn from_iter_test() {
+        let tree: Splay<i32, i32> = (0..1000).map(|x| (x, x)).collect();
+        assert!(tree.depth() <= 10);
+
+        let mut tree: Splay<i32, i32> = vec![(3, 1), (1, 1), (3, 2), (2, 1)].into_iter().collect();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree.get(&3), Some(&2));
+    }
+
+    thread_local! {
+        static BUDGET: std::cell::Cell<usize> = const { std::cell::Cell::new(usize::MAX) };
+    }
+
+    /// A key whose comparisons panic once `BUDGET` runs out.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct Fragile(u8);
+
+    impl Ord for Fragile {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            BUDGET.with(|budget| {
+                let left = budget.get().checked_sub(1).expect("out of comparisons");
+                budget.set(left);
+            });
+            self.0.cmp(&other.0)
+        }
+    }
+
+    impl PartialOrd for Fragile {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_panic_safety(keys: Vec<u8>, op: (u8, u8, u8), budget: u8) -> bool {
+        let mut tree: Splay<Fragile, ()> = Splay::new();
+        let mut before = BTreeMap::new();
+        for &k in &keys {
+            tree.set(Fragile(k), ());
+            before.insert(k, ());
+        }
+        let (kind, a, b) = op;
+        let (a, b) = (a.min(b), a.max(b));
+        let mut after = before.clone();
+        BUDGET.with(|cell| cell.set(budget as usize));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| match kind % 3 {
+            0 => tree.set(Fragile(a), ()),
+            1 => drop(tree.remove(&Fragile(a))),
+            _ => drop(tree.remove_range(Fragile(a)..=Fragile(b))),
+        }));
+        BUDGET.with(|cell| cell.set(usize::MAX));
+        match kind % 3 {
+            0 => drop(after.insert(a, ())),
+            1 => drop(after.remove(&a)),
+            _ => after.retain(|&k, _| !(a..=b).contains(&k)),
+        }
+
+        let keys: Vec<u8> = tree.keys().map(|k| k.0).collect();
+        tree.check_invariants().is_ok()
+            && (keys.iter().eq(after.keys()) || (result.is_err() && keys.iter().eq(before.keys())))
+    }
+
+    #[test]
+    fn lying_comparator_test() {
+        // Finds equal keys equal but orders the rest at random, so searches
+        // go the wrong way.
+        let state = core::cell::Cell::new(1u32);
+        let mut tree = Splay::with_comparator(|a: &u32, b: &u32| {
+            let mut x = state.get();
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            state.set(x);
+            match a == b {
+                true => Equal,
+                false if x & 1 == 0 => Less,
+                false => Greater,
+            }
+        });
+        for i in 0..64 {
+            tree.insert(i * 7 % 64, i);
+        }
+        for i in 0..256 {
+            tree.remove(&(i * 3 % 64));
+            assert_eq!(tree.iter().count(), tree.len());
+        }
+        tree.extend((0..64).map(|i| (i, i)));
+        tree.remove_range(10..40);
+        assert_eq!(tree.iter().count(), tree.len());
+    }
+
+    #[test]
+    fn try_reserve_test() {
+        let mut tree: Splay<u32, u32> = Splay::new();
+        assert!(tree.try_reserve(usize::MAX).is_err());
+        tree.try_reserve(10).unwrap();
+        let capacity = tree.capacity();
+        assert!(capacity >= 10);
+        for i in 0..10 {
+            tree.set(i, i);
+        }
+        assert_eq!(tree.capacity(), capacity);
+        tree.shrink_to_fit();
+        assert_eq!(tree.capacity(), 10);
+    }
+
+    #[test]
+    fn get_many_mut_test() {
+        let mut tree: Splay<&str, i32> = [("a", 10), ("b", 20), ("c", 30)].into();
+        let [a, c] = tree.get_many_mut(["a", "c"]).unwrap();
+        *a -= 5;
+        *c += 5;
+        assert_eq!(tree.peek("a"), Some(&5));
+        assert_eq!(tree.peek("c"), Some(&35));
+        assert!(tree.get_many_mut(["a", "a"]).is_none());
+        assert!(tree.get_many_mut(["a", "z"]).is_none());
+    }
+
+    #[test]
+    fn map_values_test() {
+        let mut tree: Splay<u32, u32> = (0..10).map(|k| (k, k)).collect();
+        tree.get(&7);
+        tree.map_values(|v| *v *= 2);
+        assert_eq!(tree.peek(&4), Some(&8));
+        let depth = tree.depth();
+
+        let tree = tree.into_map_values(|v| v.to_string());
+        assert_eq!(tree.depth(), depth);
+        assert_eq!(tree.peek(&7).map(String::as_str), Some("14"));
+        assert!(tree.keys().copied().eq(0..10));
+
+        // The comparator comes along, so the keys stay in its order.
+        let mut tree = Splay::with_comparator(crate::compare::Descending(Natural));
+        tree.extend((0..10).map(|k| (k, k)));
+        tree.map_values(|v| *v += 1);
+        let mut tree = tree.into_map_values(|v| v * 10);
+        assert!(tree.keys().copied().eq((0..10).rev()));
+        tree.insert(20, 0);
+        assert_eq!(tree.get(&3), Some(&40));
+        assert_eq!(tree.first_key_value(), Some((&20, &0)));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_remove_range(keys: Vec<u8>, lookups: Vec<u8>, a: u8, b: u8) -> bool {
+        let mut tree: Splay<u8, u8> = Splay::new();
+        for &k in &keys {
+            tree.set(k, k);
+        }
+        for k in &lookups {
+            tree.get(k);
+        }
+        let mut model: BTreeMap<u8, u8> = keys.iter().map(|&k| (k, k)).collect();
+        let range = (Bound::Excluded(a.min(b)), Bound::Included(a.max(b)));
+        let before = model.len();
+        model.retain(|k, _| !range.contains(k));
+        tree.remove_range(range) == before - model.len()
+            && tree.check_invariants().is_ok()
+            && tree.iter().eq(model.iter())
+            && tree.remove_range(..a) == model.range(..a).count()
+            && tree.iter().eq(model.range(a..))
+    }
+
+    #[test]
+    fn conversion_test() {
+        let map: BTreeMap<i32, i32> = (0..100).map(|x| (x, -x)).collect();
+        let mut tree = Splay::from(map.clone());
+        assert!(tree.depth() <= 7);
+        assert_eq!(tree.get(&42), Some(&-42));
+        assert_eq!(BTreeMap::from(tree), map);
+
+        let mut tree = Splay::from([(2, 'b'), (1, 'a'), (2, 'c')]);
+        assert_eq!(tree.get(&2), Some(&'c'));
+        tree.set(0, 'z');
+        assert_eq!(Vec::from(tree), [(0, 'z'), (1, 'a'), (2, 'c')]);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_from_iter(entries: Vec<(u8, i32)>) -> bool {
+        let mut tree: Splay<u8, i32> = entries.iter().copied().collect();
+        let map: HashMap<u8, i32> = entries.iter().copied().collect();
+
+        let mut expected: Vec<(u8, i32)> = map.into_iter().collect();
+        expected.sort();
+        tree.iter()
+            .map(|(k, v)| (*k, *v))
+            .eq(expected.iter().copied())
+            && expected.iter().all(|(k, v)| tree.get(k) == Some(v))
+    }
+
+    #[cfg(feature = "allocator_api")]
+    #[test]
+    fn allocator_test() {
+        use core::alloc::{AllocError, Layout};
+        use core::cell::Cell;
+        use core::ptr::NonNull;
+
+        #[derive(Clone, Copy)]
+        struct Counting<'a>(&'a Cell<usize>);
+
+        unsafe impl Allocator for Counting<'_> {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.0.set(self.0.get() + 1);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                unsafe { Global.deallocate(ptr, layout) }
+            }
+        }
+
+        let allocations = Cell::new(0);
+        let mut tree = Splay::new_in(Counting(&allocations));
+        for i in 0..100 {
+            tree.set(i, i);
+        }
+        assert_eq!(tree.remove(&50), Some(50));
+        assert_eq!(tree.get(&99), Some(&99));
+        assert!(allocations.get() > 0);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_remove(ops: Vec<(bool, u8)>) -> bool {
+        let mut tree: Splay<u8, u8> = Splay::new();
+        let mut map: HashMap<u8, u8> = HashMap::new();
+
+        for &(insert, k) in ops.iter() {
+            if insert {
+                tree.set(k, k);
+                map.insert(k, k);
+            } else if tree.remove(&k) != map.remove(&k) {
+                return false;
+            }
+        }
+
+        let mut keys: Vec<u8> = map.keys().copied().collect();
+        keys.sort();
+        tree.len() == map.len() && tree.iter().map(|(k, _)| *k).eq(keys)
     }
 }
@@ -0,0 +1,89 @@
+//! Node storage. With the nightly `allocator_api` feature it's a plain
+//! `Vec<T, A>`, otherwise `A` is a marker which only `Global` satisfies.
+
+#[cfg(feature = "allocator_api")]
+pub use alloc::alloc::{Allocator, Global};
+
+#[cfg(feature = "allocator_api")]
+pub(super) type Nodes<T, A> = alloc::vec::Vec<T, A>;
+
+#[cfg(feature = "allocator_api")]
+#[inline]
+pub(super) fn from_vec<T>(nodes: alloc::vec::Vec<T>) -> Nodes<T, Global> {
+    nodes
+}
+
+/// Maps every node through `f` into an arena from the same allocator.
+#[cfg(feature = "allocator_api")]
+pub(super) fn map<T, U, A: Allocator + Clone>(
+    nodes: Nodes<T, A>,
+    f: impl FnMut(T) -> U,
+) -> Nodes<U, A> {
+    let mut mapped = alloc::vec::Vec::with_capacity_in(nodes.len(), nodes.allocator().clone());
+    mapped.extend(nodes.into_iter().map(f));
+    mapped
+}
+
+#[cfg(not(feature = "allocator_api"))]
+pub use stable::{Allocator, Global};
+
+#[cfg(not(feature = "allocator_api"))]
+pub(super) use stable::{from_vec, map, Nodes};
+
+#[cfg(not(feature = "allocator_api"))]
+mod stable {
+    use alloc::vec::Vec;
+    use core::marker::PhantomData;
+    use core::ops::{Deref, DerefMut};
+
+    mod sealed {
+        pub trait Sealed {}
+    }
+
+    /// Stand-in for the unstable `core::alloc::Allocator`.
+    pub trait Allocator: sealed::Sealed {}
+
+    /// The global allocator, the only one available without the nightly
+    /// `allocator_api` feature.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct Global;
+
+    impl sealed::Sealed for Global {}
+    impl Allocator for Global {}
+
+    #[derive(Clone)]
+    pub struct Nodes<T, A>(Vec<T>, PhantomData<A>);
+
+    impl<T, A> Nodes<T, A> {
+        #[inline]
+        pub fn new_in(_: A) -> Self {
+            Nodes(Vec::new(), PhantomData)
+        }
+    }
+
+    #[inline]
+    pub fn from_vec<T>(nodes: Vec<T>) -> Nodes<T, Global> {
+        Nodes(nodes, PhantomData)
+    }
+
+    #[inline]
+    pub fn map<T, U, A>(nodes: Nodes<T, A>, f: impl FnMut(T) -> U) -> Nodes<U, A> {
+        Nodes(nodes.0.into_iter().map(f).collect(), PhantomData)
+    }
+
+    impl<T, A> Deref for Nodes<T, A> {
+        type Target = Vec<T>;
+
+        #[inline]
+        fn deref(&self) -> &Vec<T> {
+            &self.0
+        }
+    }
+
+    impl<T, A> DerefMut for Nodes<T, A> {
+        #[inline]
+        fn deref_mut(&mut self) -> &mut Vec<T> {
+            &mut self.0
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use core::borrow::Borrow;
+use core::fmt;
+
+use super::{Dir, Idx, Path, Splay, SplayIter};
+use crate::rng::split_mix64;
+
+fn drop_entry<K, V>(_: K, _: V) {}
+
+impl<K: Ord, V> Splay<K, V> {
+    /// Removes a leaf, walking down from the root the way `bits` say and
+    /// splaying the leaf reached before unlinking it.
+    fn pop_leaf(&mut self, bits: u64) -> Option<(K, V)> {
+        let root = self.root.to_option()?;
+        let mut path = Path::Empty;
+        self.visit_leaf(root, bits, &mut path);
+        self.splay_finish(root, &path);
+        let node = self.remove_root(&mut []);
+        Some((node.key, node.value))
+    }
+
+    fn visit_leaf(&mut self, idx: Idx, bits: u64, path: &mut Path) {
+        let dir = if bits & 1 == 0 { Dir::Left } else { Dir::Right };
+        let next = [dir, dir.flip()]
+            .into_iter()
+            .find_map(|dir| Some((self.child(idx, dir).to_option()?, dir)));
+        match next {
+            Some((child, dir)) => {
+                self.visit_leaf(child, bits.rotate_right(1), path);
+                path.extend(dir);
+            }
+            None => *path = Path::Empty,
+        }
+        self.splay_step(idx, path);
+    }
+}
+
+/// A [`Splay`] holding at most `capacity` entries, evicting one to make
+/// room for each new key once full.
+///
+/// Every access splays its entry to the root, so entries sink as others
+/// are used, and the leaves are those untouched for longest, roughly. The
+/// one evicted is a leaf found by walking down from the root a pseudorandom
+/// way, which costs as much as a lookup. It goes to `on_evict`, which by
+/// default drops it. Unlike the caches in [`cache`](crate::cache) this
+/// keeps no recency list, so it costs nothing beyond the tree itself, but
+/// it evicts by approximate recency only.
+pub struct BoundedSplay<K, V, F = fn(K, V)> {
+    tree: Splay<K, V>,
+    capacity: usize,
+    on_evict: F,
+    // Splitmix state for the walks down to a leaf.
+    rng: u64,
+}
+
+impl<K: Ord, V> BoundedSplay<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_eviction(capacity, drop_entry)
+    }
+}
+
+impl<K: Ord, V, F: FnMut(K, V)> BoundedSplay<K, V, F> {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_eviction(capacity: usize, on_evict: F) -> Self {
+        assert!(capacity > 0, "capacity must be positive");
+        BoundedSplay {
+            tree: Splay::new(),
+            capacity,
+            on_evict,
+            rng: 0,
+        }
+    }
+
+    /// Makes the entries evicted only depend on `seed` and the operations,
+    /// rather than on the seed every map starts with.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = seed;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Changes the capacity, evicting entries until they fit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        assert!(capacity > 0, "capacity must be positive");
+        self.capacity = capacity;
+        while self.tree.len() > capacity {
+            self.evict();
+        }
+    }
+
+    fn evict(&mut self) {
+        if let Some((key, value)) = self.tree.pop_leaf(split_mix64(&mut self.rng)) {
+            (self.on_evict)(key, value);
+        }
+    }
+
+    /// Inserts or updates an entry. A new key evicts another first if the
+    /// map is full.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.tree.get_mut(&key) {
+            return Some(core::mem::replace(old, value));
+        }
+        if self.tree.len() == self.capacity {
+            self.evict();
+        }
+        self.tree.set(key, value);
+        None
+    }
+
+    /// Looks `key` up, splaying it to the root so it's evicted last.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.get(key)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.get_mut(key)
+    }
+
+    /// Looks `key` up without splaying, so without saving it from eviction.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.peek(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.contains_key(key)
+    }
+
+    /// Removes an entry, without calling `on_evict`.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.remove(key)
+    }
+
+    pub fn iter(&self) -> SplayIter<'_, K, V> {
+        self.tree.iter()
+    }
+
+    /// Drops every entry, without calling `on_evict`.
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    pub fn into_inner(self) -> Splay<K, V> {
+        self.tree
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug, F> fmt::Debug for BoundedSplay<K, V, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.tree.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let evicted = RefCell::new(Vec::new());
+        let mut map = BoundedSplay::with_eviction(4, |k, v| evicted.borrow_mut().push((k, v)));
+        for i in 0..4 {
+            assert_eq!(map.insert(i, i * 10), None);
+        }
+        assert_eq!(map.insert(2, 21), Some(20));
+        assert_eq!(map.get(&0), Some(&0));
+        assert_eq!(map.insert(4, 40), None);
+        assert_eq!(map.len(), 4);
+        // The key just used is at the root, never a leaf.
+        assert!(map.contains_key(&0) && map.contains_key(&4));
+        map.set_capacity(2);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.remove(&4), Some(40));
+        drop(map);
+        assert_eq!(evicted.into_inner().len(), 3);
+
+        // The same seed and operations evict the same entries.
+        let [left, right] = [(); 2].map(|_| {
+            let mut map = BoundedSplay::new(8).with_seed(3);
+            (0..100).for_each(|k| _ = map.insert(k, ()));
+            map.iter().map(|(&k, _)| k).collect::<Vec<_>>()
+        });
+        assert_eq!(left, right);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(capacity: u8, ops: Vec<(u8, u8)>) -> bool {
+        let capacity = capacity as usize % 8 + 1;
+        let evicted = RefCell::new(Vec::new());
+        let mut map =
+            BoundedSplay::with_eviction(capacity, |k, v| evicted.borrow_mut().push((k, v)));
+        let mut model = BTreeMap::new();
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 16;
+            let ok = match op % 3 {
+                0 => map.insert(key, i) == model.insert(key, i),
+                1 => map.get(&key) == model.get(&key),
+                _ => map.remove(&key) == model.remove(&key),
+            };
+            for (key, value) in evicted.borrow_mut().drain(..) {
+                if model.remove(&key) != Some(value) {
+                    return false;
+                }
+            }
+            if !ok || map.len() != model.len() || map.len() > capacity {
+                return false;
+            }
+        }
+        map.iter().eq(model.iter())
+    }
+}
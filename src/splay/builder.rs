@@ -0,0 +1,88 @@
+use super::{Allocator, Global, Splay};
+use crate::compare::{Compare, Natural};
+
+/// Configures a [`Splay`] before it's created: its ordering, allocator and
+/// how many entries to make room for.
+#[derive(Clone, Debug)]
+pub struct SplayBuilder<C = Natural, A: Allocator = Global> {
+    capacity: usize,
+    cmp: C,
+    alloc: A,
+}
+
+impl SplayBuilder {
+    pub fn new() -> Self {
+        SplayBuilder {
+            capacity: 0,
+            cmp: Natural,
+            alloc: Global,
+        }
+    }
+}
+
+impl<C, A: Allocator> SplayBuilder<C, A> {
+    /// Room for this many entries, allocated up front.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Orders the keys with `cmp`, see [`Splay::with_comparator`].
+    pub fn comparator<D>(self, cmp: D) -> SplayBuilder<D, A> {
+        SplayBuilder {
+            capacity: self.capacity,
+            cmp,
+            alloc: self.alloc,
+        }
+    }
+
+    /// Allocator for the nodes, custom ones need the nightly
+    /// `allocator_api` feature.
+    pub fn allocator<B: Allocator>(self, alloc: B) -> SplayBuilder<C, B> {
+        SplayBuilder {
+            capacity: self.capacity,
+            cmp: self.cmp,
+            alloc,
+        }
+    }
+
+    pub fn build<K, V>(self) -> Splay<K, V, C, A>
+    where
+        C: Compare<K>,
+    {
+        let mut tree = Splay::with_comparator_in(self.cmp, self.alloc);
+        tree.nodes.reserve_exact(self.capacity);
+        tree
+    }
+}
+
+impl Default for SplayBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::Descending;
+
+    #[test]
+    fn basic_test() {
+        let mut tree = SplayBuilder::new().capacity(100).build();
+        assert!(tree.capacity() >= 100);
+        tree.set("a", 1);
+        assert_eq!(tree.get("a"), Some(&1));
+
+        let tree: Splay<u8, u8> = SplayBuilder::new().allocator(Global).build();
+        assert_eq!(tree.capacity(), 0);
+
+        let mut tree = SplayBuilder::new()
+            .comparator(Descending(Natural))
+            .capacity(3)
+            .build();
+        tree.extend([(1, 'a'), (3, 'c'), (2, 'b')]);
+        assert!(tree.capacity() >= 3);
+        assert_eq!(tree.keys().copied().collect::<Vec<_>>(), [3, 2, 1]);
+    }
+}
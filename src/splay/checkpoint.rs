@@ -0,0 +1,232 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{Splay, SplayIter};
+
+/// A [`Splay`] which can roll back to named checkpoints taken of it.
+///
+/// A checkpoint costs nothing to take: while there are any, each change
+/// logs the entry it overwrote, a clone of its key and old value, and
+/// `rollback_to` puts back the entries logged since the checkpoint, newest
+/// first, so it takes time in the changes made since rather than in the
+/// size of the tree. The log holds the changes since the oldest checkpoint
+/// and goes once the last is released. With no checkpoints a change is as
+/// cheap as on the tree itself.
+pub struct CheckpointSplay<K, V, L = &'static str> {
+    tree: Splay<K, V>,
+    // `(key, old value)` for every change since the oldest checkpoint.
+    log: Vec<(K, Option<V>)>,
+    // Each checkpoint with the length of the log when it was taken.
+    checkpoints: Vec<(L, usize)>,
+}
+
+impl<K: Ord + Clone, V: Clone, L: PartialEq> CheckpointSplay<K, V, L> {
+    pub fn new() -> Self {
+        CheckpointSplay {
+            tree: Splay::new(),
+            log: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.checkpoints.is_empty() {
+            return self.tree.insert(key, value);
+        }
+        let old = self.tree.insert(key.clone(), value);
+        self.log.push((key, old.clone()));
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let old = self.tree.remove(key)?;
+        if !self.checkpoints.is_empty() {
+            self.log.push((key.clone(), Some(old.clone())));
+        }
+        Some(old)
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.tree.get(key)
+    }
+
+    /// Looks `key` up without splaying.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.tree.peek(key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.tree.contains_key(key)
+    }
+
+    pub fn iter(&self) -> SplayIter<'_, K, V> {
+        self.tree.iter()
+    }
+
+    /// The map itself, to read, not change.
+    pub fn as_splay(&self) -> &Splay<K, V> {
+        &self.tree
+    }
+
+    /// Takes a checkpoint of the map as it is now, named `label`.
+    pub fn checkpoint(&mut self, label: L) {
+        self.checkpoints.push((label, self.log.len()));
+    }
+
+    /// The labels of the checkpoints, oldest first.
+    pub fn checkpoints(&self) -> impl Iterator<Item = &L> {
+        self.checkpoints.iter().map(|(label, _)| label)
+    }
+
+    fn find(&self, label: &L) -> Option<usize> {
+        self.checkpoints.iter().rposition(|(l, _)| l == label)
+    }
+
+    /// Puts the map back as it was at the latest checkpoint named `label`,
+    /// returning whether there was one. The checkpoints taken after it are
+    /// dropped, and it's kept, to roll back to again.
+    pub fn rollback_to(&mut self, label: &L) -> bool {
+        let Some(i) = self.find(label) else {
+            return false;
+        };
+        let at = self.checkpoints[i].1;
+        self.checkpoints.truncate(i + 1);
+        for (key, old) in self.log.drain(at..).rev() {
+            match old {
+                Some(value) => self.tree.insert(key, value),
+                None => self.tree.remove(&key),
+            };
+        }
+        true
+    }
+
+    /// Drops the latest checkpoint named `label`, keeping the changes made
+    /// since, and returns whether there was one.
+    pub fn release(&mut self, label: &L) -> bool {
+        let Some(i) = self.find(label) else {
+            return false;
+        };
+        self.checkpoints.remove(i);
+        if i == 0 {
+            // What's logged before the next checkpoint is no longer needed.
+            let start = self.checkpoints.first().map_or(self.log.len(), |c| c.1);
+            self.log.drain(..start);
+            self.checkpoints.iter_mut().for_each(|c| c.1 -= start);
+        }
+        true
+    }
+
+    pub fn into_inner(self) -> Splay<K, V> {
+        self.tree
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, L: PartialEq> Default for CheckpointSplay<K, V, L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug, L> fmt::Debug for CheckpointSplay<K, V, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.tree.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut map = CheckpointSplay::new();
+        map.insert(1, 'a');
+        map.checkpoint("start");
+        map.insert(2, 'b');
+        map.checkpoint("guess");
+        assert_eq!(map.insert(1, 'A'), Some('a'));
+        map.remove(&2);
+        assert!(map.rollback_to(&"guess"));
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &'a'), (&2, &'b')]);
+
+        map.insert(3, 'c');
+        assert!(map.rollback_to(&"start"));
+        assert_eq!(map.checkpoints().collect::<Vec<_>>(), [&"start"]);
+        assert!(!map.rollback_to(&"guess"));
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &'a')]);
+
+        map.insert(4, 'd');
+        assert!(map.release(&"start"));
+        assert!(map.log.is_empty());
+        assert_eq!(map.len(), 2);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>) -> bool {
+        let mut map = CheckpointSplay::new();
+        // The state at each checkpoint, under its label.
+        let mut states: Vec<(u8, Vec<(u8, usize)>)> = vec![];
+        let state = |map: &CheckpointSplay<u8, usize, u8>| -> Vec<(u8, usize)> {
+            map.iter().map(|(&k, &v)| (k, v)).collect()
+        };
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 8;
+            let label = key % 3;
+            match op % 6 {
+                0 | 1 => {
+                    map.insert(key, i);
+                }
+                2 => {
+                    map.remove(&key);
+                }
+                3 => {
+                    map.checkpoint(label);
+                    states.push((label, state(&map)));
+                }
+                4 => {
+                    let found = states.iter().rposition(|(l, _)| *l == label);
+                    if map.rollback_to(&label) != found.is_some() {
+                        return false;
+                    }
+                    if let Some(j) = found {
+                        states.truncate(j + 1);
+                        if state(&map) != states[j].1 {
+                            return false;
+                        }
+                    }
+                }
+                _ => {
+                    let found = states.iter().rposition(|(l, _)| *l == label);
+                    if let Some(j) = found {
+                        states.remove(j);
+                    }
+                    if map.release(&label) != found.is_some() {
+                        return false;
+                    }
+                }
+            }
+            if map.checkpoints().ne(states.iter().map(|(l, _)| l)) {
+                return false;
+            }
+        }
+        // Every checkpoint left still rolls back right, newest first.
+        while let Some((label, expected)) = states.pop() {
+            map.rollback_to(&label);
+            if state(&map) != expected {
+                return false;
+            }
+            map.release(&label);
+        }
+        map.log.is_empty()
+    }
+}
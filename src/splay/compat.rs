@@ -0,0 +1,914 @@
+//! The parts of `BTreeMap`'s API which `Splay` mirrors by name and
+//! signature. Lookups which reorganise the tree (`get`, `get_mut`) take
+//! `&mut self` there; [`CompatSplay`] has them leave the tree alone, so it
+//! can stand in for a `BTreeMap` unchanged.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::{self, Vec};
+use core::borrow::Borrow;
+use core::cmp::Ordering::{Equal, Greater, Less};
+use core::fmt;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Bound, Deref, DerefMut, Index, RangeBounds, RangeFull};
+use core::ptr;
+
+use super::{link, Allocator, Dir, Ends, Global, Idx, Node, OrCreate, Splay, SplayIter, IDX_NONE};
+use crate::compare::{Compare, Natural};
+
+impl<K, V, C: Compare<K>, A: Allocator> Splay<K, V, C, A> {
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.root = IDX_NONE;
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.find(key).map(|idx| self.entry_at(idx))
+    }
+
+    /// Like `set`, but hands back the value previously stored under `key`.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let value = self.visit::<K>(OrCreate::Create(key, value))?;
+        let root = self.root.to_option().unwrap();
+        Some(mem::replace(&mut self.nodes[root].value, value))
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.visit(OrCreate::Lookup(key));
+        let root = self.root.to_option()?;
+        if self.cmp.compare(self.nodes[root].key.borrow(), key) != Equal {
+            return None;
+        }
+        let node = self.remove_root(&mut []);
+        Some((node.key, node.value))
+    }
+
+    fn entry_at(&self, idx: Idx) -> (&K, &V) {
+        let node = &self.nodes[idx];
+        (&node.key, &node.value)
+    }
+
+    fn extreme(&self, dir: Dir) -> Option<Idx> {
+        let mut idx = self.root.to_option()?;
+        while let Some(child) = self.child(idx, dir).to_option() {
+            idx = child;
+        }
+        Some(idx)
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.extreme(Dir::Left).map(|idx| self.entry_at(idx))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.extreme(Dir::Right).map(|idx| self.entry_at(idx))
+    }
+
+    fn pop_extreme(&mut self, dir: Dir) -> Option<(K, V)> {
+        let root = self.root.to_option()?;
+        self.splay_extreme(root, dir);
+        let node = self.remove_root(&mut []);
+        Some((node.key, node.value))
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.pop_extreme(Dir::Left)
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.pop_extreme(Dir::Right)
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V, C, A> {
+        Keys(self.iter())
+    }
+
+    pub fn values(&self) -> Values<'_, K, V, C, A> {
+        Values(self.iter())
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys(self.into_iter())
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues(self.into_iter())
+    }
+
+    /// Mutable iteration in key order, without splaying.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            len: self.len(),
+            range: self.range_mut::<K, RangeFull>(..),
+        }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut(self.iter_mut())
+    }
+
+    /// Iterates over the entries within `range`, in key order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are the same excluded bound.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+        R: RangeBounds<Q>,
+    {
+        Range {
+            tree: self,
+            ends: self.range_ends(&range),
+        }
+    }
+
+    /// Like `range`, with the values mutable. Doesn't splay.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same malformed ranges as `range`.
+    pub fn range_mut<Q, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+        R: RangeBounds<Q>,
+    {
+        RangeMut {
+            ends: self.range_ends(&range),
+            nodes: self.nodes.as_mut_ptr(),
+            marker: PhantomData,
+        }
+    }
+
+    fn range_ends<Q, R>(&self, range: &R) -> Ends
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+        R: RangeBounds<Q>,
+    {
+        check_range_by(range, &self.cmp);
+
+        // The paths down to the first key past the start and the last key
+        // before the end, keeping the nodes below which the walk from each
+        // end still has to come back up to.
+        let mut ends = Ends {
+            front: Vec::new(),
+            back: Vec::new(),
+        };
+        let mut next = self.root.to_option();
+        while let Some(idx) = next {
+            let key = self.nodes[idx].key.borrow();
+            let after_start = match range.start_bound() {
+                Bound::Included(start) => self.cmp.compare(key, start) != Less,
+                Bound::Excluded(start) => self.cmp.compare(key, start) == Greater,
+                Bound::Unbounded => true,
+            };
+            if after_start {
+                ends.front.push(idx);
+            }
+            next = self
+                .child(idx, if after_start { Dir::Left } else { Dir::Right })
+                .to_option();
+        }
+        let mut next = self.root.to_option();
+        while let Some(idx) = next {
+            let key = self.nodes[idx].key.borrow();
+            let before_end = match range.end_bound() {
+                Bound::Included(end) => self.cmp.compare(key, end) != Greater,
+                Bound::Excluded(end) => self.cmp.compare(key, end) == Less,
+                Bound::Unbounded => true,
+            };
+            if before_end {
+                ends.back.push(idx);
+            }
+            next = self
+                .child(idx, if before_end { Dir::Right } else { Dir::Left })
+                .to_option();
+        }
+
+        match (ends.front.last(), ends.back.last()) {
+            (Some(&first), Some(&last))
+                if self
+                    .cmp
+                    .compare(&self.nodes[first].key, &self.nodes[last].key)
+                    != Greater => {}
+            _ => ends.finish(),
+        }
+        ends
+    }
+}
+
+/// Panics on the same malformed ranges `BTreeMap::range` does.
+pub(crate) fn check_range<Q: Ord + ?Sized, R: RangeBounds<Q>>(range: &R) {
+    check_range_by(range, &Natural);
+}
+
+/// `check_range` in the order of `cmp`.
+pub(crate) fn check_range_by<Q: ?Sized, C: Compare<Q>, R: RangeBounds<Q>>(range: &R, cmp: &C) {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(s), Bound::Excluded(e)) if cmp.compare(s, e) == Equal => {
+            panic!("range start and end are equal and excluded")
+        }
+        (Bound::Included(s) | Bound::Excluded(s), Bound::Included(e) | Bound::Excluded(e))
+            if cmp.compare(s, e) == Greater =>
+        {
+            panic!("range start is greater than range end")
+        }
+        _ => {}
+    }
+}
+
+impl<K: Ord, V> Splay<K, V> {
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        let mut entries = mem::take(self).into_entries();
+        entries.retain_mut(|(k, v)| f(k, v));
+        *self = Self::from_sorted_unique(entries);
+    }
+
+    /// Moves all entries of `other` into `self`, leaving `other` empty. Values
+    /// from `other` win for keys present in both.
+    pub fn append(&mut self, other: &mut Self) {
+        self.merge_with(mem::take(other), |_, _, theirs| theirs);
+    }
+
+    /// Splits off the entries with keys not less than `key`. Both halves are
+    /// rebuilt, so this takes O(n); `SplayForest::split_off` avoids the copy.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut entries = mem::take(self).into_entries();
+        let at = entries.partition_point(|(k, _)| k.borrow() < key);
+        let tail = entries.split_off(at);
+        *self = Self::from_sorted_unique(entries);
+        Self::from_sorted_unique(tail)
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> Extend<(K, V)> for Splay<K, V, C, A> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.set(key, value);
+        }
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator, Q> Index<&Q> for Splay<K, V, C, A>
+where
+    K: Borrow<Q>,
+    Q: ?Sized,
+    C: Compare<Q>,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.peek(key).expect("no entry found for key")
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, C: Compare<K>, A: Allocator> PartialEq for Splay<K, V, C, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq, C: Compare<K>, A: Allocator> Eq for Splay<K, V, C, A> {}
+
+impl<'a, K, V, C: Compare<K>, A: Allocator> IntoIterator for &'a Splay<K, V, C, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = SplayIter<'a, K, V, C, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, C: Compare<K>, A: Allocator> IntoIterator for &'a mut Splay<K, V, C, A> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> IntoIterator for Splay<K, V, C, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self.into_entries().into_iter())
+    }
+}
+
+/// A [`Splay`] whose `get` and `get_mut` leave the tree as it is, so its
+/// lookups all take `BTreeMap`'s signatures and it can replace one without
+/// touching the code using it. Everything else is the tree's, through
+/// `Deref`.
+///
+/// ```
+/// use crab_bucket::splay::CompatSplay;
+///
+/// fn lookup(map: &CompatSplay<String, u32>) -> Option<&u32> {
+///     map.get("b")
+/// }
+///
+/// let mut map = CompatSplay::new();
+/// map.insert("a".to_string(), 1);
+/// map.insert("b".to_string(), 2);
+/// *map.get_mut("a").unwrap() += 10;
+/// assert_eq!((map.get("a"), lookup(&map)), (Some(&11), Some(&2)));
+/// ```
+#[derive(Clone)]
+pub struct CompatSplay<K, V, C = Natural, A: Allocator = Global> {
+    tree: Splay<K, V, C, A>,
+}
+
+impl<K: Ord, V> CompatSplay<K, V> {
+    pub fn new() -> Self {
+        Splay::new().into()
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> CompatSplay<K, V, C, A> {
+    /// Looks `key` up without splaying, like `Splay::peek`.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.tree.peek(key)
+    }
+
+    /// Looks `key` up without splaying.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        let idx = self.tree.find(key)?;
+        Some(&mut self.tree.nodes[idx].value)
+    }
+
+    pub fn into_tree(self) -> Splay<K, V, C, A> {
+        self.tree
+    }
+}
+
+impl<K, V, C, A: Allocator> Deref for CompatSplay<K, V, C, A> {
+    type Target = Splay<K, V, C, A>;
+
+    fn deref(&self) -> &Splay<K, V, C, A> {
+        &self.tree
+    }
+}
+
+impl<K, V, C, A: Allocator> DerefMut for CompatSplay<K, V, C, A> {
+    fn deref_mut(&mut self) -> &mut Splay<K, V, C, A> {
+        &mut self.tree
+    }
+}
+
+impl<K, V, C, A: Allocator> From<Splay<K, V, C, A>> for CompatSplay<K, V, C, A> {
+    fn from(tree: Splay<K, V, C, A>) -> Self {
+        CompatSplay { tree }
+    }
+}
+
+impl<K: Ord, V> From<BTreeMap<K, V>> for CompatSplay<K, V> {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Splay::from(map).into()
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for CompatSplay<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        Splay::from(entries).into()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for CompatSplay<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Splay::from_iter(iter).into()
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> Extend<(K, V)> for CompatSplay<K, V, C, A> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.tree.extend(iter);
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator, Q> Index<&Q> for CompatSplay<K, V, C, A>
+where
+    K: Borrow<Q>,
+    Q: ?Sized,
+    C: Compare<Q>,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        &self.tree[key]
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, C: Compare<K>, A: Allocator> PartialEq
+    for CompatSplay<K, V, C, A>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.tree == other.tree
+    }
+}
+
+impl<K: Eq, V: Eq, C: Compare<K>, A: Allocator> Eq for CompatSplay<K, V, C, A> {}
+
+impl<K: Ord, V> Default for CompatSplay<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C: Compare<K>, A: Allocator> fmt::Debug
+    for CompatSplay<K, V, C, A>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tree.fmt(f)
+    }
+}
+
+impl<'a, K, V, C: Compare<K>, A: Allocator> IntoIterator for &'a CompatSplay<K, V, C, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = SplayIter<'a, K, V, C, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tree.iter()
+    }
+}
+
+impl<'a, K, V, C: Compare<K>, A: Allocator> IntoIterator for &'a mut CompatSplay<K, V, C, A> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tree.iter_mut()
+    }
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> IntoIterator for CompatSplay<K, V, C, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tree.into_iter()
+    }
+}
+
+pub struct Keys<'a, K, V, C = Natural, A: Allocator = Global>(SplayIter<'a, K, V, C, A>);
+
+impl<'a, K, V, C, A: Allocator> Iterator for Keys<'a, K, V, C, A> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.0.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V, C, A: Allocator> DoubleEndedIterator for Keys<'_, K, V, C, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<K, V, C, A: Allocator> ExactSizeIterator for Keys<'_, K, V, C, A> {}
+impl<K, V, C, A: Allocator> FusedIterator for Keys<'_, K, V, C, A> {}
+
+pub struct Values<'a, K, V, C = Natural, A: Allocator = Global>(SplayIter<'a, K, V, C, A>);
+
+impl<'a, K, V, C, A: Allocator> Iterator for Values<'a, K, V, C, A> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.0.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V, C, A: Allocator> DoubleEndedIterator for Values<'_, K, V, C, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, C, A: Allocator> ExactSizeIterator for Values<'_, K, V, C, A> {}
+impl<K, V, C, A: Allocator> FusedIterator for Values<'_, K, V, C, A> {}
+
+pub struct IterMut<'a, K, V> {
+    pub(super) range: RangeMut<'a, K, V>,
+    pub(super) len: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.range.next()?;
+        self.len -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.range.next_back()?;
+        self.len -= 1;
+        Some(entry)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+pub struct ValuesMut<'a, K, V>(IterMut<'a, K, V>);
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.0.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for ValuesMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
+
+pub struct IntoIter<K, V>(vec::IntoIter<(K, V)>);
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.0.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+pub struct IntoKeys<K, V>(IntoIter<K, V>);
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.0.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoKeys<K, V> {
+    fn next_back(&mut self) -> Option<K> {
+        self.0.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+impl<K, V> FusedIterator for IntoKeys<K, V> {}
+
+pub struct IntoValues<K, V>(IntoIter<K, V>);
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.0.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoValues<K, V> {
+    fn next_back(&mut self) -> Option<V> {
+        self.0.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+impl<K, V> FusedIterator for IntoValues<K, V> {}
+
+pub struct Range<'a, K, V, C = Natural, A: Allocator = Global> {
+    tree: &'a Splay<K, V, C, A>,
+    ends: Ends,
+}
+
+impl<'a, K, V, C, A: Allocator> Range<'a, K, V, C, A> {
+    fn step(&mut self, from: Dir) -> Option<(&'a K, &'a V)> {
+        let nodes = &self.tree.nodes;
+        let idx = self.ends.step(from, |idx, dir| link(&nodes[idx], dir))?;
+        let node = &nodes[idx];
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<'a, K, V, C, A: Allocator> Iterator for Range<'a, K, V, C, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(Dir::Left)
+    }
+}
+
+impl<K, V, C, A: Allocator> DoubleEndedIterator for Range<'_, K, V, C, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(Dir::Right)
+    }
+}
+
+impl<K, V, C, A: Allocator> FusedIterator for Range<'_, K, V, C, A> {}
+
+pub struct RangeMut<'a, K, V> {
+    pub(super) ends: Ends,
+    // The arena, borrowed mutably for `'a`. Values are handed out one node
+    // at a time, so it's kept as a pointer rather than a `&mut` to it all.
+    pub(super) nodes: *mut Node<K, V>,
+    pub(super) marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+// SAFETY: `RangeMut` hands out `&K` and `&mut V` like a `&mut` to the arena
+// would, so it can be sent or shared under the same bounds as one.
+unsafe impl<K: Sync, V: Send> Send for RangeMut<'_, K, V> {}
+// SAFETY: as above.
+unsafe impl<K: Sync, V: Sync> Sync for RangeMut<'_, K, V> {}
+
+impl<'a, K, V> RangeMut<'a, K, V> {
+    fn step(&mut self, from: Dir) -> Option<(&'a K, &'a mut V)> {
+        let nodes = self.nodes;
+        // SAFETY: every index in `ends` is a slot of the arena, which is
+        // borrowed for `'a`. Only the links are read, never the values
+        // handed out already.
+        let idx = self.ends.step(from, |idx, dir| unsafe {
+            let node = nodes.add(idx);
+            match dir {
+                Dir::Left => (*node).left,
+                Dir::Right => (*node).right,
+            }
+        })?;
+        // SAFETY: a walk hands out each slot at most once, so this is the
+        // only reference to its value.
+        unsafe {
+            let node = nodes.add(idx);
+            Some((
+                &*ptr::addr_of!((*node).key),
+                &mut *ptr::addr_of_mut!((*node).value),
+            ))
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(Dir::Left)
+    }
+}
+
+impl<K, V> DoubleEndedIterator for RangeMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(Dir::Right)
+    }
+}
+
+impl<K, V> FusedIterator for RangeMut<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basic_test() {
+        let mut tree = Splay::new();
+        assert_eq!(tree.insert(2, "b"), None);
+        assert_eq!(tree.insert(2, "B"), Some("b"));
+        tree.extend([(1, "a"), (3, "c"), (4, "d")]);
+        assert!(tree.contains_key(&1));
+        assert_eq!(tree[&3], "c");
+        assert_eq!(tree.get_key_value(&2), Some((&2, &"B")));
+        assert_eq!(tree.first_key_value(), Some((&1, &"a")));
+        assert_eq!(tree.last_key_value(), Some((&4, &"d")));
+        assert_eq!(
+            tree.range(2..4).collect::<Vec<_>>(),
+            [(&2, &"B"), (&3, &"c")]
+        );
+        assert_eq!(tree.keys().copied().collect::<Vec<_>>(), [1, 2, 3, 4]);
+
+        for (_, v) in &mut tree {
+            *v = "x";
+        }
+        assert!(tree.values().all(|v| *v == "x"));
+        assert_eq!(tree.pop_first(), Some((1, "x")));
+        assert_eq!(tree.pop_last(), Some((4, "x")));
+        assert_eq!(tree.remove_entry(&2), Some((2, "x")));
+
+        let mut other = Splay::from([(3, "y"), (5, "z")]);
+        tree.append(&mut other);
+        assert!(other.is_empty());
+        let tail = tree.split_off(&4);
+        assert_eq!(tree.into_iter().collect::<Vec<_>>(), [(3, "y")]);
+        assert_eq!(tail.into_iter().collect::<Vec<_>>(), [(5, "z")]);
+    }
+
+    #[test]
+    fn compat_test() {
+        let mut map: CompatSplay<String, u32> = (0..100).map(|i| (i.to_string(), i)).collect();
+        let shape = map.display_tree().to_string();
+        assert_eq!(map.get("42"), Some(&42));
+        *map.get_mut("7").unwrap() += 1;
+        assert_eq!((map["7"], map.get("x")), (8, None));
+        assert_eq!(map.display_tree().to_string(), shape);
+
+        map.range_mut::<str, _>((Bound::Included("5"), Bound::Excluded("6")))
+            .for_each(|(_, v)| *v = 0);
+        assert_eq!(map.values().filter(|&&v| v == 0).count(), 12);
+        for (k, v) in &mut map {
+            *v = k.len() as u32;
+        }
+        let mut tail = map.iter_mut().rev();
+        assert_eq!(tail.len(), 100);
+        assert_eq!(tail.next(), Some((&"99".to_string(), &mut 2)));
+        assert_eq!(map.into_tree().check_invariants(), Ok(()));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_double_ended(entries: Vec<(u8, u8)>, a: u8, b: u8, ends: Vec<bool>) -> bool {
+        let mut tree: Splay<u8, u8> = entries.iter().copied().collect();
+        let mut map: BTreeMap<u8, u8> = entries.into_iter().collect();
+        let (lo, hi) = (a.min(b), a.max(b));
+        let (mut ours, mut theirs) = (tree.range(lo..=hi), map.range(lo..=hi));
+        for &back in ends.iter().chain(&[false; 256]) {
+            let (x, y) = if back {
+                (ours.next_back(), theirs.next_back())
+            } else {
+                (ours.next(), theirs.next())
+            };
+            if x != y {
+                return false;
+            }
+        }
+        let (mut ours, mut theirs) = (tree.iter_mut(), map.iter_mut());
+        for &back in ends.iter().chain(&[true; 256]) {
+            let (x, y) = if back {
+                (ours.next_back(), theirs.next_back())
+            } else {
+                (ours.next(), theirs.next())
+            };
+            if x != y || ours.len() != theirs.len() {
+                return false;
+            }
+            if let (Some((k, v)), Some((_, w))) = (x, y) {
+                *v = v.wrapping_add(*k);
+                *w = w.wrapping_add(*k);
+            }
+        }
+        let (mut ours, mut theirs) = (tree.iter(), map.iter());
+        for &back in ends.iter().chain(&[false; 256]) {
+            let (x, y) = if back {
+                (ours.next_back(), theirs.next_back())
+            } else {
+                (ours.next(), theirs.next())
+            };
+            if x != y || ours.len() != theirs.len() {
+                return false;
+            }
+        }
+        tree.range_mut(lo..hi).rev().for_each(|(k, v)| *v ^= *k);
+        map.range_mut(lo..hi).rev().for_each(|(k, v)| *v ^= *k);
+        tree.values_mut()
+            .rev()
+            .step_by(2)
+            .for_each(|v| *v = v.wrapping_mul(3));
+        map.values_mut()
+            .rev()
+            .step_by(2)
+            .for_each(|v| *v = v.wrapping_mul(3));
+        tree.keys().rev().eq(map.keys().rev())
+            && tree.values().len() == map.values().len()
+            && tree.iter().rev().eq(map.iter().rev())
+    }
+
+    #[test]
+    fn into_keys_values_test() {
+        let tree = Splay::from([
+            (3, "c".to_string()),
+            (1, "a".to_string()),
+            (2, "b".to_string()),
+        ]);
+        assert_eq!(tree.clone().into_keys().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(
+            tree.into_values().rev().collect::<Vec<_>>(),
+            ["c", "b", "a"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "range start is greater than range end")]
+    fn range_panic_test() {
+        let tree: Splay<i32, ()> = Splay::new();
+        let (start, end) = (3, 1);
+        tree.range(start..end).count();
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_range(entries: Vec<(u8, u8)>, a: u8, b: u8) -> bool {
+        let tree: Splay<u8, u8> = entries.iter().copied().collect();
+        let map: BTreeMap<u8, u8> = entries.into_iter().collect();
+        let (lo, hi) = (a.min(b), a.max(b));
+        tree.range(lo..hi).eq(map.range(lo..hi))
+            && tree.range(lo..=hi).eq(map.range(lo..=hi))
+            && tree.range(..hi).eq(map.range(..hi))
+            && tree
+                .range((Bound::Excluded(lo), Bound::Unbounded))
+                .eq(map.range((Bound::Excluded(lo), Bound::Unbounded)))
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_split_append(a: Vec<(u8, u8)>, b: Vec<(u8, u8)>, at: u8) -> bool {
+        let mut tree: Splay<u8, u8> = a.iter().copied().collect();
+        let mut other: Splay<u8, u8> = b.iter().copied().collect();
+        let mut map: BTreeMap<u8, u8> = a.into_iter().collect();
+        let mut other_map: BTreeMap<u8, u8> = b.into_iter().collect();
+        tree.append(&mut other);
+        map.append(&mut other_map);
+        let tail = tree.split_off(&at);
+        let map_tail = map.split_off(&at);
+        tree.retain(|k, _| k % 3 != 0);
+        map.retain(|k, _| k % 3 != 0);
+        BTreeMap::from(tree) == map && BTreeMap::from(tail) == map_tail
+    }
+}
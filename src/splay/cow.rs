@@ -0,0 +1,135 @@
+use alloc::sync::Arc;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use super::Splay;
+
+/// A `Splay` whose node arena is shared copy-on-write with the snapshots
+/// taken of it.
+///
+/// `snapshot` is O(1). The first mutation after taking one copies the arena
+/// and later ones run at full speed until the next snapshot. Splaying
+/// lookups count as mutations, so prefer `peek` through `&self` while
+/// snapshots are alive.
+#[derive(Clone)]
+pub struct CowSplay<K, V> {
+    tree: Arc<Splay<K, V>>,
+}
+
+/// A consistent, read-only version of a [`CowSplay`].
+#[derive(Clone)]
+pub struct SplaySnapshot<K, V> {
+    tree: Arc<Splay<K, V>>,
+}
+
+impl<K: Ord, V> CowSplay<K, V> {
+    pub fn new() -> Self {
+        CowSplay {
+            tree: Arc::new(Splay::new()),
+        }
+    }
+
+    pub fn snapshot(&self) -> SplaySnapshot<K, V> {
+        SplaySnapshot {
+            tree: Arc::clone(&self.tree),
+        }
+    }
+
+    /// Whether the arena is currently shared with a snapshot, meaning the
+    /// next mutation copies it.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.tree) > 1
+    }
+}
+
+impl<K: Ord, V> Default for CowSplay<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> From<Splay<K, V>> for CowSplay<K, V> {
+    fn from(tree: Splay<K, V>) -> Self {
+        CowSplay {
+            tree: Arc::new(tree),
+        }
+    }
+}
+
+impl<K, V> Deref for CowSplay<K, V> {
+    type Target = Splay<K, V>;
+
+    fn deref(&self) -> &Splay<K, V> {
+        &self.tree
+    }
+}
+
+impl<K: Clone, V: Clone> DerefMut for CowSplay<K, V> {
+    fn deref_mut(&mut self) -> &mut Splay<K, V> {
+        Arc::make_mut(&mut self.tree)
+    }
+}
+
+impl<K, V> Deref for SplaySnapshot<K, V> {
+    type Target = Splay<K, V>;
+
+    fn deref(&self) -> &Splay<K, V> {
+        &self.tree
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for CowSplay<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tree.fmt(f)
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for SplaySnapshot<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tree.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basic_test() {
+        let mut tree = CowSplay::new();
+        tree.set(1, "a");
+        tree.set(2, "b");
+
+        let snapshot = tree.snapshot();
+        assert!(tree.is_shared());
+        tree.set(3, "c");
+        tree.remove(&1);
+        assert!(!tree.is_shared());
+
+        assert_eq!(snapshot.peek(&1), Some(&"a"));
+        assert_eq!(snapshot.peek(&3), None);
+        assert_eq!(tree.get(&3), Some(&"c"));
+        assert_eq!(tree.peek(&1), None);
+        assert_eq!(snapshot.len(), 2);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(entries: Vec<(u8, u8)>, cut: usize) -> bool {
+        let cut = cut % (entries.len() + 1);
+        let mut tree: CowSplay<u8, u8> = entries[..cut]
+            .iter()
+            .copied()
+            .collect::<Splay<_, _>>()
+            .into();
+        let snapshot = tree.snapshot();
+        for &(k, v) in &entries[cut..] {
+            tree.set(k, v);
+            tree.get(&k);
+        }
+        let before: BTreeMap<_, _> = entries[..cut].iter().copied().collect();
+        let after: BTreeMap<_, _> = entries.iter().copied().collect();
+        snapshot.iter().eq(before.iter()) && tree.iter().eq(after.iter())
+    }
+}
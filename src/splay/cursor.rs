@@ -0,0 +1,219 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering::{Greater, Less};
+use core::ops::Bound;
+
+use super::{Allocator, Global, Idx, Splay};
+use crate::compare::{Compare, Natural};
+
+/// A read-only position between two entries of a [`Splay`] (or before the
+/// first or after the last one) which can step in both directions. Doesn't
+/// splay, and each step takes amortized O(1).
+pub struct Cursor<'a, K, V, C = Natural, A: Allocator = Global> {
+    tree: &'a Splay<K, V, C, A>,
+    // Root-to-node path to the entry after the cursor, empty at the end.
+    path: Vec<Idx>,
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> Splay<K, V, C, A> {
+    /// A cursor just before the first entry within `bound`, taken as a lower
+    /// bound. Like `range`, so `Unbounded` gives the very start.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        let mut path = Vec::new();
+        let mut keep = 0;
+        let mut next = self.root.to_option();
+        while let Some(idx) = next {
+            path.push(idx);
+            let key = self.nodes[idx].key.borrow();
+            let within = match bound {
+                Bound::Included(b) => self.cmp.compare(key, b) != Less,
+                Bound::Excluded(b) => self.cmp.compare(key, b) == Greater,
+                Bound::Unbounded => true,
+            };
+            next = if within {
+                keep = path.len();
+                self.nodes[idx].left.to_option()
+            } else {
+                self.nodes[idx].right.to_option()
+            };
+        }
+        path.truncate(keep);
+        Cursor { tree: self, path }
+    }
+
+    /// A cursor just after the last entry within `bound`, taken as an upper
+    /// bound. `Unbounded` gives the very end.
+    pub fn upper_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        match bound {
+            Bound::Included(b) => self.lower_bound(Bound::Excluded(b)),
+            Bound::Excluded(b) => self.lower_bound(Bound::Included(b)),
+            Bound::Unbounded => Cursor {
+                tree: self,
+                path: Vec::new(),
+            },
+        }
+    }
+}
+
+impl<'a, K, V, C, A: Allocator> Cursor<'a, K, V, C, A> {
+    fn entry(&self, idx: Idx) -> (&'a K, &'a V) {
+        let node = &self.tree.nodes[idx];
+        (&node.key, &node.value)
+    }
+
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        self.path.last().map(|&idx| self.entry(idx))
+    }
+
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        self.prev_idx().map(|idx| self.entry(idx))
+    }
+
+    fn prev_idx(&self) -> Option<Idx> {
+        let nodes = &self.tree.nodes;
+        let Some(&last) = self.path.last() else {
+            let mut idx = self.tree.root.to_option()?;
+            while let Some(right) = nodes[idx].right.to_option() {
+                idx = right;
+            }
+            return Some(idx);
+        };
+        if let Some(mut idx) = nodes[last].left.to_option() {
+            while let Some(right) = nodes[idx].right.to_option() {
+                idx = right;
+            }
+            return Some(idx);
+        }
+        // The nearest ancestor whose right subtree holds the next entry.
+        self.path
+            .windows(2)
+            .rev()
+            .find(|w| nodes[w[0]].right.to_option() == Some(w[1]))
+            .map(|w| w[0])
+    }
+
+    /// Steps over the next entry and returns it.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let nodes = &self.tree.nodes;
+        let &idx = self.path.last()?;
+        if let Some(mut child) = nodes[idx].right.to_option() {
+            loop {
+                self.path.push(child);
+                match nodes[child].left.to_option() {
+                    Some(left) => child = left,
+                    None => break,
+                }
+            }
+        } else {
+            // Climb out of right subtrees; stopping at a parent we reached
+            // through its left link, or running out at the end.
+            let mut child = self.path.pop().unwrap();
+            while let Some(&parent) = self.path.last() {
+                if nodes[parent].left.to_option() == Some(child) {
+                    break;
+                }
+                child = self.path.pop().unwrap();
+            }
+        }
+        Some(self.entry(idx))
+    }
+
+    /// Steps back over the previous entry and returns it.
+    pub fn prev(&mut self) -> Option<(&'a K, &'a V)> {
+        let nodes = &self.tree.nodes;
+        let prev = self.prev_idx()?;
+        // Either descend to the rightmost node under the cursor's left, or
+        // the previous entry is already an ancestor on the path.
+        let down = match self.path.last() {
+            None => self.tree.root.to_option(),
+            Some(&last) => nodes[last].left.to_option(),
+        };
+        if let Some(mut idx) = down {
+            loop {
+                self.path.push(idx);
+                match nodes[idx].right.to_option() {
+                    Some(right) => idx = right,
+                    None => break,
+                }
+            }
+        } else {
+            while self.path.last() != Some(&prev) {
+                self.path.pop();
+            }
+        }
+        Some(self.entry(prev))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basic_test() {
+        let tree: Splay<u32, ()> = (1..=5).map(|k| (k * 10, ())).collect();
+        let mut cursor = tree.lower_bound(Bound::Included(&25));
+        assert_eq!(cursor.peek_prev(), Some((&20, &())));
+        assert_eq!(cursor.next(), Some((&30, &())));
+        assert_eq!(cursor.next(), Some((&40, &())));
+        assert_eq!(cursor.prev(), Some((&40, &())));
+        assert_eq!(cursor.prev(), Some((&30, &())));
+        assert_eq!(cursor.prev(), Some((&20, &())));
+
+        let mut cursor = tree.upper_bound(Bound::Included(&50));
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.prev(), Some((&50, &())));
+        let mut cursor = tree.lower_bound::<u32>(Bound::Unbounded);
+        assert_eq!(cursor.prev(), None);
+        assert_eq!(cursor.next(), Some((&10, &())));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(keys: Vec<u8>, at: u8, steps: Vec<bool>) -> bool {
+        let tree: Splay<u8, ()> = keys.iter().map(|&k| (k, ())).collect();
+        let sorted: Vec<u8> = keys
+            .iter()
+            .map(|&k| (k, ()))
+            .collect::<BTreeMap<_, _>>()
+            .into_keys()
+            .collect();
+
+        // Model the cursor as the index of the entry after it.
+        let mut pos = sorted.partition_point(|&k| k < at);
+        let mut cursor = tree.lower_bound(Bound::Included(&at));
+        let upper = tree.upper_bound(Bound::Excluded(&at));
+        if upper.peek_next() != cursor.peek_next() {
+            return false;
+        }
+        for forward in steps {
+            let (got, expected) = if forward {
+                let expected = sorted.get(pos).copied();
+                pos += expected.is_some() as usize;
+                (cursor.next(), expected)
+            } else {
+                let expected = pos.checked_sub(1).map(|p| sorted[p]);
+                pos -= expected.is_some() as usize;
+                (cursor.prev(), expected)
+            };
+            if got.map(|(k, _)| *k) != expected
+                || cursor.peek_next().map(|(k, _)| *k) != sorted.get(pos).copied()
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,89 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Write};
+
+use super::{Allocator, Splay};
+
+/// What to show on the nodes of a Graphviz rendering.
+#[derive(Clone, Copy, Debug)]
+pub struct DotOptions {
+    /// Label nodes with their keys rather than their arena slots.
+    pub keys: bool,
+    /// Add each node's depth below its label.
+    pub depths: bool,
+}
+
+impl Default for DotOptions {
+    fn default() -> Self {
+        DotOptions {
+            keys: true,
+            depths: false,
+        }
+    }
+}
+
+impl<K: Debug, V, C, A: Allocator> Splay<K, V, C, A> {
+    /// Renders the tree shape as a Graphviz `digraph`, labelling nodes with
+    /// their keys.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        self.write_dot(&mut out, DotOptions::default()).unwrap();
+        out
+    }
+
+    pub fn write_dot<W: Write>(&self, w: &mut W, options: DotOptions) -> fmt::Result {
+        writeln!(w, "digraph splay {{")?;
+        writeln!(w, "  node [shape=circle];")?;
+        let mut stack = Vec::new();
+        stack.extend(self.root.to_option().map(|root| (root, 0)));
+        while let Some((idx, depth)) = stack.pop() {
+            let node = &self.nodes[idx];
+            let mut label = String::new();
+            if options.keys {
+                write!(label, "{:?}", node.key)?;
+            } else {
+                write!(label, "#{idx}")?;
+            }
+            if options.depths {
+                write!(label, "\n{depth}")?;
+            }
+            // Quoting with `Debug` escapes quotes and turns the newline into
+            // `\n`, both of which DOT understands.
+            writeln!(w, "  n{idx} [label={label:?}];")?;
+            for (child, port) in [(node.left, "sw"), (node.right, "se")] {
+                if let Some(child) = child.to_option() {
+                    writeln!(w, "  n{idx}:{port} -> n{child};")?;
+                    stack.push((child, depth + 1));
+                }
+            }
+        }
+        writeln!(w, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_test() {
+        let tree: Splay<&str, ()> = [("a", ()), ("b", ()), ("c", ())].into();
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph splay {\n"));
+        assert!(dot.contains(r#"n1 [label="\"b\""];"#));
+        assert!(dot.contains("n1:sw -> n0;"));
+        assert!(dot.contains("n1:se -> n2;"));
+        assert!(dot.ends_with("}\n"));
+
+        let mut out = String::new();
+        let options = DotOptions {
+            keys: false,
+            depths: true,
+        };
+        tree.write_dot(&mut out, options).unwrap();
+        assert!(out.contains(r##"n2 [label="#2\n1"];"##));
+
+        let empty: Splay<u8, ()> = Splay::new();
+        assert_eq!(empty.to_dot().lines().count(), 3);
+    }
+}
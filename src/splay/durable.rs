@@ -0,0 +1,312 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::{Splay, SplayIter};
+use crate::codec::{invalid_data, Checksummed, Codec};
+
+const MAGIC: &[u8; 4] = b"CBWL";
+const VERSION: u32 = 1;
+const SNAPSHOT: &str = "snapshot";
+const WAL: &str = "wal";
+const MIN_COMPACTION: usize = 1024;
+
+const SET: u8 = 0;
+const REMOVE: u8 = 1;
+const CLEAR: u8 = 2;
+
+enum Op<K, V> {
+    Set(K, V),
+    Remove(K),
+    Clear,
+}
+
+impl<K: Ord, V> Op<K, V> {
+    fn apply(self, tree: &mut Splay<K, V>) {
+        match self {
+            Op::Set(key, value) => tree.set(key, value),
+            Op::Remove(key) => {
+                tree.remove(&key);
+            }
+            Op::Clear => tree.clear(),
+        }
+    }
+}
+
+/// Reads one log record, whose tag and fields are followed by their
+/// checksum.
+fn read_op<K: Codec, V: Codec>(r: &mut &[u8]) -> io::Result<Op<K, V>> {
+    let mut r = Checksummed::new(r);
+    let op = match u8::decode(&mut r)? {
+        SET => Op::Set(K::decode(&mut r)?, V::decode(&mut r)?),
+        REMOVE => Op::Remove(K::decode(&mut r)?),
+        CLEAR => Op::Clear,
+        _ => return Err(invalid_data("unknown log record")),
+    };
+    let checksum = r.checksum();
+    if u64::decode(&mut r.inner)? != checksum {
+        return Err(invalid_data("log record checksum mismatch"));
+    }
+    Ok(op)
+}
+
+/// A [`Splay`] kept in a directory, which survives the process exiting.
+///
+/// Each change is appended to a write-ahead log before it's made, and
+/// `open` replays the log over the last snapshot, in the format of
+/// [`Splay::write_to`]. Appends are buffered: `flush` writes them out and
+/// syncs the log, and a crash loses what came after the last one. The
+/// record a crash cut short is dropped on the next `open`. Once the log
+/// holds more records than the map has entries, and at least 1024, the map
+/// is compacted: written out as the new snapshot, and the log emptied. A
+/// crash between the two replays the log over a snapshot which already
+/// has its changes, which comes to the same map.
+pub struct DurableSplay<K, V> {
+    tree: Splay<K, V>,
+    dir: PathBuf,
+    wal: BufWriter<File>,
+    // Records in the log, and the fewest which make a compaction.
+    logged: usize,
+    min_compaction: usize,
+}
+
+impl<K: Ord + Codec, V: Codec> DurableSplay<K, V> {
+    /// Opens the map kept in `dir`, creating both if they don't exist.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut tree = match File::open(dir.join(SNAPSHOT)) {
+            Ok(file) => Splay::read_from(BufReader::new(file))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Splay::new(),
+            Err(e) => return Err(e),
+        };
+
+        let path = dir.join(WAL);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut logged = 0;
+        // The length of the log up to its last whole record.
+        let mut good = 0;
+        if !bytes.is_empty() {
+            let mut r = &bytes[..];
+            let mut magic = [0; 4];
+            io::Read::read_exact(&mut r, &mut magic)?;
+            if &magic != MAGIC {
+                return Err(invalid_data("not a write-ahead log"));
+            }
+            if u32::decode(&mut r)? != VERSION {
+                return Err(invalid_data("unsupported log version"));
+            }
+            good = bytes.len() - r.len();
+            while let Ok(op) = read_op(&mut r) {
+                op.apply(&mut tree);
+                logged += 1;
+                good = bytes.len() - r.len();
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.set_len(good as u64)?;
+        let mut wal = BufWriter::new(file);
+        if good == 0 {
+            wal.write_all(MAGIC)?;
+            VERSION.encode(&mut wal)?;
+        }
+        Ok(DurableSplay {
+            tree,
+            dir,
+            wal,
+            logged,
+            min_compaction: MIN_COMPACTION,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Sets the fewest log records which make a compaction, 1024 unless
+    /// changed.
+    pub fn set_min_compaction(&mut self, records: usize) {
+        self.min_compaction = records;
+    }
+
+    /// Records in the log since the last compaction.
+    pub fn logged(&self) -> usize {
+        self.logged
+    }
+
+    fn log(&mut self, tag: u8, key: Option<&K>, value: Option<&V>) -> io::Result<()> {
+        let mut w = Checksummed::new(&mut self.wal);
+        tag.encode(&mut w)?;
+        if let Some(key) = key {
+            key.encode(&mut w)?;
+        }
+        if let Some(value) = value {
+            value.encode(&mut w)?;
+        }
+        let checksum = w.checksum();
+        checksum.encode(&mut w.inner)?;
+        self.logged += 1;
+        Ok(())
+    }
+
+    /// Compacts once the log has grown long enough.
+    fn changed(&mut self) -> io::Result<()> {
+        if self.logged >= self.min_compaction.max(self.tree.len()) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> io::Result<Option<V>> {
+        self.log(SET, Some(&key), Some(&value))?;
+        let old = self.tree.insert(key, value);
+        self.changed()?;
+        Ok(old)
+    }
+
+    pub fn remove(&mut self, key: &K) -> io::Result<Option<V>> {
+        if !self.tree.contains_key(key) {
+            return Ok(None);
+        }
+        self.log(REMOVE, Some(key), None)?;
+        let old = self.tree.remove(key);
+        self.changed()?;
+        Ok(old)
+    }
+
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.log(CLEAR, None, None)?;
+        self.tree.clear();
+        self.changed()
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.tree.get(key)
+    }
+
+    /// Looks `key` up without splaying.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.tree.peek(key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.tree.contains_key(key)
+    }
+
+    pub fn iter(&self) -> SplayIter<'_, K, V> {
+        self.tree.iter()
+    }
+
+    /// The map itself, to read, not change.
+    pub fn as_splay(&self) -> &Splay<K, V> {
+        &self.tree
+    }
+
+    /// Writes out the changes logged so far and syncs them to disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.wal.flush()?;
+        self.wal.get_ref().sync_data()
+    }
+
+    /// Writes the map out as the new snapshot and empties the log.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let tmp = self.dir.join("snapshot.tmp");
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        self.tree.write_to(&mut w)?;
+        w.into_inner()?.sync_all()?;
+        fs::rename(&tmp, self.dir.join(SNAPSHOT))?;
+
+        self.wal.flush()?;
+        self.wal.get_ref().set_len(0)?;
+        self.wal.write_all(MAGIC)?;
+        VERSION.encode(&mut self.wal)?;
+        self.logged = 0;
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("crab-bucket-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn basic_test() {
+        let dir = scratch("durable");
+        let mut map = DurableSplay::open(&dir).unwrap();
+        for i in 0..10u32 {
+            assert_eq!(map.insert(i, i.to_string()).unwrap(), None);
+        }
+        assert_eq!(map.remove(&3).unwrap(), Some("3".to_string()));
+        assert_eq!(map.remove(&3).unwrap(), None);
+        assert_eq!(map.logged(), 11);
+        map.flush().unwrap();
+        drop(map);
+
+        let mut map = DurableSplay::<u32, String>::open(&dir).unwrap();
+        assert_eq!(map.len(), 9);
+        assert_eq!(map.get(&4).map(String::as_str), Some("4"));
+        map.compact().unwrap();
+        map.insert(20, "twenty".to_string()).unwrap();
+        drop(map);
+
+        // A record cut short by a crash is dropped, the ones before kept.
+        let mut wal = OpenOptions::new().append(true).open(dir.join(WAL)).unwrap();
+        wal.write_all(&[SET, 1, 2]).unwrap();
+        drop(wal);
+        let mut map = DurableSplay::<u32, String>::open(&dir).unwrap();
+        assert_eq!(map.logged(), 1);
+        assert_eq!(map.peek(&20).map(String::as_str), Some("twenty"));
+        map.clear().unwrap();
+        drop(map);
+        assert!(DurableSplay::<u32, String>::open(&dir).unwrap().is_empty());
+
+        fs::write(dir.join(WAL), b"junk").unwrap();
+        assert!(DurableSplay::<u32, String>::open(&dir).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8, u16)>) -> bool {
+        let dir = scratch("durable-quickcheck");
+        let mut map = DurableSplay::open(&dir).unwrap();
+        map.set_min_compaction(8);
+        let mut model = BTreeMap::new();
+        for (op, key, value) in ops {
+            let key = key % 16;
+            let ok = match op % 6 {
+                0..=2 => map.insert(key, value).unwrap() == model.insert(key, value),
+                3 => map.remove(&key).unwrap() == model.remove(&key),
+                4 => {
+                    map.flush().unwrap();
+                    map = DurableSplay::open(&dir).unwrap();
+                    map.set_min_compaction(8);
+                    true
+                }
+                _ => map.logged() <= 8.max(map.len()),
+            };
+            if !ok || map.len() != model.len() {
+                return false;
+            }
+        }
+        let same = map.iter().eq(model.iter());
+        fs::remove_dir_all(&dir).unwrap();
+        same
+    }
+}
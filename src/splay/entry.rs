@@ -0,0 +1,200 @@
+use core::mem;
+
+use core::cmp::Ordering::Equal;
+
+use super::{Allocator, Dir, Global, OrCreate, Splay};
+use crate::compare::{Compare, Natural};
+
+/// A view into a single key of a [`Splay`], from [`Splay::entry`].
+pub enum Entry<'a, K, V, C = Natural, A: Allocator = Global> {
+    Occupied(OccupiedEntry<'a, K, V, C, A>),
+    Vacant(VacantEntry<'a, K, V, C, A>),
+}
+
+/// An entry which is present. It has been splayed to the root, so every
+/// operation on it is O(1) except `remove`.
+pub struct OccupiedEntry<'a, K, V, C = Natural, A: Allocator = Global> {
+    tree: &'a mut Splay<K, V, C, A>,
+}
+
+pub struct VacantEntry<'a, K, V, C = Natural, A: Allocator = Global> {
+    tree: &'a mut Splay<K, V, C, A>,
+    key: K,
+}
+
+impl<K, V, C: Compare<K>, A: Allocator> Splay<K, V, C, A> {
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C, A> {
+        self.visit(OrCreate::Lookup(&key));
+        match self.root.to_option() {
+            Some(root) if self.cmp.compare(&self.nodes[root].key, &key) == Equal => {
+                Entry::Occupied(OccupiedEntry { tree: self })
+            }
+            _ => Entry::Vacant(VacantEntry { tree: self, key }),
+        }
+    }
+
+    fn extreme_entry(&mut self, dir: Dir) -> Option<OccupiedEntry<'_, K, V, C, A>> {
+        let root = self.root.to_option()?;
+        self.splay_extreme(root, dir);
+        Some(OccupiedEntry { tree: self })
+    }
+
+    /// The entry with the smallest key, splayed to the root.
+    pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, C, A>> {
+        self.extreme_entry(Dir::Left)
+    }
+
+    /// The entry with the largest key, splayed to the root.
+    pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, C, A>> {
+        self.extreme_entry(Dir::Right)
+    }
+}
+
+impl<'a, K, V, C: Compare<K>, A: Allocator> Entry<'a, K, V, C, A> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V, C: Compare<K>, A: Allocator> OccupiedEntry<'a, K, V, C, A> {
+    fn root(&self) -> usize {
+        self.tree.root.to_option().unwrap()
+    }
+
+    pub fn key(&self) -> &K {
+        &self.tree.nodes[self.root()].key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.tree.nodes[self.root()].value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        let root = self.root();
+        &mut self.tree.nodes[root].value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        let root = self.root();
+        &mut self.tree.nodes[root].value
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let node = self.tree.remove_root(&mut []);
+        (node.key, node.value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+}
+
+impl<'a, K, V, C: Compare<K>, A: Allocator> VacantEntry<'a, K, V, C, A> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        // The lookup which made this entry left a neighbour at the root, so
+        // inserting only walks one level.
+        self.tree.set(self.key, value);
+        let root = self.tree.root.to_option().unwrap();
+        &mut self.tree.nodes[root].value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basic_test() {
+        let mut tree = Splay::new();
+        *tree.entry("a").or_insert(0) += 1;
+        *tree.entry("a").or_insert(0) += 1;
+        tree.entry("b").and_modify(|v| *v = 10).or_default();
+        assert_eq!(tree.peek("a"), Some(&2));
+        assert_eq!(tree.peek("b"), Some(&0));
+
+        match tree.entry("a") {
+            Entry::Occupied(mut entry) => assert_eq!(entry.insert(5), 2),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(tree.entry("c").or_insert_with_key(|k| k.len()), &mut 1);
+
+        let first = tree.first_entry().unwrap();
+        assert_eq!(first.key(), &"a");
+        assert_eq!(first.remove(), 5);
+        let mut last = tree.last_entry().unwrap();
+        *last.get_mut() += 1;
+        assert_eq!(last.remove_entry(), ("c", 2));
+        assert_eq!(tree.iter().collect::<Vec<_>>(), [(&"b", &0)]);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_pop_min_if(keys: Vec<u8>, limit: u8) -> bool {
+        let mut tree: Splay<u8, ()> = keys.iter().map(|&k| (k, ())).collect();
+        let mut model: BTreeMap<u8, ()> = keys.iter().map(|&k| (k, ())).collect();
+        while let Some(entry) = tree.first_entry() {
+            if *entry.key() >= limit {
+                break;
+            }
+            entry.remove();
+        }
+        while let Some(entry) = model.first_entry() {
+            if *entry.key() >= limit {
+                break;
+            }
+            entry.remove();
+        }
+        tree.check_invariants().is_ok() && tree.keys().eq(model.keys())
+    }
+}
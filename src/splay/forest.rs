@@ -0,0 +1,572 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::mem;
+use core::slice;
+
+use super::{Dir, Idx, Link, OptionIdx, OrCreate, Splay, SplayIter, IDX_NONE};
+
+/// Handle to one tree of a [`SplayForest`]. Once the tree is removed, the
+/// handle may be given out again by `new_tree`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TreeId(usize);
+
+/// Several splay trees allocating their nodes from one shared arena.
+///
+/// Since all trees live in the same arena, moving entries from one tree to
+/// another is just relinking them: [`split_off`](Self::split_off) and
+/// [`join`](Self::join) never copy a node, nor does
+/// [`partition`](Self::partition). Each node records which tree owns it,
+/// so a removal only has to fix up the tree of the node it moves.
+#[derive(Clone)]
+pub struct SplayForest<K, V> {
+    // `arena.root` is only meaningful while one of the trees is entered.
+    arena: Splay<K, V>,
+    // Trees are kept under labels, which a split or join hands from one
+    // `TreeId` to another so that only the smaller side is relabelled.
+    roots: Vec<OptionIdx>,
+    lens: Vec<usize>,
+    // The label of the tree owning each arena slot.
+    owners: Vec<usize>,
+    // The label of each `TreeId`, `None` once removed.
+    labels: Vec<Option<usize>>,
+    free_ids: Vec<usize>,
+    free_labels: Vec<usize>,
+}
+
+impl<K: Ord, V> SplayForest<K, V> {
+    pub fn new() -> Self {
+        SplayForest {
+            arena: Splay::new(),
+            roots: Vec::new(),
+            lens: Vec::new(),
+            owners: Vec::new(),
+            labels: Vec::new(),
+            free_ids: Vec::new(),
+            free_labels: Vec::new(),
+        }
+    }
+
+    fn new_label(&mut self) -> usize {
+        match self.free_labels.pop() {
+            Some(label) => label,
+            None => {
+                self.roots.push(IDX_NONE);
+                self.lens.push(0);
+                self.roots.len() - 1
+            }
+        }
+    }
+
+    /// Adds a new, empty tree, reusing the handle of a removed one if
+    /// there is one.
+    pub fn new_tree(&mut self) -> TreeId {
+        let label = Some(self.new_label());
+        match self.free_ids.pop() {
+            Some(id) => {
+                self.labels[id] = label;
+                TreeId(id)
+            }
+            None => {
+                self.labels.push(label);
+                TreeId(self.labels.len() - 1)
+            }
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `tree` was removed.
+    fn label(&self, tree: TreeId) -> usize {
+        self.labels[tree.0].expect("the tree was removed")
+    }
+
+    /// Number of trees, including empty ones.
+    pub fn trees(&self) -> usize {
+        self.labels.len() - self.free_ids.len()
+    }
+
+    /// Number of nodes across all trees.
+    pub fn total_len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Number of entries in `tree`.
+    pub fn len(&self, tree: TreeId) -> usize {
+        self.lens[self.label(tree)]
+    }
+
+    pub fn is_empty(&self, tree: TreeId) -> bool {
+        self.len(tree) == 0
+    }
+
+    pub fn iter(&self, tree: TreeId) -> SplayIter<'_, K, V> {
+        let label = self.label(tree);
+        SplayIter::subtree(&self.arena, self.roots[label], self.lens[label])
+    }
+
+    /// Makes the tree under `label` the arena's current tree.
+    fn enter(&mut self, label: usize) {
+        self.arena.root = mem::replace(&mut self.roots[label], IDX_NONE);
+    }
+
+    fn leave(&mut self, label: usize) {
+        self.roots[label] = mem::replace(&mut self.arena.root, IDX_NONE);
+    }
+
+    pub fn get<Q>(&mut self, tree: TreeId, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let label = self.label(tree);
+        self.enter(label);
+        self.arena.visit(OrCreate::Lookup(key));
+        self.leave(label);
+        let root = self.roots[label].to_option()?;
+        let node = &self.arena.nodes[root];
+        if node.key.borrow() == key {
+            Some(&node.value)
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, tree: TreeId, key: K, value: V) {
+        let label = self.label(tree);
+        self.enter(label);
+        self.arena.set(key, value);
+        self.leave(label);
+        if self.arena.len() > self.owners.len() {
+            self.owners.push(label);
+            self.lens[label] += 1;
+        }
+    }
+
+    pub fn remove<Q>(&mut self, tree: TreeId, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let label = self.label(tree);
+        self.enter(label);
+        self.arena.visit(OrCreate::Lookup(key));
+        let value = match self.arena.root.to_option() {
+            Some(root) if self.arena.nodes[root].key.borrow() == key => {
+                // The last node moves into the freed slot. Its link is in
+                // the tree that owns it, which is this one unless stated.
+                let owner = *self.owners.last().unwrap();
+                let others = if owner == label {
+                    &mut []
+                } else {
+                    slice::from_mut(&mut self.roots[owner])
+                };
+                let node = self.arena.remove_root(others);
+                self.owners.swap_remove(root);
+                self.lens[label] -= 1;
+                Some(node.value)
+            }
+            _ => None,
+        };
+        self.leave(label);
+        value
+    }
+
+    /// Gives the nodes in `slots` to `label`.
+    fn relabel(&mut self, slots: &[Idx], label: usize) {
+        for &idx in slots {
+            self.owners[idx] = label;
+        }
+    }
+
+    /// The slots of whichever tree under `a` and `b` is smaller, and
+    /// whether that's `a`. Walks both a node at a time, so it takes time
+    /// in the size of the smaller one.
+    fn smaller(&self, a: OptionIdx, b: OptionIdx) -> (Vec<Idx>, bool) {
+        let mut walks = [a, b].map(|root| (Vec::new(), Vec::from_iter(root.to_option())));
+        loop {
+            for (i, (seen, stack)) in walks.iter_mut().enumerate() {
+                let Some(idx) = stack.pop() else {
+                    return (mem::take(seen), i == 0);
+                };
+                seen.push(idx);
+                let node = &self.arena.nodes[idx];
+                stack.extend(node.left.to_option());
+                stack.extend(node.right.to_option());
+            }
+        }
+    }
+
+    /// A new tree holding the nodes under `root`, of which there are `len`.
+    fn adopt(&mut self, root: OptionIdx, len: usize) -> TreeId {
+        let new = self.new_tree();
+        let label = self.label(new);
+        self.roots[label] = root;
+        self.lens[label] = len;
+        new
+    }
+
+    /// Moves every entry of `tree` with a key not less than `key` into a new
+    /// tree and returns it. Takes amortized O(log n) for the split and time
+    /// in the size of the smaller half to count it, as
+    /// `BTreeMap::split_off` does.
+    pub fn split_off<Q>(&mut self, tree: TreeId, key: &Q) -> TreeId
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let label = self.label(tree);
+        self.enter(label);
+        self.arena.visit(OrCreate::Lookup(key));
+        let mut tail = IDX_NONE;
+        if let Some(root) = self.arena.root.to_option() {
+            // The root is now the closest key, so the split runs right beside it.
+            if self.arena.nodes[root].key.borrow() < key {
+                tail = self.arena.child(root, Dir::Right);
+                self.arena.set_child(root, Dir::Right, IDX_NONE);
+            } else {
+                tail = self.arena.root;
+                self.arena.root = self.arena.child(root, Dir::Left);
+                self.arena.set_child(root, Dir::Left, IDX_NONE);
+            }
+        }
+        self.leave(label);
+
+        let (slots, head_smaller) = self.smaller(self.roots[label], tail);
+        let len = self.lens[label];
+        let (head_len, tail_len) = if head_smaller {
+            (slots.len(), len - slots.len())
+        } else {
+            (len - slots.len(), slots.len())
+        };
+        let new = self.adopt(tail, tail_len);
+        let new_label = self.label(new);
+        self.lens[label] = head_len;
+        if head_smaller {
+            // The tail keeps the old label, the head moves to the new one.
+            self.relabel(&slots, new_label);
+            self.roots.swap(label, new_label);
+            self.lens.swap(label, new_label);
+            self.labels.swap(tree.0, new.0);
+        } else {
+            self.relabel(&slots, new_label);
+        }
+        new
+    }
+
+    /// Moves the entries of `tree` for which `f` returns true into a new
+    /// tree and returns it. Both trees are relinked balanced, in O(n).
+    pub fn partition<F: FnMut(&K, &V) -> bool>(&mut self, tree: TreeId, mut f: F) -> TreeId {
+        let label = self.label(tree);
+        let (mut kept, mut taken) = (Vec::new(), Vec::new());
+        let mut stack = Vec::new();
+        let mut next = self.roots[label].to_option();
+        while next.is_some() || !stack.is_empty() {
+            while let Some(idx) = next {
+                stack.push(idx);
+                next = self.arena.nodes[idx].left.to_option();
+            }
+            let idx = stack.pop().unwrap();
+            let node = &self.arena.nodes[idx];
+            if f(&node.key, &node.value) {
+                taken.push(idx);
+            } else {
+                kept.push(idx);
+            }
+            next = node.right.to_option();
+        }
+        // Nothing is relinked until `f` has been called on every entry, so
+        // a panic leaves the tree as it was.
+        self.roots[label] = self.link_slots(&kept);
+        self.lens[label] = kept.len();
+        let root = self.link_slots(&taken);
+        let new = self.adopt(root, taken.len());
+        self.relabel(&taken, self.label(new));
+        new
+    }
+
+    /// Links the nodes in `slots`, which are in key order, into a balanced
+    /// tree.
+    fn link_slots(&mut self, slots: &[Idx]) -> OptionIdx {
+        if slots.is_empty() {
+            return IDX_NONE;
+        }
+        let mid = slots.len() / 2;
+        let left = self.link_slots(&slots[..mid]);
+        let right = self.link_slots(&slots[mid + 1..]);
+        self.arena.set_child(slots[mid], Dir::Left, left);
+        self.arena.set_child(slots[mid], Dir::Right, right);
+        OptionIdx(slots[mid])
+    }
+
+    /// Moves all entries of `right` to the end of `left`, leaving `right`
+    /// empty. Takes amortized O(log n), plus time in the size of the
+    /// smaller tree to record which tree its nodes now belong to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the smallest key of `right` isn't greater than the largest
+    /// key of `left`, or if both are the same tree.
+    pub fn join(&mut self, left: TreeId, right: TreeId) {
+        assert_ne!(left, right, "cannot join a tree with itself");
+        let (a, b) = (self.label(left), self.label(right));
+        let (Some(l), Some(r)) = (self.roots[a].to_option(), self.roots[b].to_option()) else {
+            if self.is_empty(left) {
+                self.labels.swap(left.0, right.0);
+            }
+            return;
+        };
+
+        // Both extremes keep their root index while being splayed up.
+        self.arena.splay_extreme(l, Dir::Right);
+        self.arena.splay_extreme(r, Dir::Left);
+        assert!(
+            self.arena.nodes[l].key < self.arena.nodes[r].key,
+            "joined trees overlap"
+        );
+        let (slots, left_smaller) = self.smaller(OptionIdx(l), OptionIdx(r));
+        self.arena.set_child(l, Dir::Right, OptionIdx(r));
+        self.roots[b] = IDX_NONE;
+        self.lens[a] += mem::take(&mut self.lens[b]);
+        if left_smaller {
+            // The joined tree goes under the right one's label.
+            self.relabel(&slots, b);
+            self.roots.swap(a, b);
+            self.lens.swap(a, b);
+            self.labels.swap(left.0, right.0);
+        } else {
+            self.relabel(&slots, a);
+        }
+    }
+
+    /// Removes `tree` with all its entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tree` was removed already.
+    pub fn remove_tree(&mut self, tree: TreeId) {
+        let label = self.label(tree);
+        let mut dead = Vec::new();
+        let mut stack = Vec::from_iter(self.roots[label].to_option());
+        while let Some(idx) = stack.pop() {
+            dead.push(idx);
+            let node = &self.arena.nodes[idx];
+            stack.extend(node.left.to_option());
+            stack.extend(node.right.to_option());
+        }
+        // Freeing the highest slots first means the node moved into each
+        // freed slot is one that stays, never one still to be freed.
+        dead.sort_unstable_by(|a, b| b.cmp(a));
+        for idx in dead {
+            let last = self.arena.len() - 1;
+            let link = if idx == last {
+                None
+            } else {
+                let owner = self.owners[last];
+                let link = self.arena.find_link(self.roots[owner], last);
+                Some((
+                    owner,
+                    link.unwrap_or_else(|| self.arena.scan_link(last, last)),
+                ))
+            };
+            self.arena.nodes.swap_remove(idx);
+            self.owners.swap_remove(idx);
+            match link {
+                None => {}
+                Some((owner, Link::Top)) => self.roots[owner] = OptionIdx(idx),
+                Some((_, Link::Child(parent, dir))) => {
+                    self.arena.set_child(parent, dir, OptionIdx(idx))
+                }
+            }
+        }
+        self.roots[label] = IDX_NONE;
+        self.lens[label] = 0;
+        self.free_labels.push(label);
+        self.labels[tree.0] = None;
+        self.free_ids.push(tree.0);
+    }
+}
+
+impl<K: Ord, V> SplayForest<K, V> {
+    /// Whether every tree's size and every node's owner are right.
+    #[cfg(test)]
+    fn is_consistent(&self) -> bool {
+        let live = self.labels.iter().flatten();
+        live.clone().all(|&label| {
+            let slots = self.iter_slots(self.roots[label]);
+            slots.len() == self.lens[label] && slots.iter().all(|&idx| self.owners[idx] == label)
+        }) && live.map(|&label| self.lens[label]).sum::<usize>() == self.arena.len()
+            && self.owners.len() == self.arena.len()
+    }
+
+    #[cfg(test)]
+    fn iter_slots(&self, root: OptionIdx) -> Vec<Idx> {
+        let mut slots = Vec::new();
+        let mut stack = Vec::from_iter(root.to_option());
+        while let Some(idx) = stack.pop() {
+            slots.push(idx);
+            let node = &self.arena.nodes[idx];
+            stack.extend(node.left.to_option());
+            stack.extend(node.right.to_option());
+        }
+        slots
+    }
+}
+
+impl<K: Ord, V> Default for SplayForest<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basic_test() {
+        let mut forest = SplayForest::new();
+        let a = forest.new_tree();
+        let b = forest.new_tree();
+        for i in 0..10 {
+            forest.set(a, i, i * 10);
+            forest.set(b, i, i * 100);
+        }
+        assert_eq!(forest.get(a, &3), Some(&30));
+        assert_eq!(forest.get(b, &3), Some(&300));
+        assert_eq!(forest.remove(a, &3), Some(30));
+        assert_eq!(forest.get(a, &3), None);
+        assert_eq!(forest.get(b, &3), Some(&300));
+        assert_eq!(forest.total_len(), 19);
+
+        let c = forest.split_off(b, &5);
+        assert_eq!(
+            forest.iter(b).map(|(k, _)| *k).collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4]
+        );
+        assert_eq!(
+            forest.iter(c).map(|(k, _)| *k).collect::<Vec<_>>(),
+            [5, 6, 7, 8, 9]
+        );
+        assert_eq!(forest.total_len(), 19);
+
+        forest.join(b, c);
+        assert!(forest.is_empty(c));
+        assert_eq!(forest.len(b), 10);
+        assert_eq!(forest.get(b, &7), Some(&700));
+
+        let odd = forest.partition(b, |k, _| k % 2 == 1);
+        assert_eq!((forest.len(b), forest.len(odd)), (5, 5));
+        assert_eq!(forest.get(odd, &7), Some(&700));
+        assert_eq!(forest.get(b, &7), None);
+
+        // A removed tree's handle goes to the next new one.
+        forest.remove_tree(a);
+        assert_eq!((forest.trees(), forest.total_len()), (3, 10));
+        assert_eq!(forest.new_tree(), a);
+        assert!(forest.is_empty(a));
+        assert!(forest.is_consistent());
+    }
+
+    #[test]
+    #[should_panic(expected = "joined trees overlap")]
+    fn join_overlap_test() {
+        let mut forest = SplayForest::new();
+        let a = forest.new_tree();
+        let b = forest.new_tree();
+        forest.set(a, 5, ());
+        forest.set(b, 1, ());
+        forest.join(a, b);
+    }
+
+    #[derive(Clone, Debug)]
+    enum Op {
+        Set(u8, u8, u8),
+        Remove(u8, u8),
+        Split(u8, u8),
+        Join(u8, u8),
+        Partition(u8, u8),
+        RemoveTree(u8),
+    }
+
+    impl quickcheck::Arbitrary for Op {
+        fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+            match u8::arbitrary(g) % 8 {
+                0..=2 => Op::Set(u8::arbitrary(g), u8::arbitrary(g), u8::arbitrary(g)),
+                3 => Op::Remove(u8::arbitrary(g), u8::arbitrary(g)),
+                4 => Op::Split(u8::arbitrary(g), u8::arbitrary(g)),
+                5 => Op::Join(u8::arbitrary(g), u8::arbitrary(g)),
+                6 => Op::Partition(u8::arbitrary(g), u8::arbitrary(g)),
+                _ => Op::RemoveTree(u8::arbitrary(g)),
+            }
+        }
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<Op>) -> bool {
+        let mut forest = SplayForest::new();
+        // The live trees, each next to its model.
+        let mut model = vec![(forest.new_tree(), BTreeMap::new())];
+        for op in ops {
+            let trees = model.len();
+            let pick = |t: u8| t as usize % trees;
+            match op {
+                Op::Set(t, k, v) => {
+                    let (t, m) = &mut model[pick(t)];
+                    forest.set(*t, k, v);
+                    m.insert(k, v);
+                }
+                Op::Remove(t, k) => {
+                    let (t, m) = &mut model[pick(t)];
+                    if forest.remove(*t, &k) != m.remove(&k) {
+                        return false;
+                    }
+                }
+                Op::Split(t, k) => {
+                    let (t, m) = &mut model[pick(t)];
+                    let new = forest.split_off(*t, &k);
+                    let tail = m.split_off(&k);
+                    model.push((new, tail));
+                }
+                Op::Join(a, b) => {
+                    let (a, b) = (pick(a), pick(b));
+                    let ordered = match (model[a].1.last_key_value(), model[b].1.first_key_value())
+                    {
+                        (Some((x, _)), Some((y, _))) => x < y,
+                        _ => true,
+                    };
+                    if a != b && ordered {
+                        forest.join(model[a].0, model[b].0);
+                        let mut moved = mem::take(&mut model[b].1);
+                        model[a].1.append(&mut moved);
+                    }
+                }
+                Op::Partition(t, m) => {
+                    let (t, map) = &mut model[pick(t)];
+                    let m = m % 4 + 1;
+                    let new = forest.partition(*t, |k, _| k % m == 0);
+                    let (taken, kept) = mem::take(map).into_iter().partition(|(k, _)| k % m == 0);
+                    *map = kept;
+                    model.push((new, taken));
+                }
+                Op::RemoveTree(t) => {
+                    if model.len() > 1 {
+                        let (t, _) = model.swap_remove(pick(t));
+                        forest.remove_tree(t);
+                    }
+                }
+            }
+            if !forest.is_consistent() || forest.trees() != model.len() {
+                return false;
+            }
+        }
+        model.iter().all(|(t, m)| {
+            forest.len(*t) == m.len()
+                && forest
+                    .iter(*t)
+                    .map(|(k, v)| (*k, *v))
+                    .eq(m.iter().map(|(k, v)| (*k, *v)))
+        }) && forest.total_len() == model.iter().map(|(_, m)| m.len()).sum::<usize>()
+    }
+}
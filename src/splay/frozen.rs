@@ -0,0 +1,198 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::ops::{Bound, RangeBounds};
+use core::slice;
+
+use super::compat::check_range;
+use super::{Allocator, Splay};
+use crate::compare::Natural;
+
+/// Immutable snapshot of a [`Splay`], stored as a sorted array.
+///
+/// Lookups are binary searches through `&self` and nothing is mutated after
+/// construction, so it's `Send + Sync` whenever `K` and `V` are and can be
+/// shared across threads behind an `Arc`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrozenSplay<K, V> {
+    entries: Vec<(K, V)>,
+}
+
+impl<K: Ord, V, A: Allocator> Splay<K, V, Natural, A> {
+    pub fn freeze(self) -> FrozenSplay<K, V> {
+        FrozenSplay {
+            entries: self.into_entries(),
+        }
+    }
+}
+
+impl<K: Ord, V> FrozenSplay<K, V> {
+    /// Turns the snapshot back into a (balanced) mutable tree.
+    pub fn thaw(self) -> Splay<K, V> {
+        Splay::from_sorted_unique(self.entries)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn position<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entries
+            .binary_search_by(|(k, _)| k.borrow().cmp(key))
+            .ok()
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.position(key).map(|i| &self.entries[i].1)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.position(key).map(|i| {
+            let (k, v) = &self.entries[i];
+            (k, v)
+        })
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.position(key).is_some()
+    }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.entries.first().map(|(k, v)| (k, v))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.entries.last().map(|(k, v)| (k, v))
+    }
+
+    pub fn iter(&self) -> FrozenIter<'_, K, V> {
+        FrozenIter(self.entries.iter())
+    }
+
+    /// Iterates over the entries within `range`, finding both ends in
+    /// O(log n).
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same malformed ranges as `Splay::range`.
+    pub fn range<Q, R>(&self, range: R) -> FrozenIter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        check_range(&range);
+        let before = |k: &K, bound: Bound<&Q>| match bound {
+            Bound::Included(b) => k.borrow() < b,
+            Bound::Excluded(b) => k.borrow() <= b,
+            Bound::Unbounded => false,
+        };
+        let within = |k: &K, bound: Bound<&Q>| match bound {
+            Bound::Included(b) => k.borrow() <= b,
+            Bound::Excluded(b) => k.borrow() < b,
+            Bound::Unbounded => true,
+        };
+        let start = self
+            .entries
+            .partition_point(|(k, _)| before(k, range.start_bound()));
+        let end = self
+            .entries
+            .partition_point(|(k, _)| within(k, range.end_bound()));
+        let end = end.max(start);
+        FrozenIter(self.entries[start..end].iter())
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a FrozenSplay<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = FrozenIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct FrozenIter<'a, K, V>(slice::Iter<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for FrozenIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for FrozenIter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(k, v)| (k, v))
+    }
+}
+
+impl<K, V> ExactSizeIterator for FrozenIter<'_, K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn basic_test() {
+        let tree: Splay<i32, i32> = (0..100).map(|x| (x * 2, x)).collect();
+        let frozen = Arc::new(tree.freeze());
+        assert_eq!(frozen.get(&10), Some(&5));
+        assert_eq!(frozen.get(&11), None);
+        assert_eq!(frozen.range(9..=14).count(), 3);
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let frozen = Arc::clone(&frozen);
+                thread::spawn(move || frozen.get(&(t * 2)).copied())
+            })
+            .collect();
+        for (t, handle) in handles.into_iter().enumerate() {
+            assert_eq!(handle.join().unwrap(), Some(t as i32));
+        }
+
+        let mut tree = Arc::try_unwrap(frozen).unwrap().thaw();
+        assert_eq!(tree.get(&198), Some(&99));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_range(entries: Vec<(u8, u8)>, a: u8, b: u8) -> bool {
+        let frozen = entries.iter().copied().collect::<Splay<_, _>>().freeze();
+        let map: BTreeMap<u8, u8> = entries.into_iter().collect();
+        let (lo, hi) = (a.min(b), a.max(b));
+        frozen.iter().eq(map.iter())
+            && frozen.range(lo..hi).eq(map.range(lo..hi))
+            && frozen.range(lo..=hi).eq(map.range(lo..=hi))
+            && frozen
+                .range((Bound::Excluded(lo), Bound::Unbounded))
+                .eq(map.range((Bound::Excluded(lo), Bound::Unbounded)))
+    }
+}
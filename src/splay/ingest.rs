@@ -0,0 +1,277 @@
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ordering::{Equal, Greater, Less};
+use core::fmt;
+use core::mem;
+
+use super::Splay;
+
+const DEFAULT_BUFFER: usize = 8192;
+// A flush rebuilds the tree when the writes are at least this fraction of
+// it, and otherwise applies them in key order.
+const REBUILD_RATIO: usize = 32;
+
+/// A [`Splay`] taking writes into an unsorted buffer, merged into the tree
+/// a batch at a time, like the memtable of an LSM tree but in memory.
+///
+/// The merge is synchronous: the write which fills the buffer sorts it and
+/// merges it before returning, so every `buffer`-th write pauses for the
+/// merge, and nothing runs in the background. A batch that is large next
+/// to the tree is merged with its entries in one pass and the tree rebuilt
+/// balanced in O(n + b), as `merge_with` does. A smaller one is applied in
+/// key order, each batch walking the other way from the last, so it starts
+/// from the end the last one left at the root, about O(log(n / b)) a key.
+/// Either way a burst of writes costs far less than as many `set`s at
+/// random places.
+///
+/// `get` merges the buffer first, so reads interleaved with writes lose
+/// the benefit; `peek` reads through it instead, newest write first.
+pub struct IngestSplay<K, V> {
+    tree: Splay<K, V>,
+    // Writes in the order made, `None` removing the key.
+    buffer: Vec<(K, Option<V>)>,
+    capacity: usize,
+    // Whether the last batch applied key by key went down from the top.
+    descending: bool,
+}
+
+impl<K: Ord, V> IngestSplay<K, V> {
+    pub fn new() -> Self {
+        Self::with_buffer(DEFAULT_BUFFER)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `buffer` is zero.
+    pub fn with_buffer(buffer: usize) -> Self {
+        Splay::new().into_ingest(buffer)
+    }
+
+    /// The number of writes waiting to be merged.
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Buffers a write, merging the buffer right away once it's full.
+    fn write(&mut self, key: K, value: Option<V>) {
+        self.buffer.push((key, value));
+        if self.buffer.len() == self.capacity {
+            self.flush();
+        }
+    }
+
+    /// Sets `key` to `value` once the buffer is merged.
+    pub fn set(&mut self, key: K, value: V) {
+        self.write(key, Some(value));
+    }
+
+    /// Removes `key` once the buffer is merged.
+    pub fn delete(&mut self, key: K) {
+        self.write(key, None);
+    }
+
+    /// Merges the waiting writes into the tree.
+    pub fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let mut writes = mem::take(&mut self.buffer);
+        // Reversing first makes the stable sort put the last write of each
+        // key first, which is the one `dedup_by` keeps.
+        writes.reverse();
+        writes.sort_by(|a, b| a.0.cmp(&b.0));
+        writes.dedup_by(|a, b| a.0 == b.0);
+        // Keep the allocation for the next batch.
+        self.buffer = Vec::with_capacity(writes.capacity());
+
+        if writes.len() * REBUILD_RATIO < self.tree.len() {
+            // Batches after one another in the same direction leave the
+            // tree a long path, which alternating avoids.
+            self.descending = !self.descending;
+            if self.descending {
+                writes.reverse();
+            }
+            for (key, write) in writes {
+                match write {
+                    Some(value) => self.tree.set(key, value),
+                    None => drop(self.tree.remove(&key)),
+                }
+            }
+            return;
+        }
+        let ours = mem::take(&mut self.tree).into_entries();
+        let mut merged = Vec::with_capacity(ours.len() + writes.len());
+        let (mut ours, mut writes) = (ours.into_iter().peekable(), writes.into_iter().peekable());
+        loop {
+            let entry = match (ours.peek(), writes.peek()) {
+                (Some(a), Some(b)) => match a.0.cmp(&b.0) {
+                    Less => ours.next(),
+                    Equal => {
+                        ours.next();
+                        let (key, write) = writes.next().unwrap();
+                        write.map(|value| (key, value))
+                    }
+                    Greater => {
+                        let (key, write) = writes.next().unwrap();
+                        write.map(|value| (key, value))
+                    }
+                },
+                (Some(_), None) => ours.next(),
+                (None, Some(_)) => {
+                    let (key, write) = writes.next().unwrap();
+                    write.map(|value| (key, value))
+                }
+                (None, None) => break,
+            };
+            merged.extend(entry);
+        }
+        self.tree = Splay::from_sorted_unique(merged);
+    }
+
+    /// Merges the waiting writes and looks `key` up, splaying it.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.flush();
+        self.tree.get(key)
+    }
+
+    /// Looks `key` up in the newest write to it, or the tree if there's
+    /// none waiting, without merging or splaying anything. O(b + log n).
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.buffer.iter().rev().find(|(k, _)| k.borrow() == key) {
+            Some((_, write)) => write.as_ref(),
+            None => self.tree.peek(key),
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.peek(key).is_some()
+    }
+
+    /// Merges the waiting writes and hands out the tree.
+    pub fn tree(&mut self) -> &mut Splay<K, V> {
+        self.flush();
+        &mut self.tree
+    }
+
+    pub fn into_tree(mut self) -> Splay<K, V> {
+        self.flush();
+        self.tree
+    }
+}
+
+impl<K: Ord, V> Splay<K, V> {
+    /// Takes writes into a buffer of `buffer` writes from now on, see
+    /// [`IngestSplay`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer` is zero.
+    pub fn into_ingest(self, buffer: usize) -> IngestSplay<K, V> {
+        assert!(buffer > 0, "the buffer must hold a write");
+        IngestSplay {
+            tree: self,
+            buffer: Vec::with_capacity(buffer),
+            capacity: buffer,
+            descending: false,
+        }
+    }
+}
+
+impl<K: Ord, V> Default for IngestSplay<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for IngestSplay<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.set(key, value);
+        }
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for IngestSplay<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IngestSplay")
+            .field("tree", &self.tree)
+            .field("pending", &self.buffer.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut map = IngestSplay::with_buffer(4);
+        map.set(3, "c");
+        map.set(1, "a");
+        map.set(3, "C");
+        assert_eq!((map.pending(), map.peek(&3)), (3, Some(&"C")));
+        map.delete(1);
+        // The fourth write merged the batch.
+        assert_eq!(map.pending(), 0);
+        assert_eq!(map.peek(&1), None);
+        map.set(2, "b");
+        assert_eq!(map.get(&2), Some(&"b"));
+        assert_eq!(map.pending(), 0);
+
+        // Batches this small next to the tree go in key by key.
+        let mut map = (0..1000)
+            .map(|k| (k, k))
+            .collect::<Splay<_, _>>()
+            .into_ingest(16);
+        map.extend((0..100).rev().map(|k| (k * 7, 0)));
+        let tree = map.into_tree();
+        assert!(tree.check_invariants().is_ok());
+        assert_eq!(
+            (tree.len(), tree.peek(&693), tree.peek(&694)),
+            (1000, Some(&0), Some(&694))
+        );
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>, buffer: u8) -> bool {
+        let mut map = IngestSplay::with_buffer(buffer as usize % 8 + 1);
+        let mut model = BTreeMap::new();
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 32;
+            let ok = match op % 5 {
+                0 | 1 => {
+                    map.set(key, i);
+                    model.insert(key, i);
+                    true
+                }
+                2 => {
+                    map.delete(key);
+                    model.remove(&key);
+                    true
+                }
+                3 => map.peek(&key) == model.get(&key),
+                _ => map.get(&key) == model.get(&key),
+            };
+            if !ok {
+                return false;
+            }
+        }
+        let tree = map.into_tree();
+        tree.check_invariants().is_ok() && tree.iter().eq(model.iter())
+    }
+}
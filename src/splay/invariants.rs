@@ -0,0 +1,184 @@
+use alloc::vec::Vec;
+use core::cmp::Ordering::Less;
+use core::fmt;
+
+use super::{Allocator, Idx, OptionIdx, Splay};
+use crate::compare::Compare;
+
+/// The first structural problem found by [`Splay::check_invariants`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvariantError {
+    /// A link points past the end of the arena. `parent` is `None` for the
+    /// root link.
+    DanglingLink { parent: Option<usize>, index: usize },
+    /// A node is linked to more than once, so the links contain a cycle or
+    /// a shared subtree.
+    DuplicateLink { index: usize },
+    /// Some arena slots aren't reachable from the root.
+    Unreachable { reachable: usize, len: usize },
+    /// A node's key isn't strictly between the keys of the ancestors it
+    /// sits beneath.
+    OutOfOrder { index: usize },
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantError::DanglingLink {
+                parent: Some(parent),
+                index,
+            } => write!(f, "node {parent} links to missing node {index}"),
+            InvariantError::DanglingLink {
+                parent: None,
+                index,
+            } => write!(f, "root links to missing node {index}"),
+            InvariantError::DuplicateLink { index } => {
+                write!(f, "node {index} is linked to more than once")
+            }
+            InvariantError::Unreachable { reachable, len } => {
+                write!(f, "only {reachable} of {len} nodes are reachable")
+            }
+            InvariantError::OutOfOrder { index } => {
+                write!(f, "key of node {index} is out of order")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvariantError {}
+
+impl<K, V, C: Compare<K>, A: Allocator> Splay<K, V, C, A> {
+    /// Checks that the arena holds exactly one binary search tree: every link
+    /// is in bounds, every slot is reached exactly once from the root, and
+    /// keys are strictly increasing in the tree's order. Takes O(n).
+    pub fn check_invariants(&self) -> Result<(), InvariantError> {
+        struct Visit {
+            link: OptionIdx,
+            parent: Option<Idx>,
+            // Nodes whose keys bound this subtree from below and above.
+            lo: Option<Idx>,
+            hi: Option<Idx>,
+        }
+
+        let len = self.nodes.len();
+        let mut seen = alloc::vec![false; len];
+        let mut stack = Vec::new();
+        stack.push(Visit {
+            link: self.root,
+            parent: None,
+            lo: None,
+            hi: None,
+        });
+        let mut reachable = 0;
+        while let Some(Visit {
+            link,
+            parent,
+            lo,
+            hi,
+        }) = stack.pop()
+        {
+            let Some(index) = link.to_option() else {
+                continue;
+            };
+            if index >= len {
+                return Err(InvariantError::DanglingLink { parent, index });
+            }
+            if seen[index] {
+                return Err(InvariantError::DuplicateLink { index });
+            }
+            seen[index] = true;
+            reachable += 1;
+
+            let key = &self.nodes[index].key;
+            let less = |a: &K, b: &K| self.cmp.compare(a, b) == Less;
+            let above_lo = lo.is_none_or(|lo| less(&self.nodes[lo].key, key));
+            let below_hi = hi.is_none_or(|hi| less(key, &self.nodes[hi].key));
+            if !(above_lo && below_hi) {
+                return Err(InvariantError::OutOfOrder { index });
+            }
+            stack.push(Visit {
+                link: self.nodes[index].left,
+                parent: Some(index),
+                lo,
+                hi: Some(index),
+            });
+            stack.push(Visit {
+                link: self.nodes[index].right,
+                parent: Some(index),
+                lo: Some(index),
+                hi,
+            });
+        }
+        if reachable != len {
+            return Err(InvariantError::Unreachable { reachable, len });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::splay::{Dir, IDX_NONE};
+    use quickcheck_macros::quickcheck;
+
+    fn tree() -> Splay<u32, ()> {
+        (0..7).map(|k| (k, ())).collect()
+    }
+
+    #[test]
+    fn corruption_test() {
+        assert_eq!(tree().check_invariants(), Ok(()));
+
+        let mut broken = tree();
+        let root = broken.root.to_option().unwrap();
+        broken.set_child(root, Dir::Left, OptionIdx(100));
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantError::DanglingLink {
+                parent: Some(root),
+                index: 100
+            })
+        );
+
+        let mut broken = tree();
+        broken.set_child(root, Dir::Left, IDX_NONE);
+        assert!(matches!(
+            broken.check_invariants(),
+            Err(InvariantError::Unreachable {
+                reachable: 4,
+                len: 7
+            })
+        ));
+
+        let mut broken = tree();
+        broken.set_child(root, Dir::Left, OptionIdx(root));
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantError::DuplicateLink { index: root })
+        );
+
+        let mut broken = tree();
+        broken.nodes[0].key = 10;
+        assert!(matches!(
+            broken.check_invariants(),
+            Err(InvariantError::OutOfOrder { .. })
+        ));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(keys: Vec<u16>) -> bool {
+        let mut tree = Splay::new();
+        for &k in &keys {
+            tree.set(k, ());
+            if k % 3 == 0 {
+                tree.remove(&(k / 2));
+            }
+            if tree.check_invariants().is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
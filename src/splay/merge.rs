@@ -0,0 +1,338 @@
+//! Linear-time operations over two trees at once, walking both in key order.
+
+use alloc::vec::Vec;
+use core::cmp::Ordering::{Equal, Greater, Less};
+use core::iter::{FusedIterator, Peekable};
+use core::mem;
+
+use super::{Allocator, Global, Splay, SplayIter};
+use crate::compare::Natural;
+
+/// Which unmatched keys a [`JoinIter`] yields.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinMode {
+    /// Only keys present in both trees.
+    Inner,
+    /// Every key of the left tree.
+    Left,
+    /// Every key of either tree.
+    Full,
+}
+
+/// A merge join of two trees, from [`Splay::join_iter`].
+pub struct JoinIter<'a, K: Ord, Va, Vb, A: Allocator = Global, B: Allocator = Global> {
+    a: Peekable<SplayIter<'a, K, Va, Natural, A>>,
+    b: Peekable<SplayIter<'a, K, Vb, Natural, B>>,
+    mode: JoinMode,
+}
+
+impl<K: Ord, V, A: Allocator> Splay<K, V, Natural, A> {
+    /// Walks `a` and `b` side by side in key order, pairing up the values
+    /// stored under equal keys. Neither tree is splayed or copied.
+    pub fn join_iter<'a, Vb, B: Allocator>(
+        a: &'a Self,
+        b: &'a Splay<K, Vb, Natural, B>,
+        mode: JoinMode,
+    ) -> JoinIter<'a, K, V, Vb, A, B> {
+        JoinIter {
+            a: a.iter().peekable(),
+            b: b.iter().peekable(),
+            mode,
+        }
+    }
+}
+
+impl<'a, K: Ord, Va, Vb, A: Allocator, B: Allocator> Iterator for JoinIter<'a, K, Va, Vb, A, B> {
+    type Item = (&'a K, Option<&'a Va>, Option<&'a Vb>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.a.peek(), self.b.peek()) {
+                (Some(x), Some(y)) => x.0.cmp(y.0),
+                (Some(_), None) if self.mode == JoinMode::Inner => return None,
+                (Some(_), None) => Less,
+                (None, Some(_)) if self.mode == JoinMode::Full => Greater,
+                (None, _) => return None,
+            };
+            match order {
+                Less => {
+                    let (k, va) = self.a.next().unwrap();
+                    if self.mode != JoinMode::Inner {
+                        return Some((k, Some(va), None));
+                    }
+                }
+                Equal => {
+                    let (k, va) = self.a.next().unwrap();
+                    let (_, vb) = self.b.next().unwrap();
+                    return Some((k, Some(va), Some(vb)));
+                }
+                Greater => {
+                    let (k, vb) = self.b.next().unwrap();
+                    if self.mode == JoinMode::Full {
+                        return Some((k, None, Some(vb)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<K: Ord, Va, Vb, A: Allocator, B: Allocator> FusedIterator for JoinIter<'_, K, Va, Vb, A, B> {}
+
+/// Entries of one tree whose keys aren't in another, from
+/// [`Splay::difference`].
+pub struct Difference<'a, K: Ord, V, Vb, A: Allocator = Global, B: Allocator = Global> {
+    join: JoinIter<'a, K, V, Vb, A, B>,
+}
+
+/// Entries whose keys are in exactly one of two trees, from
+/// [`Splay::symmetric_difference`].
+pub struct SymmetricDifference<'a, K: Ord, V, A: Allocator = Global, B: Allocator = Global> {
+    join: JoinIter<'a, K, V, V, A, B>,
+}
+
+impl<K: Ord, V, A: Allocator> Splay<K, V, Natural, A> {
+    /// Lazily yields the entries of `self` whose keys `other` lacks, in key
+    /// order.
+    pub fn difference<'a, Vb, B: Allocator>(
+        &'a self,
+        other: &'a Splay<K, Vb, Natural, B>,
+    ) -> Difference<'a, K, V, Vb, A, B> {
+        Difference {
+            join: Splay::join_iter(self, other, JoinMode::Left),
+        }
+    }
+
+    /// Lazily yields the entries of either tree whose keys the other lacks,
+    /// in key order.
+    pub fn symmetric_difference<'a, B: Allocator>(
+        &'a self,
+        other: &'a Splay<K, V, Natural, B>,
+    ) -> SymmetricDifference<'a, K, V, A, B> {
+        SymmetricDifference {
+            join: Splay::join_iter(self, other, JoinMode::Full),
+        }
+    }
+}
+
+impl<'a, K: Ord, V, Vb, A: Allocator, B: Allocator> Iterator for Difference<'a, K, V, Vb, A, B> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.join.find_map(|(k, va, vb)| match (va, vb) {
+            (Some(va), None) => Some((k, va)),
+            _ => None,
+        })
+    }
+}
+
+impl<K: Ord, V, Vb, A: Allocator, B: Allocator> FusedIterator for Difference<'_, K, V, Vb, A, B> {}
+
+impl<'a, K: Ord, V, A: Allocator, B: Allocator> Iterator for SymmetricDifference<'a, K, V, A, B> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.join.find_map(|(k, va, vb)| match (va, vb) {
+            (Some(v), None) | (None, Some(v)) => Some((k, v)),
+            _ => None,
+        })
+    }
+}
+
+impl<K: Ord, V, A: Allocator, B: Allocator> FusedIterator for SymmetricDifference<'_, K, V, A, B> {}
+
+/// One difference between two trees, from [`Splay::diff`], or a change to
+/// make to a tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffOp<K, V> {
+    /// An entry only the second tree has.
+    Added(K, V),
+    /// An entry only the first tree has.
+    Removed(K, V),
+    /// A key both have, with the first tree's value and the second's.
+    Changed(K, V, V),
+}
+
+impl<K, V> DiffOp<K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            DiffOp::Added(k, _) | DiffOp::Removed(k, _) | DiffOp::Changed(k, _, _) => k,
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> DiffOp<&K, &V> {
+    /// The same change with its key and values cloned, to keep or to apply
+    /// with [`Splay::apply_patch`].
+    pub fn cloned(self) -> DiffOp<K, V> {
+        match self {
+            DiffOp::Added(k, v) => DiffOp::Added(k.clone(), v.clone()),
+            DiffOp::Removed(k, v) => DiffOp::Removed(k.clone(), v.clone()),
+            DiffOp::Changed(k, old, new) => DiffOp::Changed(k.clone(), old.clone(), new.clone()),
+        }
+    }
+}
+
+/// The differences between two trees in key order, from [`Splay::diff`].
+pub struct Diff<'a, K: Ord, V, A: Allocator = Global, B: Allocator = Global> {
+    join: JoinIter<'a, K, V, V, A, B>,
+}
+
+impl<K: Ord, V: PartialEq, A: Allocator> Splay<K, V, Natural, A> {
+    /// Lazily yields what changed going from `a` to `b`, in key order, in
+    /// one pass over both. Equal entries are skipped, and neither tree is
+    /// splayed or copied.
+    pub fn diff<'a, B: Allocator>(
+        a: &'a Self,
+        b: &'a Splay<K, V, Natural, B>,
+    ) -> Diff<'a, K, V, A, B> {
+        Diff {
+            join: Splay::join_iter(a, b, JoinMode::Full),
+        }
+    }
+}
+
+impl<'a, K: Ord, V: PartialEq, A: Allocator, B: Allocator> Iterator for Diff<'a, K, V, A, B> {
+    type Item = DiffOp<&'a K, &'a V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.join.find_map(|(k, va, vb)| match (va, vb) {
+            (Some(va), Some(vb)) if va == vb => None,
+            (Some(va), Some(vb)) => Some(DiffOp::Changed(k, va, vb)),
+            (Some(va), None) => Some(DiffOp::Removed(k, va)),
+            (None, Some(vb)) => Some(DiffOp::Added(k, vb)),
+            (None, None) => None,
+        })
+    }
+}
+
+impl<K: Ord, V: PartialEq, A: Allocator, B: Allocator> FusedIterator for Diff<'_, K, V, A, B> {}
+
+impl<K: Ord, V> Splay<K, V> {
+    /// Moves every entry of `other` into `self`, calling `f(key, mine,
+    /// theirs)` to pick the value for keys present in both. Both trees are
+    /// flattened and merged in O(n + m) and the result is rebuilt balanced.
+    pub fn merge_with<F>(&mut self, other: Splay<K, V>, mut f: F)
+    where
+        F: FnMut(&K, V, V) -> V,
+    {
+        let ours = mem::take(self).into_entries();
+        let theirs = other.into_entries();
+        let mut merged = Vec::with_capacity(ours.len() + theirs.len());
+        let (mut ours, mut theirs) = (ours.into_iter().peekable(), theirs.into_iter().peekable());
+        loop {
+            let entry = match (ours.peek(), theirs.peek()) {
+                (Some(a), Some(b)) => match a.0.cmp(&b.0) {
+                    Less => ours.next(),
+                    Equal => {
+                        let (key, mine) = ours.next().unwrap();
+                        let theirs = theirs.next().unwrap().1;
+                        let value = f(&key, mine, theirs);
+                        Some((key, value))
+                    }
+                    Greater => theirs.next(),
+                },
+                (Some(_), None) => ours.next(),
+                (None, _) => theirs.next(),
+            };
+            match entry {
+                Some(entry) => merged.push(entry),
+                None => break,
+            }
+        }
+        *self = Self::from_sorted_unique(merged);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn basic_test() {
+        let mut a: Splay<u32, u32> = [(1, 10), (3, 30), (5, 50)].into();
+        let b: Splay<u32, u32> = [(2, 2), (3, 3), (5, 5), (6, 6)].into();
+        a.merge_with(b, |k, mine, theirs| k + mine + theirs);
+        assert!(a.check_invariants().is_ok());
+        let entries: Vec<_> = a.into();
+        assert_eq!(entries, [(1, 10), (2, 2), (3, 36), (5, 60), (6, 6)]);
+    }
+
+    #[test]
+    fn join_test() {
+        let a: Splay<u32, char> = [(1, 'a'), (2, 'b'), (4, 'd')].into();
+        let b: Splay<u32, &str> = [(2, "two"), (3, "three"), (4, "four")].into();
+        let inner: Vec<_> = Splay::join_iter(&a, &b, JoinMode::Inner).collect();
+        assert_eq!(
+            inner,
+            [
+                (&2, Some(&'b'), Some(&"two")),
+                (&4, Some(&'d'), Some(&"four"))
+            ]
+        );
+        let left: Vec<_> = Splay::join_iter(&a, &b, JoinMode::Left)
+            .map(|e| e.0)
+            .collect();
+        assert_eq!(left, [&1, &2, &4]);
+        let full: Vec<_> = Splay::join_iter(&a, &b, JoinMode::Full).collect();
+        assert_eq!(full.len(), 4);
+        assert_eq!(full[2], (&3, None, Some(&"three")));
+    }
+
+    #[test]
+    fn diff_test() {
+        let a: Splay<u32, char> = [(1, 'a'), (2, 'b'), (4, 'd')].into();
+        let b: Splay<u32, char> = [(2, 'b'), (3, 'c'), (4, 'D')].into();
+        let ops: Vec<_> = Splay::diff(&a, &b).collect();
+        assert_eq!(
+            ops,
+            [
+                DiffOp::Removed(&1, &'a'),
+                DiffOp::Added(&3, &'c'),
+                DiffOp::Changed(&4, &'d', &'D')
+            ]
+        );
+        assert_eq!(ops[1].key(), &&3);
+        assert_eq!(Splay::diff(&a, &a).next(), None);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_diff(a: Vec<(u8, bool)>, b: Vec<(u8, bool)>) -> bool {
+        let tree_a: Splay<u8, bool> = a.into_iter().collect();
+        let tree_b: Splay<u8, bool> = b.into_iter().collect();
+        // Replaying the diff over `a` gives `b`.
+        let mut entries: std::collections::BTreeMap<u8, bool> =
+            tree_a.iter().map(|(&k, &v)| (k, v)).collect();
+        for op in Splay::diff(&tree_a, &tree_b) {
+            let ok = match op {
+                DiffOp::Added(&k, &v) => entries.insert(k, v).is_none(),
+                DiffOp::Removed(&k, &v) => entries.remove(&k) == Some(v),
+                DiffOp::Changed(&k, &old, &new) => {
+                    old != new && entries.insert(k, new) == Some(old)
+                }
+            };
+            if !ok {
+                return false;
+            }
+        }
+        entries.iter().eq(tree_b.iter())
+    }
+
+    #[quickcheck]
+    fn test_quickcheck_differences(a: Vec<u8>, b: Vec<u8>) -> bool {
+        let tree_a: Splay<u8, ()> = a.iter().map(|&k| (k, ())).collect();
+        let tree_b: Splay<u8, ()> = b.iter().map(|&k| (k, ())).collect();
+        let set_a: BTreeSet<u8> = a.into_iter().collect();
+        let set_b: BTreeSet<u8> = b.into_iter().collect();
+        tree_a
+            .difference(&tree_b)
+            .map(|e| e.0)
+            .eq(set_a.difference(&set_b))
+            && tree_a
+                .symmetric_difference(&tree_b)
+                .map(|e| e.0)
+                .eq(set_a.symmetric_difference(&set_b))
+    }
+}
@@ -0,0 +1,404 @@
+use core::marker::PhantomData;
+
+use rayon::iter::plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer};
+use rayon::prelude::*;
+
+use crate::compare::Natural;
+
+use super::compat::{IterMut, RangeMut};
+use super::{arena, link, Dir, Ends, Idx, Node, OptionIdx, Splay, SplayIter, IDX_NONE};
+
+/// The paths from `root` down to the entries ranked `lo` and `hi - 1`, so
+/// a walk from both ends covers the ranks in between, with `child` reading
+/// links.
+fn rank_ends(
+    root: OptionIdx,
+    ranks: &[usize],
+    (lo, hi): (usize, usize),
+    child: impl Fn(Idx, Dir) -> OptionIdx,
+) -> Ends {
+    let mut ends = Ends {
+        front: Vec::new(),
+        back: Vec::new(),
+    };
+    if lo == hi {
+        return ends;
+    }
+    let mut next = root.to_option();
+    while let Some(idx) = next {
+        let after_start = ranks[idx] >= lo;
+        if after_start {
+            ends.front.push(idx);
+        }
+        next = child(idx, if after_start { Dir::Left } else { Dir::Right }).to_option();
+    }
+    let mut next = root.to_option();
+    while let Some(idx) = next {
+        let before_end = ranks[idx] < hi;
+        if before_end {
+            ends.back.push(idx);
+        }
+        next = child(idx, if before_end { Dir::Right } else { Dir::Left }).to_option();
+    }
+    ends
+}
+
+/// Parallel iteration over a [`Splay`] in key order.
+pub struct ParIter<'a, K, V> {
+    tree: &'a Splay<K, V>,
+    // Work is split by rank, searching down from the root, so every thread
+    // gets as many entries whatever shape the splaying left the tree in.
+    ranks: Vec<usize>,
+}
+
+/// The entries of a [`ParIter`] ranked `lo..hi`.
+struct RankProducer<'a, 'r, K, V> {
+    tree: &'a Splay<K, V>,
+    ranks: &'r [usize],
+    lo: usize,
+    hi: usize,
+}
+
+impl<'a, K: Sync, V: Sync> Producer for RankProducer<'a, '_, K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = SplayIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let nodes = &self.tree.nodes;
+        let ends = rank_ends(
+            self.tree.root,
+            self.ranks,
+            (self.lo, self.hi),
+            |idx, dir| link(&nodes[idx], dir),
+        );
+        SplayIter {
+            tree: self.tree,
+            ends,
+            len: self.hi - self.lo,
+        }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let mid = self.lo + index;
+        (
+            RankProducer { hi: mid, ..self },
+            RankProducer { lo: mid, ..self },
+        )
+    }
+}
+
+impl<K, V> Clone for RankProducer<'_, '_, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V> Copy for RankProducer<'_, '_, K, V> {}
+
+impl<'a, K: Sync, V: Sync> ParallelIterator for ParIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.ranks.len())
+    }
+}
+
+impl<K: Sync, V: Sync> IndexedParallelIterator for ParIter<'_, K, V> {
+    fn len(&self) -> usize {
+        self.ranks.len()
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(RankProducer {
+            tree: self.tree,
+            ranks: &self.ranks,
+            lo: 0,
+            hi: self.ranks.len(),
+        })
+    }
+}
+
+/// Parallel mutable iteration over a [`Splay`] in key order.
+pub struct ParIterMut<'a, K, V> {
+    root: OptionIdx,
+    // The arena, borrowed mutably for `'a`, which every split hands a
+    // different rank range of.
+    nodes: *mut Node<K, V>,
+    ranks: Vec<usize>,
+    marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+// SAFETY: `ParIterMut` hands out `&K` and `&mut V` like a `&mut` to the
+// arena would, so it can be sent under the same bounds as one.
+unsafe impl<K: Sync, V: Send> Send for ParIterMut<'_, K, V> {}
+
+/// The entries of a [`ParIterMut`] ranked `lo..hi`.
+struct RankProducerMut<'a, 'r, K, V> {
+    root: OptionIdx,
+    nodes: *mut Node<K, V>,
+    ranks: &'r [usize],
+    lo: usize,
+    hi: usize,
+    marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+// SAFETY: producers split into disjoint rank ranges, so no two of them
+// hand out the same value, and each hands out `&K` and `&mut V` as a
+// `RangeMut` over its range would.
+unsafe impl<K: Sync, V: Send> Send for RankProducerMut<'_, '_, K, V> {}
+
+impl<'a, K: Sync + 'a, V: Send + 'a> Producer for RankProducerMut<'a, '_, K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let nodes = self.nodes;
+        // SAFETY: every index reached from the root is a slot of the arena,
+        // which is borrowed for `'a`. Only the links are read, which no
+        // other producer writes.
+        let ends = rank_ends(
+            self.root,
+            self.ranks,
+            (self.lo, self.hi),
+            |idx, dir| unsafe {
+                let node = nodes.add(idx);
+                match dir {
+                    Dir::Left => (*node).left,
+                    Dir::Right => (*node).right,
+                }
+            },
+        );
+        IterMut {
+            range: RangeMut {
+                ends,
+                nodes,
+                marker: PhantomData,
+            },
+            len: self.hi - self.lo,
+        }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let mid = self.lo + index;
+        let left = RankProducerMut {
+            root: self.root,
+            nodes: self.nodes,
+            ranks: self.ranks,
+            lo: self.lo,
+            hi: mid,
+            marker: PhantomData,
+        };
+        (left, RankProducerMut { lo: mid, ..self })
+    }
+}
+
+impl<'a, K: Sync + 'a, V: Send + 'a> ParallelIterator for ParIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.ranks.len())
+    }
+}
+
+impl<'a, K: Sync + 'a, V: Send + 'a> IndexedParallelIterator for ParIterMut<'a, K, V> {
+    fn len(&self) -> usize {
+        self.ranks.len()
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(RankProducerMut {
+            root: self.root,
+            nodes: self.nodes,
+            ranks: &self.ranks,
+            lo: 0,
+            hi: self.ranks.len(),
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<K: Ord + Sync, V: Sync> Splay<K, V> {
+    /// Parallel iteration in key order, `collect` keeps the order. Ranks
+    /// the entries up front, in O(n), then splits by rank.
+    pub fn par_iter(&self) -> ParIter<'_, K, V> {
+        ParIter {
+            tree: self,
+            ranks: self.ranks(),
+        }
+    }
+}
+
+impl<K: Ord + Sync, V: Send> Splay<K, V> {
+    /// Parallel mutable iteration in key order, split like `par_iter`.
+    pub fn par_iter_mut(&mut self) -> ParIterMut<'_, K, V> {
+        ParIterMut {
+            root: self.root,
+            ranks: self.ranks(),
+            nodes: self.nodes.as_mut_ptr(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Below this many nodes a subtree is linked on the current thread.
+const SEQUENTIAL_LINK: usize = 1 << 12;
+
+/// Links `nodes`, which are in key order and start at arena slot `offset`,
+/// into a balanced tree. The halves are disjoint slices, so they can be
+/// linked on different threads.
+fn link_balanced<K: Send, V: Send>(nodes: &mut [Node<K, V>], offset: Idx) -> OptionIdx {
+    if nodes.is_empty() {
+        return IDX_NONE;
+    }
+    let mid = nodes.len() / 2;
+    let (left, rest) = nodes.split_at_mut(mid);
+    let (node, right) = rest.split_first_mut().unwrap();
+    let right_offset = offset + mid + 1;
+    (node.left, node.right) = if mid < SEQUENTIAL_LINK {
+        (
+            link_balanced(left, offset),
+            link_balanced(right, right_offset),
+        )
+    } else {
+        rayon::join(
+            || link_balanced(left, offset),
+            || link_balanced(right, right_offset),
+        )
+    };
+    OptionIdx(offset + mid)
+}
+
+impl<K: Ord + Send, V: Send> Splay<K, V> {
+    /// Builds a balanced tree from `entries`, sorting them with a parallel
+    /// sort and linking subtrees on separate threads. Later duplicates win.
+    pub fn from_par_sorted(mut entries: Vec<(K, V)>) -> Self {
+        if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+            // Same trick as the sequential path, the last duplicate wins.
+            entries.reverse();
+            entries.par_sort_by(|a, b| a.0.cmp(&b.0));
+            entries.dedup_by(|a, b| a.0 == b.0);
+        }
+        let mut nodes: Vec<Node<K, V>> = entries
+            .into_par_iter()
+            .map(|(key, value)| Node {
+                key,
+                value,
+                left: IDX_NONE,
+                right: IDX_NONE,
+                #[cfg(feature = "stats")]
+                hits: 0,
+            })
+            .collect();
+        let root = link_balanced(&mut nodes, 0);
+        Splay {
+            root,
+            nodes: arena::from_vec(nodes),
+            cmp: Natural,
+            stats: Default::default(),
+        }
+    }
+}
+
+impl<K: Ord + Send, V: Send> FromParallelIterator<(K, V)> for Splay<K, V> {
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(iter: I) -> Self {
+        Splay::from_par_sorted(iter.into_par_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn par_iter_test() {
+        let mut tree: Splay<i32, i32> = Splay::new();
+        for i in (0..10000).rev() {
+            tree.set(i * 7 % 10000, i);
+        }
+        tree.get(&5000);
+        let keys: Vec<i32> = tree.par_iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, (0..10000).collect::<Vec<i32>>());
+        assert_eq!(tree.par_iter().count(), 10000);
+
+        tree.par_iter_mut().for_each(|(k, v)| *v = *k * 2);
+        assert!(tree.iter().all(|(k, v)| *v == *k * 2));
+        tree.par_iter_mut()
+            .enumerate()
+            .for_each(|(i, (_, v))| *v = i as i32);
+        assert!(tree.iter().all(|(k, v)| k == v));
+
+        // Ascending inserts leave a path, which still splits evenly.
+        let path: Splay<i32, i32> = (0..10000).fold(Splay::new(), |mut tree, i| {
+            tree.set(i, i);
+            tree
+        });
+        assert_eq!(path.depth(), 10000);
+        let halves = path
+            .par_iter()
+            .with_min_len(5000)
+            .with_max_len(5000)
+            .fold(|| 0, |n, _| n + 1);
+        assert_eq!(halves.collect::<Vec<usize>>(), [5000, 5000]);
+        let mut path = path;
+        let halves = path
+            .par_iter_mut()
+            .with_min_len(5000)
+            .with_max_len(5000)
+            .map(|(k, v)| {
+                *v = -*k;
+                *k
+            })
+            .fold(Vec::new, |mut keys, k| {
+                keys.push(k);
+                keys
+            });
+        let halves: Vec<Vec<i32>> = halves.collect();
+        assert_eq!(
+            halves,
+            [(0..5000).collect::<Vec<_>>(), (5000..10000).collect()]
+        );
+        assert!(path.iter().all(|(k, v)| *v == -*k));
+        let last: Vec<i32> = path.par_iter().rev().take(2).map(|(k, _)| *k).collect();
+        assert_eq!(last, [9999, 9998]);
+    }
+
+    #[test]
+    fn from_par_iter_test() {
+        let mut tree: Splay<i32, i32> = (0..10000).into_par_iter().map(|i| (i % 100, i)).collect();
+        assert_eq!(tree.len(), 100);
+        assert_eq!(tree.get(&42), Some(&9942));
+
+        let tree: Splay<i32, i32> = (0..10000).into_par_iter().map(|i| (i, i)).collect();
+        assert!(tree.depth() <= 14);
+    }
+
+    #[test]
+    fn from_par_sorted_test() {
+        let entries: Vec<(u32, u32)> = (0..100_000u32)
+            .map(|i| (i.wrapping_mul(7919) % 50_000, i))
+            .collect();
+        let mut expected = std::collections::BTreeMap::new();
+        expected.extend(entries.iter().copied());
+
+        let tree = Splay::from_par_sorted(entries);
+        assert!(tree.check_invariants().is_ok());
+        assert!(tree.depth() <= 16);
+        assert!(tree.iter().eq(expected.iter()));
+    }
+}
@@ -0,0 +1,169 @@
+use alloc::vec::Vec;
+use core::ops::Bound;
+
+use super::{DiffOp, Dir, OptionIdx, OrCreate, Splay, IDX_NONE};
+
+impl<K: Ord, V> Splay<K, V> {
+    /// Applies the changes of `patch`, in its order: `Added` and `Changed`
+    /// set the key's value and `Removed` removes the key. The old values
+    /// in them aren't checked.
+    ///
+    /// A patch in key order, as from [`Splay::diff`], goes faster where it
+    /// has runs of neighbouring changes. Keys added between the same two of
+    /// the tree's are linked in as one balanced subtree, and keys removed
+    /// one after another in the tree are cut out as one range, each run in
+    /// O(log n) plus its length.
+    pub fn apply_patch<I: IntoIterator<Item = DiffOp<K, V>>>(&mut self, patch: I) {
+        let mut ops = patch.into_iter().peekable();
+        while let Some(op) = ops.next() {
+            match op {
+                DiffOp::Changed(key, _, value) => self.set(key, value),
+                DiffOp::Added(key, value) => {
+                    let mut run = Vec::from([(key, value)]);
+                    let mut cursor = self.lower_bound(Bound::Included(&run[0].0));
+                    if cursor.peek_next().is_some_and(|(k, _)| *k == run[0].0) {
+                        let (key, value) = run.pop().unwrap();
+                        self.set(key, value);
+                        continue;
+                    }
+                    // The tree's next key above the first one added.
+                    let above = cursor.next().map(|(k, _)| k);
+                    while let Some(DiffOp::Added(key, _)) = ops.peek() {
+                        if *key <= run.last().unwrap().0 || above.is_some_and(|a| key >= a) {
+                            break;
+                        }
+                        let Some(DiffOp::Added(key, value)) = ops.next() else {
+                            unreachable!()
+                        };
+                        run.push((key, value));
+                    }
+                    self.insert_run(run);
+                }
+                DiffOp::Removed(key, _) => {
+                    let mut cursor = self.lower_bound(Bound::Included(&key));
+                    if cursor.next().is_none_or(|(k, _)| *k != key) {
+                        continue;
+                    }
+                    let mut last = None;
+                    while let Some(DiffOp::Removed(next, _)) = ops.peek() {
+                        if cursor.next().is_none_or(|(k, _)| k != next) {
+                            break;
+                        }
+                        let Some(DiffOp::Removed(next, _)) = ops.next() else {
+                            unreachable!()
+                        };
+                        last = Some(next);
+                    }
+                    match last {
+                        Some(last) => {
+                            self.remove_range((Bound::Included(&key), Bound::Included(&last)));
+                        }
+                        None => {
+                            self.remove(&key);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Links in `run`, sorted and all between the same two keys of the tree,
+    /// as a balanced subtree.
+    fn insert_run(&mut self, run: Vec<(K, V)>) {
+        if run.len() == 1 {
+            let (key, value) = run.into_iter().next().unwrap();
+            self.set(key, value);
+            return;
+        }
+        // Split the tree at the run, whose neighbour ends up at the root.
+        let (left, right) = match self.root.to_option() {
+            None => (IDX_NONE, IDX_NONE),
+            Some(_) => {
+                self.visit(OrCreate::Lookup(&run[0].0));
+                let root = self.root.to_option().unwrap();
+                if self.nodes[root].key < run[0].0 {
+                    let right = self.child(root, Dir::Right);
+                    self.set_child(root, Dir::Right, IDX_NONE);
+                    (OptionIdx(root), right)
+                } else {
+                    let left = self.child(root, Dir::Left);
+                    self.set_child(root, Dir::Left, IDX_NONE);
+                    (left, OptionIdx(root))
+                }
+            }
+        };
+        let start = self.nodes.len();
+        for (key, value) in run {
+            self.new_node(key, value);
+        }
+        let mid = self.link_balanced(start, self.nodes.len());
+        let right = self.join(mid, right);
+        self.root = self.join(left, right);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let a: Splay<u32, u32> = (0..100).map(|i| (i * 2, i)).collect();
+        let mut b = a.clone();
+        b.remove_range(10..50);
+        for i in 0..20 {
+            b.set(2 * i + 101, 0);
+        }
+        b.set(60, 7);
+        b.set(300, 1);
+        let patch: Vec<_> = Splay::diff(&a, &b).map(DiffOp::cloned).collect();
+        assert_eq!(patch.len(), 20 + 20 + 1 + 1);
+
+        let mut patched = a.clone();
+        patched.apply_patch(patch);
+        assert_eq!(patched.check_invariants(), Ok(()));
+        assert!(patched.iter().eq(b.iter()));
+
+        // Out of order, and against a tree it wasn't made for.
+        let mut tree: Splay<u32, u32> = [(1, 1), (5, 5)].into();
+        tree.apply_patch([
+            DiffOp::Removed(5, 0),
+            DiffOp::Added(3, 3),
+            DiffOp::Added(2, 2),
+            DiffOp::Removed(9, 9),
+            DiffOp::Added(1, 10),
+        ]);
+        assert_eq!(Vec::from(tree), [(1, 10), (2, 2), (3, 3)]);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(a: Vec<(u8, u8)>, b: Vec<(u8, u8)>, ops: Vec<(u8, u8, u8)>) -> bool {
+        let tree_a: Splay<u8, u8> = a.into_iter().collect();
+        let tree_b: Splay<u8, u8> = b.into_iter().collect();
+        let mut patched = tree_a.clone();
+        patched.apply_patch(Splay::diff(&tree_a, &tree_b).map(DiffOp::cloned));
+        if patched.check_invariants().is_err() || !patched.iter().eq(tree_b.iter()) {
+            return false;
+        }
+
+        let mut model: BTreeMap<u8, u8> = patched.iter().map(|(&k, &v)| (k, v)).collect();
+        let patch: Vec<_> = ops
+            .into_iter()
+            .map(|(op, key, value)| match op % 3 {
+                0 => DiffOp::Added(key % 64, value),
+                1 => DiffOp::Changed(key % 64, 0, value),
+                _ => DiffOp::Removed(key % 64, 0),
+            })
+            .collect();
+        for op in &patch {
+            match *op {
+                DiffOp::Added(k, v) | DiffOp::Changed(k, _, v) => model.insert(k, v),
+                DiffOp::Removed(k, _) => model.remove(&k),
+            };
+        }
+        patched.apply_patch(patch);
+        patched.check_invariants().is_ok() && patched.iter().eq(model.iter())
+    }
+}
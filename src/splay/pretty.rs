@@ -0,0 +1,87 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Display};
+
+use super::{Allocator, Splay};
+
+/// Renders the shape of a tree as indented ASCII art, see
+/// [`Splay::display_tree`].
+pub struct TreeDisplay<'a, K, V, C, A: Allocator> {
+    tree: &'a Splay<K, V, C, A>,
+}
+
+impl<K: Debug, V, C, A: Allocator> Splay<K, V, C, A> {
+    /// Shows the tree shape, one node per line with its depth:
+    ///
+    /// ```text
+    /// 2 (0)
+    /// ├─L 1 (1)
+    /// └─R 3 (1)
+    /// ```
+    pub fn display_tree(&self) -> TreeDisplay<'_, K, V, C, A> {
+        TreeDisplay { tree: self }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn print_tree(&self) {
+        std::print!("{}", self.display_tree());
+    }
+}
+
+impl<K: Debug, V, C, A: Allocator> Display for TreeDisplay<'_, K, V, C, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nodes = &self.tree.nodes;
+        let Some(root) = self.tree.root.to_option() else {
+            return writeln!(f, "(empty)");
+        };
+        writeln!(f, "{:?} (0)", nodes[root].key)?;
+
+        // Explicit stack so degenerate trees can't overflow the call stack.
+        let mut stack = Vec::new();
+        let push = |stack: &mut Vec<_>, idx: usize, prefix: String, depth: usize| {
+            let node = &nodes[idx];
+            let children = [(node.left, 'L'), (node.right, 'R')];
+            let children: Vec<_> = children
+                .into_iter()
+                .filter_map(|(c, side)| c.to_option().map(|c| (c, side)))
+                .collect();
+            let count = children.len();
+            // Reversed so the left child is printed first.
+            for (i, (child, side)) in children.into_iter().enumerate().rev() {
+                stack.push((child, side, prefix.clone(), i + 1 == count, depth));
+            }
+        };
+        push(&mut stack, root, String::new(), 1);
+        while let Some((idx, side, prefix, last, depth)) = stack.pop() {
+            let branch = if last { "└─" } else { "├─" };
+            writeln!(f, "{prefix}{branch}{side} {:?} ({depth})", nodes[idx].key)?;
+            let prefix = prefix + if last { "  " } else { "│ " };
+            push(&mut stack, idx, prefix, depth + 1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_test() {
+        let mut tree: Splay<u32, ()> = (1..=5).map(|k| (k, ())).collect();
+        tree.set(6, ());
+        let expected = "\
+6 (0)
+└─L 3 (1)
+  ├─L 2 (2)
+  │ └─L 1 (3)
+  └─R 5 (2)
+    └─L 4 (3)
+";
+        assert_eq!(tree.display_tree().to_string(), expected);
+        assert_eq!(
+            Splay::<u8, ()>::new().display_tree().to_string(),
+            "(empty)\n"
+        );
+    }
+}
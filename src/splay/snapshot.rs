@@ -0,0 +1,135 @@
+use std::io::{self, Read, Write};
+
+use super::{arena, Idx, Node, OptionIdx, Splay, IDX_NONE};
+use crate::codec::{invalid_data, Checksummed, Codec};
+use crate::compare::Natural;
+
+const MAGIC: &[u8; 4] = b"CBSP";
+const VERSION: u32 = 1;
+
+impl Codec for OptionIdx {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        match self.to_option() {
+            None => u64::MAX.encode(w),
+            Some(idx) => (idx as u64).encode(w),
+        }
+    }
+
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        match u64::decode(r)? {
+            u64::MAX => Ok(IDX_NONE),
+            idx => Idx::try_from(idx)
+                .map(OptionIdx)
+                .map_err(|_| invalid_data("index overflow")),
+        }
+    }
+}
+
+/// Snapshot layout: magic, version, node count, root, then every node of the
+/// arena as `left, right, key, value`, followed by an FNV-1a checksum of all
+/// the preceding bytes.
+impl<K: Ord + Codec, V: Codec> Splay<K, V> {
+    pub fn write_to<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut w = Checksummed::new(w);
+        w.write_all(MAGIC)?;
+        VERSION.encode(&mut w)?;
+        self.nodes.len().encode(&mut w)?;
+        self.root.encode(&mut w)?;
+        for node in self.nodes.iter() {
+            node.left.encode(&mut w)?;
+            node.right.encode(&mut w)?;
+            node.key.encode(&mut w)?;
+            node.value.encode(&mut w)?;
+        }
+        let checksum = w.checksum();
+        checksum.encode(&mut w.inner)?;
+        w.flush()
+    }
+
+    pub fn read_from<R: Read>(r: R) -> io::Result<Self> {
+        let mut r = Checksummed::new(r);
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a splay snapshot"));
+        }
+        if u32::decode(&mut r)? != VERSION {
+            return Err(invalid_data("unsupported snapshot version"));
+        }
+
+        let len = usize::decode(&mut r)?;
+        let root = OptionIdx::decode(&mut r)?;
+        let mut nodes = Vec::with_capacity(std::cmp::min(len, 1024));
+        for _ in 0..len {
+            let left = OptionIdx::decode(&mut r)?;
+            let right = OptionIdx::decode(&mut r)?;
+            let key = K::decode(&mut r)?;
+            let value = V::decode(&mut r)?;
+            nodes.push(Node {
+                key,
+                value,
+                left,
+                right,
+                #[cfg(feature = "stats")]
+                hits: 0,
+            });
+        }
+
+        let checksum = r.checksum();
+        if u64::decode(&mut r.inner)? != checksum {
+            return Err(invalid_data("snapshot checksum mismatch"));
+        }
+
+        let tree = Splay {
+            root,
+            nodes: arena::from_vec(nodes),
+            cmp: Natural,
+            stats: Default::default(),
+        };
+        if let Err(err) = tree.check_invariants() {
+            return Err(invalid_data(&format!("snapshot isn't a valid tree: {err}")));
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_test() {
+        let mut tree: Splay<u32, String> = Splay::new();
+        for i in [5, 1, 9, 3, 7] {
+            tree.set(i, i.to_string());
+        }
+        tree.get(&3);
+
+        let mut buf = Vec::new();
+        tree.write_to(&mut buf).unwrap();
+        let mut loaded = Splay::<u32, String>::read_from(&buf[..]).unwrap();
+        assert_eq!(loaded.depth(), tree.depth());
+        assert!(loaded.iter().eq(tree.iter()));
+        assert_eq!(loaded.get(&9).map(|s| s.as_str()), Some("9"));
+
+        let empty: Splay<u32, String> = Splay::new();
+        let mut buf = Vec::new();
+        empty.write_to(&mut buf).unwrap();
+        assert!(Splay::<u32, String>::read_from(&buf[..])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn corruption_test() {
+        let tree: Splay<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let mut buf = Vec::new();
+        tree.write_to(&mut buf).unwrap();
+
+        let mut corrupted = buf.clone();
+        corrupted[40] ^= 1;
+        assert!(Splay::<u32, u32>::read_from(&corrupted[..]).is_err());
+        assert!(Splay::<u32, u32>::read_from(&buf[..buf.len() - 1]).is_err());
+        assert!(Splay::<u32, u32>::read_from(&buf[..]).is_ok());
+    }
+}
@@ -0,0 +1,175 @@
+//! Operation counters, kept per tree with the `stats` feature and compiled
+//! away without it, along with how often each key was accessed.
+
+#[cfg(feature = "stats")]
+use alloc::vec::Vec;
+#[cfg(feature = "stats")]
+use core::borrow::Borrow;
+
+#[cfg(feature = "stats")]
+use crate::compare::Compare;
+
+/// What a tree has done since it was created or its stats were last reset.
+/// Only operations through `&mut self` are counted, so `peek` and iteration
+/// don't show up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Key comparisons made while searching and relinking.
+    pub comparisons: u64,
+    /// Single rotations; a zig-zig or zig-zag step does two.
+    pub rotations: u64,
+    /// Splay steps: zig-zig, zig-zag and the final zig.
+    pub splay_steps: u64,
+    /// Times the node arena had to grow.
+    pub allocations: u64,
+}
+
+#[derive(Clone, Default)]
+pub(super) struct Recorder {
+    #[cfg(feature = "stats")]
+    pub(super) stats: Stats,
+}
+
+impl Recorder {
+    #[inline(always)]
+    pub(super) fn comparison(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.comparisons += 1;
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn rotation(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.rotations += 1;
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn splay_step(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.splay_steps += 1;
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn allocation(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.stats.allocations += 1;
+        }
+    }
+}
+
+#[cfg(feature = "stats")]
+impl<K, V, C: Compare<K>, A: super::Allocator> super::Splay<K, V, C, A> {
+    pub fn stats(&self) -> Stats {
+        self.stats.stats
+    }
+
+    /// Also zeroes every key's access count.
+    pub fn reset_stats(&mut self) {
+        self.stats.stats = Stats::default();
+        for node in self.nodes.iter_mut() {
+            node.hits = 0;
+        }
+    }
+
+    /// How often `key` was found by an operation through `&mut self`,
+    /// since it was inserted or the stats were reset. Inserting a new key
+    /// doesn't count.
+    pub fn access_count<Q>(&self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Compare<Q>,
+    {
+        self.find(key).map(|idx| self.nodes[idx].hits)
+    }
+
+    /// The `k` most accessed keys with their counts, most first, ties in
+    /// key order.
+    pub fn hottest(&self, k: usize) -> Vec<(&K, u64)> {
+        let mut keys: Vec<(&K, u64)> = self
+            .nodes
+            .iter()
+            .map(|node| (&node.key, node.hits))
+            .collect();
+        let hotter =
+            |a: &(&K, u64), b: &(&K, u64)| b.1.cmp(&a.1).then_with(|| self.cmp.compare(a.0, b.0));
+        if k == 0 {
+            return Vec::new();
+        }
+        if k < keys.len() {
+            keys.select_nth_unstable_by(k - 1, hotter);
+            keys.truncate(k);
+        }
+        keys.sort_unstable_by(hotter);
+        keys
+    }
+
+    /// How many keys were accessed how often: the first entry counts those
+    /// never accessed, entry `i` after it those accessed `2^(i - 1)` up to
+    /// `2^i - 1` times. A skewed workload, which suits a splay tree, shows
+    /// as a long tail; a flat one as a single spike.
+    pub fn access_histogram(&self) -> Vec<usize> {
+        let mut histogram = Vec::new();
+        for node in self.nodes.iter() {
+            let bucket = (u64::BITS - node.hits.leading_zeros()) as usize;
+            if histogram.len() <= bucket {
+                histogram.resize(bucket + 1, 0);
+            }
+            histogram[bucket] += 1;
+        }
+        histogram
+    }
+}
+
+#[cfg(all(test, feature = "stats"))]
+mod tests {
+    use crate::splay::Splay;
+
+    #[test]
+    fn basic_test() {
+        let mut tree = Splay::new();
+        for i in 0..100 {
+            tree.set(i, i);
+        }
+        let stats = tree.stats();
+        assert!(stats.allocations > 0 && stats.allocations < 100);
+        // Ascending inserts always land right of the root: one zig each.
+        assert_eq!(stats.rotations, 99);
+        assert_eq!(stats.splay_steps, 99);
+        assert_eq!(stats.comparisons, 99);
+
+        tree.reset_stats();
+        tree.get(&0);
+        let stats = tree.stats();
+        assert_eq!(stats.comparisons, 100);
+        assert_eq!(stats.rotations, 99);
+        assert_eq!(stats.splay_steps, 50);
+        assert_eq!(stats.allocations, 0);
+    }
+
+    #[test]
+    fn access_test() {
+        let mut tree: Splay<u32, ()> = (0..10).map(|i| (i, ())).collect();
+        for i in 0..10 {
+            for _ in 0..i {
+                tree.get(&i);
+            }
+        }
+        tree.set(9, ());
+        assert_eq!(tree.access_count(&9), Some(10));
+        assert_eq!(tree.access_count(&10), None);
+        assert_eq!(tree.hottest(2), [(&9, 10), (&8, 8)]);
+        // 0; 1; 2, 3; 4 to 7; then 8 and 9 at 8 or more.
+        assert_eq!(tree.access_histogram(), [1, 1, 2, 4, 2]);
+
+        tree.reset_stats();
+        assert_eq!(tree.access_histogram(), [10]);
+    }
+}
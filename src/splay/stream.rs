@@ -0,0 +1,194 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Bound;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use futures_core::stream::{FusedStream, Stream};
+
+use super::{FrozenSplay, SplaySnapshot};
+
+enum Source<K, V> {
+    Frozen(Arc<FrozenSplay<K, V>>),
+    Snapshot(SplaySnapshot<K, V>),
+}
+
+/// A [`Stream`] of a snapshot's entries in key order, cloned out in chunks.
+///
+/// Nothing is read until the stream is polled, and then only the next
+/// chunk, so a consumer which stops pulling (a response held up by flow
+/// control, say) leaves the stream where it was without buffering ahead.
+/// Chunks are always ready. The stream keeps its snapshot alive, so the
+/// tree it came from can go on being written meanwhile.
+pub struct SnapshotStream<K, V> {
+    source: Source<K, V>,
+    chunk: usize,
+    // The last key sent, which the next chunk starts after.
+    last: Option<K>,
+    remaining: usize,
+}
+
+impl<K: Ord, V> FrozenSplay<K, V> {
+    /// Streams the entries, `chunk` at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk` is zero.
+    pub fn into_stream(self: Arc<Self>, chunk: usize) -> SnapshotStream<K, V> {
+        SnapshotStream::new(self.len(), Source::Frozen(self), chunk)
+    }
+}
+
+impl<K: Ord, V> SplaySnapshot<K, V> {
+    /// Streams the entries, `chunk` at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk` is zero.
+    pub fn into_stream(self, chunk: usize) -> SnapshotStream<K, V> {
+        SnapshotStream::new(self.len(), Source::Snapshot(self), chunk)
+    }
+}
+
+impl<K, V> SnapshotStream<K, V> {
+    fn new(len: usize, source: Source<K, V>, chunk: usize) -> Self {
+        assert!(chunk > 0, "chunks must hold an entry");
+        SnapshotStream {
+            source,
+            chunk,
+            last: None,
+            remaining: len,
+        }
+    }
+
+    /// The number of entries not streamed yet.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> SnapshotStream<K, V> {
+    fn next_chunk(&mut self) -> Vec<(K, V)> {
+        let start = self.last.as_ref().map_or(Bound::Unbounded, Bound::Excluded);
+        let range = (start, Bound::Unbounded);
+        let take = self.chunk.min(self.remaining);
+        let clone = |(k, v): (&K, &V)| (k.clone(), v.clone());
+        let chunk: Vec<_> = match &self.source {
+            Source::Frozen(tree) => tree.range(range).take(take).map(clone).collect(),
+            Source::Snapshot(tree) => tree.range(range).take(take).map(clone).collect(),
+        };
+        self.remaining -= chunk.len();
+        self.last = chunk.last().map(|(k, _)| k.clone());
+        chunk
+    }
+}
+
+// Nothing is pinned in place.
+impl<K, V> Unpin for SnapshotStream<K, V> {}
+
+impl<K: Ord + Clone, V: Clone> Stream for SnapshotStream<K, V> {
+    type Item = Vec<(K, V)>;
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let stream = self.get_mut();
+        if stream.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        Poll::Ready(Some(stream.next_chunk()))
+    }
+
+    /// The exact number of chunks left.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let chunks = self.remaining.div_ceil(self.chunk);
+        (chunks, Some(chunks))
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> FusedStream for SnapshotStream<K, V> {
+    fn is_terminated(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+impl<K, V> fmt::Debug for SnapshotStream<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotStream")
+            .field("chunk", &self.chunk)
+            .field("remaining", &self.remaining)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::splay::{CowSplay, Splay};
+    use alloc::vec;
+    use core::task::Waker;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    fn poll<K: Ord + Clone, V: Clone>(stream: &mut SnapshotStream<K, V>) -> Option<Vec<(K, V)>> {
+        let mut cx = Context::from_waker(Waker::noop());
+        match Pin::new(stream).poll_next(&mut cx) {
+            Poll::Ready(chunk) => chunk,
+            Poll::Pending => unreachable!("snapshot streams are always ready"),
+        }
+    }
+
+    #[test]
+    fn basic_test() {
+        let frozen = Arc::new(
+            (0..5)
+                .map(|k| (k, k * 10))
+                .collect::<Splay<_, _>>()
+                .freeze(),
+        );
+        let mut stream = frozen.into_stream(2);
+        assert_eq!(stream.size_hint(), (3, Some(3)));
+        assert_eq!(poll(&mut stream), Some(vec![(0, 0), (1, 10)]));
+        assert_eq!(poll(&mut stream), Some(vec![(2, 20), (3, 30)]));
+        assert_eq!(poll(&mut stream), Some(vec![(4, 40)]));
+        assert!(stream.is_terminated());
+        assert_eq!(poll(&mut stream), None);
+
+        // Writes to the tree after the snapshot don't reach the stream.
+        let mut tree = CowSplay::new();
+        for k in 0..6 {
+            tree.set(k, "old");
+        }
+        let mut stream = tree.snapshot().into_stream(4);
+        assert_eq!(poll(&mut stream).unwrap().len(), 4);
+        tree.remove(&4);
+        tree.set(5, "new");
+        tree.set(6, "new");
+        assert_eq!(poll(&mut stream), Some(vec![(4, "old"), (5, "old")]));
+        assert_eq!((poll(&mut stream), stream.remaining()), (None, 0));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(entries: Vec<(u8, u8)>, chunk: u8) -> bool {
+        let chunk = chunk as usize % 8 + 1;
+        let model: BTreeMap<_, _> = entries.iter().copied().collect();
+        let tree: Splay<_, _> = entries.into_iter().collect();
+        let cow = CowSplay::from(tree.clone());
+        [
+            Arc::new(tree.freeze()).into_stream(chunk),
+            cow.snapshot().into_stream(chunk),
+        ]
+        .into_iter()
+        .all(|mut stream| {
+            let mut chunks = vec![];
+            while let Some(next) = poll(&mut stream) {
+                if stream.size_hint().0 != stream.remaining().div_ceil(chunk) {
+                    return false;
+                }
+                chunks.push(next);
+            }
+            chunks.len() == model.len().div_ceil(chunk)
+                && chunks.iter().rev().skip(1).all(|c| c.len() == chunk)
+                && chunks.concat().into_iter().eq(model.clone())
+        })
+    }
+}
@@ -0,0 +1,308 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{Splay, SplayIter};
+
+/// The entries a change overwrote, as `(key, old value)` in the order
+/// changed, `None` for keys which weren't there.
+type Undo<K, V> = Vec<(K, Option<V>)>;
+
+/// A [`Splay`] whose changes form transactions which can be rolled back
+/// while open and undone and redone once committed.
+///
+/// Changes between `begin` and `commit` are one transaction; any made
+/// outside one are each a transaction of their own. Every change records
+/// the entry it overwrote, which costs a clone of the key and old value,
+/// and undoing a transaction puts those back in reverse order. At most
+/// `history` committed transactions can be undone, the oldest being
+/// forgotten first, and a new one forgets whatever could be redone.
+pub struct UndoSplay<K, V> {
+    tree: Splay<K, V>,
+    open: Option<Undo<K, V>>,
+    undo: VecDeque<Undo<K, V>>,
+    redo: Vec<Undo<K, V>>,
+    history: usize,
+}
+
+impl<K: Ord + Clone, V: Clone> UndoSplay<K, V> {
+    /// A map which can undo up to `history` transactions.
+    pub fn new(history: usize) -> Self {
+        UndoSplay {
+            tree: Splay::new(),
+            open: None,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            history,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Puts `key` back to `old`, returning what it was.
+    fn restore(&mut self, key: K, old: Option<V>) -> Option<V> {
+        match old {
+            Some(value) => self.tree.insert(key, value),
+            None => self.tree.remove(&key),
+        }
+    }
+
+    fn record(&mut self, key: K, old: Option<V>) {
+        match &mut self.open {
+            Some(open) => open.push((key, old)),
+            None => self.push_undo(Vec::from([(key, old)])),
+        }
+    }
+
+    fn push_undo(&mut self, undo: Undo<K, V>) {
+        self.redo.clear();
+        if self.history == 0 {
+            return;
+        }
+        if self.undo.len() == self.history {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(undo);
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.tree.insert(key.clone(), value);
+        self.record(key, old.clone());
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let old = self.tree.remove(key)?;
+        self.record(key.clone(), Some(old.clone()));
+        Some(old)
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.tree.get(key)
+    }
+
+    /// Looks `key` up without splaying.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.tree.peek(key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.tree.contains_key(key)
+    }
+
+    pub fn iter(&self) -> SplayIter<'_, K, V> {
+        self.tree.iter()
+    }
+
+    /// The map itself, to read, not change.
+    pub fn as_splay(&self) -> &Splay<K, V> {
+        &self.tree
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.open.is_some()
+    }
+
+    /// Starts a transaction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one is open already.
+    pub fn begin(&mut self) {
+        assert!(self.open.is_none(), "transaction already open");
+        self.open = Some(Vec::new());
+    }
+
+    /// Ends the open transaction, keeping its changes as one to undo. One
+    /// which changed nothing isn't kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if none is open.
+    pub fn commit(&mut self) {
+        let undo = self.open.take().expect("no transaction open");
+        if !undo.is_empty() {
+            self.push_undo(undo);
+        }
+    }
+
+    /// Ends the open transaction, undoing its changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if none is open.
+    pub fn rollback(&mut self) {
+        let undo = self.open.take().expect("no transaction open");
+        self.replay(undo);
+    }
+
+    /// Puts back the entries of `undo` in reverse, returning the entries
+    /// that overwrote, which redo it.
+    fn replay(&mut self, undo: Undo<K, V>) -> Undo<K, V> {
+        let mut redo = Vec::with_capacity(undo.len());
+        for (key, old) in undo.into_iter().rev() {
+            let now = self.restore(key.clone(), old);
+            redo.push((key, now));
+        }
+        redo
+    }
+
+    /// Undoes the last transaction committed, returning whether there was
+    /// one to undo.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a transaction is open.
+    pub fn undo(&mut self) -> bool {
+        assert!(self.open.is_none(), "transaction open");
+        let Some(undo) = self.undo.pop_back() else {
+            return false;
+        };
+        let redo = self.replay(undo);
+        self.redo.push(redo);
+        true
+    }
+
+    /// Redoes the last transaction undone, returning whether there was one
+    /// to redo.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a transaction is open.
+    pub fn redo(&mut self) -> bool {
+        assert!(self.open.is_none(), "transaction open");
+        let Some(redo) = self.redo.pop() else {
+            return false;
+        };
+        let undo = self.replay(redo);
+        self.undo.push_back(undo);
+        true
+    }
+
+    /// How many transactions `undo` could undo, and `redo` redo.
+    pub fn depth(&self) -> (usize, usize) {
+        (self.undo.len(), self.redo.len())
+    }
+
+    /// Forgets every transaction to undo or redo, keeping the entries.
+    pub fn forget(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn into_inner(self) -> Splay<K, V> {
+        self.tree
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for UndoSplay<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.tree.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut map = UndoSplay::new(2);
+        map.insert(1, "a");
+        map.begin();
+        map.insert(2, "b");
+        assert_eq!(map.insert(1, "A"), Some("a"));
+        map.remove(&2);
+        map.commit();
+        assert_eq!(map.depth(), (2, 0));
+
+        map.begin();
+        map.insert(3, "c");
+        map.rollback();
+        assert!(!map.contains_key(&3));
+
+        assert!(map.undo());
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &"a")]);
+        assert!(map.redo() && !map.redo());
+        assert_eq!(map.peek(&1), Some(&"A"));
+
+        // The oldest goes once the history is full.
+        map.insert(4, "d");
+        map.insert(5, "e");
+        assert!(map.undo() && map.undo() && !map.undo());
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &"A")]);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>) -> bool {
+        let mut map = UndoSplay::new(4);
+        // The state before each transaction which can be undone and after
+        // the last, oldest first, and the states redo would go to.
+        let mut states = vec![Vec::new()];
+        let mut redo = Vec::new();
+        let snapshot = |map: &UndoSplay<u8, usize>| -> Vec<(u8, usize)> {
+            map.iter().map(|(&k, &v)| (k, v)).collect()
+        };
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 8;
+            match op % 6 {
+                0 => {
+                    map.insert(key, i);
+                }
+                1 => {
+                    if map.remove(&key).is_none() {
+                        continue;
+                    }
+                }
+                2 => {
+                    // A transaction of a few changes, kept or rolled back.
+                    let before = snapshot(&map);
+                    map.begin();
+                    map.insert(key, i);
+                    map.remove(&(key + 1));
+                    map.insert(key + 2, i);
+                    if i % 2 == 0 {
+                        map.rollback();
+                        if snapshot(&map) != before {
+                            return false;
+                        }
+                        continue;
+                    }
+                    map.commit();
+                }
+                3 | 4 => {
+                    if map.undo() {
+                        redo.push(states.pop().unwrap());
+                    }
+                    if snapshot(&map) != *states.last().unwrap() {
+                        return false;
+                    }
+                    continue;
+                }
+                _ => {
+                    if map.redo() {
+                        states.push(redo.pop().unwrap());
+                    }
+                    if snapshot(&map) != *states.last().unwrap() {
+                        return false;
+                    }
+                    continue;
+                }
+            }
+            states.push(snapshot(&map));
+            if states.len() > 5 {
+                states.remove(0);
+            }
+            redo.clear();
+        }
+        map.depth() == (states.len() - 1, redo.len())
+    }
+}
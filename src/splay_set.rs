@@ -0,0 +1,144 @@
+use core::borrow::Borrow;
+
+use crate::splay::{JoinMode, Keys, Splay};
+
+/// An ordered set, a [`Splay`] with `()` values. Like the map, `contains`
+/// splays the element it finds while `peek` leaves the tree alone.
+pub struct SplaySet<T> {
+    tree: Splay<T, ()>,
+}
+
+impl<T: Ord> SplaySet<T> {
+    pub fn new() -> Self {
+        SplaySet { tree: Splay::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Returns whether `value` was newly added.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.tree.insert(value, ()).is_none()
+    }
+
+    pub fn contains<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.get(value).is_some()
+    }
+
+    pub fn peek<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.contains_key(value)
+    }
+
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.remove(value).is_some()
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.tree.first_key_value().map(|(k, _)| k)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.tree.last_key_value().map(|(k, _)| k)
+    }
+
+    pub fn iter(&self) -> Keys<'_, T, ()> {
+        self.tree.keys()
+    }
+
+    /// Whether every element of `self` is in `other`. Walks both sets in
+    /// order and stops at the first element `other` lacks.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        if self.len() > other.len() {
+            return false;
+        }
+        Splay::join_iter(&self.tree, &other.tree, JoinMode::Left).all(|(_, _, b)| b.is_some())
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Whether the sets share no element, stopping at the first shared one.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        if self.is_empty() || other.is_empty() {
+            return true;
+        }
+        Splay::join_iter(&self.tree, &other.tree, JoinMode::Inner)
+            .next()
+            .is_none()
+    }
+}
+
+impl<T: Ord> Default for SplaySet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for SplaySet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        SplaySet {
+            tree: iter.into_iter().map(|value| (value, ())).collect(),
+        }
+    }
+}
+
+impl<T: Ord> Extend<T> for SplaySet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn basic_test() {
+        let mut set: SplaySet<u32> = [3, 1, 2].into_iter().collect();
+        assert!(!set.insert(2));
+        assert!(set.contains(&1));
+        assert!(set.remove(&3));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [&1, &2]);
+
+        let bigger: SplaySet<u32> = (0..5).collect();
+        assert!(set.is_subset(&bigger));
+        assert!(bigger.is_superset(&set));
+        assert!(!bigger.is_subset(&set));
+        let other: SplaySet<u32> = [5, 6].into_iter().collect();
+        assert!(other.is_disjoint(&bigger));
+        assert!(!set.is_disjoint(&bigger));
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(a: Vec<u8>, b: Vec<u8>) -> bool {
+        let set_a: SplaySet<u8> = a.iter().copied().collect();
+        let set_b: SplaySet<u8> = b.iter().copied().collect();
+        let model_a: BTreeSet<u8> = a.into_iter().collect();
+        let model_b: BTreeSet<u8> = b.into_iter().collect();
+        set_a.is_subset(&set_b) == model_a.is_subset(&model_b)
+            && set_a.is_superset(&set_b) == model_a.is_superset(&model_b)
+            && set_a.is_disjoint(&set_b) == model_a.is_disjoint(&model_b)
+    }
+}
@@ -0,0 +1,374 @@
+//! A splay tree which lives entirely inline, for targets without a heap.
+//!
+//! `Splay` keeps its nodes in a growable arena and splays bottom-up through
+//! recursion. Here the arena is an array of `N` slots and splaying is
+//! top-down, so neither the heap nor the call stack grows with the tree.
+
+use core::borrow::Borrow;
+use core::cmp::Ordering::{Equal, Greater, Less};
+use core::iter::FusedIterator;
+use core::mem;
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+/// An ordered map holding at most `N` entries, without allocating.
+pub struct StaticSplay<K, V, const N: usize> {
+    root: Option<usize>,
+    len: usize,
+    // `nodes[..len]` are occupied.
+    nodes: [Option<Node<K, V>>; N],
+}
+
+impl<K: Ord, V, const N: usize> StaticSplay<K, V, N> {
+    pub fn new() -> Self {
+        StaticSplay {
+            root: None,
+            len: 0,
+            nodes: core::array::from_fn(|_| None),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    fn node(&self, idx: usize) -> &Node<K, V> {
+        self.nodes[idx].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, idx: usize) -> &mut Node<K, V> {
+        self.nodes[idx].as_mut().unwrap()
+    }
+
+    /// Top-down splay: nodes passed on the way to `key` are hung off two
+    /// side trees, which are reassembled under the closest node at the end.
+    fn splay<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let Some(mut t) = self.root else {
+            return;
+        };
+        // Roots and innermost nodes of the trees left and right of `t`.
+        let (mut left, mut left_max) = (None, None);
+        let (mut right, mut right_min) = (None, None);
+        loop {
+            match key.cmp(self.node(t).key.borrow()) {
+                Equal => break,
+                Less => {
+                    let Some(mut next) = self.node(t).left else {
+                        break;
+                    };
+                    if key < self.node(next).key.borrow() {
+                        self.node_mut(t).left = self.node(next).right;
+                        self.node_mut(next).right = Some(t);
+                        t = next;
+                        match self.node(t).left {
+                            Some(l) => next = l,
+                            None => break,
+                        }
+                    }
+                    match right_min {
+                        Some(r) => self.node_mut(r).left = Some(t),
+                        None => right = Some(t),
+                    }
+                    right_min = Some(t);
+                    t = next;
+                }
+                Greater => {
+                    let Some(mut next) = self.node(t).right else {
+                        break;
+                    };
+                    if key > self.node(next).key.borrow() {
+                        self.node_mut(t).right = self.node(next).left;
+                        self.node_mut(next).left = Some(t);
+                        t = next;
+                        match self.node(t).right {
+                            Some(r) => next = r,
+                            None => break,
+                        }
+                    }
+                    match left_max {
+                        Some(l) => self.node_mut(l).right = Some(t),
+                        None => left = Some(t),
+                    }
+                    left_max = Some(t);
+                    t = next;
+                }
+            }
+        }
+        let (inner_left, inner_right) = (self.node(t).left, self.node(t).right);
+        match left_max {
+            Some(l) => self.node_mut(l).right = inner_left,
+            None => left = inner_left,
+        }
+        match right_min {
+            Some(r) => self.node_mut(r).left = inner_right,
+            None => right = inner_right,
+        }
+        let node = self.node_mut(t);
+        node.left = left;
+        node.right = right;
+        self.root = Some(t);
+    }
+
+    /// Inserts an entry, returning the value it replaced, or hands the entry
+    /// back if the key is new and the map is full.
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        self.splay(&key);
+        if let Some(root) = self.root {
+            if key == self.node(root).key {
+                return Ok(Some(mem::replace(&mut self.node_mut(root).value, value)));
+            }
+        }
+        if self.is_full() {
+            return Err((key, value));
+        }
+        // The root is now a neighbour of `key`, so the new node goes on top
+        // with the root on one side.
+        let (left, right) = match self.root {
+            None => (None, None),
+            Some(root) if key < self.node(root).key => {
+                (self.node_mut(root).left.take(), Some(root))
+            }
+            Some(root) => (Some(root), self.node_mut(root).right.take()),
+        };
+        self.nodes[self.len] = Some(Node {
+            key,
+            value,
+            left,
+            right,
+        });
+        self.root = Some(self.len);
+        self.len += 1;
+        Ok(None)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.splay(key);
+        let root = self.root?;
+        let node = self.node_mut(root);
+        (node.key.borrow() == key).then_some(&mut node.value)
+    }
+
+    /// Lookup which leaves the tree shape alone.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut idx = self.root;
+        while let Some(i) = idx {
+            let node = self.node(i);
+            idx = match key.cmp(node.key.borrow()) {
+                Equal => return Some(&node.value),
+                Less => node.left,
+                Greater => node.right,
+            };
+        }
+        None
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.peek(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.splay(key);
+        let root = self.root?;
+        if self.node(root).key.borrow() != key {
+            return None;
+        }
+        let right = self.node(root).right;
+        self.root = self.node(root).left;
+        if self.root.is_some() {
+            // Everything left is smaller, so its maximum comes up with no
+            // right child.
+            self.splay(key);
+            let top = self.root.unwrap();
+            self.node_mut(top).right = right;
+        } else {
+            self.root = right;
+        }
+
+        // Keep `nodes[..len]` dense: the last node takes over the slot.
+        self.len -= 1;
+        let last = self.len;
+        let node = self.nodes[last].take().unwrap();
+        let node = if root == last {
+            node
+        } else {
+            let removed = self.nodes[root].replace(node).unwrap();
+            self.relink(last, root);
+            removed
+        };
+        Some(node.value)
+    }
+
+    /// Points the link to `from` at `to`, which now holds its node.
+    fn relink(&mut self, from: usize, to: usize) {
+        if self.root == Some(from) {
+            self.root = Some(to);
+            return;
+        }
+        let mut idx = self.root.unwrap();
+        loop {
+            let go_left = self.node(to).key < self.node(idx).key;
+            let node = self.node_mut(idx);
+            let link = if go_left {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+            if *link == Some(from) {
+                *link = Some(to);
+                return;
+            }
+            idx = link.unwrap();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &mut self.nodes[..self.len] {
+            *slot = None;
+        }
+        self.root = None;
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V, N> {
+        let mut iter = Iter {
+            map: self,
+            stack: [0; N],
+            depth: 0,
+        };
+        iter.push_left(self.root);
+        iter
+    }
+}
+
+impl<K: Ord, V, const N: usize> Default for StaticSplay<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entries of a [`StaticSplay`] in key order. The path is kept inline too,
+/// no deeper than the map is long.
+pub struct Iter<'a, K, V, const N: usize> {
+    map: &'a StaticSplay<K, V, N>,
+    stack: [usize; N],
+    depth: usize,
+}
+
+impl<K: Ord, V, const N: usize> Iter<'_, K, V, N> {
+    fn push_left(&mut self, mut idx: Option<usize>) {
+        while let Some(i) = idx {
+            self.stack[self.depth] = i;
+            self.depth += 1;
+            idx = self.map.node(i).left;
+        }
+    }
+}
+
+impl<'a, K: Ord, V, const N: usize> Iterator for Iter<'a, K, V, N> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.depth = self.depth.checked_sub(1)?;
+        let node = self.map.node(self.stack[self.depth]);
+        self.push_left(node.right);
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<K: Ord, V, const N: usize> FusedIterator for Iter<'_, K, V, N> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn basic_test() {
+        let mut map: StaticSplay<u32, &str, 3> = StaticSplay::new();
+        assert_eq!(map.insert(2, "b"), Ok(None));
+        assert_eq!(map.insert(1, "a"), Ok(None));
+        assert_eq!(map.insert(3, "c"), Ok(None));
+        assert_eq!(map.insert(4, "d"), Err((4, "d")));
+        assert_eq!(map.insert(1, "A"), Ok(Some("a")));
+        assert!(map.is_full());
+        assert_eq!(map.get(&2), Some(&"b"));
+        assert!(map.iter().map(|(k, _)| *k).eq(1..=3));
+
+        assert_eq!(map.remove(&2), Some("b"));
+        assert_eq!(map.insert(4, "d"), Ok(None));
+        assert_eq!(map.peek(&4), Some(&"d"));
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(bool, u8)>) -> bool {
+        let mut map: StaticSplay<u8, usize, 8> = StaticSplay::new();
+        let mut model = BTreeMap::new();
+        for (i, (insert, key)) in ops.into_iter().enumerate() {
+            let key = key % 16;
+            if insert {
+                let expected = if model.len() == 8 && !model.contains_key(&key) {
+                    Err((key, i))
+                } else {
+                    Ok(model.insert(key, i))
+                };
+                if map.insert(key, i) != expected {
+                    return false;
+                }
+            } else if map.remove(&key) != model.remove(&key) {
+                return false;
+            }
+            if map.len() != model.len() || !map.iter().eq(model.iter()) {
+                return false;
+            }
+        }
+        true
+    }
+}
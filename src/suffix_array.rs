@@ -0,0 +1,273 @@
+//! Suffix arrays, for substring search over a fixed text.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+const NONE: usize = usize::MAX;
+
+/// The suffixes of a text in sorted order, with the longest common prefix
+/// of each neighbouring pair.
+///
+/// Built in O(n) by SA-IS, the LCPs in O(n) by Kasai's algorithm. Every
+/// occurrence of a pattern starts a suffix in one run of the order, found
+/// by binary search in O(m log n).
+#[derive(Clone, Debug)]
+pub struct SuffixArray {
+    text: Box<[u8]>,
+    suffixes: Vec<usize>,
+    lcp: Vec<usize>,
+}
+
+impl SuffixArray {
+    pub fn new(text: impl Into<Box<[u8]>>) -> Self {
+        let text = text.into();
+        let symbols: Vec<usize> = text.iter().map(|&b| b as usize).collect();
+        let suffixes = sa_is(&symbols, u8::MAX as usize);
+        let lcp = kasai(&text, &suffixes);
+        SuffixArray {
+            text,
+            suffixes,
+            lcp,
+        }
+    }
+
+    pub fn text(&self) -> &[u8] {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// The start of every suffix, in sorted order of the suffixes.
+    pub fn suffixes(&self) -> &[usize] {
+        &self.suffixes
+    }
+
+    /// At `i`, the length of the longest common prefix of the `i`th and
+    /// `i + 1`th suffixes in sorted order. One shorter than the text.
+    pub fn lcp(&self) -> &[usize] {
+        &self.lcp
+    }
+
+    /// The positions where `pattern` occurs, in no particular order.
+    pub fn find(&self, pattern: &[u8]) -> &[usize] {
+        let prefix = |&start: &usize| {
+            let suffix = &self.text[start..];
+            &suffix[..suffix.len().min(pattern.len())]
+        };
+        let lo = self.suffixes.partition_point(|s| prefix(s) < pattern);
+        let hi = lo + self.suffixes[lo..].partition_point(|s| prefix(s) == pattern);
+        &self.suffixes[lo..hi]
+    }
+
+    pub fn contains(&self, pattern: &[u8]) -> bool {
+        !self.find(pattern).is_empty()
+    }
+
+    pub fn count(&self, pattern: &[u8]) -> usize {
+        self.find(pattern).len()
+    }
+
+    /// A longest substring which occurs at least twice, `None` if no byte
+    /// repeats.
+    pub fn longest_repeat(&self) -> Option<&[u8]> {
+        let (i, &len) = self.lcp.iter().enumerate().max_by_key(|&(_, &len)| len)?;
+        let start = self.suffixes[i];
+        (len > 0).then(|| &self.text[start..start + len])
+    }
+}
+
+/// Sorts the suffixes of `s`, whose symbols are at most `upper`, by
+/// induced sorting (Nong, Zhang and Chan): sort the LMS substrings, name
+/// them, recurse on the names if any two are equal, and induce the order
+/// of every other suffix from the sorted LMS suffixes.
+fn sa_is(s: &[usize], upper: usize) -> Vec<usize> {
+    let n = s.len();
+    match n {
+        0 => return Vec::new(),
+        1 => return alloc::vec![0],
+        2 => {
+            return if s[0] < s[1] {
+                alloc::vec![0, 1]
+            } else {
+                alloc::vec![1, 0]
+            }
+        }
+        _ => {}
+    }
+    // Whether each suffix is S-type, smaller than the one after it.
+    let mut small = alloc::vec![false; n];
+    for i in (0..n - 1).rev() {
+        small[i] = if s[i] == s[i + 1] {
+            small[i + 1]
+        } else {
+            s[i] < s[i + 1]
+        };
+    }
+    // Where each symbol's L-type and S-type suffixes start in the order.
+    let mut start_l = alloc::vec![0; upper + 1];
+    let mut start_s = alloc::vec![0; upper + 1];
+    for i in 0..n {
+        if small[i] {
+            if s[i] < upper {
+                start_l[s[i] + 1] += 1;
+            }
+        } else {
+            start_s[s[i]] += 1;
+        }
+    }
+    for c in 0..=upper {
+        start_s[c] += start_l[c];
+        if c < upper {
+            start_l[c + 1] += start_s[c];
+        }
+    }
+
+    let mut sa = alloc::vec![NONE; n];
+    let induce = |sa: &mut Vec<usize>, lms: &[usize]| {
+        sa.fill(NONE);
+        let mut next = start_s.clone();
+        for &d in lms {
+            sa[next[s[d]]] = d;
+            next[s[d]] += 1;
+        }
+        let mut next = start_l.clone();
+        sa[next[s[n - 1]]] = n - 1;
+        next[s[n - 1]] += 1;
+        for i in 0..n {
+            let v = sa[i];
+            if v != NONE && v >= 1 && !small[v - 1] {
+                sa[next[s[v - 1]]] = v - 1;
+                next[s[v - 1]] += 1;
+            }
+        }
+        let mut next = start_l.clone();
+        for i in (0..n).rev() {
+            let v = sa[i];
+            if v != NONE && v >= 1 && small[v - 1] {
+                next[s[v - 1] + 1] -= 1;
+                sa[next[s[v - 1] + 1]] = v - 1;
+            }
+        }
+    };
+
+    // Leftmost S-type positions, the starts of the LMS substrings.
+    let mut lms_index = alloc::vec![NONE; n + 1];
+    let lms: Vec<usize> = (1..n).filter(|&i| !small[i - 1] && small[i]).collect();
+    for (k, &i) in lms.iter().enumerate() {
+        lms_index[i] = k;
+    }
+    let m = lms.len();
+    induce(&mut sa, &lms);
+    if m == 0 {
+        return sa;
+    }
+
+    let mut sorted_lms: Vec<usize> = sa
+        .iter()
+        .copied()
+        .filter(|&v| lms_index[v] != NONE)
+        .collect();
+    let mut names = alloc::vec![0; m];
+    let mut name = 0;
+    for i in 1..m {
+        let (mut l, mut r) = (sorted_lms[i - 1], sorted_lms[i]);
+        let end_l = lms.get(lms_index[l] + 1).copied().unwrap_or(n);
+        let end_r = lms.get(lms_index[r] + 1).copied().unwrap_or(n);
+        let mut same = end_l - l == end_r - r;
+        if same {
+            while l < end_l && s[l] == s[r] {
+                l += 1;
+                r += 1;
+            }
+            same = l < n && r < n && s[l] == s[r];
+        }
+        if !same {
+            name += 1;
+        }
+        names[lms_index[sorted_lms[i]]] = name;
+    }
+    let order = sa_is(&names, name);
+    for (sorted, &k) in sorted_lms.iter_mut().zip(&order) {
+        *sorted = lms[k];
+    }
+    induce(&mut sa, &sorted_lms);
+    sa
+}
+
+/// The longest common prefixes of neighbouring suffixes, Kasai et al.
+fn kasai(text: &[u8], suffixes: &[usize]) -> Vec<usize> {
+    let n = text.len();
+    let mut rank = alloc::vec![0; n];
+    for (i, &s) in suffixes.iter().enumerate() {
+        rank[s] = i;
+    }
+    let mut lcp = alloc::vec![0; n.saturating_sub(1)];
+    // The LCP drops by at most one from each suffix to the next in text
+    // order, so `h` only ever goes back by one.
+    let mut h: usize = 0;
+    for i in 0..n {
+        h = h.saturating_sub(1);
+        if rank[i] == 0 {
+            continue;
+        }
+        let j = suffixes[rank[i] - 1];
+        while i + h < n && j + h < n && text[i + h] == text[j + h] {
+            h += 1;
+        }
+        lcp[rank[i] - 1] = h;
+    }
+    lcp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let sa = SuffixArray::new(&b"banana"[..]);
+        assert_eq!(sa.suffixes(), [5, 3, 1, 0, 4, 2]);
+        assert_eq!(sa.lcp(), [1, 3, 0, 0, 2]);
+        let mut found = sa.find(b"an").to_vec();
+        found.sort_unstable();
+        assert_eq!(found, [1, 3]);
+        assert_eq!(sa.count(b"nab"), 0);
+        assert_eq!(sa.count(b""), 6);
+        assert_eq!(sa.longest_repeat(), Some(&b"ana"[..]));
+        assert_eq!(SuffixArray::new(Vec::new()).longest_repeat(), None);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(text: Vec<u8>, pattern: Vec<u8>) -> bool {
+        // A small alphabet, for plenty of repeats.
+        let text: Vec<u8> = text.into_iter().map(|b| b % 3).collect();
+        let pattern: Vec<u8> = pattern.into_iter().take(3).map(|b| b % 3).collect();
+        let sa = SuffixArray::new(text.clone());
+
+        let mut expected: Vec<usize> = (0..text.len()).collect();
+        expected.sort_by_key(|&i| &text[i..]);
+        let lcp: Vec<usize> = expected
+            .windows(2)
+            .map(|w| {
+                text[w[0]..]
+                    .iter()
+                    .zip(&text[w[1]..])
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            })
+            .collect();
+        let mut found = sa.find(&pattern).to_vec();
+        found.sort_unstable();
+        let occurrences: Vec<usize> = (0..text.len())
+            .filter(|&i| text[i..].starts_with(&pattern))
+            .collect();
+        sa.suffixes() == expected && sa.lcp() == lcp && found == occurrences
+    }
+}
@@ -0,0 +1,476 @@
+//! A map whose every committed version stays readable, for reads which
+//! must see one consistent state while writes go on.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Bound, RangeBounds};
+
+use crate::rng::split_mix64;
+use crate::splay::{check_range, Splay};
+
+type Link<K, V> = Option<Arc<Node<K, V>>>;
+
+// The entries below a key, the entry for it, and the ones above.
+type Split<K, V> = (Link<K, V>, Option<Node<K, V>>, Link<K, V>);
+
+/// A treap node, shared between all the versions it's in.
+#[derive(Clone)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+    priority: u64,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+/// Splits off the entry for `key`, if any, and the entries either side of
+/// it, copying the nodes on the way down which other versions share.
+fn split<K: Ord + Clone, V: Clone>(link: Link<K, V>, key: &K) -> Split<K, V> {
+    let Some(mut arc) = link else {
+        return (None, None, None);
+    };
+    let node = Arc::make_mut(&mut arc);
+    match key.cmp(&node.key) {
+        Ordering::Less => {
+            let (left, mid, right) = split(node.left.take(), key);
+            node.left = right;
+            (left, mid, Some(arc))
+        }
+        Ordering::Greater => {
+            let (left, mid, right) = split(node.right.take(), key);
+            node.right = left;
+            (Some(arc), mid, right)
+        }
+        Ordering::Equal => {
+            let (left, right) = (node.left.take(), node.right.take());
+            (left, Some(Arc::unwrap_or_clone(arc)), right)
+        }
+    }
+}
+
+/// Joins two treaps, every key of `a` below every key of `b`.
+fn join<K: Clone, V: Clone>(a: Link<K, V>, b: Link<K, V>) -> Link<K, V> {
+    match (a, b) {
+        (None, b) => b,
+        (a, None) => a,
+        (Some(mut a), Some(mut b)) => {
+            if a.priority > b.priority {
+                let node = Arc::make_mut(&mut a);
+                node.right = join(node.right.take(), Some(b));
+                Some(a)
+            } else {
+                let node = Arc::make_mut(&mut b);
+                node.left = join(Some(a), node.left.take());
+                Some(b)
+            }
+        }
+    }
+}
+
+fn find<'a, K: Ord, V>(mut link: &'a Link<K, V>, key: &K) -> Option<&'a V> {
+    while let Some(node) = link {
+        link = match key.cmp(&node.key) {
+            Ordering::Less => &node.left,
+            Ordering::Greater => &node.right,
+            Ordering::Equal => return Some(&node.value),
+        };
+    }
+    None
+}
+
+/// Names a committed version of a [`VersionedMap`]. Later commits have
+/// greater ids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version(u64);
+
+impl Version {
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+/// A map whose changes are committed in versions, each of which can still
+/// be read after later ones.
+///
+/// The entries are a treap of shared nodes. A change copies the O(log n)
+/// nodes on its path which a committed version also holds and shares the
+/// rest, so `commit` is O(1) and reading any version costs the same as
+/// reading the latest. Changes since the last commit show through `get`
+/// and `iter`, not in any version. Nodes are freed as soon as no version
+/// kept holds them, so releasing old versions with `release` or
+/// `release_before` frees whatever only they still used. A [`View`] keeps
+/// its version readable even after that, and can go to another thread.
+pub struct VersionedMap<K, V> {
+    head: Link<K, V>,
+    len: usize,
+    versions: Splay<u64, View<K, V>>,
+    next: u64,
+    // Splitmix state for the priorities.
+    rng: u64,
+}
+
+impl<K: Ord + Clone, V: Clone> VersionedMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// A map whose treap's shape only depends on `seed` and the changes
+    /// made to it. `new` always uses the same seed too, so neither needs
+    /// any entropy from the system.
+    pub fn with_seed(seed: u64) -> Self {
+        VersionedMap {
+            head: None,
+            len: 0,
+            versions: Splay::new(),
+            next: 0,
+            rng: seed,
+        }
+    }
+
+    /// The number of entries, counting the changes since the last commit.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let (left, old, right) = split(self.head.take(), &key);
+        let node = Node {
+            key,
+            value,
+            priority: split_mix64(&mut self.rng),
+            left: None,
+            right: None,
+        };
+        self.head = join(join(left, Some(Arc::new(node))), right);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old.map(|node| node.value)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (left, old, right) = split(self.head.take(), key);
+        self.head = join(left, right);
+        let old = old?;
+        self.len -= 1;
+        Some(old.value)
+    }
+
+    /// Looks `key` up, changes since the last commit included.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        find(&self.head, key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// The entries in key order, changes since the last commit included.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter::new(&self.head)
+    }
+
+    /// The entries within `range` in key order, changes since the last
+    /// commit included.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same malformed ranges as `BTreeMap::range`.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V> {
+        check_range(&range);
+        Range::new(&self.head, range)
+    }
+
+    /// Commits the changes made since the last commit as a new version.
+    pub fn commit(&mut self) -> Version {
+        let version = Version(self.next);
+        self.next += 1;
+        let view = View {
+            root: self.head.clone(),
+            len: self.len,
+        };
+        self.versions.set(version.0, view);
+        version
+    }
+
+    /// Undoes the changes since the last commit kept, or all of them if
+    /// none are kept.
+    pub fn discard(&mut self) {
+        let last = self.versions.last_key_value().map(|(_, view)| view.clone());
+        let view = last.unwrap_or_default();
+        self.head = view.root;
+        self.len = view.len;
+    }
+
+    /// The latest version kept.
+    pub fn latest(&self) -> Option<Version> {
+        self.versions.last_key_value().map(|(&id, _)| Version(id))
+    }
+
+    /// The versions kept, oldest first.
+    pub fn versions(&self) -> impl Iterator<Item = Version> + '_ {
+        self.versions.iter().map(|(&id, _)| Version(id))
+    }
+
+    /// `version` to read, unless it was released.
+    pub fn view(&self, version: Version) -> Option<View<K, V>> {
+        self.versions.peek(&version.0).cloned()
+    }
+
+    /// Looks `key` up as of `version`, or `None` if that was released.
+    pub fn get_at(&self, key: &K, version: Version) -> Option<&V> {
+        find(&self.versions.peek(&version.0)?.root, key)
+    }
+
+    /// The entries of `version` in key order, unless it was released.
+    pub fn iter_at(&self, version: Version) -> Option<Iter<'_, K, V>> {
+        Some(self.versions.peek(&version.0)?.iter())
+    }
+
+    /// Stops keeping `version`, returning whether it was kept.
+    pub fn release(&mut self, version: Version) -> bool {
+        self.versions.remove(&version.0).is_some()
+    }
+
+    /// Stops keeping every version before `version`, returning how many.
+    pub fn release_before(&mut self, version: Version) -> usize {
+        self.versions.remove_range(..version.0)
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Default for VersionedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone + fmt::Debug, V: Clone + fmt::Debug> fmt::Debug for VersionedMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// One version of a [`VersionedMap`], which stays readable while this is
+/// alive.
+pub struct View<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+impl<K: Ord, V> View<K, V> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        find(&self.root, key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter::new(&self.root)
+    }
+}
+
+impl<K, V> Clone for View<K, V> {
+    fn clone(&self) -> Self {
+        View {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<K, V> Default for View<K, V> {
+    fn default() -> Self {
+        View { root: None, len: 0 }
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for View<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// The entries of one version, or the latest changes, in key order.
+pub struct Iter<'a, K, V> {
+    // The nodes whose left subtree is done, nearest last.
+    stack: Vec<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn new(root: &'a Link<K, V>) -> Self {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.descend(root);
+        iter
+    }
+
+    fn descend(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.descend(&node.right);
+        Some((&node.key, &node.value))
+    }
+}
+
+/// The entries within a range, in key order.
+pub struct Range<'a, K, V> {
+    iter: Iter<'a, K, V>,
+    end: Bound<K>,
+}
+
+impl<'a, K: Ord + Clone, V> Range<'a, K, V> {
+    fn new<R: RangeBounds<K>>(mut link: &'a Link<K, V>, range: R) -> Self {
+        // Keep the path to the first key from the start on, like `Iter`
+        // would have after yielding everything before it.
+        let mut iter = Iter { stack: Vec::new() };
+        while let Some(node) = link {
+            let started = match range.start_bound() {
+                Bound::Included(start) => node.key >= *start,
+                Bound::Excluded(start) => node.key > *start,
+                Bound::Unbounded => true,
+            };
+            if started {
+                iter.stack.push(node);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+        Range {
+            iter,
+            end: range.end_bound().cloned(),
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.iter.next()?;
+        let within = match &self.end {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        if !within {
+            self.iter.stack.clear();
+            return None;
+        }
+        Some((key, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut map = VersionedMap::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+        let v0 = map.commit();
+        assert_eq!(map.insert("a", 10), Some(1));
+        assert_eq!(map.remove(&"b"), Some(2));
+        map.insert("c", 3);
+        assert_eq!(map.get(&"a"), Some(&10));
+        assert_eq!(map.get_at(&"a", v0), Some(&1));
+        let v1 = map.commit();
+        assert!(v0 < v1);
+
+        let view = map.view(v0).unwrap();
+        assert_eq!(map.release_before(v1), 1);
+        assert_eq!(map.get_at(&"a", v0), None);
+        assert_eq!(map.versions().collect::<Vec<_>>(), [v1]);
+        // A view reads its version after it's released.
+        assert_eq!(view.iter().collect::<Vec<_>>(), [(&"a", &1), (&"b", &2)]);
+
+        let pairs: Vec<_> = map.iter_at(v1).unwrap().collect();
+        assert_eq!(pairs, [(&"a", &10), (&"c", &3)]);
+        assert_eq!(map.range("b"..).collect::<Vec<_>>(), [(&"c", &3)]);
+        map.insert("d", 4);
+        map.discard();
+        assert!(!map.contains_key(&"d"));
+        assert_eq!(map.len(), 2);
+        assert!(map.release(v1));
+        map.discard();
+        assert!(map.is_empty() && map.latest().is_none());
+
+        // The same seed and changes build the same treap.
+        let [left, right] = [(); 2].map(|_| {
+            let mut map = VersionedMap::with_seed(7);
+            (0..100).for_each(|k| _ = map.insert(k, ()));
+            map
+        });
+        assert_eq!(
+            left.head.map(|root| root.key),
+            right.head.map(|root| root.key)
+        );
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8)>) -> bool {
+        let mut map = VersionedMap::new();
+        let mut model = BTreeMap::new();
+        let mut committed: Vec<(Version, BTreeMap<u8, usize>)> = Vec::new();
+        for (i, (op, key)) in ops.into_iter().enumerate() {
+            let key = key % 16;
+            let ok = match op % 6 {
+                0 | 1 => map.insert(key, i) == model.insert(key, i),
+                2 => map.remove(&key) == model.remove(&key),
+                3 => {
+                    committed.push((map.commit(), model.clone()));
+                    true
+                }
+                4 => {
+                    // Release a version, the oldest or one from the middle.
+                    if !committed.is_empty() {
+                        let j = key as usize % committed.len();
+                        let (version, _) = committed.remove(j);
+                        assert!(map.release(version));
+                    }
+                    true
+                }
+                _ => committed.iter().all(|(version, state)| {
+                    map.get_at(&key, *version) == state.get(&key)
+                        && map.view(*version).unwrap().len() == state.len()
+                }),
+            };
+            if !ok || map.len() != model.len() {
+                return false;
+            }
+        }
+        committed
+            .iter()
+            .all(|(version, state)| map.iter_at(*version).unwrap().eq(state.iter()))
+            && map.iter().eq(model.iter())
+    }
+}
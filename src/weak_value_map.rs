@@ -0,0 +1,200 @@
+//! A map which doesn't keep its values alive, for registries of objects
+//! owned elsewhere.
+
+use alloc::sync::{Arc, Weak};
+use core::borrow::Borrow;
+use core::fmt;
+
+use crate::splay::Splay;
+
+const MIN_THRESHOLD: usize = 16;
+
+/// A map holding [`Weak`] references to its values, so an entry dies along
+/// with the last [`Arc`] to its value elsewhere.
+///
+/// Dead entries read as missing. `get` drops one when it finds it, and
+/// `purge` drops them all. Inserts also purge once there have been more of
+/// them than entries left by the last purge, so dead entries never pile up
+/// past about the live ones, at O(1) amortized cost per insert.
+pub struct WeakValueMap<K, V> {
+    tree: Splay<K, Weak<V>>,
+    // Inserts since the last purge, and how many make the next one.
+    inserts: usize,
+    threshold: usize,
+}
+
+impl<K: Ord, V> WeakValueMap<K, V> {
+    pub fn new() -> Self {
+        WeakValueMap {
+            tree: Splay::new(),
+            inserts: 0,
+            threshold: MIN_THRESHOLD,
+        }
+    }
+
+    /// The number of entries, counting dead ones not dropped yet.
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Points `key` at `value`, returning the value it pointed at before if
+    /// that's still alive.
+    pub fn insert(&mut self, key: K, value: &Arc<V>) -> Option<Arc<V>> {
+        self.inserts += 1;
+        if self.inserts > self.threshold {
+            self.purge();
+        }
+        self.tree.insert(key, Arc::downgrade(value))?.upgrade()
+    }
+
+    /// The value for `key`, created by `f` and inserted unless it's alive.
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, f: F) -> Arc<V> {
+        if let Some(value) = self.get(&key) {
+            return value;
+        }
+        let value = Arc::new(f());
+        self.insert(key, &value);
+        value
+    }
+
+    /// The value for `key` if it's alive. A dead entry found is dropped.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let value = self.tree.get(key)?.upgrade();
+        if value.is_none() {
+            self.tree.remove(key);
+        }
+        value
+    }
+
+    /// The value for `key` if it's alive, without splaying or dropping.
+    pub fn peek<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.peek(key)?.upgrade()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree
+            .peek(key)
+            .is_some_and(|value| value.strong_count() > 0)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.remove(key)?.upgrade()
+    }
+
+    /// Drops every dead entry, returning how many there were.
+    pub fn purge(&mut self) -> usize {
+        let before = self.tree.len();
+        self.tree.retain(|_, value| value.strong_count() > 0);
+        self.inserts = 0;
+        self.threshold = self.tree.len().max(MIN_THRESHOLD);
+        before - self.tree.len()
+    }
+
+    /// The live entries, in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, Arc<V>)> {
+        self.tree
+            .iter()
+            .filter_map(|(key, value)| Some((key, value.upgrade()?)))
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.inserts = 0;
+        self.threshold = MIN_THRESHOLD;
+    }
+}
+
+impl<K: Ord, V> Default for WeakValueMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for WeakValueMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+    use alloc::vec::Vec;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        let mut registry = WeakValueMap::new();
+        let a = Arc::new("a");
+        let b = Arc::new("b");
+        assert_eq!(registry.insert(1, &a), None);
+        registry.insert(2, &b);
+        assert_eq!(registry.get(&1), Some(a.clone()));
+
+        drop(b);
+        assert!(!registry.contains_key(&2));
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.iter().count(), 1);
+        assert_eq!(registry.get(&2), None);
+        assert_eq!(registry.len(), 1);
+
+        let c = registry.get_or_insert_with(3, || "c");
+        assert!(Arc::ptr_eq(&registry.get_or_insert_with(3, || "other"), &c));
+        drop((a, c));
+        assert_eq!(registry.purge(), 2);
+        assert!(registry.is_empty());
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(ops: Vec<(u8, u8, bool)>) -> bool {
+        let mut map = WeakValueMap::new();
+        // The values kept alive; the rest were dropped as soon as inserted.
+        let mut model = BTreeMap::new();
+        for (i, (op, key, keep)) in ops.into_iter().enumerate() {
+            let key = key % 32;
+            let ok = match op % 4 {
+                0 | 1 => {
+                    let value = Arc::new(i);
+                    let old = map.insert(key, &value).map(|v| *v);
+                    let expected = match keep {
+                        true => model.insert(key, value),
+                        false => model.remove(&key),
+                    };
+                    old == expected.map(|v| *v)
+                }
+                2 => map.get(&key).map(|v| *v) == model.get(&key).map(|v| **v),
+                _ => map.remove(&key).map(|v| *v) == model.remove(&key).map(|v| *v),
+            };
+            if !ok || map.len() < model.len() {
+                return false;
+            }
+        }
+        let same = map
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq(model.iter().map(|(k, v)| (*k, **v)));
+        map.purge();
+        same && map.len() == model.len()
+    }
+}
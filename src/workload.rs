@@ -0,0 +1,294 @@
+//! Streams of keys to benchmark maps with, following the access patterns
+//! the crate's own benchmarks compare structures on.
+//!
+//! Each generator is an endless iterator of keys in `0..n`, and yields the
+//! same keys for the same seed, so a comparison can be rerun against your
+//! own structures:
+//!
+//! ```
+//! use crab_bucket::splay::Splay;
+//! use crab_bucket::workload::Zipf;
+//!
+//! let mut tree = Splay::new();
+//! for key in Zipf::with_seed(1000, 1.1, 7).take(10_000) {
+//!     *tree.entry(key).or_insert(0) += 1;
+//! }
+//! // Rank 0 is the most popular key.
+//! assert_eq!(tree.iter().max_by_key(|&(_, n)| n).map(|(&k, _)| k), Some(0));
+//! ```
+
+use crate::rng::split_mix64;
+
+/// A uniform key in `0..n`, by multiplying out of 64 random bits.
+fn below(state: &mut u64, n: u64) -> u64 {
+    ((split_mix64(state) as u128 * n as u128) >> 64) as u64
+}
+
+/// A uniform float in `[0, 1)`.
+fn unit(state: &mut u64) -> f64 {
+    (split_mix64(state) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Every key in `0..n` equally likely.
+#[derive(Clone, Debug)]
+pub struct Uniform {
+    n: u64,
+    rng: u64,
+}
+
+impl Uniform {
+    pub fn new(n: u64) -> Self {
+        Self::with_seed(n, 0)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn with_seed(n: u64, seed: u64) -> Self {
+        assert!(n > 0, "a workload needs a key");
+        Uniform { n, rng: seed }
+    }
+}
+
+impl Iterator for Uniform {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        Some(below(&mut self.rng, self.n))
+    }
+}
+
+/// The keys `0..n` in order, over and over.
+#[derive(Clone, Debug)]
+pub struct Sequential {
+    n: u64,
+    next: u64,
+}
+
+impl Sequential {
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn new(n: u64) -> Self {
+        assert!(n > 0, "a workload needs a key");
+        Sequential { n, next: 0 }
+    }
+}
+
+impl Iterator for Sequential {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let key = self.next;
+        self.next = (key + 1) % self.n;
+        Some(key)
+    }
+}
+
+/// Keys by popularity rank, rank `k` drawn in proportion to
+/// `1 / (k + 1)^exponent`, so a few keys take most of the accesses.
+///
+/// Drawn by Hörmann and Derflinger's rejection-inversion, which takes O(1)
+/// time and space whatever `n` is. An exponent around 1 is typical of
+/// caches and web traffic; YCSB's default is 0.99.
+#[derive(Clone, Debug)]
+pub struct Zipf {
+    n: u64,
+    exponent: f64,
+    // The integral of the hat function at 1.5 less 1, and at n + 0.5.
+    low: f64,
+    high: f64,
+    // Draws within this of the nearest key are accepted outright.
+    squeeze: f64,
+    rng: u64,
+}
+
+impl Zipf {
+    pub fn new(n: u64, exponent: f64) -> Self {
+        Self::with_seed(n, exponent, 0)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `n` is zero or `exponent` isn't positive and finite.
+    pub fn with_seed(n: u64, exponent: f64, seed: u64) -> Self {
+        assert!(n > 0, "a workload needs a key");
+        assert!(
+            exponent > 0.0 && exponent.is_finite(),
+            "the exponent must be positive"
+        );
+        let mut zipf = Zipf {
+            n,
+            exponent,
+            low: 0.0,
+            high: 0.0,
+            squeeze: 0.0,
+            rng: seed,
+        };
+        zipf.low = zipf.h_integral(1.5) - 1.0;
+        zipf.high = zipf.h_integral(n as f64 + 0.5);
+        zipf.squeeze = 2.0 - zipf.h_integral_inverse(zipf.h_integral(2.5) - zipf.h(2.0));
+        zipf
+    }
+
+    fn h(&self, x: f64) -> f64 {
+        (-self.exponent * x.ln()).exp()
+    }
+
+    fn h_integral(&self, x: f64) -> f64 {
+        let ln = x.ln();
+        expm1_over((1.0 - self.exponent) * ln) * ln
+    }
+
+    fn h_integral_inverse(&self, x: f64) -> f64 {
+        let t = (x * (1.0 - self.exponent)).max(-1.0);
+        (ln1p_over(t) * x).exp()
+    }
+}
+
+/// `(e^x - 1) / x`, accurate down to zero.
+fn expm1_over(x: f64) -> f64 {
+    if x.abs() > 1e-8 {
+        x.exp_m1() / x
+    } else {
+        1.0 + x * 0.5 * (1.0 + x / 3.0 * (1.0 + 0.25 * x))
+    }
+}
+
+/// `ln(1 + x) / x`, accurate down to zero.
+fn ln1p_over(x: f64) -> f64 {
+    if x.abs() > 1e-8 {
+        x.ln_1p() / x
+    } else {
+        1.0 - x * (0.5 - x * (1.0 / 3.0 - 0.25 * x))
+    }
+}
+
+impl Iterator for Zipf {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            let u = self.high + unit(&mut self.rng) * (self.low - self.high);
+            let x = self.h_integral_inverse(u);
+            let k = (x + 0.5).clamp(1.0, self.n as f64) as u64;
+            let kf = k as f64;
+            if kf - x <= self.squeeze || u >= self.h_integral(kf + 0.5) - self.h(kf) {
+                return Some(k - 1);
+            }
+        }
+    }
+}
+
+/// A hot set that moves: keys uniform within a window of `working_set`
+/// keys, which jumps to a random new place in `0..n` every `phase` keys.
+///
+/// This is the workload which separates self-adjusting structures from
+/// ones tuned to a fixed distribution, since what was hot stops being so
+/// at every shift.
+#[derive(Clone, Debug)]
+pub struct WorkingSetShift {
+    n: u64,
+    working_set: u64,
+    phase: u64,
+    start: u64,
+    // Keys left before the next shift.
+    left: u64,
+    rng: u64,
+}
+
+impl WorkingSetShift {
+    pub fn new(n: u64, working_set: u64, phase: u64) -> Self {
+        Self::with_seed(n, working_set, phase, 0)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if any of `n`, `working_set` and `phase` is zero, or the
+    /// working set is bigger than `n`.
+    pub fn with_seed(n: u64, working_set: u64, phase: u64, seed: u64) -> Self {
+        assert!(working_set > 0, "a workload needs a key");
+        assert!(working_set <= n, "the working set can't exceed the keys");
+        assert!(phase > 0, "phases must last a key");
+        WorkingSetShift {
+            n,
+            working_set,
+            phase,
+            start: 0,
+            left: 0,
+            rng: seed,
+        }
+    }
+
+    /// The first key of the current window.
+    pub fn window_start(&self) -> u64 {
+        self.start
+    }
+}
+
+impl Iterator for WorkingSetShift {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.left == 0 {
+            self.start = below(&mut self.rng, self.n - self.working_set + 1);
+            self.left = self.phase;
+        }
+        self.left -= 1;
+        Some(self.start + below(&mut self.rng, self.working_set))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[test]
+    fn basic_test() {
+        assert_eq!(
+            Sequential::new(3).take(7).collect::<Vec<_>>(),
+            [0, 1, 2, 0, 1, 2, 0]
+        );
+        let keys: Vec<_> = Uniform::with_seed(10, 1).take(1000).collect();
+        assert_eq!(
+            keys,
+            Uniform::with_seed(10, 1).take(1000).collect::<Vec<_>>()
+        );
+        assert!((0..10).all(|k| keys.contains(&k)));
+
+        // Rank k turns up about 1/(k + 1) as often as rank 0.
+        let mut counts = [0u32; 100];
+        for key in Zipf::with_seed(100, 1.0, 3).take(100_000) {
+            counts[key as usize] += 1;
+        }
+        assert!(counts.windows(2).take(5).all(|w| w[0] > w[1]));
+        let ratio = counts[0] as f64 / counts[9] as f64;
+        assert!((8.0..12.0).contains(&ratio), "{ratio}");
+
+        // Keys stay in the window until it shifts.
+        let mut shifting = WorkingSetShift::with_seed(1000, 10, 50, 5);
+        let mut starts = vec![];
+        for _ in 0..10 {
+            let phase: Vec<_> = shifting.by_ref().take(50).collect();
+            let start = shifting.window_start();
+            assert!(phase.iter().all(|k| (start..start + 10).contains(k)));
+            starts.push(start);
+        }
+        starts.dedup();
+        assert!(starts.len() > 1);
+    }
+
+    #[quickcheck]
+    fn test_quickcheck(n: u16, seed: u64, exponent: u8) -> bool {
+        let n = u64::from(n) + 1;
+        let exponent = f64::from(exponent) / 32.0 + 0.01;
+        let working_set = seed % n + 1;
+        Uniform::with_seed(n, seed).take(100).all(|k| k < n)
+            && Sequential::new(n).take(100).all(|k| k < n)
+            && Zipf::with_seed(n, exponent, seed).take(100).all(|k| k < n)
+            && WorkingSetShift::with_seed(n, working_set, 7, seed)
+                .take(100)
+                .all(|k| k < n)
+    }
+}